@@ -0,0 +1,70 @@
+//! Decode the high resolution test sounding repeatedly, reporting the time and allocations per
+//! message. Run with `cargo bench`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const ITERATIONS: usize = 50;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let file = std::fs::read("test-data/2017083115.bufr")?;
+    let start = file
+        .windows(4)
+        .position(|w| w == b"BUFR")
+        .ok_or("No message in the test file")?;
+    let message = &file[start..];
+
+    bench("read_bufr_message", || {
+        sonde_bufr::read_bufr_message(message).unwrap();
+    });
+    let bufr = sonde_bufr::read_bufr_message(message)?;
+    bench("soundings", || {
+        bufr.soundings();
+    });
+
+    Ok(())
+}
+
+fn bench(name: &str, mut run: impl FnMut()) {
+    // Warm up, e.g. so the tables are built before counting.
+    run();
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    let elapsed = start.elapsed() / ITERATIONS as u32;
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) / ITERATIONS;
+
+    println!(
+        "{:20} {:>10.2?} {:>8} allocations",
+        name, elapsed, allocations
+    );
+}
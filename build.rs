@@ -31,6 +31,9 @@ fn make_table_b() -> Result<(), Box<dyn Error>> {
     let mut scale: i32 = 0;
     let mut reference_value: i32 = 0;
     let mut width_bits = 8;
+    let mut crex_units = String::new();
+    let mut crex_scale: i32 = 0;
+    let mut crex_width: usize = 0;
 
     let mut max_width_bits = width_bits;
 
@@ -46,6 +49,9 @@ fn make_table_b() -> Result<(), Box<dyn Error>> {
                     scale = 0;
                     reference_value = 0;
                     width_bits = 0;
+                    crex_units.clear();
+                    crex_scale = 0;
+                    crex_width = 0;
                 }
                 _ => {
                     txt.clear();
@@ -56,9 +62,17 @@ fn make_table_b() -> Result<(), Box<dyn Error>> {
                     let key = fxy.clone();
                     let element_name = element_name.clone();
                     let units = units.clone();
+                    let crex = (crex_units.clone(), crex_scale, crex_width);
                     table_b.insert(
                         key,
-                        (element_name, units, scale, reference_value, width_bits),
+                        (
+                            element_name,
+                            units,
+                            scale,
+                            reference_value,
+                            width_bits,
+                            crex,
+                        ),
                     );
                 }
                 b"FXY" => {
@@ -80,6 +94,15 @@ fn make_table_b() -> Result<(), Box<dyn Error>> {
                     width_bits = txt.parse::<usize>()?;
                     max_width_bits = max_width_bits.max(width_bits);
                 }
+                b"CREX_Unit" => {
+                    crex_units.push_str(&txt);
+                }
+                b"CREX_Scale" => {
+                    crex_scale = txt.parse::<i32>()?;
+                }
+                b"CREX_DataWidth_Char" => {
+                    crex_width = txt.parse::<usize>()?;
+                }
                 _ => {}
             },
             Event::Text(e) => txt.push_str(&e.unescape().unwrap()),
@@ -109,14 +132,14 @@ fn make_table_b() -> Result<(), Box<dyn Error>> {
     writeln!(w, "lazy_static! {{")?;
     writeln!(
         w,
-        "    pub static ref TABLE_B: HashMap<&'static str, TableBEntry> = ["
+        "    pub static ref TABLE_B: HashMap<&'static str, TableBEntry<'static>> = ["
     )?;
 
     for (key, value) in table_b.into_iter() {
         writeln!(
             w,
-            r##"("{}", TableBEntry{{width_bits:{}, element_name:r#"{}"#, units:r#"{}"#, reference_val: {}, scale_val: {}}}),"##,
-            key, value.4, value.0, value.1, value.3, value.2
+            r##"("{}", TableBEntry{{width_bits:{}, element_name:r#"{}"#, units:r#"{}"#, reference_val: {}, scale_val: {}, crex_units:r#"{}"#, crex_scale: {}, crex_width: {}}}),"##,
+            key, value.4, value.0, value.1, value.3, value.2, value.5 .0, value.5 .1, value.5 .2
         )?;
     }
 
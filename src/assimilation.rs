@@ -0,0 +1,116 @@
+use crate::{
+    qc::{QcCheck, QcReport, ReportedQualityCheck},
+    sounding::{Level, Sounding},
+};
+use std::{error::Error, str::FromStr};
+
+/// What `Sounding::for_assimilation` does with the levels it rejects.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rejection {
+    /// Leave them out.
+    #[default]
+    Drop,
+    /// Keep them with only their time, position, and significance, and pressure unless the
+    /// pressure is out of order, so the profile keeps its shape.
+    Mask,
+}
+
+/// Parses `drop` or `mask`.
+impl FromStr for Rejection {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(Rejection::Drop),
+            "mask" => Ok(Rejection::Mask),
+            _ => Err(format!("Unknown rejection: {}", s).into()),
+        }
+    }
+}
+
+impl Sounding {
+    /// A clean profile for data assimilation. The `checks` are run on a copy of the sounding, as
+    /// well as a `ReportedQualityCheck` for the quality information sent with the data, and every
+    /// level with a QC flag is dropped or masked. The report lists the rejected levels, by their
+    /// index in `self`, and why they were rejected.
+    pub fn for_assimilation(
+        &self,
+        checks: &[Box<dyn QcCheck>],
+        rejection: Rejection,
+    ) -> (Sounding, QcReport) {
+        let mut checked = self.clone();
+        let reported: Box<dyn QcCheck> = Box::new(ReportedQualityCheck);
+        let mut report = checked.quality_control(std::slice::from_ref(&reported));
+        report.checks.extend(checked.quality_control(checks).checks);
+
+        let clean = match rejection {
+            Rejection::Drop => {
+                let levels = checked
+                    .levels()
+                    .iter()
+                    .filter(|lvl| lvl.qc == 0)
+                    .copied()
+                    .collect();
+                self.with_levels(levels)
+            }
+            Rejection::Mask => {
+                for lvl in checked.levels_mut().iter_mut().filter(|lvl| lvl.qc != 0) {
+                    *lvl = Level {
+                        time_offset: lvl.time_offset,
+                        significance: lvl.significance,
+                        pressure: lvl
+                            .pressure
+                            .filter(|_| !lvl.has_qc_flag(Level::QC_PRESSURE_ORDER)),
+                        lat_displacement: lvl.lat_displacement,
+                        lon_displacement: lvl.lon_displacement,
+                        qc: lvl.qc,
+                        ..Level::default()
+                    };
+                }
+                checked
+            }
+        };
+
+        (clean, report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        qc::RangeCheck,
+        sounding::{Phase, Station},
+    };
+
+    #[test]
+    fn test_for_assimilation() {
+        let level = |hpa: f64, t| Level {
+            pressure: Some(hpa * 100.0),
+            temperature: Some(t),
+            ..Level::default()
+        };
+        let mut levels = vec![
+            level(1000.0, 290.0),
+            level(850.0, 400.0),
+            level(700.0, 275.0),
+        ];
+        levels[2].qc = Level::QC_REPORTED;
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+        let checks: Vec<Box<dyn QcCheck>> = vec![Box::new(RangeCheck::default())];
+
+        let (clean, report) = sounding.for_assimilation(&checks, Rejection::Drop);
+        assert_eq!(clean.levels().len(), 1);
+        assert_eq!(clean.levels()[0].pressure, Some(100_000.0));
+        assert_eq!(report.flagged_levels(), vec![1, 2]);
+        assert_eq!(report.checks[0].name, "reported_quality");
+
+        let (masked, _) = sounding.for_assimilation(&checks, "mask".parse().unwrap());
+        assert_eq!(masked.levels().len(), 3);
+        assert_eq!(masked.levels()[1].pressure, Some(85_000.0));
+        assert_eq!(masked.levels()[1].temperature, None);
+        assert!(masked.levels()[1].has_qc_flag(Level::QC_RANGE));
+        // The sounding itself is left alone.
+        assert_eq!(sounding.levels()[1].qc, 0);
+    }
+}
@@ -0,0 +1,95 @@
+use sonde_bufr::{
+    convert_files, glob, parse_columns, ConvertFormat, ExportOptions, HeightSource,
+    SignificantLevelTolerances, Smoothing,
+};
+use std::{env, error::Error, path::Path, thread};
+
+const USAGE: &str =
+    "Usage: sonde-convert PATTERN... --to csv|json|bufkit|gempak|raob --out-dir DIR \
+     [--jobs N] [--columns SPEC] [--heights geopotential|gnss] [--smooth SECONDS] [--significant] [--reject drop|mask]";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    let (Some(format), Some(out_dir)) = (option("--to"), option("--out-dir")) else {
+        eprintln!("{}", USAGE);
+        eprintln!("Quote patterns like 'data/2024*/**.bufr.gz' so the shell leaves them alone.");
+        eprintln!("SPEC picks the CSV or JSON columns, e.g. 'pressure:hPa:.1,T=temperature:C'.");
+        return Ok(());
+    };
+    let format: ConvertFormat = format.parse()?;
+    let jobs = match option("--jobs") {
+        Some(jobs) => jobs.parse()?,
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let height_source = match option("--heights").map(String::as_str) {
+        None | Some("geopotential") => HeightSource::Geopotential,
+        Some("gnss") => HeightSource::Gnss,
+        Some(other) => return Err(format!("Unknown height source: {}", other).into()),
+    };
+    let smoothing = option("--smooth")
+        .map(|window| -> Result<Smoothing, Box<dyn Error>> {
+            Ok(Smoothing {
+                window: window.parse()?,
+                ..Smoothing::default()
+            })
+        })
+        .transpose()?;
+    let options = ExportOptions {
+        height_source,
+        smoothing,
+        rejection: option("--reject").map(|r| r.parse()).transpose()?,
+        significant_levels: args
+            .iter()
+            .any(|arg| arg == "--significant")
+            .then(SignificantLevelTolerances::default),
+        ..ExportOptions::default()
+    };
+    let columns = option("--columns")
+        .map(|spec| parse_columns(spec))
+        .transpose()?;
+
+    // The patterns are the arguments that aren't options or their values.
+    let takes_value = |arg: &str| arg.starts_with("--") && arg != "--significant";
+    let mut files = vec![];
+    for (i, arg) in args.iter().enumerate() {
+        if arg.starts_with("--") || (i > 0 && takes_value(&args[i - 1])) {
+            continue;
+        }
+        let matches = glob(arg)?;
+        if matches.is_empty() {
+            eprintln!("No files match {}", arg);
+        }
+        files.extend(matches);
+    }
+
+    let summary = convert_files(
+        &files,
+        Path::new(out_dir),
+        format,
+        columns.as_deref(),
+        &options,
+        jobs,
+    );
+    for (path, err) in &summary.failed {
+        eprintln!("{}: {}", path.display(), err);
+    }
+    let written: usize = summary.converted.iter().map(|(_, out)| out.len()).sum();
+    println!(
+        "Converted {} files into {} files, {} failed.",
+        summary.converted.len(),
+        written,
+        summary.failed.len()
+    );
+
+    if !summary.failed.is_empty() {
+        return Err(format!("{} files failed", summary.failed.len()).into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use sonde_bufr::{DiffTolerances, MessageDecoder, Sounding};
+use std::{env, error::Error, fs::File, io::BufReader};
+
+const USAGE: &str = "Usage: sonde-diff LEFT RIGHT";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [left, right] = args.as_slice() else {
+        eprintln!("{}", USAGE);
+        return Ok(());
+    };
+
+    let (left, right) = (read_soundings(left)?, read_soundings(right)?);
+    if left.len() != right.len() {
+        println!("{} soundings != {} soundings", left.len(), right.len());
+    }
+
+    let tolerances = DiffTolerances::default();
+    let mut differing = 0;
+    for (i, (l, r)) in left.iter().zip(&right).enumerate() {
+        let diff = l.diff(r, &tolerances);
+        if !diff.is_empty() {
+            println!("Sounding {}: {}", i, l.summary());
+            print!("{}", diff);
+            differing += 1;
+        }
+    }
+
+    if differing > 0 || left.len() != right.len() {
+        return Err(format!("{} soundings differ", differing).into());
+    }
+    println!("{} soundings match", left.len());
+
+    Ok(())
+}
+
+fn read_soundings(path: &str) -> Result<Vec<Sounding>, Box<dyn Error>> {
+    let f = BufReader::new(File::open(path)?);
+    let mut soundings = vec![];
+    for message in MessageDecoder::default().messages(f) {
+        soundings.extend(
+            message
+                .map_err(|err| format!("{}: {}", path, err))?
+                .soundings(),
+        );
+    }
+
+    Ok(soundings)
+}
@@ -0,0 +1,26 @@
+use sonde_bufr::{extract_subset, read_bufr_bytes, scan_to_bufr_start};
+use std::{env, error::Error, fs::File, io::BufReader};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [path, message_num, subset, out] = args.as_slice() else {
+        eprintln!("Usage: sonde-extract FILE MESSAGE SUBSET OUTPUT");
+        eprintln!("Messages and subsets are numbered from 0.");
+        return Ok(());
+    };
+    let message_num: usize = message_num.parse()?;
+    let subset: usize = subset.parse()?;
+
+    let mut f = BufReader::new(File::open(path)?);
+    let mut num = 0;
+    while scan_to_bufr_start(&mut f).is_ok() {
+        let message = read_bufr_bytes(&mut f)?;
+        if num == message_num {
+            std::fs::write(out, extract_subset(&message, subset)?)?;
+            return Ok(());
+        }
+        num += 1;
+    }
+
+    Err(format!("{} has {} messages", path, num).into())
+}
@@ -0,0 +1,21 @@
+use sonde_bufr::{build_inventory, write_inventory_csv, write_inventory_json};
+use std::{env, error::Error, io::stdout};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Write JSON, with the full position history, instead of CSV.
+    let json = args.iter().any(|arg| arg == "--json");
+    let Some(dir) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        eprintln!("Usage: sonde-inventory [--json] DIRECTORY");
+        return Ok(());
+    };
+
+    let records = build_inventory(dir)?;
+    if json {
+        write_inventory_json(stdout().lock(), &records)?;
+    } else {
+        write_inventory_csv(stdout().lock(), &records)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use sonde_bufr::{read_bufr_bytes, scan_to_bufr_start};
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(i) = args.iter().position(|arg| arg == "-o") else {
+        eprintln!("Usage: sonde-merge FILE... -o OUTPUT");
+        return Ok(());
+    };
+    let out_path = args.get(i + 1).ok_or("No output file after -o")?;
+    let inputs: Vec<&String> = args[..i].iter().chain(&args[i + 2..]).collect();
+
+    let mut out = BufWriter::new(File::create(out_path)?);
+    let mut count = 0;
+    for path in inputs {
+        let mut f = BufReader::new(File::open(path)?);
+        let mut num = 0;
+        while scan_to_bufr_start(&mut f).is_ok() {
+            let message = read_bufr_bytes(&mut f)?;
+
+            // A truncated message would corrupt the one after it in the output.
+            let length = u32::from_be_bytes([0, message[4], message[5], message[6]]) as usize;
+            if message.len() != length || !message.ends_with(b"7777") {
+                return Err(format!("{}: message {} is truncated or malformed", path, num).into());
+            }
+
+            out.write_all(&message)?;
+            num += 1;
+        }
+        count += num;
+    }
+    out.flush()?;
+
+    eprintln!("Wrote {} messages to {}", count, out_path);
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use sonde_bufr::{summarize_archive, write_monthly_csv, write_monthly_json};
+use std::{env, error::Error, io::stdout};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Write JSON instead of CSV.
+    let json = args.iter().any(|arg| arg == "--json");
+    let Some(dir) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        eprintln!("Usage: sonde-monthly [--json] DIRECTORY");
+        return Ok(());
+    };
+
+    let summaries = summarize_archive(dir)?;
+    if json {
+        write_monthly_json(stdout().lock(), &summaries)?;
+    } else {
+        write_monthly_csv(stdout().lock(), &summaries)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use sonde_bufr::{default_checks, DecoderBuilder, UnsupportedMasterTable};
+use std::{env, error::Error, io::stdout};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let csv = args.iter().any(|arg| arg == "--csv");
+    // Report progress on stderr, for big archives.
+    let progress = args.iter().any(|arg| arg == "--progress");
+    let Some(path) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        eprintln!("Usage: sonde-qc [--csv] [--progress] FILE");
+        return Ok(());
+    };
+
+    let f = std::fs::File::open(path)?;
+    let f = std::io::BufReader::new(f);
+
+    let checks = default_checks();
+    let decoder = DecoderBuilder::new().search_table_paths()?.build();
+    let mut messages = decoder.messages(f);
+    if progress {
+        messages = messages.on_progress(100, |progress| {
+            let percent = progress
+                .total_bytes
+                .filter(|&total| total > 0)
+                .map(|total| {
+                    format!(
+                        " ({:.0}%)",
+                        100.0 * progress.bytes_processed as f64 / total as f64
+                    )
+                })
+                .unwrap_or_default();
+            eprintln!("{} messages{}", progress.messages_decoded, percent);
+        });
+    }
+
+    for bufr in messages {
+        let bufr = match bufr {
+            Err(err) if err.is::<UnsupportedMasterTable>() => {
+                eprintln!("{}", err);
+                continue;
+            }
+            bufr => bufr?,
+        };
+        for mut sounding in bufr.soundings() {
+            let report = sounding.quality_control(&checks);
+            if csv {
+                report.write_csv(stdout().lock())?;
+            } else {
+                report.write_json(stdout().lock())?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use sonde_bufr::{read_bufr_bytes, read_bufr_message, scan_to_bufr_start};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (Some(path), Some(by)) = (
+        args.iter().find(|arg| !arg.starts_with("--")),
+        args.iter()
+            .position(|arg| arg == "--by")
+            .and_then(|i| args.get(i + 1)),
+    ) else {
+        eprintln!("Usage: sonde-split FILE --by station|time|category|type");
+        eprintln!("Writes FILE_<group>.bufr next to FILE for each group of messages.");
+        return Ok(());
+    };
+    if !["station", "time", "category", "type"].contains(&by.as_str()) {
+        return Err(format!("Can't split by {}", by).into());
+    }
+
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut f = BufReader::new(File::open(path)?);
+    let mut outputs: HashMap<String, BufWriter<File>> = HashMap::new();
+    while scan_to_bufr_start(&mut f).is_ok() {
+        let message = read_bufr_bytes(&mut f)?;
+
+        // Messages that can't be decoded are kept together rather than dropped.
+        let group = match read_bufr_message(message.as_slice()) {
+            Ok(bufr) => match by.as_str() {
+                "station" => bufr
+                    .soundings()
+                    .first()
+                    .and_then(|sounding| sounding.station().identifier()),
+                "time" => {
+                    let t = bufr.nominal_time();
+                    Some(format!(
+                        "{:04}{:02}{:02}{:02}",
+                        t.year, t.month, t.day, t.hour
+                    ))
+                }
+                "type" => bufr.report_type().map(|r| r.tag().to_owned()),
+                _ => Some(format!("cat{:03}", u8::from(bufr.category()))),
+            },
+            Err(_) => None,
+        };
+        let group = group.unwrap_or_else(|| "unknown".to_owned());
+
+        let out = match outputs.get_mut(&group) {
+            Some(out) => out,
+            None => {
+                let out_path = dir.join(format!("{}_{}.bufr", stem, group));
+                eprintln!("{}", out_path.display());
+                outputs
+                    .entry(group)
+                    .or_insert(BufWriter::new(File::create(out_path)?))
+            }
+        };
+        out.write_all(&message)?;
+    }
+
+    for (_, mut out) in outputs {
+        out.flush()?;
+    }
+
+    Ok(())
+}
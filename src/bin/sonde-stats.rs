@@ -0,0 +1,25 @@
+use sonde_bufr::FileStats;
+use std::{env, error::Error, io::stdout};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Print one JSON object per file instead of the text summary.
+    let json = args.iter().any(|arg| arg == "--json");
+    let paths: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: sonde-stats [--json] FILE...");
+        return Ok(());
+    }
+
+    for path in paths {
+        let stats = FileStats::from_path(path)?;
+        if json {
+            stats.write_json(stdout().lock())?;
+        } else {
+            println!("{}", path);
+            println!("{}", stats);
+        }
+    }
+
+    Ok(())
+}
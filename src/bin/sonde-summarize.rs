@@ -1,19 +1,79 @@
-use sonde_bufr::{read_bufr_message, scan_to_bufr_start};
-use std::{env, error::Error};
+use sonde_bufr::{
+    hex_dump, next_bufr_start, read_bufr_bytes, DecoderBuilder, ExportOptions, JsonLinesWriter,
+    UnsupportedMasterTable,
+};
+use std::{
+    env,
+    error::Error,
+    io::{stdout, Cursor},
+};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().skip(1).collect();
-    if args.is_empty() {
+    // Print where each element was read from instead of the summary.
+    let trace = args.iter().any(|arg| arg == "--trace");
+    // Print an annotated hex dump of each message before its summary.
+    let hex = args.iter().any(|arg| arg == "--hex");
+    // Print each sounding as a line of JSON as soon as it's decoded, and nothing else.
+    let jsonl = args.iter().any(|arg| arg == "--jsonl");
+    let Some(path) = args.iter().find(|arg| !arg.starts_with("--")) else {
         eprintln!("No file name provided!");
         return Ok(());
-    }
+    };
 
-    let f = std::fs::File::open(&args[0])?;
+    let f = std::fs::File::open(path)?;
     let mut f = std::io::BufReader::new(f);
-    scan_to_bufr_start(&mut f)?;
-    let bufr = read_bufr_message(&mut f)?;
 
-    println!("{}", &bufr);
+    let decoder = DecoderBuilder::new()
+        .search_table_paths()?
+        .trace(trace)
+        .build();
+
+    let mut json_lines = jsonl.then(|| JsonLinesWriter::new(stdout(), ExportOptions::default()));
+
+    // A file may hold several messages, e.g. the ascent and descent from one launch. A read
+    // error or a truncated message stops with an error rather than looking like the end.
+    while next_bufr_start(&mut f)? {
+        let message = read_bufr_bytes(&mut f)?;
+        if hex {
+            hex_dump(stdout().lock(), &message)?;
+            println!();
+        }
+
+        let bufr = match decoder.read_bufr_message(Cursor::new(message)) {
+            Err(err) if err.is::<UnsupportedMasterTable>() => {
+                // Keep the JSON output to JSON.
+                if jsonl {
+                    eprintln!("{}", err);
+                } else {
+                    println!("{}", err);
+                }
+                continue;
+            }
+            bufr => bufr?,
+        };
+
+        if let Some(json_lines) = &mut json_lines {
+            for sounding in bufr.soundings() {
+                json_lines.write(&sounding)?;
+            }
+            continue;
+        }
+
+        if trace {
+            for entry in bufr.trace() {
+                println!("{}", entry);
+            }
+            continue;
+        }
+
+        println!("{}", &bufr);
+
+        for sounding in bufr.soundings() {
+            println!("{}", sounding);
+            println!("{}", sounding.stability_indices());
+        }
+    }
 
     Ok(())
 }
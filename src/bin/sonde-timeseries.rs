@@ -0,0 +1,39 @@
+use sonde_bufr::{extract_time_series, write_time_series_csv, ProfileElement};
+use std::{env, error::Error, io::stdout};
+
+const USAGE: &str =
+    "Usage: sonde-timeseries DIRECTORY --station STATION --element ELEMENT --level HPA";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    let (Some(station), Some(element), Some(level)) =
+        (option("--station"), option("--element"), option("--level"))
+    else {
+        eprintln!("{}", USAGE);
+        return Ok(());
+    };
+    // The directory is the one argument that isn't an option or its value.
+    let Some(dir) = args
+        .iter()
+        .enumerate()
+        .find(|(i, arg)| !arg.starts_with("--") && (*i == 0 || !args[i - 1].starts_with("--")))
+        .map(|(_, arg)| arg)
+    else {
+        eprintln!("{}", USAGE);
+        return Ok(());
+    };
+
+    let element: ProfileElement = element.parse()?;
+    let pressure = level.parse::<f64>()? * 100.0;
+
+    let points = extract_time_series(dir, station, element, pressure)?;
+    write_time_series_csv(stdout().lock(), &points)?;
+
+    Ok(())
+}
@@ -5,11 +5,7 @@ use crate::table_b;
 // Use a buffer 8 times the size of the largest set of bits we need to read.
 // Notice we're going from bits to bytes here.
 const BUF_SIZE: usize = table_b::MAX_BIT_WIDTH;
-const BYTE_ARRAY_SIZE: usize = if table_b::MAX_BIT_WIDTH % 8 == 0 {
-    table_b::MAX_BIT_WIDTH / 8
-} else {
-    table_b::MAX_BIT_WIDTH / 8 + 1
-};
+const BYTE_ARRAY_SIZE: usize = table_b::MAX_BIT_WIDTH.div_ceil(8);
 
 pub(crate) struct BitBuffer<'b> {
     // The source
@@ -26,6 +22,9 @@ pub(crate) struct BitBuffer<'b> {
     // Track where we are in the buffer
     byte_position: usize,
     bit_position: usize,
+
+    // The last number read, for tracing.
+    last_raw: Option<u64>,
 }
 
 impl<'b> BitBuffer<'b> {
@@ -38,6 +37,7 @@ impl<'b> BitBuffer<'b> {
             byte_position: 0,
             bit_position: 0,
             buffer_len: 0,
+            last_raw: None,
         }
     }
 
@@ -45,32 +45,42 @@ impl<'b> BitBuffer<'b> {
         self.bytes_read
     }
 
+    /// Bits consumed from the source so far.
+    pub fn bit_offset(&self) -> usize {
+        (self.bytes_read - self.buffer_len + self.byte_position) * 8 + self.bit_position
+    }
+
+    /// The raw integer of the last number read, `None` if it was missing or text was read since.
+    pub fn last_raw(&self) -> Option<u64> {
+        self.last_raw
+    }
+
     fn num_bytes_to_hold_bits(n: usize) -> usize {
-        if n % 8 == 0 {
-            n / 8
-        } else {
-            n / 8 + 1
-        }
+        n.div_ceil(8)
     }
 
     fn read_n_bits(&mut self, n: usize) -> Result<Option<[u8; BYTE_ARRAY_SIZE]>, Box<dyn Error>> {
+        if n == 0 || n > BUF_SIZE {
+            return Err(format!("Can't read an element {} bits wide", n).into());
+        }
+
         let mut mask = [255u8; BYTE_ARRAY_SIZE];
         let mut vals = [0u8; BYTE_ARRAY_SIZE];
 
         // Bookkeeping
         let most_sig_byte = BYTE_ARRAY_SIZE - BitBuffer::num_bytes_to_hold_bits(n);
-        let bits_first_byte = if n % 8 == 0 { 8 } else { n % 8 };
+        let bits_first_byte = if n.is_multiple_of(8) { 8 } else { n % 8 };
 
         // Build the mask
-        for i in 0..most_sig_byte {
-            mask[i] = 0;
+        for byte in mask.iter_mut().take(most_sig_byte) {
+            *byte = 0;
         }
         mask[most_sig_byte] >>= 8 - bits_first_byte;
 
         // Load the bytes
         vals[most_sig_byte] = mask[most_sig_byte] & self.read_u8(bits_first_byte)?;
-        for i in (most_sig_byte + 1)..BYTE_ARRAY_SIZE {
-            vals[i] = mask[i] & self.read_u8(8)?;
+        for (val, mask) in vals.iter_mut().zip(mask.iter()).skip(most_sig_byte + 1) {
+            *val = mask & self.read_u8(8)?;
         }
 
         // Check for BUFR missing value (all bits are set to 1
@@ -92,8 +102,8 @@ impl<'b> BitBuffer<'b> {
             self.bytes_read += BUF_SIZE;
         } else {
             let num_bytes_remaining = self.max_bytes_to_read - self.bytes_read;
-            let mut buf = &mut self.buffer[0..num_bytes_remaining];
-            self.reader.read_exact(&mut buf)?;
+            let buf = &mut self.buffer[0..num_bytes_remaining];
+            self.reader.read_exact(buf)?;
             self.buffer_len = num_bytes_remaining;
             self.bytes_read += num_bytes_remaining;
         }
@@ -107,6 +117,9 @@ impl<'b> BitBuffer<'b> {
     fn next_byte(&mut self) -> Result<u8, Box<dyn Error>> {
         if self.bits_remaining_in_buffer() == 0 {
             self.refill_buffer()?;
+            if self.buffer_len == 0 {
+                return Err("Ran out of data in Section 4".into());
+            }
         }
 
         Ok(self.buffer[self.byte_position])
@@ -193,42 +206,55 @@ impl<'b> BitBuffer<'b> {
         Ok(val)
     }
 
-    pub fn read_text(&mut self, bits: usize) -> Result<String, Box<dyn Error>> {
-        debug_assert_eq!(bits % 8, 0, "funky string size");
+    /// Read whole octets, e.g. text that might be missing (all bits set).
+    pub fn read_bytes(&mut self, bits: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        if !bits.is_multiple_of(8) {
+            return Err(format!("Text {} bits wide isn't whole characters", bits).into());
+        }
 
+        self.last_raw = None;
         let num_chars = bits / 8;
-        //dbg!(num_chars, bits);
         let mut buf: Vec<u8> = Vec::with_capacity(num_chars);
         for _ in 0..num_chars {
-            let c = self.read_u8(8)?;
-            //dbg!(c);
-            buf.push(c);
+            buf.push(self.read_u8(8)?);
         }
 
-        Ok(String::from_utf8(buf)?)
+        Ok(buf)
     }
 
-    fn read_u64(&mut self, bits: usize) -> Result<Option<u64>, Box<dyn Error>> {
-        debug_assert!(bits <= (8 * 8), "too many bits for u64: {}", bits);
-        debug_assert!(bits > 0, "requested zero bits");
+    pub fn read_u64(&mut self, bits: usize) -> Result<Option<u64>, Box<dyn Error>> {
+        if bits == 0 || bits > 64 {
+            return Err(format!("Can't read a number {} bits wide", bits).into());
+        }
 
+        self.last_raw = None;
         let vals_buf = self.read_n_bits(bits)?;
         if let Some(vals_buf) = vals_buf {
             let mut small_buf: [u8; 8] = [0; 8];
             small_buf.clone_from_slice(&vals_buf[(BYTE_ARRAY_SIZE - 8)..]);
             let val = u64::from_be_bytes(small_buf);
             debug_assert!(
-                val < (1u64 << bits),
-                "val too large: {} >= {}",
+                bits == 64 || val < (1u64 << bits),
+                "val too large: {} for {} bits",
                 val,
-                1u64 << bits
+                bits
             );
+            self.last_raw = Some(val);
             Ok(Some(val))
         } else {
             Ok(None)
         }
     }
 
+    /// Read a number too wide for a `u64` as its bits, right aligned in whole octets. `None` if
+    /// it's missing.
+    pub fn read_wide(&mut self, bits: usize) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        self.last_raw = None;
+        let vals_buf = self.read_n_bits(bits)?;
+        let num_bytes = BitBuffer::num_bytes_to_hold_bits(bits);
+        Ok(vals_buf.map(|vals| vals[BYTE_ARRAY_SIZE - num_bytes..].to_vec()))
+    }
+
     pub fn read_usize(&mut self, bits: usize) -> Result<Option<usize>, Box<dyn Error>> {
         let val = self.read_u64(bits)?;
         match val {
@@ -244,29 +270,24 @@ impl<'b> BitBuffer<'b> {
     ) -> Result<Option<i64>, Box<dyn Error>> {
         let val = self.read_u64(bits)?;
 
-        match val {
-            Some(val) => Ok(Some(i64::try_from(val)?)),
-            None => Ok(None),
-        }
+        val.map(|val| add_reference(val, reference_val)).transpose()
     }
+}
 
-    pub fn read_f64(
-        &mut self,
-        bits: usize,
-        reference_val: i64,
-        scale: i32,
-    ) -> Result<Option<f64>, Box<dyn Error>> {
-        let mut val = self
-            .read_i64(bits, reference_val)?
-            .map(|v| v as f64)
-            .map(|v| {
-                if scale != 0 {
-                    v / f64::powi(10.0, scale)
-                } else {
-                    v
-                }
-            });
+/// Add a Table B reference value to a raw value, failing rather than overflowing.
+pub(crate) fn add_reference(val: u64, reference_val: i64) -> Result<i64, Box<dyn Error>> {
+    i64::try_from(val)?
+        .checked_add(reference_val)
+        .ok_or_else(|| format!("{} plus the reference {} overflows", val, reference_val).into())
+}
 
-        Ok(val)
+/// Apply a Table B scale to a value with its reference added.
+pub(crate) fn apply_scale(val: i64, scale: i32) -> f64 {
+    // Multiply for negative scales so values like pressure (scale -1) stay exact.
+    let val = val as f64;
+    match scale {
+        0 => val,
+        s if s > 0 => val / f64::powi(10.0, s),
+        s => val * f64::powi(10.0, -s),
     }
 }
@@ -0,0 +1,78 @@
+use std::error::Error;
+
+/// Packs values into a run of bits, most significant bit first, the inverse of `BitBuffer`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    // Bits used so far, the last byte may be partly filled.
+    bits: usize,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_u64(&mut self, val: u64, bits: usize) -> Result<(), Box<dyn Error>> {
+        debug_assert!(bits <= 64, "too many bits for u64: {}", bits);
+        if bits < 64 && val >> bits != 0 {
+            return Err(format!("{} doesn't fit in {} bits", val, bits).into());
+        }
+
+        for i in (0..bits).rev() {
+            self.push_bit((val >> i) & 1 == 1);
+        }
+        Ok(())
+    }
+
+    /// Write the low `bits` bits of right aligned `bytes`, the inverse of `read_wide`.
+    pub fn write_bytes(&mut self, bytes: &[u8], bits: usize) -> Result<(), Box<dyn Error>> {
+        let num_bytes = bits.div_ceil(8);
+        let bits_first_byte = bits - 8 * (num_bytes - 1);
+        if bytes.len() != num_bytes || (bits_first_byte < 8 && bytes[0] >> bits_first_byte != 0) {
+            return Err(format!("The bytes don't fit in {} bits", bits).into());
+        }
+
+        self.write_u64(bytes[0] as u64, bits_first_byte)?;
+        for &byte in &bytes[1..] {
+            self.write_u64(byte as u64, 8)?;
+        }
+        Ok(())
+    }
+
+    /// Write all ones, the missing value.
+    pub fn write_missing(&mut self, bits: usize) {
+        for _ in 0..bits {
+            self.push_bit(true);
+        }
+    }
+
+    /// Write `text` as `bits / 8` characters, padded with spaces.
+    pub fn write_text(&mut self, text: &str, bits: usize) -> Result<(), Box<dyn Error>> {
+        debug_assert!(bits.is_multiple_of(8), "funky string size");
+
+        let num_chars = bits / 8;
+        if text.len() > num_chars {
+            return Err(format!("'{}' is longer than {} characters", text, num_chars).into());
+        }
+        for c in text.bytes().chain(std::iter::repeat(b' ')).take(num_chars) {
+            self.write_u64(c as u64, 8)?;
+        }
+        Ok(())
+    }
+
+    /// The bytes written, padded with zeros to a whole octet.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn push_bit(&mut self, bit: bool) {
+        if self.bits.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> (self.bits % 8);
+        }
+        self.bits += 1;
+    }
+}
@@ -0,0 +1,519 @@
+use crate::{
+    crex::{read_crex_message_with, CrexMessage},
+    data_category::DataCategory,
+    fingerprint::Fnv1a,
+    messages::BufrMessages,
+    provider::TableProvider,
+    read_bufr_message_with,
+    table_paths::{read_table_path, search_tables},
+    tables::TableOverrides,
+    unknown::UnknownDescriptorHandler,
+    BufrMessage,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    hash::Hasher,
+    io::{Cursor, Read, Seek},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Configures a `MessageDecoder`.
+#[derive(Clone, Debug, Default)]
+pub struct DecoderBuilder {
+    overrides: TableOverrides,
+    trace: bool,
+    cancel: Option<Arc<AtomicBool>>,
+    budget: Option<usize>,
+    decimals: bool,
+    categories: Option<Vec<DataCategory>>,
+    humidity: bool,
+    master_tables: HashMap<u8, TableOverrides>,
+    unknown: Option<Arc<dyn UnknownDescriptorHandler>>,
+    provider: Option<Arc<dyn TableProvider>>,
+    metrics: bool,
+}
+
+impl DecoderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use these definitions in place of, or as well as, the built-in tables. Calling this more
+    /// than once merges the overrides, with later ones winning.
+    pub fn table_overrides(mut self, overrides: TableOverrides) -> Self {
+        self.overrides.merge(overrides);
+        self
+    }
+
+    /// Load overrides from a TOML file, or every `.toml` file in a directory, see
+    /// `TableOverrides::from_toml`. Like `table_overrides`, later ones win.
+    pub fn table_path(mut self, path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        self.overrides.merge(read_table_path(path.as_ref())?);
+        Ok(self)
+    }
+
+    /// Load overrides from `SONDE_BUFR_TABLES` and the per-user config and data directories,
+    /// see `table_search_paths`, so tables can be updated without changing the application.
+    /// They're layered under the overrides and paths given to the builder, whether those are
+    /// given before or after this.
+    pub fn search_table_paths(mut self) -> Result<Self, Box<dyn Error>> {
+        let mut overrides = search_tables()?;
+        overrides.merge(std::mem::take(&mut self.overrides));
+        self.overrides = overrides;
+        Ok(self)
+    }
+
+    /// Record where each element of a BUFR message was read from, see `BufrMessage::trace`.
+    /// This is for debugging and makes decoding much slower.
+    pub fn trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Stop decoding once `flag` is set, e.g. from another thread. Decoding checks the flag
+    /// between messages, subsets and replications and returns a `Cancelled` error.
+    pub fn cancel_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(flag);
+        self
+    }
+
+    /// Give up on a message whose decoded values would take more than about `bytes` of memory,
+    /// returning a `BudgetExceeded` error, e.g. a corrupt replication factor in a file that
+    /// would otherwise use up all the memory of the host.
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.budget = Some(bytes);
+        self
+    }
+
+    /// Return scaled numbers as exact `Value::Decimal`s rather than `Value::Float`s, e.g. for
+    /// archiving or comparing values digit for digit. Soundings are still made of `f64`s.
+    pub fn exact_decimals(mut self, decimals: bool) -> Self {
+        self.decimals = decimals;
+        self
+    }
+
+    /// Only decode BUFR messages of these Table A categories. Others are read without decoding
+    /// Section 4, and `MessageDecoder::messages` leaves them out.
+    pub fn categories(mut self, categories: impl IntoIterator<Item = DataCategory>) -> Self {
+        self.categories = Some(categories.into_iter().collect());
+        self
+    }
+
+    /// Apply the published daytime humidity corrections to the soundings of a message, see
+    /// `Sounding::correct_humidity`.
+    pub fn correct_humidity(mut self, correct: bool) -> Self {
+        self.humidity = correct;
+        self
+    }
+
+    /// Decode messages that use master table `number`, e.g. 10 for oceanography, with `tables`
+    /// and none of the built-in ones, which are master table 0 (meteorology). Messages of other
+    /// master tables are read without decoding Section 4, as an `UnsupportedMasterTable` error.
+    /// These messages have no soundings, but their data is in `BufrMessage::subsets`.
+    pub fn master_table(mut self, number: u8, tables: TableOverrides) -> Self {
+        self.master_tables
+            .entry(number)
+            .or_insert_with(|| TableOverrides::new().exclusive())
+            .merge(tables);
+        self
+    }
+
+    /// Ask `handler` what to do with element descriptors that aren't in any loaded table, rather
+    /// than failing the message.
+    pub fn unknown_descriptors(mut self, handler: impl UnknownDescriptorHandler + 'static) -> Self {
+        self.unknown = Some(Arc::new(handler));
+        self
+    }
+
+    /// Look definitions up in `provider` instead of the built-in tables, e.g. to choose tables
+    /// by the version and centre in Section 1. Overrides and master tables given to the builder
+    /// still win over it. CREX messages always use the built-in tables.
+    pub fn table_provider(mut self, provider: impl TableProvider + 'static) -> Self {
+        self.provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Record `DecodeMetrics` for each BUFR message, see `BufrMessage::metrics` and
+    /// `BufrMessages::metrics`, e.g. to monitor an ingest service.
+    pub fn metrics(mut self, metrics: bool) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn build(self) -> MessageDecoder {
+        MessageDecoder {
+            overrides: self.overrides,
+            trace: self.trace,
+            cancel: self.cancel,
+            budget: self.budget,
+            decimals: self.decimals,
+            categories: self.categories,
+            humidity: self.humidity,
+            master_tables: self.master_tables,
+            unknown: self.unknown,
+            provider: self.provider,
+            metrics: self.metrics,
+        }
+    }
+}
+
+/// Reads messages with the settings from a `DecoderBuilder`. The free functions
+/// `read_bufr_message` and `read_crex_message` are the same as a decoder with the defaults.
+#[derive(Clone, Debug, Default)]
+pub struct MessageDecoder {
+    overrides: TableOverrides,
+    trace: bool,
+    cancel: Option<Arc<AtomicBool>>,
+    budget: Option<usize>,
+    decimals: bool,
+    categories: Option<Vec<DataCategory>>,
+    humidity: bool,
+    master_tables: HashMap<u8, TableOverrides>,
+    unknown: Option<Arc<dyn UnknownDescriptorHandler>>,
+    provider: Option<Arc<dyn TableProvider>>,
+    metrics: bool,
+}
+
+/// The settings a `MessageDecoder` passes down to the section readers.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct DecodeOptions<'a> {
+    pub(crate) overrides: Option<&'a TableOverrides>,
+    pub(crate) trace: bool,
+    pub(crate) cancel: Option<&'a AtomicBool>,
+    pub(crate) budget: Option<usize>,
+    pub(crate) decimals: bool,
+    pub(crate) categories: Option<&'a [DataCategory]>,
+    pub(crate) humidity: bool,
+    pub(crate) master_tables: Option<&'a HashMap<u8, TableOverrides>>,
+    pub(crate) unknown: Option<&'a dyn UnknownDescriptorHandler>,
+    pub(crate) provider: Option<&'a dyn TableProvider>,
+    pub(crate) metrics: bool,
+}
+
+/// The error returned when decoding stops because the `DecoderBuilder::cancel_flag` was set.
+/// Use `error.is::<Cancelled>()` to tell it apart from a bad message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Decoding was cancelled.")
+    }
+}
+
+impl Error for Cancelled {}
+
+/// The error returned when a message needs more memory than the `DecoderBuilder::memory_budget`
+/// allows. Use `error.downcast_ref::<BudgetExceeded>()` to find the message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    /// The budget, in bytes.
+    pub budget: usize,
+    /// The byte offset of the message in its input, when read with `MessageDecoder::messages`
+    /// or `decode_at`.
+    pub offset: Option<u64>,
+}
+
+impl Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Decoding the message ")?;
+        if let Some(offset) = self.offset {
+            write!(f, "at byte {} ", offset)?;
+        }
+        write!(f, "needs more than the budget of {} bytes.", self.budget)
+    }
+}
+
+impl Error for BudgetExceeded {}
+
+/// The error returned for a BUFR message of a master table other than 0 (meteorology) that has no
+/// tables registered with `DecoderBuilder::master_table`. The rest of the message is read, so
+/// `MessageDecoder::messages` carries on with the next one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnsupportedMasterTable {
+    pub master_table: u8,
+    pub category: DataCategory,
+    /// The byte offset of the message in its input, when read with `MessageDecoder::messages`
+    /// or `decode_at`.
+    pub offset: Option<u64>,
+}
+
+impl Display for UnsupportedMasterTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Skipped the ")?;
+        if let Some(offset) = self.offset {
+            write!(f, "message at byte {}, ", offset)?;
+        } else {
+            write!(f, "message, ")?;
+        }
+        write!(
+            f,
+            "{} data from master table {}",
+            self.category, self.master_table
+        )?;
+        if self.master_table == 10 {
+            write!(f, " (oceanography)")?;
+        }
+        write!(
+            f,
+            ". Register its tables with DecoderBuilder::master_table to decode it."
+        )
+    }
+}
+
+impl Error for UnsupportedMasterTable {}
+
+pub(crate) fn check_budget(budget: Option<usize>, used: usize) -> Result<(), Box<dyn Error>> {
+    match budget {
+        Some(budget) if used > budget => Err(BudgetExceeded {
+            budget,
+            offset: None,
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Fill in where the message that went over its budget was.
+pub(crate) fn with_offset(mut err: Box<dyn Error>, offset: Option<u64>) -> Box<dyn Error> {
+    if let Some(exceeded) = err.downcast_mut::<BudgetExceeded>() {
+        exceeded.offset = offset;
+    }
+    if let Some(unsupported) = err.downcast_mut::<UnsupportedMasterTable>() {
+        unsupported.offset = offset;
+    }
+    err
+}
+
+pub(crate) fn wants(categories: Option<&[DataCategory]>, category: DataCategory) -> bool {
+    categories.is_none_or(|categories| categories.contains(&category))
+}
+
+pub(crate) fn check_cancelled(cancel: Option<&AtomicBool>) -> Result<(), Box<dyn Error>> {
+    match cancel {
+        Some(flag) if flag.load(Ordering::Relaxed) => Err(Cancelled.into()),
+        _ => Ok(()),
+    }
+}
+
+impl MessageDecoder {
+    pub fn read_bufr_message(&self, f: impl Read) -> Result<BufrMessage, Box<dyn Error>> {
+        let options = DecodeOptions {
+            overrides: self.overrides(),
+            trace: self.trace,
+            cancel: self.cancel.as_deref(),
+            budget: self.budget,
+            decimals: self.decimals,
+            categories: self.categories.as_deref(),
+            humidity: self.humidity,
+            master_tables: Some(&self.master_tables),
+            unknown: self.unknown.as_deref(),
+            provider: self.provider.as_deref(),
+            metrics: self.metrics,
+        };
+
+        read_bufr_message_with(f, &options)
+    }
+
+    /// Iterate over the BUFR messages in `reader`, starting from its current position.
+    pub fn messages<'a, R: Read + Seek>(&self, reader: R) -> BufrMessages<'a, R> {
+        BufrMessages::new(self.clone(), reader)
+    }
+
+    /// Iterate over the BUFR messages in `bytes`, e.g. a file already in memory or a network
+    /// response body.
+    pub fn decode_slice<'a>(&self, bytes: &'a [u8]) -> BufrMessages<'a, Cursor<&'a [u8]>> {
+        self.messages(Cursor::new(bytes))
+    }
+
+    pub fn read_crex_message(&self, f: impl Read) -> Result<CrexMessage, Box<dyn Error>> {
+        read_crex_message_with(f, self.overrides())
+    }
+
+    /// Whether messages of `category` are decoded.
+    pub(crate) fn wants(&self, category: DataCategory) -> bool {
+        wants(self.categories.as_deref(), category)
+    }
+
+    /// The same decoder, recording `DecodeMetrics`.
+    pub(crate) fn with_metrics(&self) -> MessageDecoder {
+        MessageDecoder {
+            metrics: true,
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn records_metrics(&self) -> bool {
+        self.metrics
+    }
+
+    /// A hash of the settings that change what is decoded from a message, see `SoundingCache`.
+    /// A table provider or unknown descriptor handler can't be hashed and isn't part of it.
+    pub(crate) fn settings_fingerprint(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        self.overrides.hash_into(&mut hasher);
+
+        let mut master_tables: Vec<_> = self.master_tables.iter().collect();
+        master_tables.sort_by_key(|(number, _)| **number);
+        for (number, tables) in master_tables {
+            hasher.write_u8(*number);
+            tables.hash_into(&mut hasher);
+        }
+
+        let settings = (self.decimals, self.humidity, &self.categories);
+        hasher.write(format!("{:?}", settings).as_bytes());
+        hasher.finish()
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        check_cancelled(self.cancel.as_deref()).is_err()
+    }
+
+    fn overrides(&self) -> Option<&TableOverrides> {
+        (!self.overrides.is_empty()).then_some(&self.overrides)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{scan_to_bufr_start, section3::Descriptor, ElementOverride, Value};
+    use std::{fs::File, io::BufReader};
+
+    #[test]
+    fn test_table_overrides() {
+        let read = |decoder: &MessageDecoder| {
+            let mut f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+            scan_to_bufr_start(&mut f).unwrap();
+            decoder.read_bufr_message(&mut f)
+        };
+
+        let station = read(&DecoderBuilder::new().build()).unwrap().soundings()[0]
+            .station()
+            .clone();
+        assert_eq!(station.elevation, Some(1225.0));
+
+        // Station height (0-07-030) in whole metres instead of tenths.
+        let mut overrides = TableOverrides::new();
+        overrides
+            .add_element(
+                Descriptor::new(0, 7, 30),
+                ElementOverride {
+                    name: "Height of station ground above mean sea level".to_owned(),
+                    units: "m".to_owned(),
+                    scale: 0,
+                    reference: -4000,
+                    width_bits: 17,
+                    crex_units: "m".to_owned(),
+                    crex_scale: 0,
+                    crex_width: 5,
+                },
+            )
+            .unwrap();
+        let decoder = DecoderBuilder::new().table_overrides(overrides).build();
+        let station = read(&decoder).unwrap().soundings()[0].station().clone();
+        assert_eq!(station.elevation, Some(16_250.0 - 4000.0));
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+
+        // 4879 levels of 10 elements don't fit in a megabyte.
+        let decoder = DecoderBuilder::new().memory_budget(1 << 20).build();
+        let err = decoder
+            .messages(std::io::Cursor::new(&file))
+            .next()
+            .unwrap()
+            .err()
+            .unwrap();
+        let exceeded = err.downcast_ref::<BudgetExceeded>().unwrap();
+        assert_eq!(exceeded.budget, 1 << 20);
+        assert_eq!(exceeded.offset, Some(start as u64));
+
+        let decoder = DecoderBuilder::new().memory_budget(1 << 24).build();
+        assert!(decoder.read_bufr_message(&file[start..]).is_ok());
+    }
+
+    #[test]
+    fn test_master_tables() {
+        let mut file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        // Octet 4 of Section 1.
+        file[start + 8 + 3] = 10;
+
+        let err = MessageDecoder::default()
+            .messages(std::io::Cursor::new(&file))
+            .next()
+            .unwrap()
+            .err()
+            .unwrap();
+        let unsupported = err.downcast_ref::<UnsupportedMasterTable>().unwrap();
+        assert_eq!(unsupported.master_table, 10);
+        assert_eq!(unsupported.category, DataCategory::VerticalSoundings);
+        assert_eq!(unsupported.offset, Some(start as u64));
+        assert!(err.to_string().contains("(oceanography)"));
+
+        // Copies of the built-in definitions stand in for the oceanographic tables.
+        let mut tables = TableOverrides::new();
+        for element in crate::table_b_entries() {
+            let element_override = crate::ElementOverride {
+                name: element.name.to_owned(),
+                units: element.units.to_owned(),
+                scale: element.scale,
+                reference: element.reference,
+                width_bits: element.width_bits,
+                crex_units: element.crex_units.to_owned(),
+                crex_scale: element.crex_scale,
+                crex_width: element.crex_width,
+            };
+            tables
+                .add_element(element.descriptor, element_override)
+                .unwrap();
+        }
+        // Without the sequences the built-in ones aren't used instead.
+        let decoder = DecoderBuilder::new()
+            .master_table(10, tables.clone())
+            .build();
+        assert!(decoder.read_bufr_message(&file[start..]).is_err());
+
+        for sequence in crate::table_d_entries() {
+            tables
+                .add_sequence(sequence.descriptor, sequence.descriptors)
+                .unwrap();
+        }
+        let decoder = DecoderBuilder::new().master_table(10, tables).build();
+        let bufr = decoder.read_bufr_message(&file[start..]).unwrap();
+        assert_eq!(bufr.master_table(), 10);
+        assert_eq!(bufr.subsets().len(), 1);
+        assert!(bufr.soundings().is_empty());
+    }
+
+    #[test]
+    fn test_exact_decimals() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let decoder = DecoderBuilder::new().exact_decimals(true).build();
+        let exact = decoder.read_bufr_message(&file[start..]).unwrap();
+        let float = crate::read_bufr_message(&file[start..]).unwrap();
+
+        let mut decimals = 0;
+        let mut elements = vec![];
+        crate::sounding::collect_elements(&exact.section_4.subsets()[0], &mut elements, true);
+        for (_, value) in elements {
+            if let Value::Decimal(decimal) = value {
+                assert_eq!(decimal.to_string().parse::<f64>().ok(), value.as_f64());
+                decimals += 1;
+            }
+        }
+        assert!(decimals > 4879);
+        assert_eq!(exact.soundings()[0].levels(), float.soundings()[0].levels());
+    }
+}
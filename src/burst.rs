@@ -0,0 +1,144 @@
+use crate::sounding::{Level, Phase, Sounding};
+
+/// Where the balloon burst in a high resolution ascent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Burst {
+    /// Index of the highest level, the last level of the ascent.
+    pub index: usize,
+    /// Pa
+    pub pressure: Option<f64>,
+    /// gpm
+    pub height: Option<f64>,
+    /// Seconds since launch.
+    pub time_offset: Option<f64>,
+}
+
+impl Sounding {
+    /// How far below the highest level (gpm) an ascent has to fall before it counts as a burst.
+    pub const BURST_HEIGHT_DROP: f64 = 100.0;
+
+    /// How much higher than the lowest pressure (relative) an ascent has to get before it counts
+    /// as a burst, for levels without heights.
+    pub const BURST_PRESSURE_RISE: f64 = 0.05;
+
+    /// Find the burst in an ascent whose levels continue past the top of the flight.
+    ///
+    /// The top is the highest level, or the lowest pressure if there are no heights. It counts as
+    /// a burst if a later level is back down by `BURST_HEIGHT_DROP` or `BURST_PRESSURE_RISE`, so
+    /// the ascent rate changed sign. Returns `None` for descents and ascents that end at the top.
+    pub fn burst(&self) -> Option<Burst> {
+        if self.phase() != Phase::Ascent {
+            return None;
+        }
+        let levels = self.levels();
+
+        let highest = |get: fn(&Level) -> Option<f64>| {
+            levels
+                .iter()
+                .enumerate()
+                .filter_map(|(i, lvl)| Some((i, get(lvl)?)))
+                .fold(None, |max: Option<(usize, f64)>, (i, v)| match max {
+                    Some((_, m)) if m >= v => max,
+                    _ => Some((i, v)),
+                })
+        };
+
+        let index = if let Some((top, z_max)) = highest(|lvl| lvl.height) {
+            levels[top..]
+                .iter()
+                .filter_map(|lvl| lvl.height)
+                .any(|z| z < z_max - Self::BURST_HEIGHT_DROP)
+                .then_some(top)
+        } else {
+            let (top, neg_p_min) = highest(|lvl| lvl.pressure.map(|p| -p))?;
+            let p_min = -neg_p_min;
+            levels[top..]
+                .iter()
+                .filter_map(|lvl| lvl.pressure)
+                .any(|p| p > p_min * (1.0 + Self::BURST_PRESSURE_RISE))
+                .then_some(top)
+        }?;
+
+        let lvl = &levels[index];
+        Some(Burst {
+            index,
+            pressure: lvl.pressure,
+            height: lvl.height,
+            time_offset: lvl.time_offset,
+        })
+    }
+
+    /// Split an ascent at the burst into the ascent and the descent after it. Without a burst
+    /// the whole sounding is the ascent.
+    pub fn split_at_burst(&self) -> (Sounding, Option<Sounding>) {
+        let Some(burst) = self.burst() else {
+            return (self.clone(), None);
+        };
+
+        let part = |phase: Phase, levels: &[Level]| Sounding {
+            phase,
+            ..self.with_levels(levels.to_vec())
+        };
+
+        let (ascent, descent) = self.levels().split_at(burst.index + 1);
+
+        (
+            part(Phase::Ascent, ascent),
+            Some(part(Phase::Descent, descent)),
+        )
+    }
+
+    /// The ascent with any levels after the burst removed.
+    pub fn truncate_at_burst(&self) -> Sounding {
+        self.split_at_burst().0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        sounding::{Level, Phase, Sounding, Station},
+        ReportType,
+    };
+
+    #[test]
+    fn test_burst() {
+        let level = |t: f64, z: f64| Level {
+            time_offset: Some(t),
+            height: Some(z),
+            ..Level::default()
+        };
+        let ascent = vec![
+            level(0.0, 1000.0),
+            level(10.0, 30_000.0),
+            level(20.0, 31_000.0),
+            level(30.0, 30_950.0),
+        ];
+        let mut with_descent = ascent.clone();
+        with_descent.push(level(40.0, 29_000.0));
+
+        let sounding =
+            |levels| Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        // Wobbling at the top isn't a burst.
+        assert!(sounding(ascent).burst().is_none());
+
+        let mut sounding = sounding(with_descent);
+        sounding.set_update_number(1);
+        sounding.set_report_type(Some(ReportType::Temp));
+        let burst = sounding.burst().unwrap();
+        assert_eq!(burst.index, 2);
+        assert_eq!(burst.height, Some(31_000.0));
+
+        let (ascent, descent) = sounding.split_at_burst();
+        assert_eq!(ascent.levels().len(), 3);
+        let descent = descent.unwrap();
+        assert_eq!(descent.phase(), Phase::Descent);
+        assert_eq!(descent.levels().len(), 2);
+        for part in [&ascent, &descent] {
+            assert_eq!(part.update_number(), 1);
+            assert_eq!(part.report_type(), Some(ReportType::Temp));
+        }
+        assert_eq!(sounding.truncate_at_burst().levels().len(), 3);
+    }
+}
@@ -0,0 +1,379 @@
+use crate::{
+    fingerprint::{message_fingerprint, Fnv1a},
+    index::parse_time,
+    sounding::{Level, Phase, Sounding, Station},
+    MessageDecoder,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    hash::Hasher,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// Remembers the soundings decoded from each message, so the same bulletin arriving by several
+/// routes is only decoded once.
+///
+/// Messages are keyed by their `message_fingerprint` and the settings of the decoder that change
+/// what is decoded, such as table overrides, master tables and humidity correction, so soundings
+/// decoded one way aren't returned for a decoder set up another. A `table_provider` or
+/// `unknown_descriptors` handler can't be part of the key, so give decoders that differ in those
+/// their own directories. The most recently used `capacity` messages are kept in memory, and
+/// with `with_directory` every message is also saved to disk so the cache survives between runs.
+#[derive(Clone, Debug)]
+pub struct SoundingCache {
+    capacity: usize,
+    entries: HashMap<u64, Vec<Sounding>>,
+    // Least recently used first.
+    order: VecDeque<u64>,
+    dir: Option<PathBuf>,
+}
+
+const CACHE_HEADER: &str = "sonde-bufr cache 1";
+
+impl SoundingCache {
+    pub fn new(capacity: usize) -> Self {
+        SoundingCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            dir: None,
+        }
+    }
+
+    /// Also keep the soundings in files under `dir`, which is created if needed.
+    pub fn with_directory(mut self, dir: impl Into<PathBuf>) -> Result<Self, Box<dyn Error>> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        self.dir = Some(dir);
+        Ok(self)
+    }
+
+    /// The soundings for a message (from `BUFR` through `7777`, see `read_bufr_bytes`), decoding
+    /// it with `decoder` if it isn't cached.
+    pub fn decode(
+        &mut self,
+        message: &[u8],
+        decoder: &MessageDecoder,
+    ) -> Result<Vec<Sounding>, Box<dyn Error>> {
+        if let Some(soundings) = self.get(message, decoder)? {
+            return Ok(soundings);
+        }
+
+        let soundings = decoder.read_bufr_message(message)?.soundings();
+        self.insert(message, decoder, soundings.clone())?;
+        Ok(soundings)
+    }
+
+    /// The soundings previously stored for a message decoded with `decoder`, if any.
+    pub fn get(
+        &mut self,
+        message: &[u8],
+        decoder: &MessageDecoder,
+    ) -> Result<Option<Vec<Sounding>>, Box<dyn Error>> {
+        let key = cache_key(message, decoder);
+
+        if let Some(soundings) = self.entries.get(&key) {
+            let soundings = soundings.clone();
+            self.touch(key);
+            return Ok(Some(soundings));
+        }
+
+        match self.path(key) {
+            Some(path) if path.exists() => {
+                let soundings = read_soundings(BufReader::new(File::open(path)?))?;
+                self.remember(key, soundings.clone());
+                Ok(Some(soundings))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Store the soundings decoded from a message with `decoder`.
+    pub fn insert(
+        &mut self,
+        message: &[u8],
+        decoder: &MessageDecoder,
+        soundings: Vec<Sounding>,
+    ) -> Result<(), Box<dyn Error>> {
+        let key = cache_key(message, decoder);
+
+        if let Some(path) = self.path(key) {
+            // Write to a temporary file first so a reader never sees half an entry.
+            let tmp = path.with_extension("tmp");
+            let mut w = BufWriter::new(File::create(&tmp)?);
+            write_soundings(&mut w, &soundings)?;
+            w.into_inner().map_err(|err| err.into_error())?;
+            fs::rename(tmp, path)?;
+        }
+
+        self.remember(key, soundings);
+        Ok(())
+    }
+
+    /// The number of messages held in memory.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn remember(&mut self, key: u64, soundings: Vec<Sounding>) {
+        if self.entries.insert(key, soundings).is_some() {
+            self.touch(key);
+            return;
+        }
+
+        self.order.push_back(key);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn touch(&mut self, key: u64) {
+        if let Some(i) = self.order.iter().position(|&k| k == key) {
+            self.order.remove(i);
+            self.order.push_back(key);
+        }
+    }
+
+    fn path(&self, key: u64) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{:016x}.txt", key)))
+    }
+}
+
+fn cache_key(message: &[u8], decoder: &MessageDecoder) -> u64 {
+    let mut hasher = Fnv1a::default();
+    hasher.write(&message_fingerprint(message).to_be_bytes());
+    hasher.write(&decoder.settings_fingerprint().to_be_bytes());
+    hasher.finish()
+}
+
+/// One `S` line per sounding followed by an `L` line per level, tab separated with empty fields
+/// for missing values.
+fn write_soundings(mut w: impl Write, soundings: &[Sounding]) -> Result<(), Box<dyn Error>> {
+    writeln!(w, "{}", CACHE_HEADER)?;
+
+    for sounding in soundings {
+        let station = sounding.station();
+        let time = sounding
+            .launch_time()
+            .map(|t| {
+                format!(
+                    "{:04}{:02}{:02}{:02}{:02}{:02}",
+                    t.year, t.month, t.day, t.hour, t.minute, t.second
+                )
+            })
+            .unwrap_or_default();
+        let phase = match sounding.phase() {
+            Phase::Ascent => "A",
+            Phase::Descent => "D",
+        };
+
+        writeln!(
+            w,
+            "S\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            phase,
+            field(station.wmo_block),
+            field(station.wmo_station),
+            station.call_sign.as_deref().unwrap_or_default(),
+            field(station.latitude),
+            field(station.longitude),
+            field(station.elevation),
+            time,
+            field(sounding.radiosonde_type()),
+            sounding.update_number(),
+            sounding.report_type().map_or("", |r| r.tag()),
+        )?;
+
+        for lvl in sounding.levels() {
+            writeln!(
+                w,
+                "L\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                field(lvl.time_offset),
+                field(lvl.significance),
+                field(lvl.pressure),
+                field(lvl.height),
+                field(lvl.temperature),
+                field(lvl.dewpoint),
+                field(lvl.relative_humidity),
+                field(lvl.wind_direction),
+                field(lvl.wind_speed),
+                field(lvl.lat_displacement),
+                field(lvl.lon_displacement),
+                lvl.derived,
+                lvl.qc,
+                field(lvl.gnss_height),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_soundings(r: impl BufRead) -> Result<Vec<Sounding>, Box<dyn Error>> {
+    let mut lines = r.lines();
+    if lines.next().transpose()?.as_deref() != Some(CACHE_HEADER) {
+        return Err("Not a sonde-bufr cache file".into());
+    }
+
+    // Each sounding is read without its levels, then rebuilt with them at the next `S` line.
+    let mut soundings = vec![];
+    let mut current: Option<(Sounding, Vec<Level>)> = None;
+    let finish = |soundings: &mut Vec<Sounding>, current: Option<(Sounding, Vec<Level>)>| {
+        if let Some((header, levels)) = current {
+            soundings.push(header.with_levels(levels));
+        }
+    };
+
+    for line in lines {
+        let line = line?;
+        let fields: Vec<&str> = line.split('\t').collect();
+
+        match fields.as_slice() {
+            ["S", phase, block, number, call_sign, lat, lon, elev, time, sonde, update, report] => {
+                finish(&mut soundings, current.take());
+
+                let phase = match *phase {
+                    "A" => Phase::Ascent,
+                    "D" => Phase::Descent,
+                    _ => return Err(format!("Invalid cache line: {}", line).into()),
+                };
+                let station = Station {
+                    wmo_block: parse(block)?,
+                    wmo_station: parse(number)?,
+                    call_sign: (!call_sign.is_empty()).then(|| call_sign.to_string()),
+                    latitude: parse(lat)?,
+                    longitude: parse(lon)?,
+                    elevation: parse(elev)?,
+                };
+                let time = if time.is_empty() {
+                    None
+                } else {
+                    Some(parse_time(time)?)
+                };
+
+                let mut sounding = Sounding::new(phase, station, time, parse(sonde)?, vec![]);
+                sounding.set_update_number(update.parse()?);
+                if !report.is_empty() {
+                    sounding.set_report_type(Some(report.parse()?));
+                }
+                current = Some((sounding, vec![]));
+            }
+            ["L", t, sig, p, z, temp, td, rh, dir, spd, lat, lon, derived, qc, gnss] => {
+                let (_, levels) = current.as_mut().ok_or("Cache level before any sounding")?;
+                levels.push(Level {
+                    time_offset: parse(t)?,
+                    significance: parse(sig)?,
+                    pressure: parse(p)?,
+                    height: parse(z)?,
+                    gnss_height: parse(gnss)?,
+                    temperature: parse(temp)?,
+                    dewpoint: parse(td)?,
+                    relative_humidity: parse(rh)?,
+                    wind_direction: parse(dir)?,
+                    wind_speed: parse(spd)?,
+                    lat_displacement: parse(lat)?,
+                    lon_displacement: parse(lon)?,
+                    derived: derived.parse()?,
+                    qc: qc.parse()?,
+                });
+            }
+            _ => return Err(format!("Invalid cache line: {}", line).into()),
+        }
+    }
+    finish(&mut soundings, current);
+
+    Ok(soundings)
+}
+
+/// Floats are written with `Display`, which round trips exactly.
+fn field<T: Display>(val: Option<T>) -> String {
+    val.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn parse<T>(field: &str) -> Result<Option<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Error + 'static,
+{
+    if field.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(field.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{read_bufr_bytes, scan_to_bufr_start, DecoderBuilder, TableOverrides};
+
+    const LOCAL_ELEMENT: &str =
+        "[[element]]\ndescriptor = \"0-63-001\"\nunits = \"K\"\nwidth_bits = 12\n";
+
+    #[test]
+    fn test_sounding_cache() {
+        let mut f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+        scan_to_bufr_start(&mut f).unwrap();
+        let message = read_bufr_bytes(&mut f).unwrap();
+        let decoder = DecoderBuilder::new().build();
+
+        let dir = std::env::temp_dir().join(format!("sonde-bufr-cache-{}", std::process::id()));
+        let mut cache = SoundingCache::new(1).with_directory(&dir).unwrap();
+        assert!(cache.get(&message, &decoder).unwrap().is_none());
+
+        let decoded = cache.decode(&message, &decoder).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        // A fresh cache over the same directory reads the soundings back from disk.
+        let mut cache = SoundingCache::new(1).with_directory(&dir).unwrap();
+        let cached = cache.get(&message, &decoder).unwrap().unwrap();
+
+        assert_eq!(cached.len(), decoded.len());
+        assert_eq!(cached[0].station(), decoded[0].station());
+        assert_eq!(cached[0].launch_time(), decoded[0].launch_time());
+        assert_eq!(cached[0].report_type(), Some(crate::ReportType::TempMobil));
+        assert_eq!(cached[0].levels(), decoded[0].levels());
+
+        // A decoder with other settings decodes the message again.
+        let corrected = DecoderBuilder::new().correct_humidity(true).build();
+        assert!(cache.get(&message, &corrected).unwrap().is_none());
+        let overridden = DecoderBuilder::new()
+            .table_overrides(TableOverrides::from_toml(LOCAL_ELEMENT).unwrap())
+            .build();
+        assert!(cache.get(&message, &overridden).unwrap().is_none());
+        let same = DecoderBuilder::new()
+            .table_overrides(TableOverrides::from_toml(LOCAL_ELEMENT).unwrap())
+            .build();
+        assert_eq!(
+            same.settings_fingerprint(),
+            overridden.settings_fingerprint()
+        );
+
+        // Only the most recent message stays in memory.
+        cache.insert(b"not a message", &decoder, vec![]).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert!(cache
+            .get(b"not a message", &decoder)
+            .unwrap()
+            .unwrap()
+            .is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Every line of this cache version has all its fields.
+        let short = format!("{}\nS\tA\t72\t776\t\t\t\t\t\t\t0\n", CACHE_HEADER);
+        assert!(read_soundings(short.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use crate::sounding::{Level, Sounding};
+use std::fmt::Display;
+
+/// Differences (BUFR minus TAC) at a level reported in both forms of a sounding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LevelDifference {
+    /// Pressure of the TAC level in Pa.
+    pub pressure: f64,
+    /// K
+    pub temperature: Option<f64>,
+    /// K
+    pub dewpoint: Option<f64>,
+    /// gpm
+    pub height: Option<f64>,
+    /// Degrees, wrapped into -180 to 180.
+    pub wind_direction: Option<f64>,
+    /// m/s
+    pub wind_speed: Option<f64>,
+}
+
+/// Level by level audit of a TAC TEMP report against the BUFR message for the same launch.
+#[derive(Clone, Debug, Default)]
+pub struct ComparisonReport {
+    pub matched: Vec<LevelDifference>,
+    /// Pressures (Pa) of TAC levels with no BUFR level close enough to compare.
+    pub only_in_tac: Vec<f64>,
+    /// Pressures (Pa) of BUFR levels flagged as standard or significant with no TAC level.
+    pub only_in_bufr: Vec<f64>,
+}
+
+/// The significance bits of levels that a TAC report would also carry.
+const REPORTED_LEVELS: u32 = Level::SURFACE
+    | Level::STANDARD
+    | Level::TROPOPAUSE
+    | Level::MAX_WIND
+    | Level::SIG_TEMPERATURE
+    | Level::SIG_HUMIDITY
+    | Level::SIG_WIND;
+
+/// Compare TAC levels to BUFR levels close in pressure, treating levels more than
+/// `pressure_tolerance` Pa apart as unmatched. Each BUFR level is compared to at most one TAC
+/// level, the closest pairs are matched first.
+pub fn compare_tac_bufr(
+    tac: &Sounding,
+    bufr: &Sounding,
+    pressure_tolerance: f64,
+) -> ComparisonReport {
+    let mut report = ComparisonReport::default();
+
+    // (TAC index, BUFR index, pressure difference) of every pair close enough to compare.
+    let mut pairs: Vec<(usize, usize, f64)> = vec![];
+    for (i, tac_lvl) in tac.levels().iter().enumerate() {
+        let Some(pressure) = tac_lvl.pressure else {
+            continue;
+        };
+        pairs.extend(
+            bufr.levels()
+                .iter()
+                .enumerate()
+                .filter_map(|(j, lvl)| lvl.pressure.map(|p| (i, j, (p - pressure).abs())))
+                .filter(|(_, _, dp)| *dp <= pressure_tolerance),
+        );
+    }
+    pairs.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+    let mut used = vec![false; bufr.levels().len()];
+    let mut partner: Vec<Option<usize>> = vec![None; tac.levels().len()];
+    for (i, j, _) in pairs {
+        if partner[i].is_none() && !used[j] {
+            partner[i] = Some(j);
+            used[j] = true;
+        }
+    }
+
+    for (tac_lvl, partner) in tac.levels().iter().zip(partner) {
+        let Some(pressure) = tac_lvl.pressure else {
+            continue;
+        };
+
+        match partner {
+            Some(j) => {
+                let bufr_lvl = &bufr.levels()[j];
+                let diff = |a: Option<f64>, b: Option<f64>| a.zip(b).map(|(a, b)| a - b);
+
+                report.matched.push(LevelDifference {
+                    pressure,
+                    temperature: diff(bufr_lvl.temperature, tac_lvl.temperature),
+                    dewpoint: diff(bufr_lvl.dewpoint, tac_lvl.dewpoint),
+                    height: diff(bufr_lvl.height, tac_lvl.height),
+                    wind_direction: diff(bufr_lvl.wind_direction, tac_lvl.wind_direction)
+                        .map(|d| (d + 540.0) % 360.0 - 180.0),
+                    wind_speed: diff(bufr_lvl.wind_speed, tac_lvl.wind_speed),
+                });
+            }
+            None => report.only_in_tac.push(pressure),
+        }
+    }
+
+    report.only_in_bufr = bufr
+        .levels()
+        .iter()
+        .zip(used)
+        .filter(|(lvl, used)| !used && lvl.has_significance(REPORTED_LEVELS))
+        .filter_map(|(lvl, _)| lvl.pressure)
+        .collect();
+
+    report
+}
+
+impl Display for ComparisonReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let cell = |v: Option<f64>| match v {
+            Some(v) => format!("{:8.1}", v),
+            None => format!("{:>8}", "-"),
+        };
+
+        writeln!(
+            f,
+            "{:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "hPa", "dT", "dTd", "dZ", "dDir", "dSpd"
+        )?;
+        for diff in &self.matched {
+            writeln!(
+                f,
+                "{:8.1} {} {} {} {} {}",
+                diff.pressure / 100.0,
+                cell(diff.temperature),
+                cell(diff.dewpoint),
+                cell(diff.height),
+                cell(diff.wind_direction),
+                cell(diff.wind_speed)
+            )?;
+        }
+        writeln!(f)?;
+
+        write!(f, "Only in TAC (hPa):")?;
+        for p in &self.only_in_tac {
+            write!(f, " {:.1}", p / 100.0)?;
+        }
+        writeln!(f)?;
+
+        write!(f, "Only in BUFR (hPa):")?;
+        for p in &self.only_in_bufr {
+            write!(f, " {:.1}", p / 100.0)?;
+        }
+        writeln!(f)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    fn sounding(levels: Vec<Level>) -> Sounding {
+        Sounding::new(Phase::Ascent, Station::default(), None, None, levels)
+    }
+
+    #[test]
+    fn test_compare_tac_bufr() {
+        let level = |hpa: f64, t: f64, dir: f64, sig: u32| Level {
+            significance: Some(sig),
+            pressure: Some(hpa * 100.0),
+            temperature: Some(t),
+            wind_direction: Some(dir),
+            ..Level::default()
+        };
+
+        let tac = sounding(vec![
+            level(850.0, 280.0, 350.0, Level::STANDARD),
+            level(500.0, 260.0, 270.0, Level::STANDARD),
+        ]);
+        let bufr = sounding(vec![
+            level(850.1, 280.5, 10.0, Level::STANDARD),
+            level(700.0, 270.0, 270.0, Level::STANDARD),
+            level(650.0, 268.0, 270.0, 0),
+        ]);
+
+        let report = compare_tac_bufr(&tac, &bufr, 50.0);
+        assert_eq!(report.matched.len(), 1);
+        assert!((report.matched[0].temperature.unwrap() - 0.5).abs() < 1.0e-9);
+        assert!((report.matched[0].wind_direction.unwrap() - 20.0).abs() < 1.0e-9);
+        assert_eq!(report.only_in_tac, vec![50000.0]);
+        assert_eq!(report.only_in_bufr, vec![70000.0]);
+
+        // Two TAC levels near one BUFR level, only the closer one is matched.
+        let tac = sounding(vec![
+            level(850.0, 280.0, 350.0, Level::STANDARD),
+            level(849.7, 280.0, 350.0, Level::SIG_TEMPERATURE),
+        ]);
+        let bufr = sounding(vec![level(849.9, 280.5, 350.0, Level::STANDARD)]);
+        let report = compare_tac_bufr(&tac, &bufr, 50.0);
+        assert_eq!(report.matched.len(), 1);
+        assert_eq!(report.matched[0].pressure, 85000.0);
+        assert_eq!(report.only_in_tac, vec![849.7 * 100.0]);
+        assert!(report.only_in_bufr.is_empty());
+    }
+}
@@ -0,0 +1,415 @@
+//! Section 4 data compressed across subsets (regulation 94.6.3). Each element is written once
+//! for all the subsets, as a reference value, the width of the increments (NBINC), and an
+//! increment for each subset. Character elements have a reference of zeros and NBINC counts
+//! octets, with the text of each subset in place of an increment.
+
+use crate::{
+    bit_buffer::{add_reference, BitBuffer},
+    builder::check_budget,
+    section3::Descriptor,
+    section4::{
+        scaled_value, text_value, DataNode, Decoder, ElementBits, Operators, TableBEntry, Value,
+        ValueSource,
+    },
+    trace::TraceEntry,
+    DecodeOptions,
+};
+use std::error::Error;
+
+/// The width of NBINC.
+pub(crate) const INCREMENT_WIDTH_BITS: usize = 6;
+
+/// Reads a whole column of values, one per subset, for each element the decoder asks for and
+/// gives the decoder the first subset's value.
+struct CompressedSource<'a, 'b> {
+    bits: &'a mut BitBuffer<'b>,
+    num_subsets: usize,
+    // The values of each element read, in order, with their raw integers.
+    columns: Vec<Vec<(Value, Option<u64>)>>,
+    // The first subset's raw integer for the last element read.
+    last_raw: Option<u64>,
+}
+
+impl CompressedSource<'_, '_> {
+    fn read_increment_width(&mut self) -> Result<usize, Box<dyn Error>> {
+        // All ones is a width of 63 here, not a missing value.
+        let width = self.bits.read_u64(INCREMENT_WIDTH_BITS)?;
+        Ok(width.map_or((1 << INCREMENT_WIDTH_BITS) - 1, |w| w as usize))
+    }
+
+    /// The raw integer of each subset, `None` where it's missing. With an NBINC of zero every
+    /// subset has the reference value, and a missing reference is missing in every subset.
+    fn read_numbers(&mut self, width: usize) -> Result<Vec<Option<u64>>, Box<dyn Error>> {
+        let reference = self.bits.read_u64(width)?;
+        let increment_width = self.read_increment_width()?;
+        if increment_width == 0 {
+            return Ok(vec![reference; self.num_subsets]);
+        }
+
+        let mut column = Vec::with_capacity(self.num_subsets);
+        for _ in 0..self.num_subsets {
+            let increment = self.bits.read_u64(increment_width)?;
+            column.push(reference.zip(increment).and_then(|(r, i)| r.checked_add(i)));
+        }
+        Ok(column)
+    }
+
+    fn read_texts(&mut self, width: usize) -> Result<Vec<Value>, Box<dyn Error>> {
+        let reference = self.bits.read_bytes(width)?;
+        let num_octets = self.read_increment_width()?;
+        if num_octets == 0 {
+            return Ok(vec![text_value(reference); self.num_subsets]);
+        }
+
+        (0..self.num_subsets)
+            .map(|_| Ok(text_value(self.bits.read_bytes(8 * num_octets)?)))
+            .collect()
+    }
+}
+
+impl ValueSource for CompressedSource<'_, '_> {
+    fn read_value(
+        &mut self,
+        entry: &TableBEntry,
+        ops: &Operators,
+    ) -> Result<Value, Box<dyn Error>> {
+        let column: Vec<(Value, Option<u64>)> = match ElementBits::new(entry, ops)? {
+            ElementBits::Text(bits) => self
+                .read_texts(bits)?
+                .into_iter()
+                .map(|text| (text, None))
+                .collect(),
+            ElementBits::Bytes(bits) => {
+                return Err(
+                    format!("Compressed elements {} bits wide aren't supported", bits).into(),
+                )
+            }
+            ElementBits::Integer { width, reference } => self
+                .read_numbers(width)?
+                .into_iter()
+                .map(|raw| match raw {
+                    Some(raw) => Ok((Value::Integer(add_reference(raw, reference)?), Some(raw))),
+                    None => Ok((Value::Missing, None)),
+                })
+                .collect::<Result<_, Box<dyn Error>>>()?,
+            ElementBits::Float {
+                width,
+                reference,
+                scale,
+            } => self
+                .read_numbers(width)?
+                .into_iter()
+                .map(|raw| match raw {
+                    Some(raw) => {
+                        let value =
+                            scaled_value(add_reference(raw, reference)?, scale, ops.decimals);
+                        Ok((value, Some(raw)))
+                    }
+                    None => Ok((Value::Missing, None)),
+                })
+                .collect::<Result<_, Box<dyn Error>>>()?,
+        };
+
+        let (first, raw) = column.first().cloned().unwrap_or((Value::Missing, None));
+        self.last_raw = raw;
+        self.columns.push(column);
+        Ok(first)
+    }
+
+    fn read_replication_factor(&mut self, entry: &TableBEntry) -> Result<usize, Box<dyn Error>> {
+        let column = self.read_numbers(entry.width_bits)?;
+        let first = column.first().copied().flatten();
+        if column.iter().any(|&factor| factor != first) {
+            return Err("Delayed replication factors differ between compressed subsets".into());
+        }
+        Ok(first.map(usize::try_from).transpose()?.unwrap_or(0))
+    }
+
+    fn position(&self) -> usize {
+        self.bits.bit_offset()
+    }
+
+    fn discard_last(&mut self) {
+        self.columns.pop();
+    }
+
+    fn last_raw(&self) -> Option<u64> {
+        self.last_raw
+    }
+}
+
+/// The subsets, the trace, and the warnings of a Section 4.
+type Subsets = (Vec<Vec<DataNode>>, Vec<TraceEntry>, Vec<String>);
+
+/// Decode every subset of compressed data. The descriptors are only walked once, so the trace
+/// is of subset 0 and each entry covers the element's values for all subsets.
+pub(crate) fn read_compressed_subsets(
+    bits: &mut BitBuffer,
+    descriptors: &[Descriptor],
+    num_subsets: usize,
+    options: &DecodeOptions,
+) -> Result<Subsets, Box<dyn Error>> {
+    let mut source = CompressedSource {
+        bits,
+        num_subsets,
+        columns: vec![],
+        last_raw: None,
+    };
+
+    let mut decoder = Decoder::new(&mut source)
+        .with_overrides(options.overrides)
+        .with_cancel(options.cancel)
+        .with_budget(options.budget, 0)
+        .with_decimals(options.decimals)
+        .with_unknown(options.unknown);
+    if options.trace {
+        decoder = decoder.with_trace(0);
+    }
+    let first = decoder.decode_descriptors(descriptors)?;
+    let trace = decoder.take_trace();
+    let warnings = decoder.take_warnings();
+    // Every subset gets a copy of the tree.
+    check_budget(options.budget, decoder.used().saturating_mul(num_subsets))?;
+
+    let subsets = (0..num_subsets)
+        .map(|subset| {
+            let mut nodes = first.clone();
+            set_values(&mut nodes, &mut source.columns.iter(), subset);
+            nodes
+        })
+        .collect();
+
+    Ok((subsets, trace, warnings))
+}
+
+/// Fill in one subset's values, in the order the elements were read.
+fn set_values<'a>(
+    nodes: &mut [DataNode],
+    columns: &mut impl Iterator<Item = &'a Vec<(Value, Option<u64>)>>,
+    subset: usize,
+) {
+    for node in nodes {
+        match node {
+            DataNode::Element { value, raw, .. } => {
+                if let Some(column) = columns.next() {
+                    (*value, *raw) = column[subset].clone();
+                }
+            }
+            DataNode::Sequence { children, .. } => set_values(children, columns, subset),
+            DataNode::Replication { repetitions, .. } => {
+                for repetition in repetitions {
+                    set_values(repetition, columns, subset);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        bit_writer::BitWriter, encode::Section3Builder, read_bufr_message, section3::Descriptor,
+        DataNode, MessageHeader, Timestamp, Value,
+    };
+
+    fn header() -> MessageHeader {
+        MessageHeader::new(Timestamp {
+            year: 2024,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        })
+    }
+
+    /// An uncompressed message for `descriptors` and `subsets`, with its Section 4 replaced by
+    /// compressed `data` and the compressed flag set.
+    fn compressed_message(
+        descriptors: &[Descriptor],
+        subsets: &[Vec<DataNode>],
+        data: Vec<u8>,
+    ) -> Vec<u8> {
+        let message = Section3Builder::new()
+            .descriptors(descriptors)
+            .build()
+            .unwrap()
+            .encode(&header(), subsets)
+            .unwrap();
+
+        // Sections 0 and 1 are 8 and 22 octets.
+        let section_3 = 30;
+        let section_4 = section_3 + crate::read_3_octet_usize(&message[section_3..]).unwrap();
+        let mut out = message[..section_4].to_vec();
+        out[section_3 + 6] |= 0x40;
+        out.extend_from_slice(&(4 + data.len() as u32).to_be_bytes()[1..]);
+        out.push(0);
+        out.extend(data);
+        out.extend_from_slice(b"7777");
+        let total = out.len() as u32;
+        out[4..7].copy_from_slice(&total.to_be_bytes()[1..]);
+        out
+    }
+
+    #[test]
+    fn test_compressed_subsets() {
+        let name = Descriptor::new(0, 1, 15);
+        let block = Descriptor::new(0, 1, 1);
+        let temperature = Descriptor::new(0, 12, 101);
+        let pressure = Descriptor::new(0, 7, 4);
+        let descriptors = [
+            name,
+            block,
+            temperature,
+            Descriptor::new(1, 1, 0),
+            Descriptor::new(0, 31, 1),
+            pressure,
+        ];
+
+        let subset = |station: Value, t: Value, p1: f64, p2: f64| {
+            let element = |descriptor, value| DataNode::Element {
+                descriptor,
+                value,
+                raw: None,
+            };
+            vec![
+                element(name, station),
+                element(block, Value::Integer(72)),
+                element(temperature, t),
+                DataNode::Replication {
+                    descriptor: Descriptor::new(1, 1, 0),
+                    repetitions: vec![
+                        vec![element(pressure, Value::Float(p1))],
+                        vec![element(pressure, Value::Float(p2))],
+                    ],
+                },
+            ]
+        };
+        let text = |s: &str| Value::Text(format!("{:20}", s));
+        let subsets = [
+            subset(text("GREAT FALLS"), Value::Float(280.0), 85_000.0, 50_000.0),
+            subset(text("GLASGOW"), Value::Float(282.15), 85_000.0, 50_010.0),
+            subset(Value::Missing, Value::Missing, 85_000.0, 50_020.0),
+        ];
+
+        let mut w = BitWriter::new();
+        // Station names: a reference of zeros, then 20 octets for each subset.
+        for _ in 0..20 {
+            w.write_u64(0, 8).unwrap();
+        }
+        w.write_u64(20, 6).unwrap();
+        w.write_text("GREAT FALLS", 160).unwrap();
+        w.write_text("GLASGOW", 160).unwrap();
+        w.write_missing(160);
+        // The same WMO block in every subset.
+        w.write_u64(72, 7).unwrap();
+        w.write_u64(0, 6).unwrap();
+        // Temperatures of 280 K plus 0, 2.15, and missing.
+        w.write_u64(28_000, 16).unwrap();
+        w.write_u64(8, 6).unwrap();
+        w.write_u64(0, 8).unwrap();
+        w.write_u64(215, 8).unwrap();
+        w.write_missing(8);
+        // Two repetitions in every subset.
+        w.write_u64(2, 8).unwrap();
+        w.write_u64(0, 6).unwrap();
+        w.write_u64(8_500, 14).unwrap();
+        w.write_u64(0, 6).unwrap();
+        // 500 hPa plus 0, 1, and 2 in units of 10 Pa.
+        w.write_u64(5_000, 14).unwrap();
+        w.write_u64(2, 6).unwrap();
+        for inc in 0..3 {
+            w.write_u64(inc, 2).unwrap();
+        }
+
+        let message = compressed_message(&descriptors, &subsets, w.into_bytes());
+        let bufr = read_bufr_message(message.as_slice()).unwrap();
+        let mut decoded = bufr.section_4.subsets().to_vec();
+        decoded
+            .iter_mut()
+            .for_each(|nodes| DataNode::clear_raw(nodes));
+        assert!(matches!(
+            &decoded[2][0],
+            DataNode::Element {
+                value: Value::Missing,
+                ..
+            }
+        ));
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", subsets));
+
+        // Each subset keeps its own raw integers.
+        let raw = |subset: usize| match &bufr.section_4.subsets()[subset][2] {
+            DataNode::Element { raw, .. } => *raw,
+            _ => None,
+        };
+        assert_eq!([raw(0), raw(1), raw(2)], [Some(28_000), Some(28_215), None]);
+
+        // The encoder compresses them the same way.
+        let encoded = Section3Builder::new()
+            .descriptors(&descriptors)
+            .build()
+            .unwrap()
+            .encode_compressed(&header(), &subsets)
+            .unwrap();
+        assert_eq!(encoded, message);
+    }
+
+    #[test]
+    fn test_compressed_shared_and_missing() {
+        let block = Descriptor::new(0, 1, 1);
+        let temperature = Descriptor::new(0, 12, 101);
+        let pressure = Descriptor::new(0, 7, 4);
+        let name = Descriptor::new(0, 1, 15);
+        let station = Descriptor::new(0, 1, 2);
+        let descriptors = [block, temperature, pressure, name, station];
+
+        let subset = |name_value: Value, number: i64| {
+            let element = |descriptor, value| DataNode::Element {
+                descriptor,
+                value,
+                raw: None,
+            };
+            vec![
+                element(block, Value::Integer(72)),
+                element(temperature, Value::Missing),
+                element(pressure, Value::Missing),
+                element(name, name_value),
+                element(station, Value::Integer(number)),
+            ]
+        };
+        let subsets = [subset(Value::Missing, 776), subset(Value::Missing, 777)];
+
+        let mut w = BitWriter::new();
+        // NBINC of zero: every subset has the reference value.
+        w.write_u64(72, 7).unwrap();
+        w.write_u64(0, 6).unwrap();
+        // A missing reference and NBINC of zero: missing in every subset.
+        w.write_missing(16);
+        w.write_u64(0, 6).unwrap();
+        // A missing reference with increments: still missing, but the increments are skipped.
+        w.write_missing(14);
+        w.write_u64(3, 6).unwrap();
+        w.write_u64(0, 3).unwrap();
+        w.write_u64(5, 3).unwrap();
+        // Missing text shared by every subset.
+        w.write_missing(160);
+        w.write_u64(0, 6).unwrap();
+        // Values after all that are still read from the right bits.
+        // A 1 bit increment of 1 would be missing, so they're 2 bits.
+        w.write_u64(776, 10).unwrap();
+        w.write_u64(2, 6).unwrap();
+        w.write_u64(0, 2).unwrap();
+        w.write_u64(1, 2).unwrap();
+
+        let encodable: Vec<Vec<DataNode>> = [776, 777]
+            .map(|number| subset(Value::Text(format!("{:20}", "")), number))
+            .to_vec();
+        let message = compressed_message(&descriptors, &encodable, w.into_bytes());
+        let bufr = read_bufr_message(message.as_slice()).unwrap();
+        let mut decoded = bufr.section_4.subsets().to_vec();
+        decoded
+            .iter_mut()
+            .for_each(|nodes| DataNode::clear_raw(nodes));
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", subsets));
+    }
+}
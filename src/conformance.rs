@@ -0,0 +1,285 @@
+use crate::{
+    data_category::DataCategory,
+    sounding::{Level, Phase, Sounding},
+    BufrMessage,
+};
+use std::fmt::Display;
+
+/// Standard pressure levels in Pa, the only levels that may carry `Level::STANDARD`.
+pub(crate) const STANDARD_LEVELS: [f64; 16] = [
+    100_000.0, 92_500.0, 85_000.0, 70_000.0, 50_000.0, 40_000.0, 30_000.0, 25_000.0, 20_000.0,
+    15_000.0, 10_000.0, 7_000.0, 5_000.0, 3_000.0, 2_000.0, 1_000.0,
+];
+
+/// A departure from the WMO regulations for reporting radiosonde data in BUFR (Manual on Codes,
+/// B/C 25).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// The B/C 25 paragraph the rule comes from, e.g. `B/C25.8.2`.
+    pub rule: &'static str,
+    pub subset: usize,
+    /// Index of the level in `Sounding::levels`, if the rule is about one level.
+    pub level: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{} subset {}", self.rule, self.subset)?;
+        if let Some(level) = self.level {
+            write!(f, " level {}", level)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Check a decoded message against the B/C 25 regulations: Section 1 and then each subset with
+/// `check_sounding`. Subsets without a profile are a violation too.
+pub fn check_conformance(message: &BufrMessage) -> Vec<Violation> {
+    let mut violations = vec![];
+    if message.category() != DataCategory::VerticalSoundings {
+        violations.push(Violation {
+            rule: "B/C25.1",
+            subset: 0,
+            level: None,
+            message: format!(
+                "data category is {}, soundings are category 2",
+                u8::from(message.category())
+            ),
+        });
+    }
+
+    for subset in 0..message.section_4.subsets().len() {
+        match message.subset_sounding(subset) {
+            Some(sounding) => violations.extend(
+                check_sounding(&sounding)
+                    .into_iter()
+                    .map(|v| Violation { subset, ..v }),
+            ),
+            None => violations.push(Violation {
+                rule: "B/C25.8",
+                subset,
+                level: None,
+                message: "no replication of levels".to_owned(),
+            }),
+        }
+    }
+
+    violations
+}
+
+/// Check one sounding, decoded or about to be encoded, against the B/C 25 regulations for the
+/// identification, position and launch time of the station and for the levels. Violations are
+/// reported for subset 0.
+pub fn check_sounding(sounding: &Sounding) -> Vec<Violation> {
+    let mut violations = vec![];
+    let mut violation = |rule, level, message: String| {
+        violations.push(Violation {
+            rule,
+            subset: 0,
+            level,
+            message,
+        })
+    };
+
+    let station = sounding.station();
+    if station.identifier().is_none() {
+        violation(
+            "B/C25.2",
+            None,
+            "no WMO block and station number or call sign".to_owned(),
+        );
+    }
+    if sounding.radiosonde_type().is_none() {
+        violation("B/C25.3", None, "no radiosonde type".to_owned());
+    }
+    if sounding.launch_time().is_none() {
+        violation("B/C25.4", None, "no launch time".to_owned());
+    }
+    match (station.latitude, station.longitude) {
+        (Some(lat), Some(lon)) => {
+            if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                violation(
+                    "B/C25.5",
+                    None,
+                    format!("position {}, {} is out of range", lat, lon),
+                );
+            }
+        }
+        _ => violation("B/C25.5", None, "no launch site position".to_owned()),
+    }
+    if station.elevation.is_none() {
+        violation("B/C25.6", None, "no launch site elevation".to_owned());
+    }
+
+    let levels = sounding.levels();
+    if levels.is_empty() {
+        violation("B/C25.8", None, "no levels".to_owned());
+    }
+
+    // Time and displacement are from the launch, so the launch level has neither.
+    let mut last_time = None;
+    for (i, lvl) in levels.iter().enumerate() {
+        if let Some(t) = lvl.time_offset {
+            if last_time.is_some_and(|last| t < last) {
+                violation(
+                    "B/C25.8.1",
+                    Some(i),
+                    format!("time {} s is before the level below", t),
+                );
+            }
+            last_time = Some(t);
+
+            if lvl.lat_displacement.is_none() || lvl.lon_displacement.is_none() {
+                violation(
+                    "B/C25.8.1",
+                    Some(i),
+                    "time without a displacement".to_owned(),
+                );
+            }
+            let displaced = [lvl.lat_displacement, lvl.lon_displacement]
+                .iter()
+                .any(|d| d.is_some_and(|d| d != 0.0));
+            if t == 0.0 && displaced && sounding.phase() == Phase::Ascent {
+                violation(
+                    "B/C25.8.1",
+                    Some(i),
+                    "displaced from the launch site at the launch time".to_owned(),
+                );
+            }
+        }
+    }
+
+    let mut surfaces = vec![];
+    for (i, lvl) in levels.iter().enumerate() {
+        if lvl.significance.is_none() {
+            violation("B/C25.8.2", Some(i), "no significance".to_owned());
+        }
+        if lvl.has_significance(Level::SURFACE) {
+            surfaces.push(i);
+        }
+        if lvl.has_significance(Level::STANDARD)
+            && !lvl.pressure.is_some_and(|p| STANDARD_LEVELS.contains(&p))
+        {
+            let message = match lvl.pressure {
+                Some(p) => format!("standard level at {} Pa", p),
+                None => "standard level without a pressure".to_owned(),
+            };
+            violation("B/C25.8.2", Some(i), message);
+        }
+        if lvl.pressure.is_none() && lvl.height.is_none() {
+            violation(
+                "B/C25.8.3",
+                Some(i),
+                "no pressure or height for the level".to_owned(),
+            );
+        }
+    }
+
+    if sounding.phase() == Phase::Ascent && !levels.is_empty() {
+        match surfaces.as_slice() {
+            [] => violation("B/C25.8.2", None, "no surface level".to_owned()),
+            [0] => {}
+            [i] => violation(
+                "B/C25.8.2",
+                Some(*i),
+                "the surface level isn't the first level".to_owned(),
+            ),
+            [_, rest @ ..] => {
+                for i in rest {
+                    violation(
+                        "B/C25.8.2",
+                        Some(*i),
+                        "more than one surface level".to_owned(),
+                    );
+                }
+            }
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{read_bufr_message, scan_to_bufr_start, sounding::Station};
+    use std::{fs::File, io::BufReader};
+
+    #[test]
+    fn test_check_sounding() {
+        let level = |hpa: f64, t: f64, significance: u32| Level {
+            time_offset: Some(t),
+            significance: Some(significance),
+            pressure: Some(hpa * 100.0),
+            lat_displacement: Some(t / 1000.0),
+            lon_displacement: Some(t / 1000.0),
+            ..Level::default()
+        };
+        let station = Station {
+            wmo_block: Some(72),
+            wmo_station: Some(773),
+            latitude: Some(46.92),
+            longitude: Some(-114.09),
+            elevation: Some(972.0),
+            ..Station::default()
+        };
+        let time = crate::Timestamp {
+            year: 2017,
+            month: 8,
+            day: 31,
+            hour: 23,
+            minute: 5,
+            second: 0,
+        };
+
+        let good = Sounding::new(
+            Phase::Ascent,
+            station.clone(),
+            Some(time),
+            Some(152),
+            vec![
+                level(900.0, 0.0, Level::SURFACE),
+                level(850.0, 100.0, Level::STANDARD),
+            ],
+        );
+        assert_eq!(check_sounding(&good), vec![]);
+
+        let bad = Sounding::new(
+            Phase::Ascent,
+            Station {
+                wmo_block: None,
+                ..station
+            },
+            Some(time),
+            Some(152),
+            vec![
+                level(900.0, 10.0, 0),
+                level(845.0, 5.0, Level::STANDARD | Level::SURFACE),
+            ],
+        );
+        let rules: Vec<_> = check_sounding(&bad)
+            .into_iter()
+            .map(|v| (v.rule, v.level))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                ("B/C25.2", None),
+                ("B/C25.8.1", Some(1)),
+                ("B/C25.8.2", Some(1)),
+                ("B/C25.8.2", Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_conformance() {
+        let mut f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+        scan_to_bufr_start(&mut f).unwrap();
+        let bufr = read_bufr_message(&mut f).unwrap();
+
+        // A real high resolution ascent conforms.
+        assert_eq!(check_conformance(&bufr), vec![]);
+    }
+}
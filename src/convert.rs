@@ -0,0 +1,383 @@
+use crate::{
+    default_checks,
+    index::find_files,
+    inflate::{gunzip, is_gzip},
+    read_bufr_message, scan_to_bufr_start, write_bufkit, write_csv, write_csv_columns,
+    write_gempak, write_json, write_json_columns, write_raob_csv, Column, ExportOptions, Rejection,
+    Sounding,
+};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Cursor, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+/// The formats `convert_files` can write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConvertFormat {
+    Csv,
+    Json,
+    Bufkit,
+    Gempak,
+    Raob,
+}
+
+impl ConvertFormat {
+    /// The extension of the files written, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ConvertFormat::Csv => "csv",
+            ConvertFormat::Json => "json",
+            ConvertFormat::Bufkit => "buf",
+            ConvertFormat::Gempak => "snl",
+            ConvertFormat::Raob => "raob.csv",
+        }
+    }
+}
+
+impl FromStr for ConvertFormat {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "csv" => ConvertFormat::Csv,
+            "json" => ConvertFormat::Json,
+            "bufkit" => ConvertFormat::Bufkit,
+            "gempak" => ConvertFormat::Gempak,
+            "raob" => ConvertFormat::Raob,
+            _ => return Err(format!("Unknown format: {}", s).into()),
+        })
+    }
+}
+
+/// A file found by `glob`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlobMatch {
+    pub path: PathBuf,
+    /// The path below the directory the pattern starts from, e.g. `2024/01/a.bufr` for
+    /// `data/**.bufr`. Outputs keep this layout.
+    pub relative: PathBuf,
+}
+
+/// Find the files matching `pattern`. `*` and `?` match within a path component, `**` matches
+/// across them, and `**/` also matches no directories at all. A pattern without wildcards is a
+/// single file.
+pub fn glob(pattern: &str) -> Result<Vec<GlobMatch>, Box<dyn Error>> {
+    let path = Path::new(pattern);
+    let is_wild = |s: &str| s.contains(['*', '?']);
+
+    if !is_wild(pattern) {
+        if !path.is_file() {
+            return Ok(vec![]);
+        }
+        return Ok(vec![GlobMatch {
+            path: path.to_owned(),
+            relative: path.file_name().map(PathBuf::from).unwrap_or_default(),
+        }]);
+    }
+
+    // The directory to search is everything before the first component with a wildcard.
+    let mut base = PathBuf::new();
+    let mut rest = vec![];
+    for component in path.components() {
+        let s = component.as_os_str().to_string_lossy();
+        if rest.is_empty() && !is_wild(&s) {
+            base.push(component);
+        } else {
+            rest.push(s.into_owned());
+        }
+    }
+    let rest = rest.join("/");
+    if base.as_os_str().is_empty() {
+        base.push(".");
+    }
+
+    let mut paths = vec![];
+    if base.is_dir() {
+        find_files(&base, &mut paths)?;
+    }
+    paths.sort();
+
+    let mut matches = vec![];
+    for path in paths {
+        let relative = path.strip_prefix(&base)?.to_owned();
+        let name: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        if glob_match(rest.as_bytes(), name.join("/").as_bytes()) {
+            matches.push(GlobMatch { path, relative });
+        }
+    }
+
+    Ok(matches)
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            if let Some(after) = rest.strip_prefix(b"/") {
+                if glob_match(after, name) {
+                    return true;
+                }
+            }
+            (0..=name.len()).any(|i| glob_match(rest, &name[i..]))
+        }
+        [b'*', rest @ ..] => (0..=name.len())
+            .take_while(|&i| i == 0 || name[i - 1] != b'/')
+            .any(|i| glob_match(rest, &name[i..])),
+        [b'?', rest @ ..] => {
+            name.first().is_some_and(|&c| c != b'/') && glob_match(rest, &name[1..])
+        }
+        [c, rest @ ..] => name.first() == Some(c) && glob_match(rest, &name[1..]),
+    }
+}
+
+/// What `convert_files` did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConvertSummary {
+    /// The input files converted and the files written for each.
+    pub converted: Vec<(PathBuf, Vec<PathBuf>)>,
+    /// The input files that couldn't be converted and why.
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+/// Convert the soundings in each file to `format` under `out_dir`, keeping the relative layout
+/// of the inputs, on up to `jobs` threads. Files may be gzipped. A file with several soundings
+/// gets numbered outputs, except for GEMPAK which holds them all. A file without any soundings
+/// is a failure, and the results are in the order of `files`. CSV and JSON files have the
+/// chosen `columns` if there are any, see `write_csv_columns`.
+pub fn convert_files(
+    files: &[GlobMatch],
+    out_dir: &Path,
+    format: ConvertFormat,
+    columns: Option<&[Column]>,
+    options: &ExportOptions,
+    jobs: usize,
+) -> ConvertSummary {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Result<Vec<PathBuf>, String>)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.clamp(1, files.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = vec![];
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(file) = files.get(i) else {
+                            return results;
+                        };
+                        let result = convert_file(file, out_dir, format, columns, options)
+                            .map_err(|err| err.to_string());
+                        results.push((i, result));
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+
+    let mut summary = ConvertSummary::default();
+    for (i, result) in results {
+        let path = files[i].path.clone();
+        match result {
+            Ok(written) => summary.converted.push((path, written)),
+            Err(err) => summary.failed.push((path, err)),
+        }
+    }
+
+    summary
+}
+
+fn convert_file(
+    file: &GlobMatch,
+    out_dir: &Path,
+    format: ConvertFormat,
+    columns: Option<&[Column]>,
+    options: &ExportOptions,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut data = std::fs::read(&file.path)?;
+    if is_gzip(&data) {
+        data = gunzip(&data)?;
+    }
+
+    let mut soundings = vec![];
+    let mut f = Cursor::new(&data);
+    while scan_to_bufr_start(&mut f).is_ok() {
+        let offset = f.stream_position()?;
+        match read_bufr_message(&mut f) {
+            Ok(bufr) => soundings.extend(bufr.soundings()),
+            // Step past this "BUFR" to look for the next message.
+            Err(_) => {
+                f.seek(SeekFrom::Start(offset + 4))?;
+            }
+        }
+    }
+    if soundings.is_empty() {
+        return Err("No soundings".into());
+    }
+
+    // a/b.bufr.gz becomes a/b.csv, or a/b_1.csv, a/b_2.csv, ... for several soundings.
+    let mut stem = file.relative.clone();
+    while matches!(
+        stem.extension().and_then(|ext| ext.to_str()),
+        Some("gz" | "bufr")
+    ) {
+        stem.set_extension("");
+    }
+    let stem = out_dir.join(stem);
+    if let Some(dir) = stem.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let out_path = |suffix: String| {
+        let mut name = stem.clone().into_os_string();
+        name.push(format!("{}.{}", suffix, format.extension()));
+        PathBuf::from(name)
+    };
+
+    if format == ConvertFormat::Gempak || soundings.len() == 1 {
+        let path = out_path(String::new());
+        write_soundings(&path, &soundings, format, columns, options)?;
+        let mut written = vec![path];
+        if let Some(rejection) = options.rejection {
+            written.push(write_rejections(&written[0], &soundings, rejection)?);
+        }
+        return Ok(written);
+    }
+
+    let mut written = vec![];
+    for (i, sounding) in soundings.iter().enumerate() {
+        let path = out_path(format!("_{}", i + 1));
+        write_soundings(
+            &path,
+            std::slice::from_ref(sounding),
+            format,
+            columns,
+            options,
+        )?;
+        let rejections = match options.rejection {
+            Some(rejection) => Some(write_rejections(
+                &path,
+                std::slice::from_ref(sounding),
+                rejection,
+            )?),
+            None => None,
+        };
+        written.push(path);
+        written.extend(rejections);
+    }
+
+    Ok(written)
+}
+
+fn write_soundings(
+    path: &Path,
+    soundings: &[Sounding],
+    format: ConvertFormat,
+    columns: Option<&[Column]>,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut w = BufWriter::new(File::create(path)?);
+    match (format, columns) {
+        (ConvertFormat::Csv, Some(columns)) => {
+            write_csv_columns(&mut w, &soundings[0], columns, options)?
+        }
+        (ConvertFormat::Json, Some(columns)) => {
+            write_json_columns(&mut w, &soundings[0], columns, options)?
+        }
+        (ConvertFormat::Csv, None) => write_csv(&mut w, &soundings[0], options)?,
+        (ConvertFormat::Json, None) => write_json(&mut w, &soundings[0], options)?,
+        (ConvertFormat::Bufkit, _) => write_bufkit(&mut w, &soundings[0], options)?,
+        (ConvertFormat::Gempak, _) => write_gempak(&mut w, soundings, options)?,
+        (ConvertFormat::Raob, _) => write_raob_csv(&mut w, &soundings[0], options)?,
+    }
+    w.flush()?;
+
+    Ok(())
+}
+
+/// Write the QC reports of the levels a `rejection` left out of the file at `path` next to
+/// it, as a JSON array with a report for each sounding, e.g. `b.rejected.json` for `b.csv`.
+fn write_rejections(
+    path: &Path,
+    soundings: &[Sounding],
+    rejection: Rejection,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let path = path.with_extension("rejected.json");
+    let mut w = BufWriter::new(File::create(&path)?);
+    writeln!(w, "[")?;
+    for (i, sounding) in soundings.iter().enumerate() {
+        if i > 0 {
+            writeln!(w, ",")?;
+        }
+        let (_, report) = sounding.for_assimilation(&default_checks(), rejection);
+        report.write_json(&mut w)?;
+    }
+    writeln!(w, "]")?;
+    w.flush()?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"2024*/**.bufr.gz", b"20240101/a.bufr.gz"));
+        assert!(glob_match(b"2024*/**.bufr.gz", b"20240101/00/a.bufr.gz"));
+        assert!(!glob_match(b"2024*/**.bufr.gz", b"20230101/a.bufr.gz"));
+        assert!(!glob_match(b"*.bufr", b"a/b.bufr"));
+        assert!(glob_match(b"**/*.bufr", b"b.bufr"));
+        assert!(glob_match(b"a?c", b"abc"));
+        assert!(!glob_match(b"a?c", b"a/c"));
+    }
+
+    #[test]
+    fn test_convert_files() {
+        let message = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let dir = std::env::temp_dir().join(format!("sonde-bufr-convert-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("in/2017/08")).unwrap();
+        std::fs::create_dir_all(dir.join("in/2016")).unwrap();
+        std::fs::write(dir.join("in/2017/08/2017083115.bufr"), &message).unwrap();
+        std::fs::write(dir.join("in/2017/bad.bufr"), "nothing here").unwrap();
+        std::fs::write(dir.join("in/2016/old.bufr"), &message).unwrap();
+
+        let pattern = format!("{}/in/2017*/**.bufr", dir.display());
+        let files = glob(&pattern).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].relative, Path::new("2017/08/2017083115.bufr"));
+
+        let out = dir.join("out");
+        let summary = convert_files(
+            &files,
+            &out,
+            ConvertFormat::Csv,
+            None,
+            &ExportOptions::default(),
+            4,
+        );
+        let written = out.join("2017/08/2017083115.csv");
+        assert_eq!(
+            summary.converted,
+            vec![(files[0].path.clone(), vec![written.clone()])]
+        );
+        assert_eq!(summary.failed[0].0, files[1].path);
+        assert_eq!(
+            std::fs::read_to_string(written).unwrap().lines().count(),
+            4880
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{
+    index::find_files,
+    inflate::{gunzip, is_gzip},
+    metrics::MetricsSummary,
+    next_bufr_start, MessageDecoder, Sounding,
+};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    io::{Cursor, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Where a sounding in a `Corpus` came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    pub path: PathBuf,
+    /// The file inside a tar file, e.g. `2017/08/2017083115.bufr`.
+    pub member: Option<String>,
+    /// Byte offset of the start of the message in the file, or in the tar member. For gzip files
+    /// the offset is into the decompressed data.
+    pub offset: u64,
+    /// Index of the subset within the message.
+    pub subset: usize,
+}
+
+/// A directory tree of BUFR files, tar files, and gzip files of either, read as one collection
+/// of soundings.
+#[derive(Clone, Debug)]
+pub struct Corpus {
+    paths: Vec<PathBuf>,
+}
+
+impl Corpus {
+    /// Find every file under `dir`, including sub-directories. The files are read by
+    /// `soundings`.
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let mut paths = vec![];
+        find_files(dir.as_ref(), &mut paths)?;
+        paths.sort();
+
+        Ok(Corpus { paths })
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Every sounding in the corpus decoded with `decoder`, in path order and then in the order
+    /// stored. Each file is read whole when it's reached. Messages that fail to decode are
+    /// skipped, and a file that can't be read or decompressed is an error item before moving on
+    /// to the next file.
+    pub fn soundings(&self, decoder: &MessageDecoder) -> CorpusSoundings<'_> {
+        CorpusSoundings {
+            paths: self.paths.iter(),
+            pending: VecDeque::new(),
+            decoder: decoder.with_metrics(),
+            metrics: MetricsSummary::default(),
+        }
+    }
+}
+
+/// The iterator returned by `Corpus::soundings`.
+#[derive(Debug)]
+pub struct CorpusSoundings<'a> {
+    paths: std::slice::Iter<'a, PathBuf>,
+    pending: VecDeque<(Provenance, Sounding)>,
+    decoder: MessageDecoder,
+    metrics: MetricsSummary,
+}
+
+impl Iterator for CorpusSoundings<'_> {
+    type Item = Result<(Provenance, Sounding), Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(Ok(item));
+            }
+
+            let path = self.paths.next()?;
+            if let Err(err) = self.read_file(path) {
+                return Some(Err(format!("{}: {}", path.display(), err).into()));
+            }
+        }
+    }
+}
+
+impl CorpusSoundings<'_> {
+    /// The totals of the messages in the files read so far, including those that failed to
+    /// decode.
+    pub fn metrics(&self) -> &MetricsSummary {
+        &self.metrics
+    }
+
+    fn read_file(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut data = std::fs::read(path)?;
+        if is_gzip(&data) {
+            data = gunzip(&data)?;
+        }
+
+        if is_tar(&data) {
+            for (member, contents) in tar_members(&data)? {
+                self.read_messages(path, Some(member), contents)?;
+            }
+        } else {
+            self.read_messages(path, None, &data)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_messages(
+        &mut self,
+        path: &Path,
+        member: Option<String>,
+        data: &[u8],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut f = Cursor::new(data);
+
+        while next_bufr_start(&mut f)? {
+            let offset = f.stream_position()?;
+
+            let Ok(bufr) = self.decoder.read_bufr_message(&mut f) else {
+                // Step past this "BUFR" to look for the next message.
+                self.metrics.failed += 1;
+                f.seek(SeekFrom::Start(offset + 4))?;
+                continue;
+            };
+            if let Some(metrics) = bufr.metrics() {
+                self.metrics.add(metrics);
+            }
+            for subset in 0..bufr.section_4.subsets().len() {
+                if let Some(sounding) = bufr.subset_sounding(subset) {
+                    let provenance = Provenance {
+                        path: path.to_owned(),
+                        member: member.clone(),
+                        offset,
+                        subset,
+                    };
+                    self.pending.push_back((provenance, sounding));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const TAR_BLOCK: usize = 512;
+
+/// Whether `data` starts with a POSIX (ustar) tar header.
+fn is_tar(data: &[u8]) -> bool {
+    data.get(257..262) == Some(b"ustar")
+}
+
+/// The name and contents of a file in a tar file.
+type TarMember<'a> = (String, &'a [u8]);
+
+/// Each regular file in a tar file.
+fn tar_members(data: &[u8]) -> Result<Vec<TarMember<'_>>, Box<dyn Error>> {
+    let text = |field: &[u8]| {
+        let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+        String::from_utf8_lossy(&field[..end]).into_owned()
+    };
+
+    let mut members = vec![];
+    let mut pos = 0;
+    while let Some(header) = data.get(pos..pos + TAR_BLOCK) {
+        // The archive ends with blocks of zeros.
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let size = text(&header[124..136]);
+        let size = usize::from_str_radix(size.trim(), 8)
+            .map_err(|_| format!("Invalid tar member size: {}", size))?;
+        let start = pos + TAR_BLOCK;
+        let contents = data.get(start..start + size).ok_or("Truncated tar file")?;
+
+        // Regular files only, not directories, links, or extended headers.
+        if matches!(header[156], b'0' | 0) {
+            let (prefix, name) = (text(&header[345..500]), text(&header[..100]));
+            let name = if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            members.push((name, contents));
+        }
+
+        pos = start + size.div_ceil(TAR_BLOCK) * TAR_BLOCK;
+    }
+
+    Ok(members)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{DataCategory, DecoderBuilder};
+
+    fn tar_header(name: &str, size: usize) -> Vec<u8> {
+        let mut header = vec![0u8; TAR_BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header
+    }
+
+    #[test]
+    fn test_corpus_soundings() {
+        let message = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let dir = std::env::temp_dir().join(format!("sonde-bufr-corpus-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("loose")).unwrap();
+        std::fs::write(dir.join("loose/2017083115.bufr"), &message).unwrap();
+
+        // A tar file with the message twice, in two members.
+        let mut tar = vec![];
+        for name in ["a.bufr", "b.bufr"] {
+            tar.extend(tar_header(name, message.len()));
+            tar.extend(&message);
+            tar.resize(tar.len().div_ceil(TAR_BLOCK) * TAR_BLOCK, 0);
+        }
+        tar.extend([0; 2 * TAR_BLOCK]);
+        std::fs::write(dir.join("archive.tar"), &tar).unwrap();
+
+        // A gzip file made of stored (uncompressed) deflate blocks.
+        let mut gz = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+        let chunks: Vec<&[u8]> = message.chunks(0xffff).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            gz.push((i == chunks.len() - 1) as u8);
+            gz.extend((chunk.len() as u16).to_le_bytes());
+            gz.extend((!(chunk.len() as u16)).to_le_bytes());
+            gz.extend(*chunk);
+        }
+        gz.extend(crate::inflate::crc32(&message).to_le_bytes());
+        gz.extend((message.len() as u32).to_le_bytes());
+        std::fs::write(dir.join("message.bufr.gz"), &gz).unwrap();
+
+        // Not BUFR at all.
+        std::fs::write(dir.join("README"), "nothing here").unwrap();
+
+        let corpus = Corpus::new(&dir).unwrap();
+        assert_eq!(corpus.paths().len(), 4);
+        let soundings: Vec<(Provenance, Sounding)> = corpus
+            .soundings(&MessageDecoder::default())
+            .map(Result::unwrap)
+            .collect();
+
+        // The corpus is read with the decoder's settings.
+        let satellite = DecoderBuilder::new()
+            .categories([DataCategory::SatelliteSoundings])
+            .build();
+        assert_eq!(corpus.soundings(&satellite).count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let found: Vec<(&Path, Option<&str>)> = soundings
+            .iter()
+            .map(|(p, _)| (p.path.strip_prefix(&dir).unwrap(), p.member.as_deref()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Path::new("archive.tar"), Some("a.bufr")),
+                (Path::new("archive.tar"), Some("b.bufr")),
+                (Path::new("loose/2017083115.bufr"), None),
+                (Path::new("message.bufr.gz"), None),
+            ]
+        );
+
+        let offset = message.windows(4).position(|w| w == b"BUFR").unwrap() as u64;
+        for (provenance, sounding) in &soundings {
+            assert_eq!(provenance.offset, offset);
+            assert_eq!(provenance.subset, 0);
+            assert_eq!(sounding.station().identifier().as_deref(), Some("MSO1"));
+            assert_eq!(sounding.levels().len(), 4879);
+        }
+    }
+}
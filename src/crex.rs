@@ -0,0 +1,328 @@
+use crate::{
+    section3::Descriptor,
+    section4::{DataNode, Decoder, Operators, TableBEntry, Value, ValueSource},
+    sounding::{Phase, Sounding},
+    tables::TableOverrides,
+};
+use std::{error::Error, fmt::Display, io::Read};
+
+/// A decoded CREX message, the character form of BUFR that shares Tables B and D.
+pub struct CrexMessage {
+    table_info: String,
+    data_category: Option<u8>,
+    check_digits: bool,
+    descriptors: Vec<Descriptor>,
+    subsets: Vec<Vec<DataNode>>,
+}
+
+impl CrexMessage {
+    /// Decode the first CREX message in `bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<CrexMessage, Box<dyn Error>> {
+        read_crex_message(bytes)
+    }
+
+    /// The Table A data category from Section 1, if present.
+    pub fn data_category(&self) -> Option<u8> {
+        self.data_category
+    }
+
+    /// Extract a sounding from each subset that contains a vertical profile.
+    pub fn soundings(&self) -> Vec<Sounding> {
+        let phase = Phase::from_descriptors(&self.descriptors);
+
+        self.subsets
+            .iter()
+            .filter_map(|subset| Sounding::from_subset(subset, phase))
+            .collect()
+    }
+}
+
+impl Display for CrexMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        writeln!(f, "Section 1:")?;
+        writeln!(f, "   Table Information: {}", self.table_info)?;
+        match self.data_category {
+            Some(category) => writeln!(f, "       Data Category: {}", category)?,
+            None => writeln!(f, "       Data Category: MISSING")?,
+        }
+        writeln!(f, "        Check Digits: {}", self.check_digits)?;
+        writeln!(f, "         Descriptors:")?;
+        for desc in &self.descriptors {
+            write!(f, "{}", desc)?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Section 2:")?;
+        writeln!(f, "     Subsets: {}", self.subsets.len())?;
+
+        Ok(())
+    }
+}
+
+/// Same as `CrexMessage::from_bytes`.
+impl TryFrom<&[u8]> for CrexMessage {
+    type Error = Box<dyn Error>;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        CrexMessage::from_bytes(bytes)
+    }
+}
+
+/// Read a CREX message, everything from `CREX++` through the closing `7777`.
+pub fn read_crex_message(f: impl Read) -> Result<CrexMessage, Box<dyn Error>> {
+    read_crex_message_with(f, None)
+}
+
+pub(crate) fn read_crex_message_with(
+    mut f: impl Read,
+    overrides: Option<&TableOverrides>,
+) -> Result<CrexMessage, Box<dyn Error>> {
+    let mut text: Vec<u8> = vec![];
+    let mut byte: [u8; 1] = [0; 1];
+    while !text.ends_with(b"7777") {
+        if f.read(&mut byte)? == 0 {
+            return Err("Unexpected end of data in CREX message.".into());
+        }
+        text.push(byte[0]);
+    }
+    let text = std::str::from_utf8(&text)?;
+
+    let start = text.find("CREX++").ok_or("Not a CREX message")? + "CREX++".len();
+    let text = &text[start..];
+
+    // Section 1, the descriptors
+    let section_1_end = text.find("++").ok_or("Unterminated CREX Section 1")?;
+    let mut table_info = String::new();
+    let mut data_category = None;
+    let mut check_digits = false;
+    let mut descriptors = vec![];
+    for token in text[..section_1_end].split_whitespace() {
+        if let Some(desc) = parse_crex_descriptor(token) {
+            descriptors.push(desc);
+        } else if token == "E" {
+            check_digits = true;
+        } else if let Some(info) = token.strip_prefix('T') {
+            table_info = info.to_owned();
+        } else if let Some(category) = token.strip_prefix('A') {
+            data_category = category.get(0..3).and_then(|c| c.parse::<u8>().ok());
+        }
+    }
+
+    if descriptors.is_empty() {
+        return Err("No descriptors in CREX Section 1".into());
+    }
+
+    // Section 2, the data
+    let text = &text[(section_1_end + 2)..];
+    let mut source = CrexValues {
+        data: text.as_bytes(),
+        position: 0,
+        check_digits,
+    };
+
+    let mut subsets = vec![];
+    loop {
+        let mut decoder = Decoder::new(&mut source).with_overrides(overrides);
+        subsets.push(decoder.decode_descriptors(&descriptors)?);
+
+        source.skip_whitespace();
+        if source.remaining().starts_with(b"++") {
+            break;
+        } else if source.remaining().starts_with(b"+") {
+            source.position += 1;
+        } else {
+            return Err("Expected a subset separator in CREX Section 2".into());
+        }
+    }
+
+    Ok(CrexMessage {
+        table_info,
+        data_category,
+        check_digits,
+        descriptors,
+        subsets,
+    })
+}
+
+/// CREX descriptors look like B12101, R01000, C07001 or D09052.
+fn parse_crex_descriptor(token: &str) -> Option<Descriptor> {
+    if token.len() != 6 || !token[1..].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let f = match &token[0..1] {
+        "B" => 0,
+        "R" => 1,
+        "C" => 2,
+        "D" => 3,
+        _ => return None,
+    };
+    let x = token[1..3].parse().ok()?;
+    let y = token[3..6].parse().ok()?;
+
+    Some(Descriptor::new(f, x, y))
+}
+
+/// The values in CREX Section 2 are fixed width character fields separated by spaces. Table C
+/// operators are not applied to CREX data.
+struct CrexValues<'a> {
+    data: &'a [u8],
+    position: usize,
+    check_digits: bool,
+}
+
+impl CrexValues<'_> {
+    fn remaining(&self) -> &[u8] {
+        &self.data[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .data
+            .get(self.position)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    /// Get the next field of `width` characters, not counting a leading minus sign.
+    fn next_field(&mut self, width: usize) -> Result<(bool, &str), Box<dyn Error>> {
+        self.skip_whitespace();
+        if self.check_digits {
+            self.position += 1;
+        }
+
+        let negative = self.remaining().first() == Some(&b'-');
+        if negative {
+            self.position += 1;
+        }
+
+        let end = self.position + width;
+        let field = self
+            .data
+            .get(self.position..end)
+            .ok_or("Unexpected end of CREX Section 2")?;
+        self.position = end;
+
+        Ok((negative, std::str::from_utf8(field)?))
+    }
+
+    fn next_integer(&mut self, width: usize, radix: u32) -> Result<Option<i64>, Box<dyn Error>> {
+        let (negative, field) = self.next_field(width)?;
+        if field.bytes().all(|b| b == b'/') {
+            return Ok(None);
+        }
+
+        let val = i64::from_str_radix(field, radix)
+            .map_err(|_| format!("Invalid CREX value: {}", field))?;
+
+        Ok(Some(if negative { -val } else { val }))
+    }
+}
+
+impl ValueSource for CrexValues<'_> {
+    fn read_value(
+        &mut self,
+        entry: &TableBEntry,
+        _ops: &Operators,
+    ) -> Result<Value, Box<dyn Error>> {
+        if entry.crex_width == 0 {
+            return Err(format!("No CREX definition for {}", entry.element_name).into());
+        }
+
+        let value = match entry.units {
+            "CCITT IA5" => {
+                let (_, field) = self.next_field(entry.crex_width)?;
+                if field.bytes().all(|b| b == b'/') {
+                    Value::Missing
+                } else {
+                    Value::Text(field.to_owned())
+                }
+            }
+            // Flag tables are written in octal in CREX.
+            "Flag table" => self
+                .next_integer(entry.crex_width, 8)?
+                .map(Value::Integer)
+                .unwrap_or(Value::Missing),
+            "Code table" => self
+                .next_integer(entry.crex_width, 10)?
+                .map(Value::Integer)
+                .unwrap_or(Value::Missing),
+            units => match self.next_integer(entry.crex_width, 10)? {
+                None => Value::Missing,
+                Some(val) if entry.crex_scale == 0 && units == "Numeric" => Value::Integer(val),
+                Some(val) => {
+                    let val = match entry.crex_scale {
+                        0 => val as f64,
+                        s if s > 0 => val as f64 / f64::powi(10.0, s),
+                        s => val as f64 * f64::powi(10.0, -s),
+                    };
+                    Value::Float(to_bufr_units(val, entry.crex_units, units))
+                }
+            },
+        };
+
+        Ok(value)
+    }
+
+    fn read_replication_factor(&mut self, entry: &TableBEntry) -> Result<usize, Box<dyn Error>> {
+        // Table B doesn't carry CREX widths for the class 31 replication factors.
+        let width = match entry.crex_width {
+            0 if entry.width_bits <= 8 => 3,
+            0 => 5,
+            width => width,
+        };
+
+        Ok(self.next_integer(width, 10)?.unwrap_or(0) as usize)
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// Convert a value in CREX units into the BUFR units of the same element so that decoded data
+/// looks the same no matter which form it came from.
+fn to_bufr_units(val: f64, crex_units: &str, bufr_units: &str) -> f64 {
+    match (crex_units, bufr_units) {
+        ("C", "K") => val + 273.15,
+        ("kPa", "Pa") => val * 1000.0,
+        ("ft", "m") => val * 0.3048,
+        ("mm", "m") => val / 1000.0,
+        ("mm/h", "kg m-2 s-1") => val / 3600.0,
+        _ => val,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_crex_sounding() {
+        let message = "CREX++\r\r\n\
+            T000103 A002 B01001 B01002 R02000 B31001 B07004 B12101++\r\r\n\
+            72 776 002 08500 -1050 07000 -2030++\r\r\n\
+            7777";
+
+        let crex = read_crex_message(Cursor::new(message)).unwrap();
+        assert_eq!(crex.data_category(), Some(2));
+
+        let soundings = crex.soundings();
+        assert_eq!(soundings.len(), 1);
+
+        let sounding = &soundings[0];
+        assert_eq!(sounding.station().wmo_block, Some(72));
+        assert_eq!(sounding.station().wmo_station, Some(776));
+
+        let levels = sounding.levels();
+        assert_eq!(levels.len(), 2);
+        assert_eq!(levels[0].pressure, Some(85000.0));
+        assert!((levels[1].temperature.unwrap() - 252.85).abs() < 1.0e-9);
+
+        let from_bytes = CrexMessage::try_from(message.as_bytes()).unwrap();
+        assert_eq!(from_bytes.soundings()[0].levels(), levels);
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt::Display;
+
+/// The BUFR Table A data categories, from Section 1 of a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DataCategory {
+    SurfaceLand,
+    SurfaceSea,
+    /// Other than satellite, e.g. radiosondes.
+    VerticalSoundings,
+    SatelliteSoundings,
+    /// Other than satellite, e.g. aircraft reports.
+    SingleLevelUpperAir,
+    SatelliteSingleLevelUpperAir,
+    Radar,
+    SynopticFeatures,
+    PhysicalChemicalConstituents,
+    DispersalAndTransport,
+    Radiological,
+    /// Table definitions, see `BufrMessage::is_table_message`.
+    Tables,
+    SatelliteSurface,
+    Forecasts,
+    Warnings,
+    Status,
+    Radiances,
+    SatelliteRadar,
+    SatelliteLidar,
+    Scatterometry,
+    Altimetry,
+    Spectrometry,
+    Gravity,
+    PreciseOrbit,
+    SpaceEnvironment,
+    Calibration,
+    Oceanographic,
+    Image,
+    /// A reserved or local category.
+    Other(u8),
+}
+
+impl DataCategory {
+    /// The Table A code.
+    pub fn code(&self) -> u8 {
+        match self {
+            DataCategory::SurfaceLand => 0,
+            DataCategory::SurfaceSea => 1,
+            DataCategory::VerticalSoundings => 2,
+            DataCategory::SatelliteSoundings => 3,
+            DataCategory::SingleLevelUpperAir => 4,
+            DataCategory::SatelliteSingleLevelUpperAir => 5,
+            DataCategory::Radar => 6,
+            DataCategory::SynopticFeatures => 7,
+            DataCategory::PhysicalChemicalConstituents => 8,
+            DataCategory::DispersalAndTransport => 9,
+            DataCategory::Radiological => 10,
+            DataCategory::Tables => 11,
+            DataCategory::SatelliteSurface => 12,
+            DataCategory::Forecasts => 13,
+            DataCategory::Warnings => 14,
+            DataCategory::Status => 20,
+            DataCategory::Radiances => 21,
+            DataCategory::SatelliteRadar => 22,
+            DataCategory::SatelliteLidar => 23,
+            DataCategory::Scatterometry => 24,
+            DataCategory::Altimetry => 25,
+            DataCategory::Spectrometry => 26,
+            DataCategory::Gravity => 27,
+            DataCategory::PreciseOrbit => 28,
+            DataCategory::SpaceEnvironment => 29,
+            DataCategory::Calibration => 30,
+            DataCategory::Oceanographic => 31,
+            DataCategory::Image => 101,
+            DataCategory::Other(code) => *code,
+        }
+    }
+}
+
+impl From<u8> for DataCategory {
+    fn from(code: u8) -> Self {
+        match code {
+            0 => DataCategory::SurfaceLand,
+            1 => DataCategory::SurfaceSea,
+            2 => DataCategory::VerticalSoundings,
+            3 => DataCategory::SatelliteSoundings,
+            4 => DataCategory::SingleLevelUpperAir,
+            5 => DataCategory::SatelliteSingleLevelUpperAir,
+            6 => DataCategory::Radar,
+            7 => DataCategory::SynopticFeatures,
+            8 => DataCategory::PhysicalChemicalConstituents,
+            9 => DataCategory::DispersalAndTransport,
+            10 => DataCategory::Radiological,
+            11 => DataCategory::Tables,
+            12 => DataCategory::SatelliteSurface,
+            13 => DataCategory::Forecasts,
+            14 => DataCategory::Warnings,
+            20 => DataCategory::Status,
+            21 => DataCategory::Radiances,
+            22 => DataCategory::SatelliteRadar,
+            23 => DataCategory::SatelliteLidar,
+            24 => DataCategory::Scatterometry,
+            25 => DataCategory::Altimetry,
+            26 => DataCategory::Spectrometry,
+            27 => DataCategory::Gravity,
+            28 => DataCategory::PreciseOrbit,
+            29 => DataCategory::SpaceEnvironment,
+            30 => DataCategory::Calibration,
+            31 => DataCategory::Oceanographic,
+            101 => DataCategory::Image,
+            code => DataCategory::Other(code),
+        }
+    }
+}
+
+impl From<DataCategory> for u8 {
+    fn from(category: DataCategory) -> Self {
+        category.code()
+    }
+}
+
+impl Display for DataCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            DataCategory::Other(code) => write!(f, "Category {}", code),
+            category => write!(f, "{:?}", category),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        for code in 0..=u8::MAX {
+            assert_eq!(DataCategory::from(code).code(), code);
+        }
+        assert_eq!(DataCategory::from(2), DataCategory::VerticalSoundings);
+        assert_eq!(DataCategory::from(15), DataCategory::Other(15));
+        assert_eq!(u8::from(DataCategory::Radar), 6);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{fingerprint::content_hash, sounding::Sounding};
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// Remembers which soundings have been seen, e.g. in memory or in a file shared between runs.
+pub trait SeenStore {
+    /// Record a content hash, returns `true` if it had not been seen before.
+    fn insert(&mut self, hash: u64) -> bool;
+
+    /// Save anything not saved yet, and report any error saving since the last call.
+    fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+impl SeenStore for HashSet<u64> {
+    fn insert(&mut self, hash: u64) -> bool {
+        HashSet::insert(self, hash)
+    }
+}
+
+/// A `SeenStore` kept in a file of hex hashes, one per line, so it lasts between runs. New
+/// hashes are appended as they're inserted and written out by `sync`.
+#[derive(Debug)]
+pub struct FileSeenStore {
+    seen: HashSet<u64>,
+    file: BufWriter<File>,
+    error: Option<std::io::Error>,
+}
+
+impl FileSeenStore {
+    /// Open a store, creating the file if it doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let mut seen = HashSet::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if !line.is_empty() {
+                    seen.insert(u64::from_str_radix(&line, 16)?);
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileSeenStore {
+            seen,
+            file: BufWriter::new(file),
+            error: None,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+}
+
+impl SeenStore for FileSeenStore {
+    fn insert(&mut self, hash: u64) -> bool {
+        if !self.seen.insert(hash) {
+            return false;
+        }
+        if let Err(err) = writeln!(self.file, "{:016x}", hash) {
+            self.error.get_or_insert(err);
+        }
+        true
+    }
+
+    fn sync(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(err) = self.error.take() {
+            return Err(err.into());
+        }
+        Ok(self.file.flush()?)
+    }
+}
+
+/// Remove soundings that have already been seen from an iterator.
+pub fn dedup<I, S>(iter: I, seen: S) -> Dedup<I::IntoIter, S>
+where
+    I: IntoIterator<Item = Sounding>,
+    S: SeenStore,
+{
+    Dedup {
+        iter: iter.into_iter(),
+        seen,
+    }
+}
+
+/// An iterator that yields each unique sounding once, see `dedup`.
+pub struct Dedup<I, S> {
+    iter: I,
+    seen: S,
+}
+
+impl<I, S> Dedup<I, S> {
+    /// Get the store back, e.g. to save it for the next run.
+    pub fn into_seen(self) -> S {
+        self.seen
+    }
+}
+
+impl<I, S> Iterator for Dedup<I, S>
+where
+    I: Iterator<Item = Sounding>,
+    S: SeenStore,
+{
+    type Item = Sounding;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .by_ref()
+            .find(|sounding| self.seen.insert(content_hash(sounding)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Level, Phase, Station};
+
+    #[test]
+    fn test_dedup() {
+        let sounding = |temperature: f64| {
+            let station = Station {
+                wmo_block: Some(72),
+                wmo_station: Some(776),
+                ..Station::default()
+            };
+            let level = Level {
+                pressure: Some(85000.0),
+                temperature: Some(temperature),
+                ..Level::default()
+            };
+            Sounding::new(Phase::Ascent, station, None, None, vec![level])
+        };
+
+        let soundings = vec![sounding(280.0), sounding(280.0), sounding(281.0)];
+        let mut unique = dedup(soundings, HashSet::new());
+        assert_eq!(unique.by_ref().count(), 2);
+        let seen = unique.into_seen();
+
+        // A store carried over from an earlier run filters everything it has seen.
+        assert_eq!(dedup(vec![sounding(281.0)], seen).count(), 0);
+
+        let path = std::env::temp_dir().join(format!("sonde-bufr-seen-{}", std::process::id()));
+        let mut store = FileSeenStore::open(&path).unwrap();
+        assert!(store.is_empty());
+        let mut unique = dedup(vec![sounding(280.0), sounding(280.0)], store);
+        assert_eq!(unique.by_ref().count(), 1);
+        unique.into_seen().sync().unwrap();
+
+        store = FileSeenStore::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        let mut unique = dedup(vec![sounding(280.0), sounding(281.0)], store);
+        assert_eq!(unique.by_ref().count(), 1);
+        unique.into_seen().sync().unwrap();
+        let store = FileSeenStore::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(dedup(vec![sounding(281.0)], store).count(), 0);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::sounding::{Level, Sounding};
+
+/// Formulas for the saturation vapor pressure over liquid water.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VaporPressureFormula {
+    /// Bolton (1980), e_s = 6.112 exp(17.67 t / (t + 243.5)) hPa.
+    #[default]
+    Bolton,
+    /// The Magnus form recommended in WMO-No. 8, e_s = 6.112 exp(17.62 t / (t + 243.12)) hPa.
+    Wmo,
+}
+
+impl VaporPressureFormula {
+    fn coefficients(self) -> (f64, f64) {
+        match self {
+            VaporPressureFormula::Bolton => (17.67, 243.5),
+            VaporPressureFormula::Wmo => (17.62, 243.12),
+        }
+    }
+
+    /// Saturation vapor pressure in hPa at a temperature in C.
+    pub fn saturation_vapor_pressure(self, t: f64) -> f64 {
+        let (a, b) = self.coefficients();
+        6.112 * (a * t / (t + b)).exp()
+    }
+
+    /// The dewpoint in C for a temperature in C and relative humidity in %.
+    pub fn dewpoint(self, t: f64, rh: f64) -> Option<f64> {
+        if rh.is_nan() || rh <= 0.0 {
+            return None;
+        }
+
+        let (a, b) = self.coefficients();
+        let gamma = (rh / 100.0).ln() + a * t / (t + b);
+
+        Some(b * gamma / (a - gamma))
+    }
+}
+
+impl Sounding {
+    /// Fill in missing dewpoints from the temperature and relative humidity, for templates that
+    /// report relative humidity (0-13-003) instead of dewpoint. Filled levels are flagged with
+    /// `Level::DERIVED_DEWPOINT`. Returns the number of levels filled.
+    pub fn fill_dewpoint_from_rh(&mut self, formula: VaporPressureFormula) -> usize {
+        let mut num_filled = 0;
+        for lvl in self.levels_mut() {
+            if lvl.dewpoint.is_some() {
+                continue;
+            }
+            let (Some(t), Some(rh)) = (lvl.temperature, lvl.relative_humidity) else {
+                continue;
+            };
+
+            if let Some(td) = formula.dewpoint(t - 273.15, rh) {
+                lvl.dewpoint = Some(td + 273.15);
+                lvl.derived |= Level::DERIVED_DEWPOINT;
+                num_filled += 1;
+            }
+        }
+
+        num_filled
+    }
+
+    /// Fill in missing geopotential heights by integrating the hypsometric equation upward
+    /// from the surface, using the virtual temperature where there is a dewpoint. Each layer
+    /// starts from the last level below it with a height, so reported heights are kept and
+    /// re-anchor the integration. A surface level without a height takes the station elevation.
+    /// Filled levels are flagged with `Level::DERIVED_HEIGHT`. Returns the number of levels
+    /// filled.
+    pub fn fill_heights_hydrostatic(&mut self) -> usize {
+        /// Gas constant for dry air over standard gravity, gpm / K.
+        const RD_OVER_G: f64 = 287.04 / 9.80665;
+
+        let elevation = self.station().elevation;
+        let levels = self.levels_mut();
+
+        let mut order: Vec<(usize, f64)> = levels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, lvl)| Some((i, lvl.pressure?)))
+            .collect();
+        order.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut num_filled = 0;
+        let mut below: Option<(f64, f64, f64)> = None; // pressure, height, virtual temperature
+        for (i, p) in order {
+            let lvl = &mut levels[i];
+
+            if lvl.height.is_none() {
+                if below.is_none() && lvl.has_significance(Level::SURFACE) {
+                    lvl.height = elevation;
+                } else if let (Some((p0, z0, tv0)), Some(tv)) = (below, virtual_temperature(lvl)) {
+                    lvl.height = Some(z0 + RD_OVER_G * (tv0 + tv) / 2.0 * (p0 / p).ln());
+                }
+
+                if lvl.height.is_some() {
+                    lvl.derived |= Level::DERIVED_HEIGHT;
+                    num_filled += 1;
+                }
+            }
+
+            if let (Some(z), Some(tv)) = (lvl.height, virtual_temperature(lvl)) {
+                below = Some((p, z, tv));
+            }
+        }
+
+        num_filled
+    }
+}
+
+/// The virtual temperature (K) at a level, or the temperature if there is no dewpoint.
+fn virtual_temperature(lvl: &Level) -> Option<f64> {
+    let t = lvl.temperature?;
+    let (Some(td), Some(p)) = (lvl.dewpoint, lvl.pressure) else {
+        return Some(t);
+    };
+
+    let e = VaporPressureFormula::Bolton.saturation_vapor_pressure(td - 273.15) * 100.0;
+    Some(t / (1.0 - e / p * (1.0 - 0.622)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_fill_dewpoint_from_rh() {
+        let level = |dewpoint: Option<f64>, rh: f64| Level {
+            temperature: Some(293.15),
+            dewpoint,
+            relative_humidity: Some(rh),
+            ..Level::default()
+        };
+        let mut sounding = Sounding::new(
+            Phase::Ascent,
+            Station::default(),
+            None,
+            None,
+            vec![
+                level(Some(280.0), 50.0),
+                level(None, 100.0),
+                level(None, 50.0),
+            ],
+        );
+
+        assert_eq!(
+            sounding.fill_dewpoint_from_rh(VaporPressureFormula::Bolton),
+            2
+        );
+
+        let levels = sounding.levels();
+        assert_eq!(levels[0].dewpoint, Some(280.0));
+        assert!(!levels[0].is_derived(Level::DERIVED_DEWPOINT));
+        assert!((levels[1].dewpoint.unwrap() - 293.15).abs() < 1.0e-9);
+        assert!(levels[1].is_derived(Level::DERIVED_DEWPOINT));
+        // About 9.3 C at 20 C and 50%.
+        assert!((levels[2].dewpoint.unwrap() - 273.15 - 9.27).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_fill_heights_hydrostatic() {
+        let level = |hpa: f64, height: Option<f64>, sig: u32| Level {
+            significance: Some(sig),
+            pressure: Some(hpa * 100.0),
+            height,
+            temperature: Some(273.15),
+            ..Level::default()
+        };
+        let station = Station {
+            elevation: Some(100.0),
+            ..Station::default()
+        };
+        let mut sounding = Sounding::new(
+            Phase::Ascent,
+            station,
+            None,
+            None,
+            vec![
+                level(1000.0, None, Level::SURFACE),
+                level(900.0, None, 0),
+                level(850.0, Some(1500.0), Level::STANDARD),
+                level(800.0, None, 0),
+            ],
+        );
+
+        assert_eq!(sounding.fill_heights_hydrostatic(), 3);
+
+        let levels = sounding.levels();
+        assert_eq!(levels[0].height, Some(100.0));
+        // An isothermal layer at 0 C is about 8 km per e-folding of pressure.
+        let dz = 287.04 / 9.80665 * 273.15 * (1000.0f64 / 900.0).ln();
+        assert!((levels[1].height.unwrap() - 100.0 - dz).abs() < 1.0e-6);
+        assert!(!levels[2].is_derived(Level::DERIVED_HEIGHT));
+        let dz = 287.04 / 9.80665 * 273.15 * (850.0f64 / 800.0).ln();
+        assert!((levels[3].height.unwrap() - 1500.0 - dz).abs() < 1.0e-6);
+        assert!(levels[3].is_derived(Level::DERIVED_HEIGHT));
+    }
+}
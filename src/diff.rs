@@ -0,0 +1,418 @@
+use crate::sounding::{Level, Sounding};
+use std::fmt::{Debug, Display};
+
+/// How far apart two values may be and still count as the same, see `Sounding::diff`. The
+/// defaults are half the resolution BUFR reports each value in, so two decodes of the same data
+/// match.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiffTolerances {
+    /// Seconds
+    pub time_offset: f64,
+    /// Pa
+    pub pressure: f64,
+    /// m, for heights and the station elevation.
+    pub height: f64,
+    /// K, for temperatures and dewpoints.
+    pub temperature: f64,
+    /// %
+    pub relative_humidity: f64,
+    /// Degrees
+    pub wind_direction: f64,
+    /// m/s
+    pub wind_speed: f64,
+    /// Degrees of latitude or longitude, for the station and the balloon's displacement.
+    pub position: f64,
+}
+
+impl Default for DiffTolerances {
+    fn default() -> Self {
+        DiffTolerances {
+            time_offset: 0.5,
+            pressure: 5.0,
+            height: 0.5,
+            temperature: 0.005,
+            relative_humidity: 0.5,
+            wind_direction: 0.5,
+            wind_speed: 0.05,
+            position: 0.000_005,
+        }
+    }
+}
+
+/// A sounding property, such as the station or launch time, that differs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetadataDifference {
+    pub field: &'static str,
+    /// The value in `self`, as text.
+    pub left: String,
+    /// The value in the other sounding, as text.
+    pub right: String,
+}
+
+/// A value that differs at a pair of matched levels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldDifference {
+    /// The `Level` field name, e.g. `temperature`.
+    pub field: &'static str,
+    pub left: Option<f64>,
+    pub right: Option<f64>,
+}
+
+/// A level of `self` matched to a level of the other sounding, with the values that differ.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelDiff {
+    /// Index in `self.levels()`.
+    pub left: usize,
+    /// Index in `other.levels()`.
+    pub right: usize,
+    pub fields: Vec<FieldDifference>,
+}
+
+/// Everything that differs between two soundings, see `Sounding::diff`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SoundingDiff {
+    pub metadata: Vec<MetadataDifference>,
+    /// Only matched levels with at least one difference are listed.
+    pub levels: Vec<LevelDiff>,
+    /// Indices of the levels of `self` that weren't matched.
+    pub only_in_left: Vec<usize>,
+    /// Indices of the levels of the other sounding that weren't matched.
+    pub only_in_right: Vec<usize>,
+}
+
+impl SoundingDiff {
+    /// Whether the soundings are the same within the tolerances.
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty()
+            && self.levels.is_empty()
+            && self.only_in_left.is_empty()
+            && self.only_in_right.is_empty()
+    }
+}
+
+impl Sounding {
+    /// Compare this sounding to `other`, e.g. the output of another decoder or a reprocessing
+    /// run.
+    ///
+    /// Levels are matched in order by the first of time since launch, pressure, and height that
+    /// both levels have, within `tolerances`. A level with no match in the other sounding is
+    /// listed as only in its own; the levels either side of it are still compared.
+    pub fn diff(&self, other: &Sounding, tolerances: &DiffTolerances) -> SoundingDiff {
+        let mut diff = SoundingDiff {
+            metadata: metadata_differences(self, other, tolerances),
+            ..SoundingDiff::default()
+        };
+
+        let right = other.levels();
+        let mut next = 0;
+        for (i, lvl) in self.levels().iter().enumerate() {
+            let Some(j) = (next..right.len()).find(|&j| same_position(lvl, &right[j], tolerances))
+            else {
+                diff.only_in_left.push(i);
+                continue;
+            };
+            diff.only_in_right.extend(next..j);
+            next = j + 1;
+
+            let fields = level_differences(lvl, &right[j], tolerances);
+            if !fields.is_empty() {
+                diff.levels.push(LevelDiff {
+                    left: i,
+                    right: j,
+                    fields,
+                });
+            }
+        }
+        diff.only_in_right.extend(next..right.len());
+
+        diff
+    }
+}
+
+fn metadata_differences(
+    left: &Sounding,
+    right: &Sounding,
+    tolerances: &DiffTolerances,
+) -> Vec<MetadataDifference> {
+    let mut differences = vec![];
+    let mut compare = |field, same: bool, l: String, r: String| {
+        if !same {
+            differences.push(MetadataDifference {
+                field,
+                left: l,
+                right: r,
+            });
+        }
+    };
+    let text = |v: &dyn Debug| format!("{:?}", v);
+
+    let (ls, rs) = (left.station(), right.station());
+    compare(
+        "phase",
+        left.phase() == right.phase(),
+        left.phase().to_string(),
+        right.phase().to_string(),
+    );
+    compare(
+        "station",
+        ls.identifier() == rs.identifier(),
+        text(&ls.identifier()),
+        text(&rs.identifier()),
+    );
+    for (field, l, r, tolerance) in [
+        ("latitude", ls.latitude, rs.latitude, tolerances.position),
+        ("longitude", ls.longitude, rs.longitude, tolerances.position),
+        ("elevation", ls.elevation, rs.elevation, tolerances.height),
+    ] {
+        compare(field, close(l, r, tolerance), text(&l), text(&r));
+    }
+    compare(
+        "launch_time",
+        left.launch_time() == right.launch_time(),
+        text(&left.launch_time()),
+        text(&right.launch_time()),
+    );
+    compare(
+        "radiosonde_type",
+        left.radiosonde_type() == right.radiosonde_type(),
+        text(&left.radiosonde_type()),
+        text(&right.radiosonde_type()),
+    );
+    compare(
+        "update_number",
+        left.update_number() == right.update_number(),
+        left.update_number().to_string(),
+        right.update_number().to_string(),
+    );
+    compare(
+        "report_type",
+        left.report_type() == right.report_type(),
+        text(&left.report_type()),
+        text(&right.report_type()),
+    );
+
+    differences
+}
+
+fn level_differences(
+    left: &Level,
+    right: &Level,
+    tolerances: &DiffTolerances,
+) -> Vec<FieldDifference> {
+    let flags = |v: Option<u32>| v.map(f64::from);
+    let fields = [
+        (
+            "time_offset",
+            left.time_offset,
+            right.time_offset,
+            tolerances.time_offset,
+        ),
+        (
+            "significance",
+            flags(left.significance),
+            flags(right.significance),
+            0.0,
+        ),
+        (
+            "pressure",
+            left.pressure,
+            right.pressure,
+            tolerances.pressure,
+        ),
+        ("height", left.height, right.height, tolerances.height),
+        (
+            "gnss_height",
+            left.gnss_height,
+            right.gnss_height,
+            tolerances.height,
+        ),
+        (
+            "temperature",
+            left.temperature,
+            right.temperature,
+            tolerances.temperature,
+        ),
+        (
+            "dewpoint",
+            left.dewpoint,
+            right.dewpoint,
+            tolerances.temperature,
+        ),
+        (
+            "relative_humidity",
+            left.relative_humidity,
+            right.relative_humidity,
+            tolerances.relative_humidity,
+        ),
+        (
+            "wind_speed",
+            left.wind_speed,
+            right.wind_speed,
+            tolerances.wind_speed,
+        ),
+        (
+            "lat_displacement",
+            left.lat_displacement,
+            right.lat_displacement,
+            tolerances.position,
+        ),
+        (
+            "lon_displacement",
+            left.lon_displacement,
+            right.lon_displacement,
+            tolerances.position,
+        ),
+        (
+            "derived",
+            Some(f64::from(left.derived)),
+            Some(f64::from(right.derived)),
+            0.0,
+        ),
+        (
+            "qc",
+            Some(f64::from(left.qc)),
+            Some(f64::from(right.qc)),
+            0.0,
+        ),
+    ];
+
+    let mut differences: Vec<FieldDifference> = fields
+        .into_iter()
+        .filter(|(_, l, r, tolerance)| !close(*l, *r, *tolerance))
+        .map(|(field, left, right, _)| FieldDifference { field, left, right })
+        .collect();
+
+    // Directions wrap around, so 359 and 1 are 2 degrees apart.
+    let (l, r) = (left.wind_direction, right.wind_direction);
+    let same_direction = match l.zip(r) {
+        Some((l, r)) => {
+            let turn = (l - r).rem_euclid(360.0);
+            turn.min(360.0 - turn) <= tolerances.wind_direction
+        }
+        None => l.is_none() && r.is_none(),
+    };
+    if !same_direction {
+        differences.push(FieldDifference {
+            field: "wind_direction",
+            left: l,
+            right: r,
+        });
+    }
+
+    differences
+}
+
+/// Whether both values are missing, or both present and within `tolerance`.
+fn close(left: Option<f64>, right: Option<f64>, tolerance: f64) -> bool {
+    match (left, right) {
+        (Some(l), Some(r)) => (l - r).abs() <= tolerance,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn same_position(left: &Level, right: &Level, tolerances: &DiffTolerances) -> bool {
+    let coordinates = [
+        (left.time_offset, right.time_offset, tolerances.time_offset),
+        (left.pressure, right.pressure, tolerances.pressure),
+        (left.height, right.height, tolerances.height),
+    ];
+
+    coordinates
+        .into_iter()
+        .find_map(|(l, r, tolerance)| Some((l? - r?).abs() <= tolerance))
+        .unwrap_or(false)
+}
+
+impl Display for SoundingDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let val = |v: Option<f64>| v.map_or("missing".to_owned(), |v| v.to_string());
+
+        for diff in &self.metadata {
+            writeln!(f, "{}: {} != {}", diff.field, diff.left, diff.right)?;
+        }
+        for level in &self.levels {
+            for diff in &level.fields {
+                writeln!(
+                    f,
+                    "level {}/{} {}: {} != {}",
+                    level.left,
+                    level.right,
+                    diff.field,
+                    val(diff.left),
+                    val(diff.right)
+                )?;
+            }
+        }
+        if !self.only_in_left.is_empty() {
+            writeln!(f, "only in left: levels {:?}", self.only_in_left)?;
+        }
+        if !self.only_in_right.is_empty() {
+            writeln!(f, "only in right: levels {:?}", self.only_in_right)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_diff() {
+        let level = |hpa: f64, t: f64, dir: f64| Level {
+            pressure: Some(hpa * 100.0),
+            temperature: Some(t),
+            wind_direction: Some(dir),
+            ..Level::default()
+        };
+        let left = Sounding::new(
+            Phase::Ascent,
+            Station::default(),
+            None,
+            Some(141),
+            vec![
+                level(1000.0, 290.0, 359.8),
+                level(900.0, 285.0, 10.0),
+                level(850.0, 282.0, 20.0),
+            ],
+        );
+
+        let tolerances = DiffTolerances::default();
+        assert!(left.diff(&left, &tolerances).is_empty());
+
+        let right = Sounding::new(
+            Phase::Ascent,
+            Station::default(),
+            None,
+            Some(152),
+            vec![
+                level(1000.0, 290.001, 0.2),
+                level(850.0, 283.0, 20.0),
+                level(700.0, 275.0, 30.0),
+            ],
+        );
+        let diff = left.diff(&right, &tolerances);
+        assert_eq!(diff.metadata.len(), 1);
+        assert_eq!(diff.metadata[0].field, "radiosonde_type");
+        assert_eq!(
+            diff.levels,
+            vec![LevelDiff {
+                left: 2,
+                right: 1,
+                fields: vec![FieldDifference {
+                    field: "temperature",
+                    left: Some(282.0),
+                    right: Some(283.0),
+                }],
+            }]
+        );
+        assert_eq!(diff.only_in_left, vec![1]);
+        assert_eq!(diff.only_in_right, vec![2]);
+        assert!(diff
+            .to_string()
+            .contains("level 2/1 temperature: 282 != 283"));
+    }
+}
@@ -0,0 +1,923 @@
+use crate::{
+    bit_buffer::{add_reference, apply_scale},
+    bit_writer::BitWriter,
+    compressed::INCREMENT_WIDTH_BITS,
+    expansion::{expand_descriptors, ExpansionNode},
+    section3::Descriptor,
+    section4::{DataNode, Operators, TableBEntry, Value},
+    sounding::Timestamp,
+    tables::{self, TableOverrides},
+};
+use std::error::Error;
+
+/// Builds the descriptor list of Section 3 for encoding, see `DataDescription`.
+///
+/// Descriptors are added in order. Mistakes such as an unknown sequence or a replication of
+/// too many descriptors are reported by `build`.
+#[derive(Clone, Debug, Default)]
+pub struct Section3Builder {
+    descriptors: Vec<Descriptor>,
+    observed: bool,
+    overrides: TableOverrides,
+    errors: Vec<String>,
+}
+
+impl Section3Builder {
+    pub fn new() -> Self {
+        Section3Builder {
+            observed: true,
+            ..Self::default()
+        }
+    }
+
+    /// Add a Table B element (0-XX-YYY).
+    pub fn element(mut self, descriptor: Descriptor) -> Self {
+        self.expect_type(descriptor, 0);
+        self.descriptors.push(descriptor);
+        self
+    }
+
+    /// Add a Table D sequence (3-XX-YYY).
+    pub fn sequence(mut self, descriptor: Descriptor) -> Self {
+        self.expect_type(descriptor, 3);
+        self.descriptors.push(descriptor);
+        self
+    }
+
+    /// Add a Table C operator (2-XX-YYY).
+    pub fn operator(mut self, descriptor: Descriptor) -> Self {
+        self.expect_type(descriptor, 2);
+        self.descriptors.push(descriptor);
+        self
+    }
+
+    /// Repeat `group` `count` times, 1-XX-YYY with the count in the descriptor.
+    pub fn replicate(mut self, count: u8, group: &[Descriptor]) -> Self {
+        if count == 0 {
+            self.errors
+                .push("A replication count of 0 means delayed replication".to_owned());
+        }
+        self.push_replication(count, None, group);
+        self
+    }
+
+    /// Repeat `group` the number of times given in the data by `factor`, a delayed replication
+    /// factor such as 0-31-002.
+    pub fn delayed_replicate(mut self, factor: Descriptor, group: &[Descriptor]) -> Self {
+        if factor.f_value() != 0 || factor.x_value() != 31 {
+            self.errors.push(format!(
+                "{} is not a delayed replication factor",
+                factor.string_form()
+            ));
+        }
+        self.push_replication(0, Some(factor), group);
+        self
+    }
+
+    /// Add descriptors as they are, e.g. copied from `BufrMessage::descriptors`.
+    pub fn descriptors(mut self, descriptors: &[Descriptor]) -> Self {
+        self.descriptors.extend_from_slice(descriptors);
+        self
+    }
+
+    /// Set the observed data flag of Section 3, on by default.
+    pub fn observed(mut self, observed: bool) -> Self {
+        self.observed = observed;
+        self
+    }
+
+    /// Look descriptors up in `overrides` before the built-in tables.
+    pub fn table_overrides(mut self, overrides: TableOverrides) -> Self {
+        self.overrides.merge(overrides);
+        self
+    }
+
+    /// Check every descriptor expands through the tables.
+    pub fn build(self) -> Result<DataDescription, Box<dyn Error>> {
+        if let Some(error) = self.errors.first() {
+            return Err(error.clone().into());
+        }
+        if self.descriptors.is_empty() {
+            return Err("Section 3 needs at least one descriptor".into());
+        }
+
+        let overrides = (!self.overrides.is_empty()).then_some(&self.overrides);
+        let expansion = expand_descriptors(&self.descriptors, overrides)?;
+
+        Ok(DataDescription {
+            descriptors: self.descriptors,
+            observed: self.observed,
+            expansion,
+            overrides: self.overrides,
+        })
+    }
+
+    fn expect_type(&mut self, descriptor: Descriptor, f: u8) {
+        if descriptor.f_value() != f {
+            self.errors.push(format!(
+                "{} used where an F = {} descriptor was expected",
+                descriptor.string_form(),
+                f
+            ));
+        }
+    }
+
+    fn push_replication(&mut self, count: u8, factor: Option<Descriptor>, group: &[Descriptor]) {
+        if group.is_empty() || group.len() > 63 {
+            self.errors.push(format!(
+                "A replication covers 1 to 63 descriptors, not {}",
+                group.len()
+            ));
+        }
+        self.descriptors
+            .push(Descriptor::new(1, group.len().min(63) as u8, count));
+        self.descriptors.extend(factor);
+        self.descriptors.extend_from_slice(group);
+    }
+}
+
+/// The descriptors of a message to encode and their expansion, from `Section3Builder`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataDescription {
+    descriptors: Vec<Descriptor>,
+    observed: bool,
+    expansion: Vec<ExpansionNode>,
+    overrides: TableOverrides,
+}
+
+impl DataDescription {
+    pub fn descriptors(&self) -> &[Descriptor] {
+        &self.descriptors
+    }
+
+    pub fn expansion(&self) -> &[ExpansionNode] {
+        &self.expansion
+    }
+
+    /// Check that each subset has the shape of the expansion: the same elements, sequences
+    /// and replications in the same order, fixed replications repeated the right number of
+    /// times, and text only in CCITT IA5 elements. The error names the first mismatch.
+    pub fn validate(&self, subsets: &[Vec<DataNode>]) -> Result<(), Box<dyn Error>> {
+        for (i, subset) in subsets.iter().enumerate() {
+            check_nodes(&self.expansion, subset).map_err(|err| format!("Subset {}: {}", i, err))?;
+        }
+        Ok(())
+    }
+
+    /// Section 3 for `num_subsets` uncompressed subsets.
+    pub fn section_3_bytes(&self, num_subsets: u16) -> Vec<u8> {
+        self.section_3(num_subsets, false)
+    }
+
+    fn section_3(&self, num_subsets: u16, compressed: bool) -> Vec<u8> {
+        // 7 octets of header and 2 per descriptor, padded to an even length.
+        let len = 7 + 2 * self.descriptors.len();
+        let len = len + len % 2;
+
+        let mut flags = 0;
+        if self.observed {
+            flags |= 0b1000_0000;
+        }
+        if compressed {
+            flags |= 0b0100_0000;
+        }
+
+        let mut out = Vec::with_capacity(len);
+        out.extend_from_slice(&(len as u32).to_be_bytes()[1..]);
+        out.push(0);
+        out.extend_from_slice(&num_subsets.to_be_bytes());
+        out.push(flags);
+        for descriptor in &self.descriptors {
+            out.extend_from_slice(&descriptor.encode_binary_descriptor().to_be_bytes());
+        }
+        out.resize(len, 0);
+
+        out
+    }
+
+    /// Encode `subsets` as an uncompressed BUFR edition 4 message, from `BUFR` through `7777`.
+    /// The subsets are checked with `validate` first, and values that don't fit in their
+    /// element's width are an error rather than being written as missing.
+    pub fn encode(
+        &self,
+        header: &MessageHeader,
+        subsets: &[Vec<DataNode>],
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut writer = BitWriter::new();
+        for subset in self.subset_values(subsets)? {
+            for value in subset {
+                value.write(&mut writer)?;
+            }
+        }
+
+        self.message(header, subsets.len(), false, writer.into_bytes())
+    }
+
+    /// Like `encode`, but with the data compressed across subsets (regulation 94.6.3). Every
+    /// subset needs the same delayed replication counts, and elements wider than 64 bits
+    /// can't be compressed.
+    pub fn encode_compressed(
+        &self,
+        header: &MessageHeader,
+        subsets: &[Vec<DataNode>],
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let values = self.subset_values(subsets)?;
+        let first = values.first().map_or(&[][..], Vec::as_slice);
+        for (i, subset) in values.iter().enumerate() {
+            let same_shape = subset.len() == first.len()
+                && subset.iter().zip(first).all(|(a, b)| a.same_kind(b));
+            if !same_shape {
+                return Err(format!(
+                    "Subset {} has different replication counts from subset 0, so the subsets \
+                     can't be compressed",
+                    i
+                )
+                .into());
+            }
+        }
+
+        let mut writer = BitWriter::new();
+        for i in 0..first.len() {
+            let column: Vec<&EncodedValue> = values.iter().map(|subset| &subset[i]).collect();
+            write_column(&mut writer, &column)?;
+        }
+
+        self.message(header, subsets.len(), true, writer.into_bytes())
+    }
+
+    /// The values of each subset in the order they're written, after `validate`.
+    fn subset_values(
+        &self,
+        subsets: &[Vec<DataNode>],
+    ) -> Result<Vec<Vec<EncodedValue>>, Box<dyn Error>> {
+        self.validate(subsets)?;
+
+        let overrides = (!self.overrides.is_empty()).then_some(&self.overrides);
+        subsets
+            .iter()
+            .map(|subset| {
+                let mut encoder = Encoder {
+                    values: vec![],
+                    ops: Operators::default(),
+                    overrides,
+                };
+                encoder.encode_descriptors(&self.descriptors, subset)?;
+                Ok(encoder.values)
+            })
+            .collect()
+    }
+
+    fn message(
+        &self,
+        header: &MessageHeader,
+        num_subsets: usize,
+        compressed: bool,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let num_subsets = u16::try_from(num_subsets)
+            .map_err(|_| format!("Too many subsets for one message: {}", num_subsets))?;
+        if num_subsets == 0 {
+            return Err("A message needs at least one subset".into());
+        }
+
+        let section_1 = header.section_1_bytes();
+        let section_3 = self.section_3(num_subsets, compressed);
+        let section_4_len = 4 + data.len();
+        let total = 8 + section_1.len() + section_3.len() + section_4_len + 4;
+        if total >= 1 << 24 {
+            return Err(format!("The message is too long: {} octets", total).into());
+        }
+
+        let mut out = Vec::with_capacity(total);
+        out.extend_from_slice(b"BUFR");
+        out.extend_from_slice(&(total as u32).to_be_bytes()[1..]);
+        out.push(4);
+        out.extend_from_slice(&section_1);
+        out.extend_from_slice(&section_3);
+        out.extend_from_slice(&(section_4_len as u32).to_be_bytes()[1..]);
+        out.push(0);
+        out.extend_from_slice(&data);
+        out.extend_from_slice(b"7777");
+
+        Ok(out)
+    }
+}
+
+/// The Section 1 fields of a message to encode, see `DataDescription::encode`. Messages are
+/// written without a Section 2.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MessageHeader {
+    /// Originating centre, common code table C-11.
+    pub centre: u16,
+    pub sub_centre: u16,
+    pub update_number: u8,
+    /// BUFR Table A data category, 2 is vertical soundings other than satellite.
+    pub data_category: u8,
+    /// International data sub-category, common code table C-13.
+    pub data_sub_category: u8,
+    pub local_sub_category: u8,
+    pub master_table_version: u8,
+    pub local_tables_version: u8,
+    /// The typical time of the data, for soundings the nominal time.
+    pub time: Timestamp,
+}
+
+impl MessageHeader {
+    /// A header for vertical sounding data at `time`, from an unnamed centre using master table
+    /// version 26 and no local tables.
+    pub fn new(time: Timestamp) -> Self {
+        MessageHeader {
+            centre: 0,
+            sub_centre: 0,
+            update_number: 0,
+            data_category: 2,
+            data_sub_category: 255,
+            local_sub_category: 255,
+            master_table_version: 26,
+            local_tables_version: 0,
+            time,
+        }
+    }
+
+    #[rustfmt::skip]
+    fn section_1_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(22);
+        out.extend_from_slice(&22u32.to_be_bytes()[1..]);                  // octets 1-3
+        out.push(0);                                                        // octet 4
+        out.extend_from_slice(&self.centre.to_be_bytes());                  // octets 5-6
+        out.extend_from_slice(&self.sub_centre.to_be_bytes());              // octets 7-8
+        out.push(self.update_number);                                       // octet 9
+        out.push(0);                                                        // octet 10
+        out.push(self.data_category);                                       // octet 11
+        out.push(self.data_sub_category);                                   // octet 12
+        out.push(self.local_sub_category);                                  // octet 13
+        out.push(self.master_table_version);                                // octet 14
+        out.push(self.local_tables_version);                                // octet 15
+        out.extend_from_slice(&self.time.year.to_be_bytes());               // octets 16-17
+        out.push(self.time.month);                                          // octet 18
+        out.push(self.time.day);                                            // octet 19
+        out.push(self.time.hour);                                           // octet 20
+        out.push(self.time.minute);                                         // octet 21
+        out.push(self.time.second);                                         // octet 22
+        out
+    }
+}
+
+/// A value as it goes in Section 4, before it's packed.
+#[derive(Clone, Debug, PartialEq)]
+enum EncodedValue {
+    /// An unsigned integer, `None` for missing, which is written as all ones.
+    Number { raw: Option<u64>, width: usize },
+    /// `None` for missing, which is written as all ones.
+    Text { text: Option<String>, width: usize },
+    /// A number wider than 64 bits.
+    Bytes { bytes: Vec<u8>, width: usize },
+}
+
+impl EncodedValue {
+    fn write(&self, writer: &mut BitWriter) -> Result<(), Box<dyn Error>> {
+        match self {
+            EncodedValue::Number {
+                raw: Some(raw),
+                width,
+            } => writer.write_u64(*raw, *width),
+            EncodedValue::Number { raw: None, width } => {
+                writer.write_missing(*width);
+                Ok(())
+            }
+            EncodedValue::Text {
+                text: Some(text),
+                width,
+            } => writer.write_text(text, *width),
+            EncodedValue::Text { text: None, width } => {
+                writer.write_missing(*width);
+                Ok(())
+            }
+            EncodedValue::Bytes { bytes, width } => writer.write_bytes(bytes, *width),
+        }
+    }
+
+    /// Whether the values can share a compressed column.
+    fn same_kind(&self, other: &EncodedValue) -> bool {
+        match (self, other) {
+            (EncodedValue::Number { width: a, .. }, EncodedValue::Number { width: b, .. })
+            | (EncodedValue::Text { width: a, .. }, EncodedValue::Text { width: b, .. })
+            | (EncodedValue::Bytes { width: a, .. }, EncodedValue::Bytes { width: b, .. }) => {
+                a == b
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Write one value of each subset as a compressed reference, NBINC, and increments, the inverse
+/// of `CompressedSource::read_value`.
+fn write_column(writer: &mut BitWriter, column: &[&EncodedValue]) -> Result<(), Box<dyn Error>> {
+    match column[0] {
+        EncodedValue::Number { width, .. } => {
+            let raws: Vec<Option<u64>> = column
+                .iter()
+                .map(|value| match value {
+                    EncodedValue::Number { raw, .. } => *raw,
+                    _ => None,
+                })
+                .collect();
+            let (Some(min), Some(max)) = (
+                raws.iter().flatten().min().copied(),
+                raws.iter().flatten().max().copied(),
+            ) else {
+                writer.write_missing(*width);
+                return writer.write_u64(0, INCREMENT_WIDTH_BITS);
+            };
+
+            writer.write_u64(min, *width)?;
+            if raws.iter().all(|&raw| raw == Some(min)) {
+                return writer.write_u64(0, INCREMENT_WIDTH_BITS);
+            }
+            // All ones is a missing increment, so the largest increment is one less.
+            let increment_width = (64 - (max - min + 1).leading_zeros()) as usize;
+            writer.write_u64(increment_width as u64, INCREMENT_WIDTH_BITS)?;
+            for raw in raws {
+                match raw {
+                    Some(raw) => writer.write_u64(raw - min, increment_width)?,
+                    None => writer.write_missing(increment_width),
+                }
+            }
+            Ok(())
+        }
+        EncodedValue::Text { text, width } => {
+            if column.iter().all(|value| *value == column[0]) {
+                match text {
+                    Some(text) => writer.write_text(text, *width)?,
+                    None => writer.write_missing(*width),
+                }
+                return writer.write_u64(0, INCREMENT_WIDTH_BITS);
+            }
+
+            // A reference of zeros and the text of each subset.
+            let octets = width / 8;
+            for _ in 0..octets {
+                writer.write_u64(0, 8)?;
+            }
+            writer
+                .write_u64(octets as u64, INCREMENT_WIDTH_BITS)
+                .map_err(|_| format!("Text {} octets long can't be compressed", octets))?;
+            for value in column {
+                match value {
+                    EncodedValue::Text {
+                        text: Some(text), ..
+                    } => writer.write_text(text, *width)?,
+                    _ => writer.write_missing(*width),
+                }
+            }
+            Ok(())
+        }
+        EncodedValue::Bytes { width, .. } => {
+            Err(format!("Elements {} bits wide can't be compressed", width).into())
+        }
+    }
+}
+
+/// Walks the descriptors and collects the values of a subset, the inverse of `Decoder`.
+struct Encoder<'a> {
+    values: Vec<EncodedValue>,
+    ops: Operators,
+    overrides: Option<&'a TableOverrides>,
+}
+
+impl Encoder<'_> {
+    fn encode_descriptors(
+        &mut self,
+        descriptors: &[Descriptor],
+        nodes: &[DataNode],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut nodes = nodes.iter();
+
+        let mut i = 0;
+        while i < descriptors.len() {
+            let desc = descriptors[i];
+            i += 1;
+
+            // Operators change how values are written but don't have values of their own.
+            if desc.f_value() == 2 {
+                self.ops.apply(desc)?;
+                continue;
+            }
+            let node = nodes
+                .next()
+                .ok_or_else(|| format!("No data for {}", desc.string_form()))?;
+
+            match (desc.f_value(), node) {
+                (0, DataNode::Element { value, raw, .. }) => {
+                    let entry = tables::table_b_entry(desc, self.overrides)?;
+                    self.write_value(desc, &entry, value, *raw)?;
+                }
+                (1, DataNode::Replication { repetitions, .. }) => {
+                    let mut consumed = 0;
+                    if desc.y_value() == 0 {
+                        let factor = descriptors.get(i).ok_or("Ran out of descriptors")?;
+                        let entry = tables::table_b_entry(*factor, self.overrides)?;
+                        let max = (1u64 << entry.width_bits) - 1;
+                        if repetitions.len() as u64 >= max {
+                            return Err(format!(
+                                "{} can't count {} repetitions",
+                                factor.string_form(),
+                                repetitions.len()
+                            )
+                            .into());
+                        }
+                        self.values.push(EncodedValue::Number {
+                            raw: Some(repetitions.len() as u64),
+                            width: entry.width_bits,
+                        });
+                        consumed += 1;
+                    }
+
+                    let num_descriptors = desc.x_value() as usize;
+                    let group = descriptors
+                        .get(i + consumed..i + consumed + num_descriptors)
+                        .ok_or("Ran out of descriptors in replication!")?;
+                    for repetition in repetitions {
+                        self.encode_descriptors(group, repetition)?;
+                    }
+                    i += consumed + num_descriptors;
+                }
+                (3, DataNode::Sequence { children, .. }) => {
+                    let sequence = tables::table_d_sequence(desc, self.overrides)?;
+                    self.encode_descriptors(sequence, children)?;
+                }
+                _ => return Err(format!("The data doesn't match {}", desc.string_form()).into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The inverse of `ValueSource::read_value` for BUFR, applying the operators the same way.
+    /// A decoded `raw` integer is written as it is, if it still decodes to `value`.
+    fn write_value(
+        &mut self,
+        desc: Descriptor,
+        entry: &TableBEntry,
+        value: &Value,
+        raw: Option<u64>,
+    ) -> Result<(), Box<dyn Error>> {
+        let (width, reference, scale) = match entry.units {
+            "CCITT IA5" => {
+                let bits = self.ops.text_width.unwrap_or(entry.width_bits);
+                self.values.push(EncodedValue::Text {
+                    text: value.as_str().map(str::to_owned),
+                    width: bits,
+                });
+                return Ok(());
+            }
+            "Code table" | "Flag table" => (entry.width_bits, entry.reference_val, 0),
+            _ => {
+                let ops = &self.ops;
+                let width =
+                    entry.width_bits as i32 + ops.width_change + (10 * ops.srw_increase + 2) / 3;
+                let scale = entry.scale_val + ops.scale_change + ops.srw_increase;
+                let reference = entry.reference_val * 10i64.pow(ops.srw_increase as u32);
+                (usize::try_from(width)?, reference, scale)
+            }
+        };
+
+        if let Value::Bytes(bytes) = value {
+            self.values.push(EncodedValue::Bytes {
+                bytes: bytes.clone(),
+                width,
+            });
+            return Ok(());
+        }
+        let Some(val) = value.as_f64() else {
+            self.values.push(EncodedValue::Number { raw: None, width });
+            return Ok(());
+        };
+        if width > 64 {
+            return Err(format!(
+                "{} is {} bits wide, so needs Value::Bytes",
+                desc.string_form(),
+                width
+            )
+            .into());
+        }
+
+        // Write a decimal at the same scale or an unchanged raw integer exactly, and otherwise
+        // round the scaled value.
+        let unchanged =
+            |raw: u64| add_reference(raw, reference).is_ok_and(|v| apply_scale(v, scale) == val);
+        let raw = match (value, raw) {
+            (Value::Decimal(decimal), _) if decimal.scale == scale => {
+                decimal.mantissa.checked_sub(reference).unwrap_or(-1)
+            }
+            (_, Some(raw)) if unchanged(raw) => i64::try_from(raw)?,
+            _ => {
+                let scaled = match scale {
+                    0 => val,
+                    s if s > 0 => val * f64::powi(10.0, s),
+                    s => val / f64::powi(10.0, -s),
+                };
+                scaled.round() as i64 - reference
+            }
+        };
+
+        // All ones is the missing value, so the largest value is one less.
+        let max = if width == 64 {
+            u64::MAX
+        } else {
+            (1 << width) - 1
+        };
+        match u64::try_from(raw) {
+            Ok(raw) if raw < max => {
+                self.values.push(EncodedValue::Number {
+                    raw: Some(raw),
+                    width,
+                });
+                Ok(())
+            }
+            _ => Err(format!("{} is out of range for {}", val, desc.string_form()).into()),
+        }
+    }
+}
+
+fn check_nodes(expansion: &[ExpansionNode], nodes: &[DataNode]) -> Result<(), String> {
+    // Operators change how values are encoded but don't have values of their own.
+    let mut expected = expansion
+        .iter()
+        .filter(|node| !matches!(node, ExpansionNode::Operator { .. }));
+    let mut nodes = nodes.iter();
+
+    loop {
+        match (expected.next(), nodes.next()) {
+            (None, None) => return Ok(()),
+            (Some(expected), None) => {
+                return Err(format!("missing {}", expected_descriptor(expected)))
+            }
+            (None, Some(node)) => {
+                return Err(format!(
+                    "unexpected {}",
+                    node_descriptor(node).string_form()
+                ))
+            }
+            (Some(expected), Some(node)) => check_node(expected, node)?,
+        }
+    }
+}
+
+fn check_node(expected: &ExpansionNode, node: &DataNode) -> Result<(), String> {
+    let mismatch = || {
+        format!(
+            "expected {}, found {}",
+            expected_descriptor(expected),
+            node_descriptor(node).string_form()
+        )
+    };
+
+    match (expected, node) {
+        (
+            ExpansionNode::Element {
+                descriptor, units, ..
+            },
+            DataNode::Element {
+                descriptor: d,
+                value,
+                ..
+            },
+        ) if descriptor == d => {
+            let is_text = units == "CCITT IA5";
+            match value {
+                Value::Text(_) if !is_text => {
+                    Err(format!("{} is numeric, found text", d.string_form()))
+                }
+                Value::Integer(_) | Value::Float(_) | Value::Bytes(_) if is_text => {
+                    Err(format!("{} is text, found a number", d.string_form()))
+                }
+                _ => Ok(()),
+            }
+        }
+        (
+            ExpansionNode::Sequence {
+                descriptor,
+                children,
+            },
+            DataNode::Sequence {
+                descriptor: d,
+                children: nodes,
+            },
+        ) if descriptor == d => check_nodes(children, nodes),
+        (
+            ExpansionNode::Replication {
+                descriptor,
+                factor,
+                children,
+            },
+            DataNode::Replication {
+                descriptor: d,
+                repetitions,
+            },
+        ) if descriptor == d => {
+            if factor.is_none() && repetitions.len() != descriptor.y_value() as usize {
+                return Err(format!(
+                    "{} repeats {} times, found {}",
+                    d.string_form(),
+                    descriptor.y_value(),
+                    repetitions.len()
+                ));
+            }
+            repetitions
+                .iter()
+                .try_for_each(|repetition| check_nodes(children, repetition))
+        }
+        _ => Err(mismatch()),
+    }
+}
+
+fn expected_descriptor(node: &ExpansionNode) -> String {
+    match node {
+        ExpansionNode::Element { descriptor, .. }
+        | ExpansionNode::Sequence { descriptor, .. }
+        | ExpansionNode::Replication { descriptor, .. }
+        | ExpansionNode::Operator { descriptor } => descriptor.string_form(),
+    }
+}
+
+fn node_descriptor(node: &DataNode) -> Descriptor {
+    match node {
+        DataNode::Element { descriptor, .. }
+        | DataNode::Sequence { descriptor, .. }
+        | DataNode::Replication { descriptor, .. } => *descriptor,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{read_bufr_message, section3::read_section_3};
+    use std::{fs::File, io::BufReader};
+
+    #[test]
+    fn test_encode_missing_text() {
+        let station_name = Descriptor::new(0, 1, 15);
+        let description = Section3Builder::new()
+            .element(station_name)
+            .build()
+            .unwrap();
+        let subset = |value| {
+            vec![DataNode::Element {
+                descriptor: station_name,
+                value,
+                raw: None,
+            }]
+        };
+        let subsets = [
+            subset(Value::Missing),
+            subset(Value::Text("MISSOULA".into())),
+        ];
+        let header = MessageHeader::new(crate::Timestamp {
+            year: 2020,
+            month: 6,
+            day: 1,
+            hour: 12,
+            minute: 0,
+            second: 0,
+        });
+
+        for compressed in [false, true] {
+            let message = if compressed {
+                description.encode_compressed(&header, &subsets)
+            } else {
+                description.encode(&header, &subsets)
+            }
+            .unwrap();
+            let decoded = read_bufr_message(message.as_slice()).unwrap();
+            let values: Vec<&Value> = decoded
+                .subsets()
+                .iter()
+                .map(|subset| match &subset[0] {
+                    DataNode::Element { value, .. } => value,
+                    _ => panic!("not an element"),
+                })
+                .collect();
+            assert_eq!(values[0], &Value::Missing);
+            assert_eq!(values[1].as_str().map(str::trim_end), Some("MISSOULA"));
+        }
+    }
+
+    #[test]
+    fn test_section_3_builder() {
+        let pressure = Descriptor::new(0, 7, 4);
+        let temperature = Descriptor::new(0, 12, 101);
+
+        let description = Section3Builder::new()
+            .element(Descriptor::new(0, 1, 1))
+            .delayed_replicate(Descriptor::new(0, 31, 1), &[pressure, temperature])
+            .build()
+            .unwrap();
+        assert_eq!(
+            description.descriptors(),
+            [
+                Descriptor::new(0, 1, 1),
+                Descriptor::new(1, 2, 0),
+                Descriptor::new(0, 31, 1),
+                pressure,
+                temperature,
+            ]
+        );
+
+        // Section 3 reads back the same.
+        let bytes = description.section_3_bytes(3);
+        assert_eq!(bytes.len() % 2, 0);
+        let sec3 = read_section_3(bytes.as_slice()).unwrap();
+        assert_eq!(sec3.num_datasets(), 3);
+        assert_eq!(sec3.descriptors(), description.descriptors());
+
+        let element = |descriptor, value| DataNode::Element {
+            descriptor,
+            value,
+            raw: None,
+        };
+        let subset = vec![
+            element(Descriptor::new(0, 1, 1), Value::Integer(72)),
+            DataNode::Replication {
+                descriptor: Descriptor::new(1, 2, 0),
+                repetitions: vec![vec![
+                    element(pressure, Value::Float(85_000.0)),
+                    element(temperature, Value::Missing),
+                ]],
+            },
+        ];
+        description.validate(std::slice::from_ref(&subset)).unwrap();
+
+        // Encoding it reads back the same, and values too big for their width are an error.
+        let time = crate::Timestamp {
+            year: 2020,
+            month: 6,
+            day: 1,
+            hour: 12,
+            minute: 0,
+            second: 0,
+        };
+        let message = description
+            .encode(&MessageHeader::new(time), std::slice::from_ref(&subset))
+            .unwrap();
+        let decoded = read_bufr_message(message.as_slice()).unwrap();
+        assert_eq!(decoded.nominal_time(), time);
+        let mut subsets = decoded.section_4.subsets().to_vec();
+        DataNode::clear_raw(&mut subsets[0]);
+        assert_eq!(format!("{:?}", subsets), format!("{:?}", [&subset]));
+        let too_big = vec![
+            element(Descriptor::new(0, 1, 1), Value::Integer(128)),
+            subset[1].clone(),
+        ];
+        let err = description
+            .encode(&MessageHeader::new(time), &[too_big])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "128 is out of range for 001001");
+
+        // A raw integer is written as it is while it matches the value, and ignored once the
+        // value has been changed.
+        let with_raw = |value, raw| {
+            let mut subset = subset.clone();
+            subset[0] = DataNode::Element {
+                descriptor: Descriptor::new(0, 1, 1),
+                value: Value::Integer(value),
+                raw: Some(raw),
+            };
+            let message = description
+                .encode(&MessageHeader::new(time), &[subset])
+                .unwrap();
+            match &read_bufr_message(message.as_slice())
+                .unwrap()
+                .section_4
+                .subsets()[0][0]
+            {
+                DataNode::Element { raw, .. } => *raw,
+                _ => None,
+            }
+        };
+        assert_eq!(with_raw(72, 72), Some(72));
+        assert_eq!(with_raw(73, 72), Some(73));
+
+        let mut wrong = subset;
+        wrong.truncate(1);
+        let err = description.validate(&[wrong]).unwrap_err();
+        assert_eq!(err.to_string(), "Subset 0: missing 102000");
+
+        assert!(Section3Builder::new().sequence(pressure).build().is_err());
+        assert!(Section3Builder::new()
+            .sequence(Descriptor::new(3, 63, 255))
+            .build()
+            .is_err());
+
+        // A decoded message has the shape of its own descriptors.
+        let mut f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+        crate::scan_to_bufr_start(&mut f).unwrap();
+        let bufr = read_bufr_message(&mut f).unwrap();
+        Section3Builder::new()
+            .descriptors(bufr.descriptors())
+            .build()
+            .unwrap()
+            .validate(bufr.section_4.subsets())
+            .unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{
+    section3::Descriptor,
+    tables::{table_b_entry, table_d_sequence, TableOverrides},
+};
+use std::{error::Error, fmt::Display};
+
+/// One node of a descriptor list expanded through Tables B and D, see `expand_descriptors`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpansionNode {
+    Element {
+        descriptor: Descriptor,
+        name: String,
+        units: String,
+        width_bits: usize,
+    },
+    Sequence {
+        descriptor: Descriptor,
+        children: Vec<ExpansionNode>,
+    },
+    /// `factor` is the delayed replication factor descriptor (0-31-YYY), whose value in the data
+    /// gives the number of repetitions, or `None` if the count is in the replication descriptor.
+    Replication {
+        descriptor: Descriptor,
+        factor: Option<Descriptor>,
+        children: Vec<ExpansionNode>,
+    },
+    /// A Table C operator, listed whether or not the decoder supports it.
+    Operator { descriptor: Descriptor },
+}
+
+/// Sequences nested deeper than this are assumed to be a loop in the tables.
+pub(crate) const MAX_DEPTH: usize = 32;
+
+/// Expand descriptors, e.g. from `BufrMessage::descriptors`, into the tree the decoder walks,
+/// without any data. Descriptors are looked up in `overrides` before the built-in tables.
+pub fn expand_descriptors(
+    descriptors: &[Descriptor],
+    overrides: Option<&TableOverrides>,
+) -> Result<Vec<ExpansionNode>, Box<dyn Error>> {
+    expand(descriptors, overrides, 0)
+}
+
+fn expand(
+    descriptors: &[Descriptor],
+    overrides: Option<&TableOverrides>,
+    depth: usize,
+) -> Result<Vec<ExpansionNode>, Box<dyn Error>> {
+    if depth > MAX_DEPTH {
+        return Err("Table D sequences are nested too deeply.".into());
+    }
+
+    let mut nodes = Vec::with_capacity(descriptors.len());
+
+    let mut i = 0;
+    while i < descriptors.len() {
+        let descriptor = descriptors[i];
+        i += 1;
+
+        match descriptor.f_value() {
+            0 => {
+                let entry = table_b_entry(descriptor, overrides)?;
+                nodes.push(ExpansionNode::Element {
+                    descriptor,
+                    name: entry.element_name.to_owned(),
+                    units: entry.units.to_owned(),
+                    width_bits: entry.width_bits,
+                });
+            }
+            1 => {
+                let factor = if descriptor.y_value() == 0 {
+                    let factor = *descriptors
+                        .get(i)
+                        .ok_or("Ran out of descriptors in delayed replication!")?;
+                    if factor.f_value() != 0 || factor.x_value() != 31 {
+                        return Err(format!(
+                            "Expected delayed replication factor, found {}",
+                            factor.string_form()
+                        )
+                        .into());
+                    }
+                    i += 1;
+                    Some(factor)
+                } else {
+                    None
+                };
+
+                let num_descriptors = descriptor.x_value() as usize;
+                let group = descriptors
+                    .get(i..(i + num_descriptors))
+                    .ok_or("Ran out of descriptors in replication!")?;
+                i += num_descriptors;
+
+                nodes.push(ExpansionNode::Replication {
+                    descriptor,
+                    factor,
+                    children: expand(group, overrides, depth + 1)?,
+                });
+            }
+            2 => nodes.push(ExpansionNode::Operator { descriptor }),
+            3 => {
+                let sequence = table_d_sequence(descriptor, overrides)?;
+                nodes.push(ExpansionNode::Sequence {
+                    descriptor,
+                    children: expand(sequence, overrides, depth + 1)?,
+                });
+            }
+            _ => {
+                return Err(format!("Unknown descriptor type: {}", descriptor.string_form()).into())
+            }
+        }
+    }
+
+    Ok(nodes)
+}
+
+impl Display for ExpansionNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        self.fmt_indented(f, 0)
+    }
+}
+
+impl ExpansionNode {
+    fn fmt_indented(
+        &self,
+        f: &mut std::fmt::Formatter,
+        indent: usize,
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            ExpansionNode::Element {
+                descriptor,
+                name,
+                units,
+                width_bits,
+            } => writeln!(
+                f,
+                "{:indent$}{} {} ({}, {} bits)",
+                "",
+                descriptor.string_form(),
+                name,
+                units,
+                width_bits,
+                indent = indent
+            ),
+            ExpansionNode::Sequence {
+                descriptor,
+                children,
+            } => {
+                writeln!(
+                    f,
+                    "{:indent$}{}",
+                    "",
+                    descriptor.string_form(),
+                    indent = indent
+                )?;
+                for child in children {
+                    child.fmt_indented(f, indent + 2)?;
+                }
+                Ok(())
+            }
+            ExpansionNode::Replication {
+                descriptor,
+                factor,
+                children,
+            } => {
+                let count = match factor {
+                    Some(factor) => format!("delayed by {}", factor.string_form()),
+                    None => format!("{} times", descriptor.y_value()),
+                };
+                writeln!(
+                    f,
+                    "{:indent$}{} repeated {}",
+                    "",
+                    descriptor.string_form(),
+                    count,
+                    indent = indent
+                )?;
+                for child in children {
+                    child.fmt_indented(f, indent + 2)?;
+                }
+                Ok(())
+            }
+            ExpansionNode::Operator { descriptor } => writeln!(
+                f,
+                "{:indent$}{} operator",
+                "",
+                descriptor.string_form(),
+                indent = indent
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expand_descriptors() {
+        let descriptors = [
+            Descriptor::new(2, 1, 129),
+            Descriptor::new(1, 2, 0),
+            Descriptor::new(0, 31, 1),
+            Descriptor::new(0, 7, 4),
+            Descriptor::new(3, 1, 1),
+        ];
+        let nodes = expand_descriptors(&descriptors, None).unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert!(matches!(nodes[0], ExpansionNode::Operator { .. }));
+
+        let ExpansionNode::Replication {
+            factor, children, ..
+        } = &nodes[1]
+        else {
+            panic!("expected a replication");
+        };
+        assert_eq!(*factor, Some(Descriptor::new(0, 31, 1)));
+        assert!(matches!(
+            &children[0],
+            ExpansionNode::Element { units, .. } if units == "Pa"
+        ));
+        // 3-01-001 is the WMO block and station numbers.
+        assert!(matches!(
+            &children[1],
+            ExpansionNode::Sequence { children, .. } if children.len() == 2
+        ));
+
+        let short = [Descriptor::new(1, 2, 0), Descriptor::new(0, 31, 1)];
+        assert!(expand_descriptors(&short, None).is_err());
+
+        // A sequence that contains itself.
+        let mut overrides = TableOverrides::new();
+        let looped = Descriptor::new(3, 63, 1);
+        overrides.add_sequence(looped, vec![looped]).unwrap();
+        assert!(expand_descriptors(&[looped], Some(&overrides)).is_err());
+    }
+}
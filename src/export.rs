@@ -0,0 +1,1052 @@
+use crate::{
+    assimilation::Rejection,
+    heights::HeightSource,
+    qc::default_checks,
+    section3::Descriptor,
+    significant::SignificantLevelTolerances,
+    smooth::Smoothing,
+    sounding::{self, Level, Sounding},
+};
+use std::{borrow::Cow, error::Error, io::Write, str::FromStr};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TemperatureUnit {
+    #[default]
+    Kelvin,
+    Celsius,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpeedUnit {
+    #[default]
+    MetersPerSecond,
+    Knots,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PressureUnit {
+    #[default]
+    Pascals,
+    Hectopascals,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeightUnit {
+    #[default]
+    Meters,
+    Feet,
+}
+
+/// How the exporters write a value that is missing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MissingValue {
+    /// Whatever the format usually uses, e.g. an empty CSV field or a JSON `null`.
+    #[default]
+    Format,
+    /// `NaN`. JSON has no NaN, so `write_json` still writes `null`.
+    Nan,
+    /// A number such as -9999.
+    Sentinel(f64),
+}
+
+impl MissingValue {
+    /// Fill in a missing value, e.g. a `Level` field or `Value::as_f64`. `None` means the format
+    /// decides.
+    pub fn fill(&self, val: Option<f64>) -> Option<f64> {
+        match (val, self) {
+            (Some(val), _) => Some(val),
+            (None, MissingValue::Format) => None,
+            (None, MissingValue::Nan) => Some(f64::NAN),
+            (None, MissingValue::Sentinel(sentinel)) => Some(*sentinel),
+        }
+    }
+
+    /// Fill in a missing value for a format that has its own missing value.
+    fn fill_or(&self, val: Option<f64>, format: f64) -> f64 {
+        self.fill(val).unwrap_or(format)
+    }
+}
+
+/// The units written by the exporters. The default is the BUFR units a sounding is decoded in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExportOptions {
+    pub temperature: TemperatureUnit,
+    pub speed: SpeedUnit,
+    pub pressure: PressureUnit,
+    /// Used for both level heights and the station elevation.
+    pub height: HeightUnit,
+    pub missing: MissingValue,
+    /// Which height each level is written with, see `Sounding::use_height_source`.
+    pub height_source: HeightSource,
+    /// Smooth noisy high resolution profiles before writing them, see `Sounding::smooth`.
+    pub smoothing: Option<Smoothing>,
+    /// Write only the significant levels, after any smoothing, see
+    /// `Sounding::significant_levels`.
+    pub significant_levels: Option<SignificantLevelTolerances>,
+    /// Drop or mask the levels that fail the default QC checks or that the data's own quality
+    /// information marks as suspect, before anything else, see `Sounding::for_assimilation`.
+    pub rejection: Option<Rejection>,
+}
+
+impl ExportOptions {
+    /// Convert a temperature in K.
+    pub fn temperature(&self, val: f64) -> f64 {
+        match self.temperature {
+            TemperatureUnit::Kelvin => val,
+            TemperatureUnit::Celsius => val - 273.15,
+        }
+    }
+
+    /// Convert a speed in m/s.
+    pub fn speed(&self, val: f64) -> f64 {
+        match self.speed {
+            SpeedUnit::MetersPerSecond => val,
+            SpeedUnit::Knots => val * 3600.0 / 1852.0,
+        }
+    }
+
+    /// Convert a pressure in Pa.
+    pub fn pressure(&self, val: f64) -> f64 {
+        match self.pressure {
+            PressureUnit::Pascals => val,
+            PressureUnit::Hectopascals => val / 100.0,
+        }
+    }
+
+    /// Convert a height in m.
+    pub fn height(&self, val: f64) -> f64 {
+        match self.height {
+            HeightUnit::Meters => val,
+            HeightUnit::Feet => val / 0.3048,
+        }
+    }
+
+    pub fn temperature_label(&self) -> &'static str {
+        match self.temperature {
+            TemperatureUnit::Kelvin => "K",
+            TemperatureUnit::Celsius => "C",
+        }
+    }
+
+    pub fn speed_label(&self) -> &'static str {
+        match self.speed {
+            SpeedUnit::MetersPerSecond => "m/s",
+            SpeedUnit::Knots => "kt",
+        }
+    }
+
+    pub fn pressure_label(&self) -> &'static str {
+        match self.pressure {
+            PressureUnit::Pascals => "Pa",
+            PressureUnit::Hectopascals => "hPa",
+        }
+    }
+
+    pub fn height_label(&self) -> &'static str {
+        match self.height {
+            HeightUnit::Meters => "m",
+            HeightUnit::Feet => "ft",
+        }
+    }
+
+    /// `sounding` with the heights from the `height_source`, cleaned, smoothed, and reduced to
+    /// its significant levels if asked for.
+    fn prepare<'a>(&self, sounding: &'a Sounding) -> Cow<'a, Sounding> {
+        let mut sounding = match self.rejection {
+            Some(rejection) => {
+                Cow::Owned(sounding.for_assimilation(&default_checks(), rejection).0)
+            }
+            None => Cow::Borrowed(sounding),
+        };
+        if let Some(smoothing) = &self.smoothing {
+            sounding = Cow::Owned(sounding.smooth(smoothing));
+        }
+        if let Some(tolerances) = &self.significant_levels {
+            sounding = Cow::Owned(sounding.significant_levels(tolerances));
+        }
+        if self.height_source != HeightSource::Geopotential {
+            sounding.to_mut().use_height_source(self.height_source);
+        }
+
+        sounding
+    }
+
+    /// The values of a level that the exporters write, converted.
+    fn row(&self, lvl: &Level) -> [Option<f64>; 7] {
+        [
+            lvl.time_offset,
+            lvl.pressure.map(|v| self.pressure(v)),
+            lvl.height.map(|v| self.height(v)),
+            lvl.temperature.map(|v| self.temperature(v)),
+            lvl.dewpoint.map(|v| self.temperature(v)),
+            lvl.wind_direction,
+            lvl.wind_speed.map(|v| self.speed(v)),
+        ]
+    }
+
+    fn column_names(&self) -> [String; 7] {
+        [
+            "time_offset_s".to_owned(),
+            format!("pressure_{}", self.pressure_label()),
+            format!("height_{}", self.height_label()),
+            format!("temperature_{}", self.temperature_label()),
+            format!("dewpoint_{}", self.temperature_label()),
+            "wind_direction_deg".to_owned(),
+            format!("wind_speed_{}", self.speed_label().replace('/', "")),
+        ]
+    }
+}
+
+/// Write the levels of a sounding as CSV with a header row naming each column and its units.
+/// Missing values are empty fields unless `options.missing` says otherwise.
+pub fn write_csv(
+    mut w: impl Write,
+    sounding: &Sounding,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let sounding = &*options.prepare(sounding);
+    writeln!(w, "{},significance", options.column_names().join(","))?;
+
+    for lvl in sounding.levels() {
+        for val in options.row(lvl) {
+            if let Some(val) = options.missing.fill(val) {
+                write!(w, "{}", val)?;
+            }
+            write!(w, ",")?;
+        }
+        if let Some(sig) = options.missing.fill(lvl.significance.map(f64::from)) {
+            write!(w, "{}", sig)?;
+        }
+        writeln!(w)?;
+    }
+
+    Ok(())
+}
+
+/// Write a sounding as a JSON object with the station, launch, units, and an array of levels.
+/// Missing values are `null` unless `options.missing` is a sentinel.
+pub fn write_json(
+    mut w: impl Write,
+    sounding: &Sounding,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let sounding = &*options.prepare(sounding);
+    write_json_header(&mut w, sounding, options, &json_units(options))?;
+
+    writeln!(w, "  \"levels\": [")?;
+    let num_levels = sounding.levels().len();
+    for (i, lvl) in sounding.levels().iter().enumerate() {
+        writeln!(
+            w,
+            "    {}{}",
+            json_level(lvl, options),
+            if i + 1 == num_levels { "" } else { "," }
+        )?;
+    }
+    writeln!(w, "  ]")?;
+    writeln!(w, "}}")?;
+
+    Ok(())
+}
+
+/// Write a sounding as for `write_json`, all on one line and followed by a newline, for JSON
+/// Lines output. See `JsonLinesWriter`.
+pub fn write_json_line(
+    mut w: impl Write,
+    sounding: &Sounding,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let sounding = &*options.prepare(sounding);
+
+    let mut fields: Vec<String> = json_header(sounding, options, &json_units(options))
+        .into_iter()
+        .map(|(name, val)| format!("\"{}\": {}", name, val))
+        .collect();
+    let levels: Vec<String> = sounding
+        .levels()
+        .iter()
+        .map(|lvl| json_level(lvl, options))
+        .collect();
+    fields.push(format!("\"levels\": [{}]", levels.join(", ")));
+    writeln!(w, "{{{}}}", fields.join(", "))?;
+
+    Ok(())
+}
+
+/// The units object of a JSON sounding.
+fn json_units(options: &ExportOptions) -> String {
+    format!(
+        "{{\"pressure\": \"{}\", \"height\": \"{}\", \"temperature\": \"{}\", \"speed\": \"{}\"}}",
+        options.pressure_label(),
+        options.height_label(),
+        options.temperature_label(),
+        options.speed_label()
+    )
+}
+
+/// One level of a JSON sounding.
+fn json_level(lvl: &Level, options: &ExportOptions) -> String {
+    let json_number = |val: Option<f64>| json_number(options.missing.fill(val));
+    let [time_offset, pressure, height, temperature, dewpoint, direction, speed] = options.row(lvl);
+    format!(
+        "{{\"time_offset\": {}, \"significance\": {}, \"pressure\": {}, \"height\": {}, \
+         \"temperature\": {}, \"dewpoint\": {}, \"wind_direction\": {}, \
+         \"wind_speed\": {}}}",
+        json_number(time_offset),
+        json_number(lvl.significance.map(f64::from)),
+        json_number(pressure),
+        json_number(height),
+        json_number(temperature),
+        json_number(dewpoint),
+        json_number(direction),
+        json_number(speed)
+    )
+}
+
+/// The fields of a JSON sounding before its levels, with `units` as the units object.
+fn write_json_header(
+    mut w: impl Write,
+    sounding: &Sounding,
+    options: &ExportOptions,
+    units: &str,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(w, "{{")?;
+    for (name, val) in json_header(sounding, options, units) {
+        writeln!(w, "  \"{}\": {},", name, val)?;
+    }
+
+    Ok(())
+}
+
+/// The names and JSON values of the fields of a sounding before its levels.
+fn json_header(
+    sounding: &Sounding,
+    options: &ExportOptions,
+    units: &str,
+) -> [(&'static str, String); 8] {
+    let station = sounding.station();
+    let json_number = |val: Option<f64>| json_number(options.missing.fill(val));
+
+    [
+        ("station", json_string(station.identifier().as_deref())),
+        ("latitude", json_number(station.latitude)),
+        ("longitude", json_number(station.longitude)),
+        (
+            "elevation",
+            json_number(station.elevation.map(|v| options.height(v))),
+        ),
+        (
+            "launch_time",
+            json_string(sounding.launch_time().map(|t| t.to_string()).as_deref()),
+        ),
+        ("phase", format!("\"{}\"", sounding.phase())),
+        (
+            "report_type",
+            json_string(sounding.report_type().map(|r| r.to_string()).as_deref()),
+        ),
+        ("units", units.to_owned()),
+    ]
+}
+
+/// Write a sounding in the BUFKIT / GEMPAK text sounding layout. Column names are the GEMPAK
+/// parameters for the chosen units (TMPC or TMPK, SKNT or SPED, HGHT or HGFT). GEMPAK defines
+/// PRES in hPa, so the pressure option is ignored. Missing values are -9999.00 unless
+/// `options.missing` says otherwise.
+pub fn write_bufkit(
+    mut w: impl Write,
+    sounding: &Sounding,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let names = gempak_names(options);
+
+    writeln!(w, "SNPARM = {}", names.join(";"))?;
+    writeln!(w)?;
+    write_gempak_station(&mut w, sounding, options, &names)
+}
+
+/// Write several soundings as one GEMPAK SNLIST file, the SNPARM line once and then a block per
+/// sounding grouped by station and ordered by time. The layout of each block and the units are
+/// as for `write_bufkit`.
+pub fn write_gempak(
+    mut w: impl Write,
+    soundings: &[Sounding],
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let names = gempak_names(options);
+
+    let mut sorted: Vec<&Sounding> = soundings.iter().collect();
+    sorted.sort_by_key(|s| (s.station().identifier(), s.launch_time()));
+
+    writeln!(w, "SNPARM = {}", names.join(";"))?;
+    for sounding in sorted {
+        writeln!(w)?;
+        write_gempak_station(&mut w, sounding, options, &names)?;
+    }
+
+    Ok(())
+}
+
+fn gempak_names(options: &ExportOptions) -> [&'static str; 6] {
+    let (tmp, dwp) = match options.temperature {
+        TemperatureUnit::Kelvin => ("TMPK", "DWPK"),
+        TemperatureUnit::Celsius => ("TMPC", "DWPC"),
+    };
+    let spd = match options.speed {
+        SpeedUnit::MetersPerSecond => "SPED",
+        SpeedUnit::Knots => "SKNT",
+    };
+    let hgt = match options.height {
+        HeightUnit::Meters => "HGHT",
+        HeightUnit::Feet => "HGFT",
+    };
+
+    ["PRES", tmp, dwp, "DRCT", spd, hgt]
+}
+
+/// The station and time lines and the levels of one sounding.
+fn write_gempak_station(
+    mut w: impl Write,
+    sounding: &Sounding,
+    options: &ExportOptions,
+    names: &[&str],
+) -> Result<(), Box<dyn Error>> {
+    let sounding = &*options.prepare(sounding);
+    let station = sounding.station();
+    let stnm = match (station.wmo_block, station.wmo_station) {
+        (Some(block), Some(stn)) => u32::from(block) * 1000 + u32::from(stn),
+        _ => 0,
+    };
+    let time = match sounding.launch_time() {
+        Some(t) => format!(
+            "{:02}{:02}{:02}/{:02}{:02}",
+            t.year % 100,
+            t.month,
+            t.day,
+            t.hour,
+            t.minute
+        ),
+        None => "000000/0000".to_owned(),
+    };
+    let num = |val: Option<f64>| format!("{:.2}", options.missing.fill_or(val, -9999.0));
+
+    writeln!(
+        w,
+        "STID = {} STNM = {} TIME = {}",
+        station.identifier().unwrap_or_default(),
+        stnm,
+        time
+    )?;
+    writeln!(
+        w,
+        "SLAT = {} SLON = {} SELV = {}",
+        num(station.latitude),
+        num(station.longitude),
+        num(station.elevation.map(|v| options.height(v)))
+    )?;
+    writeln!(w)?;
+    writeln!(w, "{}", names.join(" "))?;
+
+    for lvl in sounding.levels() {
+        let Some(pressure) = lvl.pressure else {
+            continue;
+        };
+        writeln!(
+            w,
+            "{} {} {} {} {} {}",
+            num(Some(pressure / 100.0)),
+            num(lvl.temperature.map(|v| options.temperature(v))),
+            num(lvl.dewpoint.map(|v| options.temperature(v))),
+            num(lvl.wind_direction),
+            num(lvl.wind_speed.map(|v| options.speed(v))),
+            num(lvl.height.map(|v| options.height(v)))
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write a sounding in the CSV layout RAOB imports, the keyword header lines followed by
+/// `RAOB/DATA` and a row per level. RAOB expects pressure in hPa, temperatures in C and heights
+/// in m MSL, so only the speed and missing options are used. Missing values are -999 unless
+/// `options.missing` says otherwise.
+pub fn write_raob_csv(
+    mut w: impl Write,
+    sounding: &Sounding,
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let sounding = &*options.prepare(sounding);
+    let missing = options.missing.fill_or(None, -999.0);
+
+    let station = sounding.station();
+    let num =
+        |val: Option<f64>, decimals: usize| format!("{:.*}", decimals, val.unwrap_or(missing));
+    let hemisphere = |val: Option<f64>, pos: &str, neg: &str| match val {
+        Some(v) if v < 0.0 => format!("{:.2}, {}", -v, neg),
+        Some(v) => format!("{:.2}, {}", v, pos),
+        None => format!("{}, {}", missing, pos),
+    };
+
+    writeln!(w, "RAOB/CSV, {}", station.identifier().unwrap_or_default())?;
+    if let Some(t) = sounding.launch_time() {
+        writeln!(
+            w,
+            "DTG, {:04}/{:02}/{:02} {:02}:{:02}",
+            t.year, t.month, t.day, t.hour, t.minute
+        )?;
+    }
+    writeln!(w, "LAT, {}", hemisphere(station.latitude, "N", "S"))?;
+    writeln!(w, "LON, {}", hemisphere(station.longitude, "E", "W"))?;
+    writeln!(w, "ELEV, {}, M", num(station.elevation, 0))?;
+    writeln!(w, "MOISTURE, TD")?;
+    let speed = match options.speed {
+        SpeedUnit::MetersPerSecond => "m/s",
+        SpeedUnit::Knots => "kts",
+    };
+    writeln!(w, "WIND, {}, DIR", speed)?;
+    writeln!(w, "GPM, MSL")?;
+    writeln!(w, "MISSING, {}", missing)?;
+    writeln!(w, "SORT, YES")?;
+    writeln!(w, "RAOB/DATA")?;
+    writeln!(w, "PRES, TEMP, TD, WIND, SPEED, GPM")?;
+
+    for lvl in sounding.levels() {
+        let Some(pressure) = lvl.pressure else {
+            continue;
+        };
+        writeln!(
+            w,
+            "{}, {}, {}, {}, {}, {}",
+            num(Some(pressure / 100.0), 1),
+            num(lvl.temperature.map(|t| t - 273.15), 1),
+            num(lvl.dewpoint.map(|t| t - 273.15), 1),
+            num(lvl.wind_direction, 0),
+            num(lvl.wind_speed.map(|v| options.speed(v)), 1),
+            num(lvl.height, 0)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// A value of a level that `write_csv_columns` and `write_json_columns` can write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnField {
+    TimeOffset,
+    Significance,
+    Pressure,
+    Height,
+    GnssHeight,
+    Temperature,
+    Dewpoint,
+    RelativeHumidity,
+    WindDirection,
+    WindSpeed,
+    LatDisplacement,
+    LonDisplacement,
+}
+
+impl ColumnField {
+    /// The snake case name of the `Level` field, e.g. `wind_speed`.
+    pub fn key(&self) -> &'static str {
+        match self {
+            ColumnField::TimeOffset => "time_offset",
+            ColumnField::Significance => "significance",
+            ColumnField::Pressure => "pressure",
+            ColumnField::Height => "height",
+            ColumnField::GnssHeight => "gnss_height",
+            ColumnField::Temperature => "temperature",
+            ColumnField::Dewpoint => "dewpoint",
+            ColumnField::RelativeHumidity => "relative_humidity",
+            ColumnField::WindDirection => "wind_direction",
+            ColumnField::WindSpeed => "wind_speed",
+            ColumnField::LatDisplacement => "lat_displacement",
+            ColumnField::LonDisplacement => "lon_displacement",
+        }
+    }
+
+    /// The value, converted to the units of `options`.
+    fn value(&self, lvl: &Level, options: &ExportOptions) -> Option<f64> {
+        match self {
+            ColumnField::TimeOffset => lvl.time_offset,
+            ColumnField::Significance => lvl.significance.map(f64::from),
+            ColumnField::Pressure => lvl.pressure.map(|v| options.pressure(v)),
+            ColumnField::Height => lvl.height.map(|v| options.height(v)),
+            ColumnField::GnssHeight => lvl.gnss_height.map(|v| options.height(v)),
+            ColumnField::Temperature => lvl.temperature.map(|v| options.temperature(v)),
+            ColumnField::Dewpoint => lvl.dewpoint.map(|v| options.temperature(v)),
+            ColumnField::RelativeHumidity => lvl.relative_humidity,
+            ColumnField::WindDirection => lvl.wind_direction,
+            ColumnField::WindSpeed => lvl.wind_speed.map(|v| options.speed(v)),
+            ColumnField::LatDisplacement => lvl.lat_displacement,
+            ColumnField::LonDisplacement => lvl.lon_displacement,
+        }
+    }
+
+    fn unit_label(&self, options: &ExportOptions) -> &'static str {
+        match self {
+            ColumnField::TimeOffset => "s",
+            ColumnField::Significance => "",
+            ColumnField::Pressure => options.pressure_label(),
+            ColumnField::Height | ColumnField::GnssHeight => options.height_label(),
+            ColumnField::Temperature | ColumnField::Dewpoint => options.temperature_label(),
+            ColumnField::RelativeHumidity => "%",
+            ColumnField::WindDirection
+            | ColumnField::LatDisplacement
+            | ColumnField::LonDisplacement => "deg",
+            ColumnField::WindSpeed => options.speed_label(),
+        }
+    }
+}
+
+/// Parses the key, e.g. `wind_speed`, or the descriptor the value is decoded from, e.g.
+/// `0-11-002`.
+impl FromStr for ColumnField {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const FIELDS: [ColumnField; 12] = [
+            ColumnField::TimeOffset,
+            ColumnField::Significance,
+            ColumnField::Pressure,
+            ColumnField::Height,
+            ColumnField::GnssHeight,
+            ColumnField::Temperature,
+            ColumnField::Dewpoint,
+            ColumnField::RelativeHumidity,
+            ColumnField::WindDirection,
+            ColumnField::WindSpeed,
+            ColumnField::LatDisplacement,
+            ColumnField::LonDisplacement,
+        ];
+        if let Some(field) = FIELDS.iter().find(|field| field.key() == s) {
+            return Ok(*field);
+        }
+
+        let unknown = || format!("Unknown column: {}", s);
+        let descriptor: Descriptor = s.parse().map_err(|_| unknown())?;
+        Ok(match descriptor {
+            sounding::TIME_OFFSET => ColumnField::TimeOffset,
+            sounding::SIGNIFICANCE => ColumnField::Significance,
+            sounding::PRESSURE => ColumnField::Pressure,
+            sounding::GEOPOTENTIAL_HEIGHT | sounding::GEOPOTENTIAL_HEIGHT_PILOT => {
+                ColumnField::Height
+            }
+            sounding::RELEASE_HEIGHT => ColumnField::GnssHeight,
+            sounding::TEMPERATURE => ColumnField::Temperature,
+            sounding::DEWPOINT => ColumnField::Dewpoint,
+            sounding::RELATIVE_HUMIDITY => ColumnField::RelativeHumidity,
+            sounding::WIND_DIRECTION => ColumnField::WindDirection,
+            sounding::WIND_SPEED => ColumnField::WindSpeed,
+            sounding::LAT_DISPLACEMENT => ColumnField::LatDisplacement,
+            sounding::LON_DISPLACEMENT => ColumnField::LonDisplacement,
+            _ => return Err(unknown().into()),
+        })
+    }
+}
+
+/// A column of `write_csv_columns` and `write_json_columns`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub field: ColumnField,
+    /// The CSV header or JSON key. The default is the key and the units, e.g. `height_ft`.
+    pub name: Option<String>,
+    /// Units for this column in place of the ones in the `ExportOptions`, e.g. `hPa`, `C`, `kt`.
+    pub unit: Option<String>,
+    /// Digits after the decimal point. The default is as many as needed.
+    pub decimals: Option<usize>,
+}
+
+impl Column {
+    pub fn new(field: ColumnField) -> Self {
+        Column {
+            field,
+            name: None,
+            unit: None,
+            decimals: None,
+        }
+    }
+
+    /// `options` with the units of this column.
+    fn options(&self, options: &ExportOptions) -> Result<ExportOptions, Box<dyn Error>> {
+        let mut options = *options;
+        let Some(unit) = self.unit.as_deref() else {
+            return Ok(options);
+        };
+
+        match (self.field, unit) {
+            (ColumnField::Pressure, "Pa") => options.pressure = PressureUnit::Pascals,
+            (ColumnField::Pressure, "hPa") => options.pressure = PressureUnit::Hectopascals,
+            (ColumnField::Height | ColumnField::GnssHeight, "m") => {
+                options.height = HeightUnit::Meters
+            }
+            (ColumnField::Height | ColumnField::GnssHeight, "ft") => {
+                options.height = HeightUnit::Feet
+            }
+            (ColumnField::Temperature | ColumnField::Dewpoint, "K") => {
+                options.temperature = TemperatureUnit::Kelvin
+            }
+            (ColumnField::Temperature | ColumnField::Dewpoint, "C") => {
+                options.temperature = TemperatureUnit::Celsius
+            }
+            (ColumnField::WindSpeed, "m/s") => options.speed = SpeedUnit::MetersPerSecond,
+            (ColumnField::WindSpeed, "kt") => options.speed = SpeedUnit::Knots,
+            _ => return Err(format!("Can't write {} in {}", self.field.key(), unit).into()),
+        }
+
+        Ok(options)
+    }
+
+    fn name(&self, options: &ExportOptions) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        match self.field.unit_label(options) {
+            "" => self.field.key().to_owned(),
+            "%" => format!("{}_pct", self.field.key()),
+            unit => format!("{}_{}", self.field.key(), unit.replace('/', "")),
+        }
+    }
+
+    fn format(&self, val: f64) -> String {
+        match self.decimals {
+            Some(decimals) => format!("{:.*}", decimals, val),
+            None => format!("{}", val),
+        }
+    }
+}
+
+/// Parses `[NAME=]FIELD[:UNIT][:.DECIMALS]`, e.g. `temperature:C:.1` or `T=0-12-101:C`.
+impl FromStr for Column {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, spec) = match s.split_once('=') {
+            Some((name, spec)) => (Some(name.trim().to_owned()), spec),
+            None => (None, s),
+        };
+        let mut parts = spec.split(':').map(str::trim);
+        let mut column = Column::new(parts.next().unwrap_or_default().parse()?);
+        column.name = name;
+
+        for part in parts {
+            match part.strip_prefix('.') {
+                Some(decimals) => column.decimals = Some(decimals.parse()?),
+                None => column.unit = Some(part.to_owned()),
+            }
+        }
+        column.options(&ExportOptions::default())?;
+
+        Ok(column)
+    }
+}
+
+/// Parse a comma separated list of columns, see `Column::from_str`.
+pub fn parse_columns(spec: &str) -> Result<Vec<Column>, Box<dyn Error>> {
+    spec.split(',').map(str::parse).collect()
+}
+
+/// Write the levels of a sounding as CSV with the chosen `columns`, in order, converted with
+/// `options` unless a column has its own units.
+pub fn write_csv_columns(
+    mut w: impl Write,
+    sounding: &Sounding,
+    columns: &[Column],
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let sounding = &*options.prepare(sounding);
+    let columns = column_options(columns, options)?;
+
+    let names: Vec<String> = columns.iter().map(|(c, o)| c.name(o)).collect();
+    writeln!(w, "{}", names.join(","))?;
+
+    for lvl in sounding.levels() {
+        for (i, (column, options)) in columns.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            if let Some(val) = options.missing.fill(column.field.value(lvl, options)) {
+                write!(w, "{}", column.format(val))?;
+            }
+        }
+        writeln!(w)?;
+    }
+
+    Ok(())
+}
+
+/// Write a sounding as for `write_json`, with the chosen `columns` in each level and the units
+/// of each column in `units`.
+pub fn write_json_columns(
+    mut w: impl Write,
+    sounding: &Sounding,
+    columns: &[Column],
+    options: &ExportOptions,
+) -> Result<(), Box<dyn Error>> {
+    let sounding = &*options.prepare(sounding);
+    let columns = column_options(columns, options)?;
+
+    let units: Vec<String> = columns
+        .iter()
+        .filter(|(c, o)| !c.field.unit_label(o).is_empty())
+        .map(|(c, o)| {
+            format!(
+                "{}: {}",
+                json_string(Some(&c.name(o))),
+                json_string(Some(c.field.unit_label(o)))
+            )
+        })
+        .collect();
+    write_json_header(
+        &mut w,
+        sounding,
+        options,
+        &format!("{{{}}}", units.join(", ")),
+    )?;
+
+    writeln!(w, "  \"levels\": [")?;
+    let num_levels = sounding.levels().len();
+    for (i, lvl) in sounding.levels().iter().enumerate() {
+        let values: Vec<String> = columns
+            .iter()
+            .map(|(column, options)| {
+                let val = options.missing.fill(column.field.value(lvl, options));
+                let val = match val {
+                    Some(val) if val.is_finite() => column.format(val),
+                    _ => "null".to_owned(),
+                };
+                format!("{}: {}", json_string(Some(&column.name(options))), val)
+            })
+            .collect();
+        writeln!(
+            w,
+            "    {{{}}}{}",
+            values.join(", "),
+            if i + 1 == num_levels { "" } else { "," }
+        )?;
+    }
+    writeln!(w, "  ]")?;
+    writeln!(w, "}}")?;
+
+    Ok(())
+}
+
+fn column_options<'a>(
+    columns: &'a [Column],
+    options: &ExportOptions,
+) -> Result<Vec<(&'a Column, ExportOptions)>, Box<dyn Error>> {
+    columns
+        .iter()
+        .map(|column| Ok((column, column.options(options)?)))
+        .collect()
+}
+
+pub(crate) fn json_number(val: Option<f64>) -> String {
+    match val {
+        Some(val) if val.is_finite() => format!("{}", val),
+        _ => "null".to_owned(),
+    }
+}
+
+pub(crate) fn json_string(val: Option<&str>) -> String {
+    let Some(val) = val else {
+        return "null".to_owned();
+    };
+
+    let mut out = String::from("\"");
+    for c in val.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_export_units() {
+        let level = Level {
+            pressure: Some(85000.0),
+            height: Some(1524.0),
+            temperature: Some(273.15),
+            wind_speed: Some(0.0),
+            ..Level::default()
+        };
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, vec![level]);
+        let options = ExportOptions {
+            temperature: TemperatureUnit::Celsius,
+            speed: SpeedUnit::Knots,
+            pressure: PressureUnit::Hectopascals,
+            height: HeightUnit::Feet,
+            ..ExportOptions::default()
+        };
+
+        let mut csv = vec![];
+        write_csv(&mut csv, &sounding, &options).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "time_offset_s,pressure_hPa,height_ft,temperature_C,dewpoint_C,\
+             wind_direction_deg,wind_speed_kt,significance"
+        );
+        assert_eq!(lines.next().unwrap(), ",850,5000,0,,,0,");
+        assert!((options.speed(10.0) - 19.438).abs() < 1.0e-3);
+
+        let mut bufkit = vec![];
+        write_bufkit(&mut bufkit, &sounding, &options).unwrap();
+        let bufkit = String::from_utf8(bufkit).unwrap();
+        assert!(bufkit.contains("PRES TMPC DWPC DRCT SKNT HGFT"));
+        assert!(bufkit.contains("850.00 0.00 -9999.00 -9999.00 0.00 5000.00"));
+
+        let second = Sounding::new(
+            Phase::Ascent,
+            Station {
+                call_sign: Some("AAAA".to_owned()),
+                ..Station::default()
+            },
+            None,
+            None,
+            vec![level],
+        );
+        let mut gempak = vec![];
+        write_gempak(&mut gempak, &[sounding.clone(), second], &options).unwrap();
+        let gempak = String::from_utf8(gempak).unwrap();
+        assert_eq!(gempak.matches("SNPARM").count(), 1);
+        assert_eq!(gempak.matches("STID = ").count(), 2);
+        // Stations without an identifier sort first.
+        assert!(gempak.find("STID =  ").unwrap() < gempak.find("STID = AAAA").unwrap());
+
+        let mut raob = vec![];
+        write_raob_csv(&mut raob, &sounding, &options).unwrap();
+        let raob = String::from_utf8(raob).unwrap();
+        assert!(raob.starts_with("RAOB/CSV, "));
+        assert!(raob.contains("WIND, kts, DIR\n"));
+        assert!(raob.ends_with(
+            "RAOB/DATA\nPRES, TEMP, TD, WIND, SPEED, GPM\n850.0, 0.0, -999.0, -999, 0.0, 1524\n"
+        ));
+
+        let mut json = vec![];
+        write_json(&mut json, &sounding, &options).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains("\"pressure\": 850, \"height\": 5000, \"temperature\": 0,"));
+    }
+
+    #[test]
+    fn test_missing_values() {
+        let level = Level {
+            pressure: Some(85000.0),
+            ..Level::default()
+        };
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, vec![level]);
+        let write = |missing| {
+            let options = ExportOptions {
+                missing,
+                ..ExportOptions::default()
+            };
+            let mut csv = vec![];
+            write_csv(&mut csv, &sounding, &options).unwrap();
+            let mut json = vec![];
+            write_json(&mut json, &sounding, &options).unwrap();
+            let mut raob = vec![];
+            write_raob_csv(&mut raob, &sounding, &options).unwrap();
+            [csv, json, raob].map(|out| String::from_utf8(out).unwrap())
+        };
+
+        let [csv, json, raob] = write(MissingValue::Nan);
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "NaN,85000,NaN,NaN,NaN,NaN,NaN,NaN"
+        );
+        assert!(json.contains("\"pressure\": 85000, \"height\": null,"));
+        assert!(raob.contains("MISSING, NaN\n"));
+
+        let [csv, json, raob] = write(MissingValue::Sentinel(-9999.0));
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "-9999,85000,-9999,-9999,-9999,-9999,-9999,-9999"
+        );
+        assert!(json.contains("\"pressure\": 85000, \"height\": -9999,"));
+        assert!(json.contains("\"latitude\": -9999,"));
+        assert!(raob.ends_with("850.0, -9999.0, -9999.0, -9999, -9999.0, -9999\n"));
+        assert_eq!(MissingValue::Format.fill(None), None);
+        assert_eq!(MissingValue::Nan.fill(Some(1.0)), Some(1.0));
+    }
+
+    #[test]
+    fn test_columns() {
+        let level = Level {
+            pressure: Some(85_012.0),
+            temperature: Some(273.15),
+            wind_speed: Some(10.0),
+            ..Level::default()
+        };
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, vec![level]);
+        let options = ExportOptions {
+            speed: SpeedUnit::Knots,
+            ..ExportOptions::default()
+        };
+        let columns = parse_columns("pressure:hPa:.1,T=0-12-101:C,wind_speed:.0,dewpoint").unwrap();
+
+        let mut csv = vec![];
+        write_csv_columns(&mut csv, &sounding, &columns, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "pressure_hPa,T,wind_speed_kt,dewpoint_K\n850.1,0,19,\n"
+        );
+
+        let mut json = vec![];
+        write_json_columns(&mut json, &sounding, &columns, &options).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains(
+            "  \"units\": {\"pressure_hPa\": \"hPa\", \"T\": \"C\", \"wind_speed_kt\": \"kt\", \
+             \"dewpoint_K\": \"K\"},"
+        ));
+        assert!(json.contains(
+            "    {\"pressure_hPa\": 850.1, \"T\": 0, \"wind_speed_kt\": 19, \"dewpoint_K\": null}\n"
+        ));
+
+        assert!(parse_columns("height:kt").is_err());
+        assert!(parse_columns("0-01-001").is_err());
+        assert!(parse_columns("pressure,").is_err());
+    }
+
+    #[test]
+    fn test_height_source() {
+        let level = Level {
+            pressure: Some(12_000.0),
+            height: Some(15_000.0),
+            gnss_height: Some(15_000.0),
+            ..Level::default()
+        };
+        // Without a station latitude heights are converted at 45 degrees.
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, vec![level]);
+        let columns = parse_columns("height:.0,0-07-007:.0").unwrap();
+        let write = |height_source| {
+            let options = ExportOptions {
+                height_source,
+                ..ExportOptions::default()
+            };
+            let mut csv = vec![];
+            write_csv_columns(&mut csv, &sounding, &columns, &options).unwrap();
+            String::from_utf8(csv).unwrap()
+        };
+
+        assert_eq!(
+            write(HeightSource::Geopotential),
+            "height_m,gnss_height_m\n15000,15000\n"
+        );
+        assert_eq!(
+            write(HeightSource::Gnss),
+            "height_m,gnss_height_m\n14964,15000\n"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{read_3_octet_usize, read_bufr_message};
+use std::error::Error;
+
+/// Copy one subset of an uncompressed BUFR edition 4 message (from `BUFR` through `7777`, see
+/// `read_bufr_bytes`) into a message of its own, e.g. to share one station from a bulletin.
+///
+/// Sections 1 to 3 are copied as they are except for the number of subsets, and Section 4 holds
+/// just the bits of the chosen subset. The message is decoded to find where the subset is, so
+/// it has to be one this crate can decode.
+pub fn extract_subset(message: &[u8], subset: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let bufr = read_bufr_message(message)?;
+    if bufr.is_table_message() {
+        return Err("Table messages don't have data subsets".into());
+    }
+    if bufr.section_3.compressed_data() {
+        return Err("Subsets can't be copied out of compressed messages".into());
+    }
+    let subset_bits = bufr.section_4.subset_bits();
+    let bits = subset_bits.get(subset).ok_or_else(|| {
+        format!(
+            "Subset {} is out of range, the message has {} subsets",
+            subset,
+            subset_bits.len()
+        )
+    })?;
+
+    let section = |start: usize| -> Result<&[u8], Box<dyn Error>> {
+        let len = read_3_octet_usize(message.get(start..).unwrap_or_default())?;
+        message
+            .get(start..start + len)
+            .filter(|_| len >= 4)
+            .ok_or_else(|| "Section lengths run past the end of the message".into())
+    };
+
+    let section_1 = section(8)?;
+    let mut next = 8 + section_1.len();
+    let section_2 = if bufr.section_1.section_2_exists() {
+        let section_2 = section(next)?;
+        next += section_2.len();
+        section_2
+    } else {
+        &[]
+    };
+    let mut section_3 = section(next)?.to_vec();
+    next += section_3.len();
+    let section_4 = section(next)?;
+
+    // Octets 5 and 6 of Section 3 are the number of subsets.
+    if section_3.len() < 7 {
+        return Err("Section 3 is too short".into());
+    }
+    section_3[4..6].copy_from_slice(&1u16.to_be_bytes());
+
+    let data = copy_bits(&section_4[4..], bits.start, bits.end);
+    let section_4_len = 4 + data.len();
+
+    let total = 8 + section_1.len() + section_2.len() + section_3.len() + section_4_len + 4;
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(b"BUFR");
+    out.extend_from_slice(&(total as u32).to_be_bytes()[1..]);
+    out.push(message[7]);
+    out.extend_from_slice(section_1);
+    out.extend_from_slice(section_2);
+    out.extend_from_slice(&section_3);
+    out.extend_from_slice(&(section_4_len as u32).to_be_bytes()[1..]);
+    out.push(0);
+    out.extend_from_slice(&data);
+    out.extend_from_slice(b"7777");
+
+    Ok(out)
+}
+
+/// The bits `start..end` of `bytes`, shifted to start on an octet and padded with zeros to a
+/// whole number of octets.
+fn copy_bits(bytes: &[u8], start: usize, end: usize) -> Vec<u8> {
+    let mut out = vec![0u8; (end - start).div_ceil(8)];
+    for (i, bit) in (start..end).enumerate() {
+        if bytes[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+            out[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{read_bufr_bytes, scan_to_bufr_start};
+    use std::{fs::File, io::BufReader};
+
+    #[test]
+    fn test_extract_subset() {
+        let mut f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+        scan_to_bufr_start(&mut f).unwrap();
+        let message = read_bufr_bytes(&mut f).unwrap();
+
+        let extracted = extract_subset(&message, 0).unwrap();
+        assert!(extract_subset(&message, 1).is_err());
+
+        // Section 0 has the new length and the soundings are unchanged.
+        assert_eq!(
+            read_3_octet_usize(&extracted[4..7]).unwrap(),
+            extracted.len()
+        );
+        let original = read_bufr_message(message.as_slice()).unwrap().soundings();
+        let copy = read_bufr_message(extracted.as_slice()).unwrap().soundings();
+        assert_eq!(copy.len(), 1);
+        assert_eq!(copy[0].station(), original[0].station());
+        assert_eq!(copy[0].levels(), original[0].levels());
+
+        assert_eq!(copy_bits(&[0b1010_1100, 0b1100_0000], 2, 10), [0b1011_0011]);
+    }
+}
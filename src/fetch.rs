@@ -0,0 +1,223 @@
+//! Download soundings over HTTP, with the `fetch` feature.
+//!
+//! Only plain `http://` URLs are supported, as there's no TLS without another dependency. Fetch
+//! `https://` archives with an external tool, or through a local proxy or mirror.
+
+use crate::{
+    inflate::{gunzip, is_gzip},
+    sounding::Timestamp,
+    MessageDecoder, Sounding,
+};
+use std::{
+    error::Error,
+    io::{Cursor, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+const MAX_REDIRECTS: usize = 5;
+
+/// Fill in a URL template for a station and time. The placeholders are `{station}`, `{yyyy}`,
+/// `{mm}`, `{dd}`, and `{hh}`, e.g. `http://example.com/bufr/{station}/{yyyy}{mm}{dd}{hh}.bufr`.
+pub fn sounding_url(template: &str, station: &str, time: Timestamp) -> String {
+    template
+        .replace("{station}", station)
+        .replace("{yyyy}", &format!("{:04}", time.year))
+        .replace("{mm}", &format!("{:02}", time.month))
+        .replace("{dd}", &format!("{:02}", time.day))
+        .replace("{hh}", &format!("{:02}", time.hour))
+}
+
+/// Download the file for a station and time (see `sounding_url`) and decode every sounding in
+/// it. Gzip responses are decompressed first. Messages that fail to decode are skipped.
+pub fn fetch_soundings(
+    template: &str,
+    station: &str,
+    time: Timestamp,
+) -> Result<Vec<Sounding>, Box<dyn Error>> {
+    let mut body = fetch_url(&sounding_url(template, station, time))?;
+    if is_gzip(&body) {
+        body = gunzip(&body)?;
+    }
+
+    Ok(MessageDecoder::default()
+        .messages(Cursor::new(body))
+        .filter_map(Result::ok)
+        .flat_map(|bufr| bufr.soundings())
+        .collect())
+}
+
+/// Download the body of an `http://` URL, following redirects. Responses other than 200 OK are
+/// errors.
+pub fn fetch_url(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut url = url.to_owned();
+    for _ in 0..=MAX_REDIRECTS {
+        let response = get(&url)?;
+        match response.status {
+            200 => return Ok(response.body),
+            301 | 302 | 303 | 307 | 308 => {
+                let location = response
+                    .location
+                    .ok_or_else(|| format!("Redirect without a location from {}", url))?;
+                url = if location.starts_with('/') {
+                    let (host, _) = split_url(&url)?;
+                    format!("http://{}{}", host, location)
+                } else {
+                    location
+                };
+            }
+            status => return Err(format!("HTTP status {} from {}", status, url).into()),
+        }
+    }
+
+    Err(format!("Too many redirects fetching {}", url).into())
+}
+
+struct Response {
+    status: u16,
+    location: Option<String>,
+    body: Vec<u8>,
+}
+
+/// The host (with any port) and path of an `http://` URL.
+fn split_url(url: &str) -> Result<(&str, &str), Box<dyn Error>> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("Only http:// URLs can be fetched: {}", url))?;
+    Ok(match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    })
+}
+
+/// One HTTP/1.0 GET, so the server sends the body whole and closes the connection.
+fn get(url: &str) -> Result<Response, Box<dyn Error>> {
+    let (host, path) = split_url(url)?;
+    let address = if host.contains(':') {
+        host.to_owned()
+    } else {
+        format!("{}:80", host)
+    };
+
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: sonde-bufr\r\nAccept: */*\r\n\r\n",
+        path, host
+    );
+    stream.write_all(request.as_bytes())?;
+
+    let mut raw = vec![];
+    stream.read_to_end(&mut raw)?;
+    parse_response(&raw)
+}
+
+fn parse_response(raw: &[u8]) -> Result<Response, Box<dyn Error>> {
+    let header_end = raw
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or("Incomplete HTTP response")?;
+    let head = std::str::from_utf8(&raw[..header_end])?;
+    let mut lines = head.split("\r\n");
+
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or("Invalid HTTP status line")?;
+
+    let mut location = None;
+    let mut content_length = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("location") {
+            location = Some(value.to_owned());
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse::<usize>().ok();
+        } else if name.eq_ignore_ascii_case("transfer-encoding") && value != "identity" {
+            return Err(format!("Unsupported transfer encoding: {}", value).into());
+        }
+    }
+
+    let mut body = raw[header_end + 4..].to_vec();
+    if let Some(len) = content_length {
+        if body.len() < len {
+            return Err("HTTP response ended early".into());
+        }
+        body.truncate(len);
+    }
+
+    Ok(Response {
+        status,
+        location,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    #[test]
+    fn test_fetch_soundings() {
+        let message = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // Redirect once, then serve the file.
+        let server = thread::spawn(move || {
+            for response in [
+                b"HTTP/1.0 302 Found\r\nLocation: /bufr/MSO1.bufr\r\n\r\n".to_vec(),
+                [
+                    format!(
+                        "HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n",
+                        message.len()
+                    )
+                    .as_bytes(),
+                    &message,
+                ]
+                .concat(),
+            ] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend(&buf[..n]);
+                }
+                assert!(request.starts_with(b"GET /"));
+                stream.write_all(&response).unwrap();
+            }
+        });
+
+        let time = Timestamp {
+            year: 2017,
+            month: 8,
+            day: 31,
+            hour: 18,
+            minute: 0,
+            second: 0,
+        };
+        let template = format!(
+            "http://127.0.0.1:{}/{{station}}/{{yyyy}}{{mm}}{{dd}}{{hh}}",
+            port
+        );
+        assert_eq!(
+            sounding_url(&template, "MSO1", time),
+            format!("http://127.0.0.1:{}/MSO1/2017083118", port)
+        );
+
+        let soundings = fetch_soundings(&template, "MSO1", time).unwrap();
+        server.join().unwrap();
+        assert_eq!(soundings.len(), 1);
+        assert_eq!(soundings[0].levels().len(), 4879);
+
+        assert!(fetch_url("https://example.com/").is_err());
+        let not_found = parse_response(b"HTTP/1.1 404 Not Found\r\n\r\n").unwrap();
+        assert_eq!(not_found.status, 404);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::sounding::{Level, Sounding};
+use std::hash::Hasher;
+
+/// A hash of the raw bytes of a message, e.g. to check an archived copy hasn't changed. Any
+/// difference in the bytes, including the headers, gives a different fingerprint.
+///
+/// The hash is stable between runs and builds, so it may be persisted.
+pub fn message_fingerprint(message: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::default();
+    hasher.write(message);
+    hasher.finish()
+}
+
+/// A hash of the station, launch time, phase, and levels of a sounding. Copies of the same
+/// launch delivered by different routes hash the same even if their messages differ in headers
+/// or encode the values at slightly different resolutions.
+///
+/// Level values are rounded first: pressure to 10 Pa, height to 1 m, temperatures to 0.1 K,
+/// humidity to 1 %, wind direction to 1 degree, wind speed to 0.1 m/s, time to 1 s, and
+/// displacements to 0.00001 degrees. The QC and derived flags aren't included.
+///
+/// The hash is stable between runs and builds, so it may be persisted.
+pub fn content_hash(sounding: &Sounding) -> u64 {
+    // Feed the hasher bytes directly, the std Hash impls aren't guaranteed to be stable.
+    let mut hasher = Fnv1a::default();
+
+    let station = sounding.station().identifier().unwrap_or_default();
+    hasher.write(station.as_bytes());
+    hasher.write_u8(0);
+
+    if let Some(t) = sounding.launch_time() {
+        hasher.write(&t.year.to_be_bytes());
+        hasher.write(&[t.month, t.day, t.hour, t.minute, t.second]);
+    }
+    hasher.write_u8(sounding.phase() as u8);
+
+    let rounded = |hasher: &mut Fnv1a, val: Option<f64>, resolution: f64| match val {
+        Some(val) => hasher.write(&((val / resolution).round() as i64).to_be_bytes()),
+        None => hasher.write_u8(0xFF),
+    };
+    for lvl in sounding.levels() {
+        let Level {
+            time_offset,
+            significance,
+            pressure,
+            height,
+            gnss_height: _,
+            temperature,
+            dewpoint,
+            relative_humidity,
+            wind_direction,
+            wind_speed,
+            lat_displacement,
+            lon_displacement,
+            derived: _,
+            qc: _,
+        } = *lvl;
+
+        rounded(&mut hasher, time_offset, 1.0);
+        rounded(&mut hasher, significance.map(f64::from), 1.0);
+        rounded(&mut hasher, pressure, 10.0);
+        rounded(&mut hasher, height, 1.0);
+        rounded(&mut hasher, temperature, 0.1);
+        rounded(&mut hasher, dewpoint, 0.1);
+        rounded(&mut hasher, relative_humidity, 1.0);
+        rounded(&mut hasher, wind_direction, 1.0);
+        rounded(&mut hasher, wind_speed, 0.1);
+        rounded(&mut hasher, lat_displacement, 1.0e-5);
+        rounded(&mut hasher, lon_displacement, 1.0e-5);
+    }
+
+    hasher.finish()
+}
+
+/// 64 bit FNV-1a, unlike `DefaultHasher` the output is the same with every Rust release.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_fingerprints() {
+        // The FNV-1a test vector, so the value can't drift between releases.
+        assert_eq!(message_fingerprint(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+        let sounding = |temperature: f64| {
+            let level = Level {
+                pressure: Some(85_000.0),
+                temperature: Some(temperature),
+                ..Level::default()
+            };
+            Sounding::new(Phase::Ascent, Station::default(), None, None, vec![level])
+        };
+
+        // Below the rounding, or only differing in flags.
+        let hash = content_hash(&sounding(280.0));
+        assert_eq!(hash, content_hash(&sounding(280.04)));
+        assert_ne!(hash, content_hash(&sounding(280.1)));
+
+        let mut flagged = sounding(280.0);
+        flagged.levels_mut()[0].qc = Level::QC_RANGE;
+        assert_eq!(hash, content_hash(&flagged));
+    }
+}
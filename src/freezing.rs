@@ -0,0 +1,108 @@
+use crate::{
+    sounding::{Level, Sounding},
+    thermo::{lcl, moist_adiabat},
+};
+
+/// Where a profile crosses a value, e.g. the 0 C isotherm.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crossing {
+    /// Pa
+    pub pressure: f64,
+    /// gpm, if the levels on either side have heights.
+    pub height: Option<f64>,
+}
+
+impl Sounding {
+    /// Every level where the temperature crosses 0 C, from the bottom up. Elevated warm layers
+    /// give more than one crossing.
+    pub fn freezing_levels(&self) -> Vec<Crossing> {
+        self.zero_crossings(|lvl| lvl.temperature)
+    }
+
+    /// Every level where the wet-bulb temperature crosses 0 C, from the bottom up.
+    pub fn wet_bulb_zero_levels(&self) -> Vec<Crossing> {
+        self.zero_crossings(wet_bulb_temperature)
+    }
+
+    fn zero_crossings(&self, get: impl Fn(&Level) -> Option<f64>) -> Vec<Crossing> {
+        const FREEZING: f64 = 273.15;
+
+        let mut profile: Vec<(f64, Option<f64>, f64)> = self
+            .levels()
+            .iter()
+            .filter_map(|lvl| Some((lvl.pressure?, lvl.height, get(lvl)? - FREEZING)))
+            .collect();
+        profile.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        profile
+            .windows(2)
+            .filter_map(|pair| {
+                let ((p0, z0, t0), (p1, z1, t1)) = (pair[0], pair[1]);
+                // A level at exactly 0 C is treated as freezing.
+                if (t0 > 0.0) == (t1 > 0.0) {
+                    return None;
+                }
+
+                let frac = t0 / (t0 - t1);
+                let ln_p = p0.ln() + (p1.ln() - p0.ln()) * frac;
+                let height = z0.zip(z1).map(|(z0, z1)| z0 + (z1 - z0) * frac);
+
+                Some(Crossing {
+                    pressure: ln_p.exp(),
+                    height,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The wet-bulb temperature (K) by Normand's rule, lifting to the LCL and descending the moist
+/// adiabat back to the level.
+fn wet_bulb_temperature(lvl: &Level) -> Option<f64> {
+    let (p, t, td) = (lvl.pressure?, lvl.temperature?, lvl.dewpoint?);
+    let (p_lcl, t_lcl) = lcl(t, td.min(t), p);
+
+    Some(moist_adiabat(t_lcl, p_lcl, p))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_freezing_levels() {
+        // Sub-freezing at the surface under a warm nose, as with freezing rain.
+        let level = |hpa: f64, z: f64, t: f64| Level {
+            pressure: Some(hpa * 100.0),
+            height: Some(z),
+            temperature: Some(t + 273.15),
+            dewpoint: Some(t + 273.15 - 0.5),
+            ..Level::default()
+        };
+        let sounding = Sounding::new(
+            Phase::Ascent,
+            Station::default(),
+            None,
+            None,
+            vec![
+                level(1000.0, 100.0, -2.0),
+                level(900.0, 1000.0, 2.0),
+                level(850.0, 1500.0, 2.0),
+                level(700.0, 3000.0, -6.0),
+            ],
+        );
+
+        let levels = sounding.freezing_levels();
+        assert_eq!(levels.len(), 2);
+        assert!((levels[0].height.unwrap() - 550.0).abs() < 1.0e-9);
+        assert!((levels[1].height.unwrap() - 1875.0).abs() < 1.0e-9);
+        assert!(levels[0].pressure < 100_000.0 && levels[0].pressure > 90_000.0);
+
+        // The wet bulb is a little cooler, so its crossings are inside the warm nose.
+        let wet_bulb = sounding.wet_bulb_zero_levels();
+        assert_eq!(wet_bulb.len(), 2);
+        assert!(wet_bulb[0].height.unwrap() > levels[0].height.unwrap());
+        assert!(wet_bulb[1].height.unwrap() < levels[1].height.unwrap());
+    }
+}
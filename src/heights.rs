@@ -0,0 +1,137 @@
+use crate::{
+    sounding::{Level, Sounding},
+    thermo::G,
+};
+
+/// Mean radius of the Earth, m.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Which height of a level to use, see `ExportOptions::height_source`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeightSource {
+    /// The geopotential height reported with the level (or computed from pressure).
+    #[default]
+    Geopotential,
+    /// The GNSS height, converted to geopotential height. Levels without one have no height.
+    Gnss,
+}
+
+/// A level with both a geopotential and a GNSS height, see `Sounding::height_discrepancies`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeightDiscrepancy {
+    /// Index into `Sounding::levels`.
+    pub index: usize,
+    /// Pa
+    pub pressure: Option<f64>,
+    /// gpm
+    pub geopotential: f64,
+    /// The GNSS height converted to gpm.
+    pub gnss: f64,
+    /// `gnss - geopotential`, gpm.
+    pub difference: f64,
+}
+
+/// The geopotential height in gpm of a geometric height `z` in m at latitude `lat`, using the
+/// normal gravity formula and a spherical Earth.
+pub(crate) fn geopotential_height(z: f64, lat: f64) -> f64 {
+    let sin_lat = lat.to_radians().sin();
+    let sin_2lat = (2.0 * lat.to_radians()).sin();
+    let gravity = 9.780327 * (1.0 + 0.0053024 * sin_lat.powi(2) - 0.0000058 * sin_2lat.powi(2));
+
+    gravity / G * EARTH_RADIUS * z / (EARTH_RADIUS + z)
+}
+
+impl Sounding {
+    /// Compare the geopotential and GNSS heights of the levels that have both. GNSS heights are
+    /// geometric, so they're converted to geopotential heights at the station latitude (45
+    /// degrees if it's missing) before comparing.
+    pub fn height_discrepancies(&self) -> Vec<HeightDiscrepancy> {
+        let lat = self.station().latitude.unwrap_or(45.0);
+
+        self.levels()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, lvl)| {
+                let (Some(geopotential), Some(z)) = (lvl.height, lvl.gnss_height) else {
+                    return None;
+                };
+                let gnss = geopotential_height(z, lat);
+                Some(HeightDiscrepancy {
+                    index,
+                    pressure: lvl.pressure,
+                    geopotential,
+                    gnss,
+                    difference: gnss - geopotential,
+                })
+            })
+            .collect()
+    }
+
+    /// Make `source` the height of every level. With `HeightSource::Gnss` each height is the
+    /// converted GNSS height, flagged with `Level::GNSS_HEIGHT`, or missing if the level has no
+    /// GNSS height, so the two are never mixed. Returns the number of levels with a height.
+    pub fn use_height_source(&mut self, source: HeightSource) -> usize {
+        let lat = self.station().latitude.unwrap_or(45.0);
+
+        let mut num_heights = 0;
+        for lvl in self.levels_mut() {
+            if source == HeightSource::Gnss && !lvl.is_derived(Level::GNSS_HEIGHT) {
+                lvl.height = lvl.gnss_height.map(|z| geopotential_height(z, lat));
+                lvl.derived = (lvl.derived & !Level::DERIVED_HEIGHT) | Level::GNSS_HEIGHT;
+            }
+            num_heights += usize::from(lvl.height.is_some());
+        }
+
+        num_heights
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_height_discrepancies() {
+        // Geopotential is a little less than geometric height at mid latitudes.
+        let h = geopotential_height(10_000.0, 45.0);
+        assert!(h < 10_000.0 && h > 9_970.0);
+
+        let levels = vec![
+            Level {
+                pressure: Some(85_000.0),
+                height: Some(1500.0),
+                gnss_height: Some(1510.0),
+                ..Level::default()
+            },
+            Level {
+                pressure: Some(70_000.0),
+                height: Some(3000.0),
+                ..Level::default()
+            },
+        ];
+        let station = Station {
+            latitude: Some(45.0),
+            ..Station::default()
+        };
+        let mut sounding = Sounding::new(Phase::Ascent, station, None, None, levels);
+
+        let discrepancies = sounding.height_discrepancies();
+        assert_eq!(discrepancies.len(), 1);
+        assert_eq!(discrepancies[0].index, 0);
+        assert!(
+            (discrepancies[0].difference - (geopotential_height(1510.0, 45.0) - 1500.0)).abs()
+                < 1.0e-9
+        );
+        assert!(discrepancies[0].difference > 9.0 && discrepancies[0].difference < 10.0);
+
+        assert_eq!(sounding.use_height_source(HeightSource::Geopotential), 2);
+        assert_eq!(sounding.use_height_source(HeightSource::Gnss), 1);
+        assert_eq!(sounding.levels()[0].height, Some(discrepancies[0].gnss));
+        assert!(sounding.levels()[1].is_derived(Level::GNSS_HEIGHT));
+        assert_eq!(sounding.levels()[1].height, None);
+        // Doing it twice doesn't convert again.
+        assert_eq!(sounding.use_height_source(HeightSource::Gnss), 1);
+        assert_eq!(sounding.levels()[0].height, Some(discrepancies[0].gnss));
+    }
+}
@@ -0,0 +1,225 @@
+use std::{error::Error, io::Write};
+
+/// Write a hex dump of a BUFR edition 4 message, from `BUFR` through `7777`, e.g. from
+/// `read_bufr_bytes`. The fixed fields of each section are shown one per line with their names
+/// and values, and the rest of a section in rows of 16 octets. Offsets are from the start of the
+/// message.
+///
+/// Section lengths are taken from the message, so a malformed message is dumped as far as it
+/// can be and the dump notes where the lengths don't add up.
+pub fn hex_dump(w: impl Write, message: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut d = Dumper {
+        w,
+        bytes: message,
+        pos: 0,
+    };
+
+    d.heading("Section 0, indicator section")?;
+    d.text(4, "Start of message")?;
+    d.field(3, "Total length of message")?;
+    d.field(1, "BUFR edition number")?;
+
+    let section_2 = d.section("Section 1, identification section", |d, end| {
+        d.field(1, "Master table")?;
+        d.field(2, "Originating centre")?;
+        d.field(2, "Originating sub-centre")?;
+        d.field(1, "Update sequence number")?;
+        let section_2 = d.field(1, "Optional section flag")?;
+        d.field(1, "Data category")?;
+        d.field(1, "International data sub-category")?;
+        d.field(1, "Local data sub-category")?;
+        d.field(1, "Master table version")?;
+        d.field(1, "Local tables version")?;
+        d.field(2, "Year")?;
+        d.field(1, "Month")?;
+        d.field(1, "Day")?;
+        d.field(1, "Hour")?;
+        d.field(1, "Minute")?;
+        d.field(1, "Second")?;
+        d.rows(end, "Local use")?;
+
+        Ok(section_2.is_some_and(|flag| flag & 0x80 != 0))
+    })?;
+
+    if section_2 == Some(true) {
+        d.section("Section 2, optional section", |d, end| {
+            d.field(1, "Reserved")?;
+            d.rows(end, "Local use")
+        })?;
+    }
+
+    d.section("Section 3, data description section", |d, end| {
+        d.field(1, "Reserved")?;
+        d.field(2, "Number of data subsets")?;
+        d.field(1, "Observed and compressed data flags")?;
+        while d.pos + 2 <= end {
+            d.descriptor()?;
+        }
+        d.rows(end, "Padding")
+    })?;
+
+    d.section("Section 4, data section", |d, end| {
+        d.field(1, "Reserved")?;
+        d.rows(end, "Data")
+    })?;
+
+    d.heading("Section 5, end section")?;
+    d.text(4, "End of message")?;
+
+    if d.pos < d.bytes.len() {
+        let end = d.bytes.len();
+        d.heading("After the end of the message")?;
+        d.rows(end, "Extra data")?;
+    }
+
+    Ok(())
+}
+
+struct Dumper<'a, W: Write> {
+    w: W,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<W: Write> Dumper<'_, W> {
+    fn heading(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        writeln!(self.w, "{}", name)?;
+        Ok(())
+    }
+
+    /// The next `n` octets, or as many as there are, with a note if the message ends early.
+    fn take(&mut self, n: usize) -> Result<&[u8], Box<dyn Error>> {
+        let end = (self.pos + n).min(self.bytes.len());
+        if end < self.pos + n {
+            writeln!(
+                self.w,
+                "{:06x}  message ends {} octets early",
+                end,
+                self.pos + n - end
+            )?;
+        }
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn line(&mut self, offset: usize, bytes: &[u8], note: &str) -> Result<(), Box<dyn Error>> {
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        writeln!(self.w, "{:06x}  {:<48}  {}", offset, hex.join(" "), note)?;
+        Ok(())
+    }
+
+    /// A big endian unsigned integer field.
+    fn field(&mut self, n: usize, name: &str) -> Result<Option<u64>, Box<dyn Error>> {
+        let offset = self.pos;
+        let bytes = self.take(n)?.to_vec();
+        if bytes.len() < n {
+            return Ok(None);
+        }
+        let val = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+        self.line(offset, &bytes, &format!("{} = {}", name, val))?;
+        Ok(Some(val))
+    }
+
+    fn text(&mut self, n: usize, name: &str) -> Result<(), Box<dyn Error>> {
+        let offset = self.pos;
+        let bytes = self.take(n)?.to_vec();
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        self.line(offset, &bytes, &format!("{} = {:?}", name, text))
+    }
+
+    fn descriptor(&mut self) -> Result<(), Box<dyn Error>> {
+        let offset = self.pos;
+        let bytes = self.take(2)?.to_vec();
+        let f = bytes[0] >> 6;
+        let x = bytes[0] & 0x3f;
+        let y = bytes[1];
+        self.line(
+            offset,
+            &bytes,
+            &format!("Descriptor {}-{:02}-{:03}", f, x, y),
+        )
+    }
+
+    /// Octets up to `end` in rows of 16.
+    fn rows(&mut self, end: usize, name: &str) -> Result<(), Box<dyn Error>> {
+        let mut first = true;
+        while self.pos < end {
+            let offset = self.pos;
+            let bytes = self.take((end - self.pos).min(16))?.to_vec();
+            if bytes.is_empty() {
+                break;
+            }
+            self.line(offset, &bytes, if first { name } else { "" })?;
+            first = false;
+        }
+        Ok(())
+    }
+
+    /// A section that starts with its 3 octet length. `body` dumps the rest of the section,
+    /// given the offset where it ends. Returns `None` if the message ends before the length.
+    fn section<T>(
+        &mut self,
+        name: &str,
+        body: impl FnOnce(&mut Self, usize) -> Result<T, Box<dyn Error>>,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        self.heading(name)?;
+        let start = self.pos;
+        let Some(len) = self.field(3, "Length of section")? else {
+            return Ok(None);
+        };
+
+        let mut end = start + len as usize;
+        if end > self.bytes.len() {
+            writeln!(
+                self.w,
+                "{:06x}  section length runs {} octets past the end of the message",
+                start,
+                end - self.bytes.len()
+            )?;
+            end = self.bytes.len();
+        }
+
+        let result = body(self, end)?;
+        if self.pos > end {
+            writeln!(
+                self.w,
+                "{:06x}  fixed fields run {} octets past the section length",
+                end,
+                self.pos - end
+            )?;
+        }
+        self.pos = self.pos.max(end);
+
+        Ok(Some(result))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{read_bufr_bytes, scan_to_bufr_start};
+    use std::{fs::File, io::BufReader};
+
+    #[test]
+    fn test_hex_dump() {
+        let mut f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+        scan_to_bufr_start(&mut f).unwrap();
+        let message = read_bufr_bytes(&mut f).unwrap();
+
+        let mut out = vec![];
+        hex_dump(&mut out, &message).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("Section 0, indicator section\n000000  42 55 46 52"));
+        assert!(out.contains("Year = 2017"));
+        assert!(out.contains("Descriptor 3-09-052"));
+        assert!(out.trim_end().ends_with("End of message = \"7777\""));
+
+        // A truncated message is dumped as far as it goes.
+        let mut out = vec![];
+        hex_dump(&mut out, &message[..40]).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("section length runs"));
+        assert!(out.contains("message ends 4 octets early"));
+    }
+}
@@ -0,0 +1,133 @@
+use crate::sounding::Sounding;
+
+impl Sounding {
+    /// The height of the ground in gpm, the station elevation or else the lowest level height.
+    pub(crate) fn ground_height(&self) -> Option<f64> {
+        self.station().elevation.or_else(|| {
+            self.levels()
+                .iter()
+                .filter_map(|lvl| lvl.height)
+                .min_by(f64::total_cmp)
+        })
+    }
+
+    /// (height above ground in m, u, v in m/s) for every level with a height and wind, sorted by
+    /// height.
+    pub fn hodograph(&self) -> Vec<(f64, f64, f64)> {
+        let Some(ground) = self.ground_height() else {
+            return vec![];
+        };
+
+        let mut hodo: Vec<(f64, f64, f64)> = self
+            .levels()
+            .iter()
+            .filter_map(|lvl| {
+                let (u, v) = lvl.wind_components()?;
+                Some((lvl.height? - ground, u, v))
+            })
+            .collect();
+        hodo.sort_by(|a, b| a.0.total_cmp(&b.0));
+        hodo.dedup_by(|a, b| a.0 == b.0);
+
+        hodo
+    }
+
+    /// The bulk shear vector (u, v) in m/s from the lowest wind to the wind `depth` meters above
+    /// ground, e.g. 1000.0, 3000.0, or 6000.0 for the 0-1, 0-3, and 0-6 km shear.
+    pub fn bulk_shear(&self, depth: f64) -> Option<(f64, f64)> {
+        let hodo = self.hodograph();
+        let (_, u0, v0) = *hodo.first()?;
+        let (u1, v1) = wind_at(&hodo, depth)?;
+
+        Some((u1 - u0, v1 - v0))
+    }
+
+    /// Storm relative helicity in m²/s² from the ground to `depth` meters above ground, for a
+    /// storm moving with the (u, v) velocity `storm_motion` in m/s.
+    pub fn storm_relative_helicity(&self, depth: f64, storm_motion: (f64, f64)) -> Option<f64> {
+        let hodo = self.hodograph();
+        let top = wind_at(&hodo, depth)?;
+        let (cu, cv) = storm_motion;
+
+        let mut winds: Vec<(f64, f64)> = hodo
+            .iter()
+            .filter(|(z, _, _)| *z < depth)
+            .map(|&(_, u, v)| (u, v))
+            .collect();
+        winds.push(top);
+
+        let srh = winds
+            .windows(2)
+            .map(|w| {
+                let ((u0, v0), (u1, v1)) = (w[0], w[1]);
+                (u1 - cu) * (v0 - cv) - (u0 - cu) * (v1 - cv)
+            })
+            .sum();
+
+        Some(srh)
+    }
+}
+
+/// Interpolate the wind linearly in height, without extrapolating.
+fn wind_at(hodo: &[(f64, f64, f64)], height: f64) -> Option<(f64, f64)> {
+    let idx = hodo.partition_point(|(z, _, _)| *z < height);
+
+    let (z1, u1, v1) = *hodo.get(idx)?;
+    if z1 == height {
+        return Some((u1, v1));
+    }
+
+    let (z0, u0, v0) = *hodo.get(idx.checked_sub(1)?)?;
+    let frac = (height - z0) / (z1 - z0);
+
+    Some((u0 + (u1 - u0) * frac, v0 + (v1 - v0) * frac))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sounding::{Level, Phase, Sounding, Station};
+
+    #[test]
+    fn test_shear_and_helicity() {
+        // Winds veering from south to west over the lowest 3 km.
+        let level = |height: f64, u: f64, v: f64| {
+            let mut lvl = Level {
+                height: Some(height),
+                ..Level::default()
+            };
+            lvl.set_wind_components(u, v);
+            lvl
+        };
+        let station = Station {
+            elevation: Some(500.0),
+            ..Station::default()
+        };
+        let sounding = Sounding::new(
+            Phase::Ascent,
+            station,
+            None,
+            None,
+            vec![
+                level(500.0, 0.0, 10.0),
+                level(2000.0, 10.0, 10.0),
+                level(3500.0, 10.0, 0.0),
+            ],
+        );
+
+        let hodo = sounding.hodograph();
+        assert_eq!(hodo.len(), 3);
+        assert!((hodo[1].0 - 1500.0).abs() < 1.0e-9);
+
+        let (u, v) = sounding.bulk_shear(3000.0).unwrap();
+        assert!((u - 10.0).abs() < 1.0e-9 && (v + 10.0).abs() < 1.0e-9);
+        let (u, v) = sounding.bulk_shear(750.0).unwrap();
+        assert!((u - 5.0).abs() < 1.0e-9 && v.abs() < 1.0e-9);
+        assert!(sounding.bulk_shear(6000.0).is_none());
+
+        // Clockwise turning relative to a stationary storm is positive helicity.
+        let srh = sounding
+            .storm_relative_helicity(3000.0, (0.0, 0.0))
+            .unwrap();
+        assert!((srh - 200.0).abs() < 1.0e-9);
+    }
+}
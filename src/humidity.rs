@@ -0,0 +1,225 @@
+use crate::{
+    derive::VaporPressureFormula,
+    monthly::days_in_month,
+    sounding::{Level, Sounding, Timestamp},
+};
+
+/// A published correction of the daytime dry bias of a radiosonde humidity sensor, see
+/// `Sounding::humidity_correction`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HumidityCorrection {
+    pub name: &'static str,
+    /// Where the correction comes from.
+    pub reference: &'static str,
+    /// Radiosonde types it applies to, code table 0-02-011.
+    pub radiosonde_types: &'static [u16],
+    /// Factors to multiply the relative humidity by in daylight, by pressure in hPa from the
+    /// surface up. Factors are interpolated in log pressure and held beyond the ends. Empty if
+    /// nothing needs applying.
+    pub daytime_factors: &'static [(f64, f64)],
+}
+
+const CORRECTIONS: [HumidityCorrection; 2] = [HumidityCorrection::RS92, HumidityCorrection::RS41];
+
+impl HumidityCorrection {
+    /// Vaisala RS92 with the DigiCORA I, II, III and AUTOSONDE ground systems.
+    pub const RS92: HumidityCorrection = HumidityCorrection {
+        name: "RS92 solar radiation dry bias",
+        reference: "Vömel et al. (2007), J. Atmos. Oceanic Technol. 24, 953-963. A smooth fit \
+                    between the mean dry biases of 9% at the surface and 50% at 15 km.",
+        radiosonde_types: &[79, 80, 81],
+        daytime_factors: &[
+            (1000.0, 1.09),
+            (700.0, 1.12),
+            (500.0, 1.17),
+            (300.0, 1.27),
+            (200.0, 1.38),
+            (120.0, 1.50),
+        ],
+    };
+
+    /// Vaisala RS41, with the pressure sensor or with pressure from GPS heights.
+    pub const RS41: HumidityCorrection = HumidityCorrection {
+        name: "RS41 solar radiation correction",
+        reference: "Applied by the MW41 and AUTOSONDE ground systems before the data are sent, so \
+                    there is nothing left to correct.",
+        radiosonde_types: &[123, 124, 141, 142],
+        daytime_factors: &[],
+    };
+
+    /// The correction for a radiosonde type, code table 0-02-011.
+    pub fn for_radiosonde_type(radiosonde_type: u16) -> Option<&'static HumidityCorrection> {
+        CORRECTIONS
+            .iter()
+            .find(|c| c.radiosonde_types.contains(&radiosonde_type))
+    }
+
+    /// The daytime factor at a pressure in Pa.
+    pub fn factor(&self, pressure: f64) -> Option<f64> {
+        let factors = self.daytime_factors;
+        let log_p = (pressure / 100.0).ln();
+
+        let (first, last) = (factors.first()?, factors.last()?);
+        if log_p >= first.0.ln() {
+            return Some(first.1);
+        }
+        if log_p <= last.0.ln() {
+            return Some(last.1);
+        }
+
+        factors.windows(2).find_map(|pair| {
+            let [(p0, f0), (p1, f1)] = [pair[0], pair[1]];
+            let (l0, l1) = (p0.ln(), p1.ln());
+            (log_p <= l0 && log_p >= l1).then(|| f0 + (f1 - f0) * (log_p - l0) / (l1 - l0))
+        })
+    }
+}
+
+impl Sounding {
+    /// The humidity correction that applies to the radiosonde type of this sounding, if it's one
+    /// of the types with a published correction.
+    pub fn humidity_correction(&self) -> Option<&'static HumidityCorrection> {
+        HumidityCorrection::for_radiosonde_type(self.radiosonde_type()?)
+    }
+
+    /// Apply the `humidity_correction` if the sun was up at the launch site. The relative
+    /// humidity, capped at 100%, and the dewpoint are corrected and flagged with
+    /// `Level::CORRECTED_HUMIDITY`. Returns the number of levels corrected.
+    pub fn correct_humidity(&mut self) -> usize {
+        let Some(correction) = self.humidity_correction() else {
+            return 0;
+        };
+        let station = self.station();
+        let (Some(time), Some(lat), Some(lon)) =
+            (self.launch_time(), station.latitude, station.longitude)
+        else {
+            return 0;
+        };
+        if solar_elevation(time, lat, lon) <= 0.0 {
+            return 0;
+        }
+
+        let formula = VaporPressureFormula::default();
+        let mut num_corrected = 0;
+        for lvl in self.levels_mut() {
+            let Some(factor) = lvl.pressure.and_then(|p| correction.factor(p)) else {
+                continue;
+            };
+
+            let mut corrected = false;
+            if let Some(rh) = lvl.relative_humidity {
+                lvl.relative_humidity = Some((rh * factor).min(100.0));
+                corrected = true;
+            }
+            if let (Some(t), Some(td)) = (lvl.temperature, lvl.dewpoint) {
+                let (t, td) = (t - 273.15, td - 273.15);
+                let rh = 100.0 * formula.saturation_vapor_pressure(td)
+                    / formula.saturation_vapor_pressure(t);
+                if let Some(td) = formula.dewpoint(t, (rh * factor).min(100.0)) {
+                    lvl.dewpoint = Some(td + 273.15);
+                    corrected = true;
+                }
+            }
+
+            if corrected {
+                lvl.derived |= Level::CORRECTED_HUMIDITY;
+                num_corrected += 1;
+            }
+        }
+
+        num_corrected
+    }
+}
+
+/// The approximate elevation of the sun in degrees, from the NOAA general solar position
+/// formulas.
+fn solar_elevation(time: Timestamp, lat: f64, lon: f64) -> f64 {
+    let day_of_year: u32 = (1..time.month)
+        .map(|month| days_in_month(time.year, month))
+        .sum::<u32>()
+        + u32::from(time.day);
+    let hour = f64::from(time.hour) + f64::from(time.minute) / 60.0;
+
+    let g =
+        2.0 * std::f64::consts::PI / 365.0 * (f64::from(day_of_year) - 1.0 + (hour - 12.0) / 24.0);
+    let declination = 0.006918 - 0.399912 * g.cos() + 0.070257 * g.sin()
+        - 0.006758 * (2.0 * g).cos()
+        + 0.000907 * (2.0 * g).sin()
+        - 0.002697 * (3.0 * g).cos()
+        + 0.00148 * (3.0 * g).sin();
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * g.cos()
+            - 0.032077 * g.sin()
+            - 0.014615 * (2.0 * g).cos()
+            - 0.040849 * (2.0 * g).sin());
+
+    let solar_minutes = hour * 60.0 + equation_of_time + 4.0 * lon;
+    let hour_angle = (solar_minutes / 4.0 - 180.0).to_radians();
+    let lat = lat.to_radians();
+    let cos_zenith =
+        lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos();
+
+    90.0 - cos_zenith.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_correct_humidity() {
+        assert_eq!(HumidityCorrection::RS92.factor(100_000.0), Some(1.09));
+        assert_eq!(HumidityCorrection::RS92.factor(5000.0), Some(1.5));
+        let mid = HumidityCorrection::RS92.factor(60_000.0).unwrap();
+        assert!(mid > 1.12 && mid < 1.17);
+        assert_eq!(HumidityCorrection::RS41.factor(50_000.0), None);
+
+        let level = Level {
+            pressure: Some(50_000.0),
+            temperature: Some(263.15),
+            dewpoint: Some(253.15),
+            relative_humidity: Some(40.0),
+            ..Level::default()
+        };
+        let station = Station {
+            latitude: Some(46.9),
+            longitude: Some(-114.1),
+            ..Station::default()
+        };
+        // 18 UTC is late morning in Montana, 06 UTC is night.
+        let time = |hour| Timestamp {
+            year: 2017,
+            month: 8,
+            day: 31,
+            hour,
+            minute: 0,
+            second: 0,
+        };
+        assert!(solar_elevation(time(18), 46.9, -114.1) > 30.0);
+        assert!(solar_elevation(time(6), 46.9, -114.1) < 0.0);
+
+        let mut night = Sounding::new(
+            Phase::Ascent,
+            station.clone(),
+            Some(time(6)),
+            Some(80),
+            vec![level],
+        );
+        assert_eq!(night.correct_humidity(), 0);
+
+        let mut day = Sounding::new(
+            Phase::Ascent,
+            station,
+            Some(time(18)),
+            Some(80),
+            vec![level],
+        );
+        assert_eq!(day.humidity_correction(), Some(&HumidityCorrection::RS92));
+        assert_eq!(day.correct_humidity(), 1);
+        let lvl = day.levels()[0];
+        assert!(lvl.is_derived(Level::CORRECTED_HUMIDITY));
+        assert!((lvl.relative_humidity.unwrap() - 40.0 * 1.17).abs() < 1.0e-9);
+        assert!(lvl.dewpoint.unwrap() > 253.15 && lvl.dewpoint.unwrap() < 263.15);
+    }
+}
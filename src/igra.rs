@@ -0,0 +1,323 @@
+use crate::sounding::{Level, Phase, Sounding, Station, Timestamp};
+use std::{
+    error::Error,
+    io::{BufRead, Write},
+};
+
+/// IGRA's missing value.
+const MISSING: i64 = -9999;
+
+/// IGRA's value for data removed by its quality checks.
+const REMOVED: i64 = -8888;
+
+/// Write a sounding as one NOAA IGRA version 2 record: a `#` header line followed by a data line
+/// per level, in the fixed width layout of the IGRA 2 format description.
+///
+/// `station_id` is the 11 character IGRA identifier (e.g. `USM00072776`), which can't be made
+/// from the BUFR station because it includes the FIPS country code. The launch time gives both
+/// the nominal hour and the release time, and the source fields are left blank. The climate
+/// check flags are blank too, as the values haven't been through IGRA's checks.
+pub fn write_igra(
+    mut w: impl Write,
+    sounding: &Sounding,
+    station_id: &str,
+) -> Result<(), Box<dyn Error>> {
+    if station_id.len() != 11 || !station_id.is_ascii() {
+        return Err(format!("IGRA station ids are 11 characters: {}", station_id).into());
+    }
+    let t = sounding
+        .launch_time()
+        .ok_or("IGRA records need a launch time")?;
+
+    let levels: Vec<&Level> = sounding
+        .levels()
+        .iter()
+        .filter(|lvl| lvl.pressure.is_some() || lvl.height.is_some())
+        .collect();
+
+    let station = sounding.station();
+    let coord = |val: Option<f64>| val.map_or(-99999, |v| (v * 10_000.0).round() as i64);
+    writeln!(
+        w,
+        "#{} {:04} {:02} {:02} {:02} {:02}{:02} {:4} {:8} {:8} {:7} {:8}",
+        station_id,
+        t.year,
+        t.month,
+        t.day,
+        t.hour,
+        t.hour,
+        t.minute,
+        levels.len(),
+        "",
+        "",
+        coord(station.latitude),
+        coord(station.longitude),
+    )?;
+
+    let scaled =
+        |val: Option<f64>, factor: f64| val.map_or(MISSING, |v| (v * factor).round() as i64);
+    for lvl in levels {
+        let level_type = match lvl.pressure {
+            None => 3,
+            Some(_) if lvl.has_significance(Level::STANDARD) => 1,
+            Some(_) => 2,
+        };
+        let surface_type = if lvl.has_significance(Level::SURFACE) {
+            1
+        } else if lvl.has_significance(Level::TROPOPAUSE) {
+            2
+        } else {
+            0
+        };
+
+        // Elapsed time is MMMSS.
+        let elapsed = match lvl.time_offset {
+            Some(s) if (0.0..60_000.0).contains(&s) => {
+                let s = s.round() as i64;
+                s / 60 * 100 + s % 60
+            }
+            _ => MISSING,
+        };
+        let depression = lvl.temperature.zip(lvl.dewpoint).map(|(t, td)| t - td);
+
+        writeln!(
+            w,
+            "{}{} {:5} {:6} {:5} {:5} {:5} {:5} {:5} {:5}",
+            level_type,
+            surface_type,
+            elapsed,
+            scaled(lvl.pressure, 1.0),
+            scaled(lvl.height, 1.0),
+            scaled(lvl.temperature.map(|t| t - 273.15), 10.0),
+            scaled(lvl.relative_humidity, 10.0),
+            scaled(depression, 10.0),
+            scaled(lvl.wind_direction, 1.0),
+            scaled(lvl.wind_speed, 10.0),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Read the soundings in an IGRA version 2 data file, e.g. to compare a station's IGRA record
+/// with decoded BUFR using `compare_tac_bufr`.
+///
+/// Stations in the WMO network (an `M` in the third character of the id) get their WMO block
+/// and station number from the id; other stations keep the whole id as the call sign. IGRA
+/// doesn't give the station elevation. Missing and removed values are `None`. The climate check
+/// flags are not kept.
+pub fn read_igra(r: impl BufRead) -> Result<Vec<Sounding>, Box<dyn Error>> {
+    let mut soundings = vec![];
+    // The header of the sounding being read, with its levels so far.
+    let mut current: Option<(Station, Option<Timestamp>, Vec<Level>)> = None;
+    let mut finish = |current: Option<(Station, Option<Timestamp>, Vec<Level>)>| {
+        if let Some((station, time, levels)) = current {
+            soundings.push(Sounding::new(Phase::Ascent, station, time, None, levels));
+        }
+    };
+
+    for (line_num, line) in r.lines().enumerate() {
+        let line = line?;
+        let bad = || format!("Invalid IGRA line {}: {}", line_num + 1, line);
+
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('#') {
+            finish(current.take());
+            current = Some(parse_header(header).ok_or_else(bad)?);
+            continue;
+        }
+
+        let (_, _, levels) = current
+            .as_mut()
+            .ok_or_else(|| format!("IGRA level before any header on line {}", line_num + 1))?;
+        levels.push(parse_level(&line).ok_or_else(bad)?);
+    }
+    finish(current);
+
+    Ok(soundings)
+}
+
+/// The text in 1-based, inclusive columns `first..=last` as in the format description.
+fn columns(line: &str, first: usize, last: usize) -> Option<&str> {
+    line.get(first - 1..last.min(line.len())).map(str::trim)
+}
+
+/// An integer field, `None` if it is missing or was removed by IGRA's checks.
+fn value(line: &str, first: usize, last: usize) -> Option<Option<i64>> {
+    let val: i64 = columns(line, first, last)?.parse().ok()?;
+    Some((val != MISSING && val != REMOVED).then_some(val))
+}
+
+fn parse_header(header: &str) -> Option<(Station, Option<Timestamp>, Vec<Level>)> {
+    // The columns are numbered with the `#` as column one.
+    let field = |first: usize, last: usize| columns(header, first - 1, last - 1);
+    let number = |first: usize, last: usize| -> Option<i64> { field(first, last)?.parse().ok() };
+
+    let id = field(2, 12)?;
+    let mut station = Station {
+        latitude: number(56, 62)
+            .filter(|&v| v != -99999)
+            .map(|v| v as f64 / 10_000.0),
+        longitude: number(64, 71)
+            .filter(|&v| v != -99999)
+            .map(|v| v as f64 / 10_000.0),
+        ..Station::default()
+    };
+    match (id.get(2..3), id.get(6..8), id.get(8..11)) {
+        (Some("M"), Some(block), Some(number)) if id.len() == 11 => {
+            station.wmo_block = Some(block.parse().ok()?);
+            station.wmo_station = Some(number.parse().ok()?);
+        }
+        _ => station.call_sign = Some(id.to_owned()),
+    }
+
+    // The release time if there is one, otherwise the nominal hour.
+    let release = number(28, 31)?;
+    let (hour, minute) = match (release / 100, release % 100) {
+        (hour, minute) if hour < 24 && minute < 60 => (hour, minute),
+        (hour, 99) if hour < 24 => (hour, 0),
+        _ => (number(25, 26)?, 0),
+    };
+    let time = (hour < 24).then_some(Timestamp {
+        year: number(14, 17)? as u16,
+        month: number(19, 20)? as u8,
+        day: number(22, 23)? as u8,
+        hour: hour as u8,
+        minute: minute as u8,
+        second: 0,
+    });
+
+    Some((station, time, vec![]))
+}
+
+fn parse_level(line: &str) -> Option<Level> {
+    let mut significance = 0;
+    match columns(line, 1, 1)? {
+        "1" => significance |= Level::STANDARD,
+        "2" | "3" => {}
+        _ => return None,
+    }
+    match columns(line, 2, 2)? {
+        "1" => significance |= Level::SURFACE,
+        "2" => significance |= Level::TROPOPAUSE,
+        _ => {}
+    }
+
+    // Elapsed time is MMMSS.
+    let time_offset = value(line, 4, 8)?.map(|t| (t / 100 * 60 + t % 100) as f64);
+    let temperature = value(line, 23, 27)?.map(|t| t as f64 / 10.0 + 273.15);
+    let depression = value(line, 35, 39)?.map(|d| d as f64 / 10.0);
+
+    Some(Level {
+        time_offset,
+        significance: Some(significance),
+        pressure: value(line, 10, 15)?.map(|p| p as f64),
+        height: value(line, 17, 21)?.map(|z| z as f64),
+        temperature,
+        dewpoint: temperature.zip(depression).map(|(t, d)| t - d),
+        relative_humidity: value(line, 29, 33)?.map(|rh| rh as f64 / 10.0),
+        wind_direction: value(line, 41, 45)?.map(|dir| dir as f64),
+        wind_speed: value(line, 47, 51)?.map(|spd| spd as f64 / 10.0),
+        ..Level::default()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station, Timestamp};
+
+    #[test]
+    fn test_write_igra() {
+        let station = Station {
+            latitude: Some(46.9),
+            longitude: Some(-114.1),
+            ..Station::default()
+        };
+        let time = Timestamp {
+            year: 2017,
+            month: 8,
+            day: 31,
+            hour: 23,
+            minute: 5,
+            second: 0,
+        };
+        let levels = vec![
+            Level {
+                time_offset: Some(0.0),
+                significance: Some(Level::SURFACE),
+                pressure: Some(89_000.0),
+                height: Some(972.0),
+                temperature: Some(303.15),
+                dewpoint: Some(280.65),
+                relative_humidity: Some(23.4),
+                wind_direction: Some(270.0),
+                wind_speed: Some(3.6),
+                ..Level::default()
+            },
+            Level {
+                time_offset: Some(95.0),
+                significance: Some(Level::STANDARD),
+                pressure: Some(85_000.0),
+                ..Level::default()
+            },
+            Level {
+                height: Some(1500.0),
+                wind_speed: Some(5.0),
+                ..Level::default()
+            },
+        ];
+        let sounding = Sounding::new(Phase::Ascent, station, Some(time), None, levels);
+
+        let mut out = vec![];
+        write_igra(&mut out, &sounding, "USM00072773").unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "#USM00072773 2017 08 31 23 2305    3                    469000 -1141000"
+        );
+        // The flag columns (16, 22 and 28) are blank.
+        assert_eq!(
+            lines[1],
+            "21     0  89000   972   300   234   225   270    36"
+        );
+        assert_eq!(
+            lines[2],
+            "10   135  85000 -9999 -9999 -9999 -9999 -9999 -9999"
+        );
+        assert_eq!(
+            lines[3],
+            "30 -9999  -9999  1500 -9999 -9999 -9999 -9999    50"
+        );
+
+        assert!(write_igra(vec![], &sounding, "72773").is_err());
+
+        // Reading it back gives the same levels, to IGRA's precision.
+        let read = read_igra(out.as_bytes()).unwrap();
+        assert_eq!(read.len(), 1);
+        let read = &read[0];
+        assert_eq!(read.station().identifier().as_deref(), Some("72773"));
+        assert_eq!(read.launch_time(), Some(time));
+        assert_eq!(read.levels().len(), 3);
+        for (a, b) in read.levels().iter().zip(sounding.levels()) {
+            assert_eq!(a.pressure, b.pressure);
+            assert_eq!(a.height, b.height);
+            assert_eq!(a.time_offset, b.time_offset);
+            assert_eq!(a.wind_speed, b.wind_speed);
+            let close = |x: Option<f64>, y: Option<f64>| match (x, y) {
+                (Some(x), Some(y)) => (x - y).abs() < 0.06,
+                (x, y) => x.is_none() && y.is_none(),
+            };
+            assert!(close(a.temperature, b.temperature));
+            assert!(close(a.dewpoint, b.dewpoint));
+        }
+        assert!(read.levels()[0].has_significance(Level::SURFACE));
+        assert!(read.levels()[1].has_significance(Level::STANDARD));
+
+        assert!(read_igra("21 bad".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{next_bufr_start, sounding::Timestamp, BufrMessage, MessageDecoder, Sounding};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// Where to find one sounding in an archive of BUFR files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// The station identifier, see `Station::identifier`.
+    pub station: String,
+    /// The nominal time from Section 1.
+    pub nominal_time: Timestamp,
+    pub path: PathBuf,
+    /// Byte offset of the start of the message in the file.
+    pub offset: u64,
+    /// Index of the subset within the message.
+    pub subset: usize,
+}
+
+/// An index of the soundings in a directory of BUFR files, so a station can be found without
+/// decoding every file again.
+#[derive(Clone, Debug, Default)]
+pub struct ArchiveIndex {
+    entries: Vec<IndexEntry>,
+}
+
+const INDEX_HEADER: &str = "sonde-bufr index 1";
+
+impl ArchiveIndex {
+    /// Scan every file under `dir`, including sub-directories, decoding with `decoder`. Messages
+    /// that fail to decode are skipped, but a file that can't be read is an error.
+    pub fn build(dir: impl AsRef<Path>, decoder: &MessageDecoder) -> Result<Self, Box<dyn Error>> {
+        let mut entries = vec![];
+        walk_archive(dir.as_ref(), decoder, |path, offset, bufr| {
+            for subset in 0..bufr.section_4.subsets().len() {
+                let Some(sounding) = bufr.subset_sounding(subset) else {
+                    continue;
+                };
+                let Some(station) = sounding.station().identifier() else {
+                    continue;
+                };
+
+                entries.push(IndexEntry {
+                    station,
+                    nominal_time: bufr.nominal_time(),
+                    path: path.to_owned(),
+                    offset,
+                    subset,
+                });
+            }
+        })?;
+
+        Ok(ArchiveIndex { entries })
+    }
+
+    pub fn entries(&self) -> &[IndexEntry] {
+        &self.entries
+    }
+
+    /// All the entries for a station at a nominal time, e.g. the ascent and descent or several
+    /// updates of the same launch.
+    pub fn find<'a>(
+        &'a self,
+        station: &'a str,
+        nominal_time: Timestamp,
+    ) -> impl Iterator<Item = &'a IndexEntry> + 'a {
+        self.entries
+            .iter()
+            .filter(move |e| e.station == station && e.nominal_time == nominal_time)
+    }
+
+    /// Decode the sounding for an entry with `decoder`, without scanning the rest of its file.
+    pub fn open(entry: &IndexEntry, decoder: &MessageDecoder) -> Result<Sounding, Box<dyn Error>> {
+        let mut f = BufReader::new(File::open(&entry.path)?);
+        f.seek(SeekFrom::Start(entry.offset))?;
+
+        decoder
+            .read_bufr_message(&mut f)?
+            .subset_sounding(entry.subset)
+            .ok_or_else(|| format!("No sounding at {}", entry.path.display()).into())
+    }
+
+    /// Save the index, one tab separated line per entry with the file paths listed once.
+    pub fn write(&self, mut w: impl Write) -> Result<(), Box<dyn Error>> {
+        writeln!(w, "{}", INDEX_HEADER)?;
+
+        let mut paths: HashMap<&Path, usize> = HashMap::new();
+        for entry in &self.entries {
+            let file_num = match paths.get(entry.path.as_path()) {
+                Some(&num) => num,
+                None => {
+                    writeln!(w, "F\t{}", entry.path.display())?;
+                    let num = paths.len();
+                    paths.insert(&entry.path, num);
+                    num
+                }
+            };
+
+            let t = entry.nominal_time;
+            writeln!(
+                w,
+                "E\t{}\t{:04}{:02}{:02}{:02}{:02}{:02}\t{}\t{}\t{}",
+                entry.station,
+                t.year,
+                t.month,
+                t.day,
+                t.hour,
+                t.minute,
+                t.second,
+                file_num,
+                entry.offset,
+                entry.subset
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Load an index saved with `write`.
+    pub fn read(r: impl BufRead) -> Result<Self, Box<dyn Error>> {
+        let mut lines = r.lines();
+        if lines.next().transpose()?.as_deref() != Some(INDEX_HEADER) {
+            return Err("Not a sonde-bufr index".into());
+        }
+
+        let mut paths: Vec<PathBuf> = vec![];
+        let mut entries = vec![];
+        for line in lines {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+
+            match fields.as_slice() {
+                ["F", path] => paths.push(PathBuf::from(path)),
+                ["E", station, time, file_num, offset, subset] => {
+                    let path = paths
+                        .get(file_num.parse::<usize>()?)
+                        .ok_or("Index entry refers to an unknown file")?;
+
+                    entries.push(IndexEntry {
+                        station: station.to_string(),
+                        nominal_time: parse_time(time)?,
+                        path: path.clone(),
+                        offset: offset.parse()?,
+                        subset: subset.parse()?,
+                    });
+                }
+                _ => return Err(format!("Invalid index line: {}", line).into()),
+            }
+        }
+
+        Ok(ArchiveIndex { entries })
+    }
+}
+
+/// Decode every message in every file under `dir`, including sub-directories, in path order.
+/// `visit` gets the path, the byte offset of the message, and the message. Messages that fail to
+/// decode are skipped, errors reading the files aren't.
+pub(crate) fn walk_archive(
+    dir: &Path,
+    decoder: &MessageDecoder,
+    mut visit: impl FnMut(&Path, u64, &BufrMessage),
+) -> Result<(), Box<dyn Error>> {
+    let mut paths = vec![];
+    find_files(dir, &mut paths)?;
+    paths.sort();
+
+    for path in paths {
+        let mut f = BufReader::new(File::open(&path)?);
+
+        while next_bufr_start(&mut f)? {
+            let offset = f.stream_position()?;
+
+            match decoder.read_bufr_message(&mut f) {
+                Ok(bufr) => visit(&path, offset, &bufr),
+                Err(_) => {
+                    // Step past this "BUFR" to look for the next message.
+                    f.seek(SeekFrom::Start(offset + 4))?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn find_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_files(&path, paths)?;
+        } else {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a YYYYMMDDHHMMSS time, as written in the index.
+pub(crate) fn parse_time(time: &str) -> Result<Timestamp, Box<dyn Error>> {
+    let field = |range: std::ops::Range<usize>| -> Result<u16, Box<dyn Error>> {
+        Ok(time
+            .get(range)
+            .ok_or_else(|| format!("Invalid index time: {}", time))?
+            .parse()?)
+    };
+
+    Ok(Timestamp {
+        year: field(0..4)?,
+        month: field(4..6)? as u8,
+        day: field(6..8)? as u8,
+        hour: field(8..10)? as u8,
+        minute: field(10..12)? as u8,
+        second: field(12..14)? as u8,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_index_round_trip() {
+        let decoder = MessageDecoder::default();
+        let index = ArchiveIndex::build("test-data", &decoder).unwrap();
+        assert_eq!(index.entries().len(), 1);
+
+        let mut saved = vec![];
+        index.write(&mut saved).unwrap();
+        let index = ArchiveIndex::read(saved.as_slice()).unwrap();
+
+        let time = index.entries()[0].nominal_time;
+        let entry = index.find("MSO1", time).next().unwrap();
+        let sounding = ArchiveIndex::open(entry, &decoder).unwrap();
+        assert_eq!(sounding.levels().len(), 4879);
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{
+    sounding::Sounding,
+    thermo::{
+        dewpoint_from_mixing_ratio, mixing_ratio, potential_temperature, temperature_from_theta,
+        virtual_temperature, Parcel, RD,
+    },
+};
+use std::fmt::Display;
+
+/// Common stability indices computed from a decoded profile.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StabilityIndices {
+    /// Surface based CAPE, J/kg.
+    pub sb_cape: Option<f64>,
+    /// Surface based CIN, J/kg, zero or negative.
+    pub sb_cin: Option<f64>,
+    /// CAPE of the mean parcel of the lowest 100 hPa, J/kg.
+    pub ml_cape: Option<f64>,
+    /// CIN of the mean parcel of the lowest 100 hPa, J/kg.
+    pub ml_cin: Option<f64>,
+    /// 500 hPa temperature minus that of the surface parcel lifted to 500 hPa, K.
+    pub lifted_index: Option<f64>,
+    /// (T850 - T500) + Td850 - (T700 - Td700), C.
+    pub k_index: Option<f64>,
+    /// T850 + Td850 - 2 T500, C.
+    pub total_totals: Option<f64>,
+}
+
+impl Sounding {
+    pub fn stability_indices(&self) -> StabilityIndices {
+        let env = self.environment();
+        let surface = env.iter().find_map(|&(p, t, td)| Some((p, t, td?)));
+        let mixed = self.mixed_layer_parcel(&env, 10_000.0);
+
+        let (sb_cape, sb_cin) = match surface {
+            Some(parcel) => parcel_cape_cin(&env, parcel),
+            None => (None, None),
+        };
+        let (ml_cape, ml_cin) = match mixed {
+            Some(parcel) => parcel_cape_cin(&env, parcel),
+            None => (None, None),
+        };
+
+        let mandatory = self.interpolate_to(&[85_000.0, 70_000.0, 50_000.0]);
+        let c = |t: Option<f64>| t.map(|t| t - 273.15);
+        let (t850, td850) = (c(mandatory[0].temperature), c(mandatory[0].dewpoint));
+        let (t700, td700) = (c(mandatory[1].temperature), c(mandatory[1].dewpoint));
+        let t500 = c(mandatory[2].temperature);
+
+        let lifted_index = surface.zip(t500).and_then(|((p0, t0, td0), t500)| {
+            if p0 <= 50_000.0 {
+                return None;
+            }
+            let mut parcel = Parcel::new(p0, t0, td0);
+            Some(t500 + 273.15 - parcel.lift(50_000.0).0)
+        });
+
+        let k_index = match (t850, t500, td850, t700, td700) {
+            (Some(t850), Some(t500), Some(td850), Some(t700), Some(td700)) => {
+                Some((t850 - t500) + td850 - (t700 - td700))
+            }
+            _ => None,
+        };
+        let total_totals = match (t850, td850, t500) {
+            (Some(t850), Some(td850), Some(t500)) => Some(t850 + td850 - 2.0 * t500),
+            _ => None,
+        };
+
+        StabilityIndices {
+            sb_cape,
+            sb_cin,
+            ml_cape,
+            ml_cin,
+            lifted_index,
+            k_index,
+            total_totals,
+        }
+    }
+
+    /// (pressure, temperature, dewpoint) of every level with a pressure and temperature, sorted
+    /// from the highest pressure.
+    fn environment(&self) -> Vec<(f64, f64, Option<f64>)> {
+        let mut env: Vec<_> = self
+            .levels()
+            .iter()
+            .filter_map(|lvl| Some((lvl.pressure?, lvl.temperature?, lvl.dewpoint)))
+            .collect();
+        env.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        env
+    }
+
+    /// A parcel with the pressure weighted mean potential temperature and mixing ratio of the
+    /// lowest `depth` Pa, starting at the lowest level.
+    fn mixed_layer_parcel(
+        &self,
+        env: &[(f64, f64, Option<f64>)],
+        depth: f64,
+    ) -> Option<(f64, f64, f64)> {
+        let top = env.first()?.0 - depth;
+        let point = |p: f64, t: f64, td: Option<f64>| {
+            Some((p, potential_temperature(t, p), mixing_ratio(td?, p)))
+        };
+
+        let mut layer: Vec<(f64, f64, f64)> = env
+            .iter()
+            .take_while(|(p, _, _)| *p > top)
+            .filter_map(|&(p, t, td)| point(p, t, td))
+            .collect();
+        let top_lvl = self.interpolate_to(&[top])[0];
+        if let Some(top_lvl) = top_lvl
+            .temperature
+            .and_then(|t| point(top, t, top_lvl.dewpoint))
+        {
+            layer.push(top_lvl);
+        }
+        if layer.len() < 2 {
+            return None;
+        }
+
+        let (mut theta_sum, mut w_sum, mut dp_sum) = (0.0, 0.0, 0.0);
+        for pair in layer.windows(2) {
+            let ((p0, theta0, w0), (p1, theta1, w1)) = (pair[0], pair[1]);
+            let dp = p0 - p1;
+            theta_sum += (theta0 + theta1) / 2.0 * dp;
+            w_sum += (w0 + w1) / 2.0 * dp;
+            dp_sum += dp;
+        }
+        if dp_sum <= 0.0 {
+            return None;
+        }
+
+        let p = env[0].0;
+        let t = temperature_from_theta(theta_sum / dp_sum, p);
+        let td = dewpoint_from_mixing_ratio(w_sum / dp_sum, p)?.min(t);
+
+        Some((p, t, td))
+    }
+}
+
+/// CAPE and CIN for a parcel starting at `(p, t, td)`. The CIN is the negative area below the
+/// first positive area.
+fn parcel_cape_cin(
+    env: &[(f64, f64, Option<f64>)],
+    (p0, t0, td0): (f64, f64, f64),
+) -> (Option<f64>, Option<f64>) {
+    let mut parcel = Parcel::new(p0, t0, td0);
+
+    let buoyancy: Vec<(f64, f64)> = env
+        .iter()
+        .filter(|(p, _, _)| *p <= p0)
+        .map(|&(p, t, td)| {
+            let (tp, wp) = parcel.lift(p);
+            let we = td.map(|td| mixing_ratio(td, p)).unwrap_or(0.0);
+            let b = RD * (virtual_temperature(tp, wp) - virtual_temperature(t, we));
+            (p.ln(), b)
+        })
+        .collect();
+    if buoyancy.len() < 2 {
+        return (None, None);
+    }
+
+    let (mut cape, mut cin) = (0.0, 0.0);
+    let mut found_positive = false;
+    for pair in buoyancy.windows(2) {
+        let ((x0, b0), (x1, b1)) = (pair[0], pair[1]);
+        let dx = x0 - x1;
+
+        // Split layers that cross zero buoyancy.
+        let (pos, neg) = if b0 * b1 < 0.0 {
+            let frac = b0 / (b0 - b1);
+            let first = b0 / 2.0 * dx * frac;
+            let second = b1 / 2.0 * dx * (1.0 - frac);
+            if b0 > 0.0 {
+                (first, second)
+            } else {
+                (second, first)
+            }
+        } else if b0 + b1 >= 0.0 {
+            ((b0 + b1) / 2.0 * dx, 0.0)
+        } else {
+            (0.0, (b0 + b1) / 2.0 * dx)
+        };
+
+        if !found_positive && b0 <= 0.0 {
+            cin += neg;
+        }
+        if pos > 0.0 {
+            found_positive = true;
+        }
+        cape += pos;
+    }
+
+    if cape == 0.0 {
+        cin = 0.0;
+    }
+
+    (Some(cape), Some(cin))
+}
+
+impl Display for StabilityIndices {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let val = |v: Option<f64>| match v {
+            Some(v) => format!("{:.0}", v),
+            None => "-".to_owned(),
+        };
+        let val1 = |v: Option<f64>| match v {
+            Some(v) => format!("{:.1}", v),
+            None => "-".to_owned(),
+        };
+
+        writeln!(
+            f,
+            "SBCAPE: {} J/kg  SBCIN: {} J/kg  MLCAPE: {} J/kg  MLCIN: {} J/kg",
+            val(self.sb_cape),
+            val(self.sb_cin),
+            val(self.ml_cape),
+            val(self.ml_cin)
+        )?;
+        write!(
+            f,
+            "LI: {}  K: {}  TT: {}",
+            val1(self.lifted_index),
+            val1(self.k_index),
+            val1(self.total_totals)
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sounding::{Level, Phase, Sounding, Station};
+
+    #[test]
+    fn test_stability_indices() {
+        // A warm, moist surface under a steep lapse rate.
+        let levels = [
+            (1000.0, 30.0, 22.0),
+            (850.0, 18.0, 14.0),
+            (700.0, 6.0, -2.0),
+            (500.0, -15.0, -30.0),
+            (300.0, -42.0, -55.0),
+            (200.0, -58.0, -75.0),
+        ]
+        .iter()
+        .map(|&(hpa, t, td)| Level {
+            pressure: Some(hpa * 100.0),
+            temperature: Some(t + 273.15),
+            dewpoint: Some(td + 273.15),
+            ..Level::default()
+        })
+        .collect();
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        let indices = sounding.stability_indices();
+        assert!((indices.k_index.unwrap() - (33.0 + 14.0 - 8.0)).abs() < 1.0e-9);
+        assert!((indices.total_totals.unwrap() - (18.0 + 14.0 + 30.0)).abs() < 1.0e-9);
+
+        let sb_cape = indices.sb_cape.unwrap();
+        assert!(sb_cape > 1000.0, "{}", sb_cape);
+        assert!(indices.ml_cape.unwrap() < sb_cape);
+        assert!(indices.lifted_index.unwrap() < -3.0);
+        assert!(indices.sb_cin.unwrap() <= 0.0);
+    }
+}
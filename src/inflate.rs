@@ -0,0 +1,341 @@
+//! A small DEFLATE (RFC 1951) decoder for reading gzip (RFC 1952) files, so compressed archives
+//! can be read without another dependency.
+
+use std::error::Error;
+
+/// Whether `data` starts with the gzip magic number.
+pub(crate) fn is_gzip(data: &[u8]) -> bool {
+    data.starts_with(&[0x1f, 0x8b])
+}
+
+/// Decompress a gzip file, including files of several concatenated members.
+pub(crate) fn gunzip(mut data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut out = vec![];
+    while !data.is_empty() {
+        let start = out.len();
+        let header = gzip_header_len(data)?;
+        let consumed = inflate(&data[header..], &mut out)?;
+
+        let trailer = data
+            .get(header + consumed..header + consumed + 8)
+            .ok_or("Truncated gzip trailer")?;
+        let crc = u32::from_le_bytes(trailer[..4].try_into()?);
+        let size = u32::from_le_bytes(trailer[4..].try_into()?);
+        if crc != crc32(&out[start..]) || size != (out.len() - start) as u32 {
+            return Err("gzip checksum mismatch".into());
+        }
+
+        data = &data[header + consumed + 8..];
+    }
+    Ok(out)
+}
+
+fn gzip_header_len(data: &[u8]) -> Result<usize, Box<dyn Error>> {
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+    const FHCRC: u8 = 2;
+
+    if !is_gzip(data) || data.get(2) != Some(&8) {
+        return Err("Not a gzip file".into());
+    }
+    let flags = *data.get(3).ok_or("Truncated gzip header")?;
+    let mut pos = 10;
+    if flags & FEXTRA != 0 {
+        let len = data.get(pos..pos + 2).ok_or("Truncated gzip header")?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|&b| b == 0))
+                .ok_or("Truncated gzip header")?;
+            pos += end + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+    if pos > data.len() {
+        return Err("Truncated gzip header".into());
+    }
+    Ok(pos)
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut c = i as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+
+    !data.iter().fold(!0u32, |crc, &b| {
+        table[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Reads bits least significant first, as DEFLATE packs them.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl Bits<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, Box<dyn Error>> {
+        let mut val = 0;
+        for i in 0..n {
+            let byte = *self
+                .data
+                .get(self.pos)
+                .ok_or("Unexpected end of deflate data")?;
+            val |= ((byte >> self.bit) as u32 & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(val)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// A canonical Huffman code: the number of codes of each length and the symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, Box<dyn Error>> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid Huffman code in deflate data".into())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Decompress raw DEFLATE data onto `out`, returning the number of bytes of `data` used.
+fn inflate(data: &[u8], out: &mut Vec<u8>) -> Result<usize, Box<dyn Error>> {
+    let mut bits = Bits {
+        data,
+        pos: 0,
+        bit: 0,
+    };
+
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let header = data
+                    .get(bits.pos..bits.pos + 4)
+                    .ok_or("Truncated stored block")?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err("Corrupt stored block length".into());
+                }
+                let start = bits.pos + 4;
+                let block = data
+                    .get(start..start + len as usize)
+                    .ok_or("Truncated stored block")?;
+                out.extend_from_slice(block);
+                bits.pos = start + len as usize;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut bits, &literals, &distances, out)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &literals, &distances, out)?;
+            }
+            _ => return Err("Invalid deflate block type".into()),
+        }
+
+        if last {
+            bits.align();
+            return Ok(bits.pos);
+        }
+    }
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), Box<dyn Error>> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let num_literals = bits.bits(5)? as usize + 257;
+    let num_distances = bits.bits(5)? as usize + 1;
+    let num_code_lengths = bits.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &ORDER[..num_code_lengths] {
+        code_lengths[i] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(num_literals + num_distances);
+    while lengths.len() < num_literals + num_distances {
+        let (value, repeat) = match code_lengths.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("Repeat with no previous length")?;
+                (previous, 3 + bits.bits(2)?)
+            }
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() != num_literals + num_distances {
+        return Err("Too many code lengths in deflate data".into());
+    }
+
+    Ok((
+        Huffman::new(&lengths[..num_literals]),
+        Huffman::new(&lengths[num_literals..]),
+    ))
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    literals: &Huffman,
+    distances: &Huffman,
+    out: &mut Vec<u8>,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err("Invalid length code in deflate data".into());
+                }
+                let len = LENGTH_BASE[i] as usize + bits.bits(LENGTH_EXTRA[i] as u32)? as usize;
+
+                let d = distances.decode(bits)? as usize;
+                if d >= DIST_BASE.len() {
+                    return Err("Invalid distance code in deflate data".into());
+                }
+                let dist = DIST_BASE[d] as usize + bits.bits(DIST_EXTRA[d] as u32)? as usize;
+                if dist > out.len() {
+                    return Err("Distance too far back in deflate data".into());
+                }
+
+                // Copies may overlap the bytes they produce, so go a byte at a time.
+                let start = out.len() - dist;
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gunzip() {
+        // From `gzip -9n`, with fixed Huffman codes.
+        let fixed = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x73, 0x0a, 0x75, 0x0b,
+            0x52, 0x28, 0xce, 0x2f, 0xcd, 0x4b, 0xc9, 0xcc, 0x4b, 0x57, 0x70, 0xc2, 0xcd, 0xe3,
+            0x02, 0x00, 0x18, 0x90, 0xa0, 0x85, 0x2a, 0x00, 0x00, 0x00,
+        ];
+        let expected = b"BUFR sounding BUFR sounding BUFR sounding\n";
+        assert_eq!(gunzip(&fixed).unwrap(), expected);
+
+        // Concatenated members decompress one after the other.
+        let twice = [fixed, fixed].concat();
+        assert_eq!(gunzip(&twice).unwrap(), [&expected[..], expected].concat());
+
+        // With dynamic Huffman codes.
+        let dynamic = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x0d, 0xcb, 0x49, 0x01,
+            0x04, 0x21, 0x00, 0x03, 0x30, 0x2b, 0x91, 0x40, 0x0b, 0xc3, 0xe1, 0xdf, 0xd8, 0x6e,
+            0xfe, 0x19, 0xa2, 0xa6, 0xe5, 0xb3, 0x1d, 0xd7, 0x93, 0x21, 0x91, 0xca, 0x94, 0x25,
+            0x9f, 0x6c, 0x39, 0x72, 0xe5, 0xe9, 0xd0, 0xff, 0xa9, 0x4e, 0x5d, 0xfa, 0xe9, 0xd6,
+            0xa3, 0x57, 0x9f, 0x1f, 0x1e, 0x70, 0x47, 0x96, 0x50, 0x00, 0x00, 0x00,
+        ];
+        let expected: String = (0..30).map(|i| format!("{} ", i)).collect();
+        assert_eq!(gunzip(&dynamic).unwrap(), expected.as_bytes());
+
+        let mut corrupt = fixed;
+        corrupt[30] ^= 0xff;
+        assert!(gunzip(&corrupt).is_err());
+        assert!(gunzip(b"BUFR").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Continuous ingestion of BUFR messages from a socket feed, with the `ingest` feature.
+
+use crate::{fingerprint::content_hash, read_3_octet_usize, MessageDecoder, SeenStore, Sounding};
+use std::{error::Error, io::Read, net::TcpStream, sync::mpsc::Sender, thread, time::Duration};
+
+/// Reads a feed of BUFR messages, resynchronizing on the `BUFR` marker after any bytes between
+/// or inside broken messages, and hands each sounding not seen before to a callback.
+///
+/// Use a `FileSeenStore` so duplicates are still filtered after a restart.
+pub struct Ingest<S> {
+    address: String,
+    seen: S,
+    decoder: MessageDecoder,
+    reconnect: Option<Duration>,
+}
+
+impl<S: SeenStore> Ingest<S> {
+    /// Ingest from a TCP address, e.g. `localhost:5000`.
+    pub fn new(address: impl Into<String>, seen: S) -> Self {
+        Ingest {
+            address: address.into(),
+            seen,
+            decoder: MessageDecoder::default(),
+            reconnect: None,
+        }
+    }
+
+    /// Decode with the tables and options of `decoder`.
+    pub fn decoder(mut self, decoder: MessageDecoder) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Reconnect after `delay` when the connection closes or fails, rather than stopping.
+    pub fn reconnect(mut self, delay: Duration) -> Self {
+        self.reconnect = Some(delay);
+        self
+    }
+
+    /// Get the store back, e.g. to sync it one last time.
+    pub fn into_seen(self) -> S {
+        self.seen
+    }
+
+    /// Connect and ingest until the connection closes, or forever with `reconnect`. An error
+    /// from the callback or the seen store stops ingestion.
+    pub fn run(
+        &mut self,
+        mut on_sounding: impl FnMut(Sounding) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        loop {
+            let result = TcpStream::connect(&self.address)
+                .map_err(Box::<dyn Error>::from)
+                .and_then(|stream| self.ingest(stream, &mut on_sounding));
+
+            match (self.reconnect, result) {
+                (Some(delay), Err(err)) if err.is::<std::io::Error>() => thread::sleep(delay),
+                (Some(delay), Ok(())) => thread::sleep(delay),
+                (_, result) => return result,
+            }
+        }
+    }
+
+    /// `run`, sending the soundings to a channel. Stops when the receiver is dropped.
+    pub fn run_channel(&mut self, tx: Sender<Sounding>) -> Result<(), Box<dyn Error>> {
+        self.run(|sounding| Ok(tx.send(sounding)?))
+    }
+
+    /// Ingest from any reader until the end of its data, e.g. a file being appended to or stdin.
+    pub fn ingest(
+        &mut self,
+        mut reader: impl Read,
+        mut on_sounding: impl FnMut(Sounding) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut buffer = vec![];
+        let mut chunk = [0; 64 * 1024];
+        loop {
+            let n = match reader.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(n) => n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+            buffer.extend_from_slice(&chunk[..n]);
+
+            while let Some(message) = next_message(&mut buffer) {
+                let Ok(bufr) = self.decoder.read_bufr_message(message.as_slice()) else {
+                    continue;
+                };
+                for sounding in bufr.soundings() {
+                    if self.seen.insert(content_hash(&sounding)) {
+                        on_sounding(sounding)?;
+                    }
+                }
+                self.seen.sync()?;
+            }
+        }
+    }
+}
+
+/// Take the next complete message from the front of `buffer`, dropping anything before it. A
+/// `BUFR` without a sensible length or a `7777` at the end is skipped. `None` if more data is
+/// needed.
+fn next_message(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+    loop {
+        let Some(start) = buffer.windows(4).position(|w| w == b"BUFR") else {
+            // Keep enough to find a marker split across reads.
+            buffer.drain(..buffer.len().saturating_sub(3));
+            return None;
+        };
+        buffer.drain(..start);
+
+        let len = read_3_octet_usize(buffer.get(4..7)?).ok()?;
+        if len < 12 {
+            buffer.drain(..4);
+            continue;
+        }
+        let message = buffer.get(..len)?;
+        if !message.ends_with(b"7777") {
+            buffer.drain(..4);
+            continue;
+        }
+        return Some(buffer.drain(..len).collect());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::FileSeenStore;
+    use std::{io::Write, net::TcpListener, sync::mpsc};
+
+    #[test]
+    fn test_ingest() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let message = crate::read_bufr_bytes(&file[start..]).unwrap();
+
+        // Noise, a message cut off by another, and the same message twice.
+        let mut feed = b"noise".to_vec();
+        feed.extend(&message[..500]);
+        feed.extend(&message);
+        feed.extend(b"\r\n");
+        feed.extend(&message);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Write in pieces so messages are split across reads.
+            for piece in feed.chunks(30_000) {
+                stream.write_all(piece).unwrap();
+            }
+        });
+
+        let path = std::env::temp_dir().join(format!("sonde-bufr-ingest-{}", std::process::id()));
+        let mut ingest = Ingest::new(address, FileSeenStore::open(&path).unwrap());
+        let (tx, rx) = mpsc::channel();
+        ingest.run_channel(tx).unwrap();
+        server.join().unwrap();
+
+        let soundings: Vec<Sounding> = rx.iter().collect();
+        assert_eq!(soundings.len(), 1);
+        assert_eq!(soundings[0].levels().len(), 4879);
+
+        // The store remembers the sounding for the next run.
+        let mut ingest = Ingest::new("", FileSeenStore::open(&path).unwrap());
+        let mut count = 0;
+        ingest
+            .ingest(message.as_slice(), |_| {
+                count += 1;
+                Ok(())
+            })
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(count, 0);
+    }
+}
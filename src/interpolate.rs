@@ -0,0 +1,143 @@
+use crate::sounding::{Level, Sounding};
+
+impl Sounding {
+    /// The largest gap, in natural log of pressure, between the levels on either side of a
+    /// target pressure that `interpolate_to` will interpolate across. About 0.5 is the gap
+    /// from 500 hPa to 300 hPa.
+    pub const DEFAULT_INTERPOLATION_GAP: f64 = 0.5;
+
+    /// Interpolate the profile to the pressures (Pa) in `grid`, returning one level per grid
+    /// pressure. See `interpolate_to_with_gap`.
+    pub fn interpolate_to(&self, grid: &[f64]) -> Vec<Level> {
+        self.interpolate_to_with_gap(grid, Self::DEFAULT_INTERPOLATION_GAP)
+    }
+
+    /// Interpolate the profile to the pressures (Pa) in `grid`, returning one level per grid
+    /// pressure.
+    ///
+    /// Each value is interpolated linearly in log pressure from the nearest levels above and
+    /// below that have it, so a level missing one value doesn't hide the others. Winds are
+    /// interpolated as u and v components. A value is missing if the grid pressure is outside
+    /// the profile or the levels on either side are more than `max_gap` apart in log pressure.
+    pub fn interpolate_to_with_gap(&self, grid: &[f64], max_gap: f64) -> Vec<Level> {
+        let profile = |get: fn(&Level) -> Option<f64>| -> Vec<(f64, f64)> {
+            let mut pts: Vec<(f64, f64)> = self
+                .levels()
+                .iter()
+                .filter_map(|lvl| Some((lvl.pressure?.ln(), get(lvl)?)))
+                .collect();
+            pts.sort_by(|a, b| a.0.total_cmp(&b.0));
+            pts
+        };
+
+        let time_offset = profile(|lvl| lvl.time_offset);
+        let height = profile(|lvl| lvl.height);
+        let gnss_height = profile(|lvl| lvl.gnss_height);
+        let temperature = profile(|lvl| lvl.temperature);
+        let dewpoint = profile(|lvl| lvl.dewpoint);
+        let relative_humidity = profile(|lvl| lvl.relative_humidity);
+        let u = profile(|lvl| lvl.wind_components().map(|(u, _)| u));
+        let v = profile(|lvl| lvl.wind_components().map(|(_, v)| v));
+        let lat_displacement = profile(|lvl| lvl.lat_displacement);
+        let lon_displacement = profile(|lvl| lvl.lon_displacement);
+
+        grid.iter()
+            .map(|&pressure| {
+                let x = pressure.ln();
+                let interp = |pts: &[(f64, f64)]| interpolate(pts, x, max_gap);
+
+                let mut lvl = Level {
+                    time_offset: interp(&time_offset),
+                    significance: None,
+                    pressure: Some(pressure),
+                    height: interp(&height),
+                    gnss_height: interp(&gnss_height),
+                    temperature: interp(&temperature),
+                    dewpoint: interp(&dewpoint),
+                    relative_humidity: interp(&relative_humidity),
+                    wind_direction: None,
+                    wind_speed: None,
+                    lat_displacement: interp(&lat_displacement),
+                    lon_displacement: interp(&lon_displacement),
+                    derived: 0,
+                    qc: 0,
+                };
+                if let (Some(u), Some(v)) = (interp(&u), interp(&v)) {
+                    lvl.set_wind_components(u, v);
+                }
+
+                lvl
+            })
+            .collect()
+    }
+}
+
+/// Linear interpolation in points sorted by x, without extrapolating.
+fn interpolate(pts: &[(f64, f64)], x: f64, max_gap: f64) -> Option<f64> {
+    let idx = pts.partition_point(|(px, _)| *px < x);
+
+    let (x1, y1) = *pts.get(idx)?;
+    if x1 == x {
+        return Some(y1);
+    }
+
+    let (x0, y0) = *pts.get(idx.checked_sub(1)?)?;
+    if x1 - x0 > max_gap {
+        return None;
+    }
+
+    Some(y0 + (y1 - y0) * (x - x0) / (x1 - x0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_interpolate_to() {
+        let level = |hpa: f64, t: Option<f64>, dir: f64| Level {
+            pressure: Some(hpa * 100.0),
+            temperature: t,
+            wind_direction: Some(dir),
+            wind_speed: Some(10.0),
+            ..Level::default()
+        };
+        let sounding = Sounding::new(
+            Phase::Ascent,
+            Station::default(),
+            None,
+            None,
+            vec![
+                level(1000.0, Some(290.0), 350.0),
+                level(900.0, None, 10.0),
+                level(800.0, Some(280.0), 10.0),
+                level(200.0, Some(220.0), 10.0),
+            ],
+        );
+
+        let grid = [
+            100000.0,
+            f64::sqrt(1000.0 * 900.0) * 100.0,
+            50000.0,
+            150000.0,
+        ];
+        let levels = sounding.interpolate_to(&grid);
+
+        assert_eq!(levels[0].temperature, Some(290.0));
+
+        // Interpolated from 1000 and 800 hPa, skipping the level with no temperature.
+        let t = levels[1].temperature.unwrap();
+        let frac = (1000.0f64 / 948.683).ln() / (1000.0f64 / 800.0).ln();
+        assert!((t - (290.0 - 10.0 * frac)).abs() < 1.0e-3);
+
+        // Winds are averaged as components, so they don't swing through 180 degrees.
+        let dir = levels[1].wind_direction.unwrap();
+        assert!(!(90.0..270.0).contains(&dir));
+
+        // The gap from 800 to 200 hPa is too large, and 1500 hPa is outside the profile.
+        assert_eq!(levels[2].temperature, None);
+        assert_eq!(levels[3].temperature, None);
+        assert_eq!(levels[3].pressure, Some(150000.0));
+    }
+}
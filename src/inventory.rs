@@ -0,0 +1,217 @@
+use crate::{
+    export::{json_number, json_string},
+    index::walk_archive,
+    section3::Descriptor,
+    sounding::Timestamp,
+    MessageDecoder,
+};
+use std::{collections::BTreeMap, error::Error, io::Write, path::Path};
+
+/// Everything an archive says about one station, see `build_inventory`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StationRecord {
+    /// See `Station::identifier`.
+    pub station: String,
+    pub first: Timestamp,
+    pub last: Timestamp,
+    /// Observations by the template of the message, e.g. `309052`. Messages without a
+    /// 3-09-YYY template are counted under their first descriptor.
+    pub counts_by_template: BTreeMap<String, usize>,
+    /// The position at the first observation and at each move after it, in time order.
+    pub positions: Vec<PositionChange>,
+}
+
+impl StationRecord {
+    pub fn observations(&self) -> usize {
+        self.counts_by_template.values().sum()
+    }
+}
+
+/// The station position from `time` until the next change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionChange {
+    pub time: Timestamp,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub elevation: Option<f64>,
+}
+
+impl PositionChange {
+    /// Whether `other` is somewhere else, ignoring changes of less than 0.01 degrees or 1 m.
+    fn moved(&self, other: &PositionChange) -> bool {
+        let differs = |a: Option<f64>, b: Option<f64>, tolerance: f64| match (a, b) {
+            (Some(a), Some(b)) => (a - b).abs() >= tolerance,
+            (a, b) => a.is_some() != b.is_some(),
+        };
+        differs(self.latitude, other.latitude, 0.01)
+            || differs(self.longitude, other.longitude, 0.01)
+            || differs(self.elevation, other.elevation, 1.0)
+    }
+}
+
+/// Scan every sounding under `dir` and build the history of each station, sorted by station.
+/// Observations are timed by the launch time, or the nominal time if there isn't one.
+pub fn build_inventory(dir: impl AsRef<Path>) -> Result<Vec<StationRecord>, Box<dyn Error>> {
+    // Each observation's station, template, and position.
+    let mut observations = vec![];
+    walk_archive(dir.as_ref(), &MessageDecoder::default(), |_, _, bufr| {
+        let template = bufr
+            .descriptors()
+            .iter()
+            .find(|d| d.f_value() == 3 && d.x_value() == 9)
+            .or(bufr.descriptors().first())
+            .map_or_else(String::new, Descriptor::string_form);
+
+        for sounding in bufr.soundings() {
+            let station = sounding.station();
+            let Some(id) = station.identifier() else {
+                continue;
+            };
+            let position = PositionChange {
+                time: sounding.launch_time().unwrap_or(bufr.nominal_time()),
+                latitude: station.latitude,
+                longitude: station.longitude,
+                elevation: station.elevation,
+            };
+            observations.push((id, template.clone(), position));
+        }
+    })?;
+    observations.sort_by(|a, b| (&a.0, a.2.time).cmp(&(&b.0, b.2.time)));
+
+    let mut records: Vec<StationRecord> = vec![];
+    for (station, template, position) in observations {
+        match records.last_mut() {
+            Some(record) if record.station == station => {
+                record.last = position.time;
+                *record.counts_by_template.entry(template).or_default() += 1;
+                if record.positions.last().is_some_and(|p| p.moved(&position)) {
+                    record.positions.push(position);
+                }
+            }
+            _ => records.push(StationRecord {
+                station,
+                first: position.time,
+                last: position.time,
+                counts_by_template: BTreeMap::from([(template, 1)]),
+                positions: vec![position],
+            }),
+        }
+    }
+
+    Ok(records)
+}
+
+/// Write one CSV row per station. The templates are `template:count` pairs separated by `;`,
+/// and the position is the latest one.
+pub fn write_inventory_csv(
+    mut w: impl Write,
+    records: &[StationRecord],
+) -> Result<(), Box<dyn Error>> {
+    let opt = |val: Option<f64>| val.map_or(String::new(), |v| v.to_string());
+
+    writeln!(
+        w,
+        "station,first,last,observations,templates,position_changes,latitude,longitude,elevation"
+    )?;
+    for record in records {
+        let templates: Vec<String> = record
+            .counts_by_template
+            .iter()
+            .map(|(template, count)| format!("{}:{}", template, count))
+            .collect();
+        let latest = record.positions.last();
+        writeln!(
+            w,
+            "{},{},{},{},{},{},{},{},{}",
+            record.station,
+            record.first,
+            record.last,
+            record.observations(),
+            templates.join(";"),
+            record.positions.len().saturating_sub(1),
+            opt(latest.and_then(|p| p.latitude)),
+            opt(latest.and_then(|p| p.longitude)),
+            opt(latest.and_then(|p| p.elevation)),
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the inventory as a JSON array, with the full position history of each station.
+pub fn write_inventory_json(
+    mut w: impl Write,
+    records: &[StationRecord],
+) -> Result<(), Box<dyn Error>> {
+    let time = |t: Timestamp| json_string(Some(&t.to_string()));
+
+    write!(w, "[")?;
+    for (i, record) in records.iter().enumerate() {
+        let sep = if i > 0 { "," } else { "" };
+        write!(
+            w,
+            "{}{{\"station\":{},\"first\":{},\"last\":{},\"observations\":{},\"templates\":{{",
+            sep,
+            json_string(Some(&record.station)),
+            time(record.first),
+            time(record.last),
+            record.observations()
+        )?;
+        for (j, (template, count)) in record.counts_by_template.iter().enumerate() {
+            let sep = if j > 0 { "," } else { "" };
+            write!(w, "{}{}:{}", sep, json_string(Some(template)), count)?;
+        }
+        write!(w, "}},\"positions\":[")?;
+        for (j, p) in record.positions.iter().enumerate() {
+            let sep = if j > 0 { "," } else { "" };
+            write!(
+                w,
+                "{}{{\"time\":{},\"latitude\":{},\"longitude\":{},\"elevation\":{}}}",
+                sep,
+                time(p.time),
+                json_number(p.latitude),
+                json_number(p.longitude),
+                json_number(p.elevation)
+            )?;
+        }
+        write!(w, "]}}")?;
+    }
+    writeln!(w, "]")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_inventory() {
+        let records = build_inventory("test-data").unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.station, "MSO1");
+        assert_eq!(record.first, record.last);
+        assert_eq!(record.observations(), 1);
+        assert_eq!(record.counts_by_template.get("309052"), Some(&1));
+        assert_eq!(record.positions.len(), 1);
+
+        let mut csv = vec![];
+        write_inventory_csv(&mut csv, &records).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.lines().nth(1).unwrap().starts_with("MSO1,"));
+
+        let mut json = vec![];
+        write_inventory_json(&mut json, &records).unwrap();
+        assert!(String::from_utf8(json)
+            .unwrap()
+            .starts_with("[{\"station\":\"MSO1\","));
+
+        let position = |lat: f64| PositionChange {
+            time: record.first,
+            latitude: Some(lat),
+            longitude: Some(-114.0),
+            elevation: None,
+        };
+        assert!(!position(46.9).moved(&position(46.905)));
+        assert!(position(46.9).moved(&position(46.95)));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::sounding::Sounding;
+
+/// A layer where the temperature increases with height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Inversion {
+    /// Pa
+    pub base_pressure: f64,
+    /// gpm
+    pub base_height: Option<f64>,
+    /// Pa
+    pub top_pressure: f64,
+    /// gpm
+    pub top_height: Option<f64>,
+    /// Temperature at the top minus that at the base, K.
+    pub strength: f64,
+    /// The base is the lowest level of the profile.
+    pub surface_based: bool,
+}
+
+impl Sounding {
+    /// Inversions weaker than this (K) are ignored.
+    pub const MIN_INVERSION_STRENGTH: f64 = 0.5;
+
+    /// A cooling of less than this (K) inside an inversion is treated as noise rather than the
+    /// top of the inversion, so high resolution data doesn't split one inversion into many.
+    pub const INVERSION_NOISE: f64 = 0.2;
+
+    /// The surface based and elevated inversions in the profile, from the bottom up.
+    pub fn inversions(&self) -> Vec<Inversion> {
+        let mut profile: Vec<(f64, Option<f64>, f64)> = self
+            .levels()
+            .iter()
+            .filter_map(|lvl| Some((lvl.pressure?, lvl.height, lvl.temperature?)))
+            .collect();
+        profile.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut inversions = vec![];
+        let mut push = |base: usize, top: usize| {
+            let ((p0, z0, t0), (p1, z1, t1)) = (profile[base], profile[top]);
+            if t1 - t0 >= Self::MIN_INVERSION_STRENGTH {
+                inversions.push(Inversion {
+                    base_pressure: p0,
+                    base_height: z0,
+                    top_pressure: p1,
+                    top_height: z1,
+                    strength: t1 - t0,
+                    surface_based: base == 0,
+                });
+            }
+        };
+
+        // (base, warmest level so far)
+        let mut current: Option<(usize, usize)> = None;
+        for k in 1..profile.len() {
+            let t = profile[k].2;
+            match current {
+                None => {
+                    if t > profile[k - 1].2 {
+                        current = Some((k - 1, k));
+                    }
+                }
+                Some((base, top)) => {
+                    if t >= profile[top].2 {
+                        current = Some((base, k));
+                    } else if t < profile[top].2 - Self::INVERSION_NOISE {
+                        push(base, top);
+                        current = None;
+                    }
+                }
+            }
+        }
+        if let Some((base, top)) = current {
+            push(base, top);
+        }
+
+        inversions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sounding::{Level, Phase, Sounding, Station};
+
+    #[test]
+    fn test_inversions() {
+        let levels = [
+            (1000.0, 270.0),
+            (990.0, 272.0),
+            (980.0, 271.9), // noise inside the surface inversion
+            (970.0, 274.0),
+            (950.0, 272.0),
+            (900.0, 268.0),
+            (880.0, 268.3), // too weak to count
+            (860.0, 267.0),
+            (800.0, 264.0),
+            (780.0, 266.0),
+            (700.0, 260.0),
+        ]
+        .iter()
+        .map(|&(hpa, t)| Level {
+            pressure: Some(hpa * 100.0),
+            temperature: Some(t),
+            ..Level::default()
+        })
+        .collect();
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        let inversions = sounding.inversions();
+        assert_eq!(inversions.len(), 2);
+
+        assert!(inversions[0].surface_based);
+        assert_eq!(inversions[0].top_pressure, 97_000.0);
+        assert!((inversions[0].strength - 4.0).abs() < 1.0e-9);
+
+        assert!(!inversions[1].surface_based);
+        assert_eq!(inversions[1].base_pressure, 80_000.0);
+        assert_eq!(inversions[1].top_pressure, 78_000.0);
+    }
+}
@@ -0,0 +1,63 @@
+use crate::{
+    export::{write_json_line, ExportOptions},
+    sounding::Sounding,
+};
+use std::{error::Error, io::Write};
+
+/// Writes soundings as JSON Lines, one `write_json_line` object per line, e.g.
+/// `sonde-app | jq .station`.
+///
+/// Each line is flushed as it's written, so a reader downstream sees a sounding as soon as it's
+/// decoded, however large the input.
+pub struct JsonLinesWriter<W: Write> {
+    w: W,
+    options: ExportOptions,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(w: W, options: ExportOptions) -> Self {
+        JsonLinesWriter { w, options }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+
+    pub fn write(&mut self, sounding: &Sounding) -> Result<(), Box<dyn Error>> {
+        write_json_line(&mut self.w, sounding, &self.options)?;
+        self.w.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{read_bufr_message, write_json};
+
+    #[test]
+    fn test_json_lines() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let sounding = &read_bufr_message(&file[start..]).unwrap().soundings()[0];
+
+        let mut writer = JsonLinesWriter::new(vec![], ExportOptions::default());
+        writer.write(sounding).unwrap();
+        writer.write(sounding).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"station\": \"MSO1\", "));
+        assert!(lines[0].ends_with("]}"));
+
+        // The same object as `write_json`, without the layout.
+        let mut pretty = vec![];
+        write_json(&mut pretty, sounding, &ExportOptions::default()).unwrap();
+        let squash = |text: &str| text.split_whitespace().collect::<String>();
+        assert_eq!(
+            squash(lines[0]),
+            squash(&String::from_utf8(pretty).unwrap())
+        );
+    }
+}
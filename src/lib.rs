@@ -1,7 +1,8 @@
 use std::{
     error::Error,
     fmt::Display,
-    io::{Read, Seek},
+    io::{Cursor, Read, Seek},
+    time::Instant,
 };
 
 mod section0;
@@ -14,19 +15,242 @@ mod section2;
 use section2::Section2;
 
 mod section3;
+pub use section3::Descriptor;
 use section3::Section3;
 
 mod section4;
 use section4::Section4;
+pub use section4::{DataNode, Decimal, Value};
 
 mod section5;
 use section5::Section5;
 
 mod bit_buffer;
+mod bit_writer;
+mod compressed;
+
+mod sounding;
+pub use sounding::{Level, Phase, Sounding, Station, Timestamp};
+
+mod quality;
+
+mod summary;
+
+mod report_type;
+pub use report_type::ReportType;
+
+mod data_category;
+pub use data_category::DataCategory;
+
+mod humidity;
+pub use humidity::HumidityCorrection;
+
+mod heights;
+pub use heights::{HeightDiscrepancy, HeightSource};
+
+mod crex;
+pub use crex::{read_crex_message, CrexMessage};
+
+mod tac;
+pub use tac::parse_temp;
+
+mod compare;
+pub use compare::{compare_tac_bufr, ComparisonReport, LevelDifference};
+
+mod diff;
+pub use diff::{DiffTolerances, FieldDifference, LevelDiff, MetadataDifference, SoundingDiff};
+
+mod sql_script;
+pub use sql_script::{SqlScriptWriter, WriteMode};
+
+mod jsonl;
+pub use jsonl::JsonLinesWriter;
+
+mod index;
+pub use index::{ArchiveIndex, IndexEntry};
+
+mod fingerprint;
+pub use fingerprint::{content_hash, message_fingerprint};
+
+mod dedup;
+pub use dedup::{dedup, Dedup, FileSeenStore, SeenStore};
+
+mod merge;
+pub use merge::merge_soundings;
+
+mod interpolate;
+
+mod thin;
+
+mod smooth;
+pub use smooth::Smoothing;
+
+mod significant;
+pub use significant::SignificantLevelTolerances;
+
+mod derive;
+pub use derive::VaporPressureFormula;
+
+mod hodograph;
+
+mod thermo;
+
+mod indices;
+pub use indices::StabilityIndices;
+
+mod moisture;
+
+mod freezing;
+pub use freezing::Crossing;
+
+mod inversion;
+pub use inversion::Inversion;
+
+mod burst;
+pub use burst::Burst;
+
+mod qc;
+pub use qc::{
+    default_checks, HydrostaticCheck, MonotonicCheck, QcCheck, QcCheckResult, QcIssue, QcReport,
+    RangeCheck, ReportedQualityCheck, SuperadiabaticCheck,
+};
+
+mod assimilation;
+pub use assimilation::Rejection;
+
+mod conformance;
+pub use conformance::{check_conformance, check_sounding, Violation};
+
+mod export;
+pub use export::{
+    parse_columns, write_bufkit, write_csv, write_csv_columns, write_gempak, write_json,
+    write_json_columns, write_json_line, write_raob_csv, Column, ColumnField, ExportOptions,
+    HeightUnit, MissingValue, PressureUnit, SpeedUnit, TemperatureUnit,
+};
+
+mod redact;
+pub use redact::Redaction;
+
+mod wrf;
+pub use wrf::{write_wrf_input_sounding, BoundaryLayerFill, WrfOptions};
+
+mod igra;
+pub use igra::{read_igra, write_igra};
+
+mod tables;
+pub use tables::{
+    lookup_element, lookup_sequence, table_b_entries, table_d_entries, ElementDefinition,
+    ElementOverride, SequenceDefinition, TableOverrides,
+};
+
+mod table_paths;
+pub use table_paths::{table_search_paths, TABLES_ENV_VAR};
+
+mod expansion;
+pub use expansion::{expand_descriptors, ExpansionNode};
+
+mod trace;
+pub use trace::{write_trace_csv, TraceEntry};
+
+mod messages;
+pub use messages::{BufrMessages, Progress};
+
+mod metrics;
+use metrics::Counted;
+pub use metrics::{DecodeMetrics, MetricsSummary};
+
+mod offsets;
+pub use offsets::{
+    decode_at, decode_selected, scan_headers, scan_offsets, MessageOffset, MessageSummary,
+};
+
+mod hexdump;
+pub use hexdump::hex_dump;
+
+mod stats;
+pub use stats::FileStats;
+
+mod monthly;
+pub use monthly::{
+    summarize_archive, summarize_soundings, write_monthly_csv, write_monthly_json, MonthlySummary,
+};
+
+mod inventory;
+pub use inventory::{
+    build_inventory, write_inventory_csv, write_inventory_json, PositionChange, StationRecord,
+};
+
+mod timeseries;
+pub use timeseries::{
+    extract_time_series, time_series_point, write_time_series_csv, ProfileElement, TimeSeriesPoint,
+};
+
+mod inflate;
+
+mod corpus;
+pub use corpus::{Corpus, CorpusSoundings, Provenance};
+
+mod convert;
+pub use convert::{convert_files, glob, ConvertFormat, ConvertSummary, GlobMatch};
+
+mod sbn;
+pub use sbn::{Product, Products, WmoHeading};
+
+#[cfg(feature = "fetch")]
+mod fetch;
+#[cfg(feature = "fetch")]
+pub use fetch::{fetch_soundings, fetch_url, sounding_url};
+
+#[cfg(feature = "ingest")]
+mod ingest;
+#[cfg(feature = "ingest")]
+pub use ingest::Ingest;
+
+mod cache;
+pub use cache::SoundingCache;
+
+mod encode;
+pub use encode::{DataDescription, MessageHeader, Section3Builder};
+
+mod template;
+pub use template::{SoundingEncoder, Template};
+
+#[cfg(feature = "testdata")]
+mod testdata;
+#[cfg(feature = "testdata")]
+pub use testdata::{synthetic_levels, SyntheticTemp};
+
+mod extract;
+pub use extract::extract_subset;
+
+mod builder;
+use builder::DecodeOptions;
+pub use builder::{
+    BudgetExceeded, Cancelled, DecoderBuilder, MessageDecoder, UnsupportedMasterTable,
+};
+
+mod unknown;
+pub use unknown::{UnknownDescriptorAction, UnknownDescriptorHandler};
+
+mod provider;
+pub use provider::{BuiltinTables, TableProvider, TableVersion};
 
 mod table_b;
 mod table_d;
 
+// Decoded output is plain owned data, so it can be decoded on one thread and used on another.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<BufrMessage>();
+    assert_send_sync::<CrexMessage>();
+    assert_send_sync::<Sounding>();
+    assert_send_sync::<DataNode>();
+    assert_send_sync::<TraceEntry>();
+    assert_send_sync::<QcReport>();
+    assert_send_sync::<MessageDecoder>();
+    assert_send_sync::<SoundingCache>();
+};
+
 pub struct BufrMessage {
     section_0: Section0,
     section_1: Section1,
@@ -34,17 +258,185 @@ pub struct BufrMessage {
     section_3: Section3,
     section_4: Section4,
     section_5: Section5,
+    correct_humidity: bool,
+    metrics: Option<DecodeMetrics>,
 }
 
-pub fn read_bufr_message(mut f: impl Read) -> Result<BufrMessage, Box<dyn Error>> {
+impl BufrMessage {
+    /// Decode the first BUFR message in `bytes`, skipping anything before `BUFR` such as a WMO
+    /// heading.
+    pub fn from_bytes(bytes: &[u8]) -> Result<BufrMessage, Box<dyn Error>> {
+        let start = bytes
+            .windows(4)
+            .position(|w| w == b"BUFR")
+            .ok_or("Not a bufr file")?;
+        read_bufr_message(&bytes[start..])
+    }
+
+    /// The BUFR master table from Section 1, 0 for meteorology. See
+    /// `DecoderBuilder::master_table` for the others.
+    pub fn master_table(&self) -> u8 {
+        self.section_1.master_table()
+    }
+
+    /// The decoded data of each subset, e.g. for messages with no soundings.
+    pub fn subsets(&self) -> &[Vec<DataNode>] {
+        self.section_4.subsets()
+    }
+
+    /// The Table A data category from Section 1.
+    pub fn category(&self) -> DataCategory {
+        DataCategory::from(self.section_1.data_category())
+    }
+
+    /// TEMP, TEMP SHIP, PILOT, etc., from the data sub-category or the template. Each sounding
+    /// has its own, which can also tell ships by their station, see `Sounding::report_type`.
+    pub fn report_type(&self) -> Option<ReportType> {
+        ReportType::classify(
+            self.section_1.data_category(),
+            self.section_1.data_subcategory(),
+            self.section_3.descriptors(),
+            None,
+        )
+    }
+
+    /// Messages that define tables (e.g. NCEP DX dictionary messages) are read, but their data
+    /// section is not decoded.
+    pub fn is_table_message(&self) -> bool {
+        self.section_1.is_table_message()
+    }
+
+    /// The position of each element decoded, if the message was read by a decoder built with
+    /// `DecoderBuilder::trace`.
+    pub fn trace(&self) -> &[TraceEntry] {
+        self.section_4.trace()
+    }
+
+    /// How decoding went, if the message was read by a decoder built with
+    /// `DecoderBuilder::metrics`.
+    pub fn metrics(&self) -> Option<&DecodeMetrics> {
+        self.metrics.as_ref()
+    }
+
+    /// The Section 3 descriptors, see `expand_descriptors`.
+    pub fn descriptors(&self) -> &[Descriptor] {
+        self.section_3.descriptors()
+    }
+
+    /// The typical time from Section 1, for soundings this is the nominal (synoptic) time.
+    pub fn nominal_time(&self) -> Timestamp {
+        self.section_1.time()
+    }
+
+    /// Extract a sounding from each subset that contains a vertical profile.
+    pub fn soundings(&self) -> Vec<Sounding> {
+        (0..self.section_4.subsets().len())
+            .filter_map(|i| self.subset_sounding(i))
+            .collect()
+    }
+
+    /// Extract the sounding from one subset, if it contains a vertical profile.
+    pub(crate) fn subset_sounding(&self, subset: usize) -> Option<Sounding> {
+        // The sounding descriptors mean something else in other master tables.
+        if self.master_table() != 0 {
+            return None;
+        }
+        let phase = Phase::from_descriptors(self.section_3.descriptors());
+
+        let mut sounding = Sounding::from_subset(self.section_4.subsets().get(subset)?, phase)?;
+        sounding.set_update_number(self.section_1.update_number());
+        if self.correct_humidity {
+            sounding.correct_humidity();
+        }
+        sounding.set_report_type(ReportType::classify(
+            self.section_1.data_category(),
+            self.section_1.data_subcategory(),
+            self.section_3.descriptors(),
+            Some(sounding.station()),
+        ));
+
+        Some(sounding)
+    }
+}
+
+/// Same as `BufrMessage::from_bytes`.
+impl TryFrom<&[u8]> for BufrMessage {
+    type Error = Box<dyn Error>;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        BufrMessage::from_bytes(bytes)
+    }
+}
+
+/// Iterate over the BUFR messages in `bytes` with the default decoder, see
+/// `MessageDecoder::decode_slice`.
+pub fn decode_slice(bytes: &[u8]) -> BufrMessages<'_, Cursor<&[u8]>> {
+    MessageDecoder::default().decode_slice(bytes)
+}
+
+pub fn read_bufr_message(f: impl Read) -> Result<BufrMessage, Box<dyn Error>> {
+    read_bufr_message_with(f, &DecodeOptions::default())
+}
+
+pub(crate) fn read_bufr_message_with(
+    f: impl Read,
+    options: &DecodeOptions,
+) -> Result<BufrMessage, Box<dyn Error>> {
+    let started = options.metrics.then(Instant::now);
+    let mut f = Counted::new(f);
+
     // Read section 0
     let section_0 = section0::read_section_0(&mut f)?;
     let section_1 = section1::read_section_1(&mut f)?;
     let section_2 = section2::read_section_2(&mut f, section_1.section_2_exists())?;
     let section_3 = section3::read_section_3(&mut f)?;
-    let section_4 = section4::read_section_4(&mut f, &section_3)?;
+    let category = DataCategory::from(section_1.data_category());
+    let wanted = builder::wants(options.categories, category);
+
+    // Other master tables are decoded only with their own tables, or a table provider.
+    let master_table = section_1.master_table();
+    let mut options = *options;
+    if master_table != 0 {
+        options.overrides = options
+            .master_tables
+            .and_then(|tables| tables.get(&master_table));
+        if options.overrides.is_none() && options.provider.is_none() {
+            section4::skip_section_4(&mut f)?;
+            section5::read_section_5(&mut f)?;
+            return Err(UnsupportedMasterTable {
+                master_table,
+                category,
+                offset: None,
+            }
+            .into());
+        }
+    }
+
+    let resolved;
+    if let Some(tables) = options.provider.filter(|_| wanted) {
+        let version = TableVersion::of(&section_1);
+        resolved = provider::resolve(tables, &version, section_3.descriptors(), options.overrides)?;
+        options.overrides = Some(&resolved);
+    }
+
+    let mut section_4 = if section_1.is_table_message() || !wanted {
+        section4::skip_section_4(&mut f)?
+    } else {
+        section4::read_section_4(&mut f, &section_3, &options)?
+    };
     let section_5 = section5::read_section_5(&mut f)?;
 
+    let metrics = started.map(|started| {
+        let warnings = section_4.take_warnings();
+        DecodeMetrics::new(
+            started.elapsed(),
+            f.bytes,
+            section_0.message_size() as u64,
+            section_4.subsets(),
+            warnings,
+        )
+    });
+
     Ok(BufrMessage {
         section_0,
         section_1,
@@ -52,39 +444,62 @@ pub fn read_bufr_message(mut f: impl Read) -> Result<BufrMessage, Box<dyn Error>
         section_3,
         section_4,
         section_5,
+        correct_humidity: options.humidity,
+        metrics,
     })
 }
 
-pub fn scan_to_bufr_start(mut f: impl Seek + Read) -> Result<(), Box<dyn Error>> {
-    let mut position: usize = 0;
+/// Read the raw bytes of a message, from `BUFR` through `7777`, without decoding it, e.g. for
+/// `hex_dump`.
+pub fn read_bufr_bytes(mut f: impl Read) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut message = vec![0; 8];
+    f.read_exact(&mut message)?;
+    if !message.starts_with(b"BUFR") {
+        return Err("Not a BUFR message".into());
+    }
+
+    let length = read_3_octet_usize(&message[4..7])?;
+    if length < 8 {
+        return Err(format!("Invalid BUFR message length: {}", length).into());
+    }
+    f.take(length as u64 - 8).read_to_end(&mut message)?;
+
+    Ok(message)
+}
+
+pub fn scan_to_bufr_start(f: impl Seek + Read) -> Result<(), Box<dyn Error>> {
+    if next_bufr_start(f)? {
+        Ok(())
+    } else {
+        Err(Box::new(std::io::Error::other("Not a bufr file")))
+    }
+}
+
+/// Like `scan_to_bufr_start`, but `Ok(false)` at the end of the input, so a loop over the
+/// messages in a file can tell the end apart from a read error.
+pub fn next_bufr_start(mut f: impl Seek + Read) -> Result<bool, Box<dyn Error>> {
+    let mut position = f.stream_position()?;
 
     let mut buffer: [u8; 24] = [0; 24];
     loop {
         let num_read = f.read(&mut buffer)?;
 
         if num_read == 0 {
-            return Err(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Not a bufr file",
-            )));
+            return Ok(false);
         }
 
         let mut scan_start = 0;
-        if buffer[0] == 'B' as u8
-            && buffer[1] == 'U' as u8
-            && buffer[2] == 'F' as u8
-            && buffer[3] == 'R' as u8
-        {
-            f.seek(std::io::SeekFrom::Start(position as u64))?;
-            return Ok(());
-        } else if buffer[0] == 'B' as u8 {
+        if buffer.starts_with(b"BUFR") {
+            f.seek(std::io::SeekFrom::Start(position))?;
+            return Ok(true);
+        } else if buffer[0] == b'B' {
             scan_start = 1;
             position += 1;
         }
 
-        for i in scan_start..num_read {
-            if buffer[i] == 'B' as u8 {
-                f.seek(std::io::SeekFrom::Start(position as u64))?;
+        for &byte in &buffer[scan_start..num_read] {
+            if byte == b'B' {
+                f.seek(std::io::SeekFrom::Start(position))?;
                 break;
             }
             position += 1;
@@ -149,3 +564,199 @@ fn read_3_octet_usize(mut f: impl Read) -> Result<usize, Box<dyn Error>> {
 
     Ok(message_size)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_skip_table_message() {
+        #[rustfmt::skip]
+        let section_1: [u8; 22] = [
+            0, 0, 22,       // section length
+            0,              // master table
+            0, 7, 0, 0,     // originating center / subcenter
+            0, 0,           // update number, no section 2
+            11, 0, 0,       // data category 11, BUFR tables
+            26, 0,          // table versions
+            7, 225, 8, 31, 12, 0, 0,
+        ];
+        // Local sequences like 3-60-001 aren't in Table D, so decoding would fail.
+        let section_3: [u8; 9] = [0, 0, 9, 0, 0, 1, 0, 0xFC, 0x01];
+        let section_4: [u8; 8] = [0, 0, 8, 0, 1, 2, 3, 4];
+
+        let mut message: Vec<u8> = b"BUFR".to_vec();
+        let total = 8 + section_1.len() + section_3.len() + section_4.len() + 4;
+        message.extend_from_slice(&total.to_be_bytes()[5..8]);
+        message.push(4);
+        message.extend_from_slice(&section_1);
+        message.extend_from_slice(&section_3);
+        message.extend_from_slice(&section_4);
+        message.extend_from_slice(b"7777");
+
+        let msg = read_bufr_message(&mut Cursor::new(message)).unwrap();
+        assert!(msg.is_table_message());
+        assert!(msg.soundings().is_empty());
+    }
+
+    #[test]
+    fn test_decode_slice() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let mut bytes = b"IUSN01 KWBC 311500\r\r\n".to_vec();
+        bytes.extend_from_slice(&file);
+
+        let msg = BufrMessage::from_bytes(&bytes).unwrap();
+        assert_eq!(msg.soundings().len(), 1);
+        let msg = BufrMessage::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(msg.soundings()[0].station().elevation, Some(1225.0));
+
+        bytes.extend_from_slice(&file);
+        let messages: Vec<_> = decode_slice(&bytes).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(Result::is_ok));
+
+        assert!(BufrMessage::from_bytes(b"7777").is_err());
+    }
+
+    #[test]
+    fn test_next_bufr_start() {
+        let message = small_message();
+        let mut bytes = b"IUSN01 KWBC 311500\r\r\n".to_vec();
+        bytes.extend_from_slice(&message);
+        bytes.extend_from_slice(b"\r\r\n\x03");
+
+        let mut f = Cursor::new(&bytes);
+        assert!(next_bufr_start(&mut f).unwrap());
+        assert_eq!(read_bufr_bytes(&mut f).unwrap(), message);
+        assert!(!next_bufr_start(&mut f).unwrap());
+        assert!(scan_to_bufr_start(&mut f).is_err());
+
+        // A read error isn't the end of the input.
+        struct Unreadable;
+        impl Read for Unreadable {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("unreadable"))
+            }
+        }
+        impl Seek for Unreadable {
+            fn seek(&mut self, _: std::io::SeekFrom) -> std::io::Result<u64> {
+                Ok(0)
+            }
+        }
+        assert!(next_bufr_start(Unreadable).is_err());
+    }
+
+    fn small_message() -> Vec<u8> {
+        let sounding = MessageDecoder::default()
+            .messages(std::fs::File::open("test-data/2017083115.bufr").unwrap())
+            .next()
+            .unwrap()
+            .unwrap()
+            .soundings()
+            .remove(0);
+        SoundingEncoder::new(Template::Temp)
+            .sounding(&sounding)
+            .levels(sounding.levels()[..20].to_vec())
+            .encode()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_corrupt_messages_are_errors() {
+        let message = small_message();
+        assert_eq!(
+            read_bufr_message(message.as_slice())
+                .unwrap()
+                .soundings()
+                .len(),
+            1
+        );
+
+        // A fixed xorshift generator, so any failure can be reproduced.
+        let mut state: u64 = 0x2017_0831_1500_0001;
+        let mut random = |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n as u64) as usize
+        };
+
+        // Everything must come back as a value or an error, never as a panic.
+        let decode = |bytes: &[u8]| {
+            if let Ok(bufr) = read_bufr_message(bytes) {
+                bufr.soundings();
+            }
+            MessageDecoder::default()
+                .messages(Cursor::new(bytes))
+                .for_each(drop);
+        };
+
+        for len in 0..message.len() {
+            assert!(read_bufr_message(&message[..len]).is_err());
+            decode(&message[..len]);
+        }
+        for _ in 0..2000 {
+            let mut corrupt = message.clone();
+            for _ in 0..1 + random(4) {
+                let i = random(corrupt.len());
+                corrupt[i] = random(256) as u8;
+            }
+            decode(&corrupt);
+        }
+
+        assert!(read_bufr_message(&b"BUFR\0\0\x08\x047777"[..]).is_err());
+    }
+
+    #[test]
+    fn test_missing_and_invalid_text() {
+        let message = small_message();
+        let bufr = DecoderBuilder::new()
+            .trace(true)
+            .build()
+            .read_bufr_message(message.as_slice())
+            .unwrap();
+        let text = bufr
+            .trace()
+            .iter()
+            .find(|entry| matches!(entry.value, Value::Text(_)))
+            .unwrap();
+
+        // This message has no Section 2.
+        let length = |at: usize| read_3_octet_usize(&message[at..at + 3]).unwrap();
+        let section_3 = 8 + length(8);
+        let start = 8 * (section_3 + length(section_3)) + text.bit_offset;
+        let set_text = |bytes: &[u8]| {
+            let mut patched = message.clone();
+            for (bit, byte) in (start..).step_by(8).zip(bytes) {
+                for i in 0..8 {
+                    let mask = 0x80 >> ((bit + i) % 8);
+                    if byte & (0x80 >> i) != 0 {
+                        patched[(bit + i) / 8] |= mask;
+                    } else {
+                        patched[(bit + i) / 8] &= !mask;
+                    }
+                }
+            }
+            let bufr = read_bufr_message(patched.as_slice()).unwrap();
+            let mut elements = vec![];
+            sounding::collect_elements(&bufr.subsets()[0], &mut elements, false);
+            elements
+                .iter()
+                .find(|(d, _)| *d == text.descriptor)
+                .map(|(_, v)| (*v).clone())
+                .unwrap()
+        };
+
+        // All ones is a missing value, not a broken message.
+        let chars = text.width_bits / 8;
+        assert_eq!(set_text(&vec![0xff; chars]), Value::Missing);
+
+        let mut invalid = vec![b'A'; chars];
+        invalid[0] = 0xff;
+        let Value::Text(decoded) = set_text(&invalid) else {
+            panic!("not text");
+        };
+        assert!(decoded.starts_with('\u{fffd}') && decoded.ends_with('A'));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::sounding::{Level, Phase, Sounding, Station, Timestamp};
+use std::cmp::Ordering;
+
+/// Combine soundings for the same launch that were split across messages, e.g. separate
+/// standard level and significant level reports.
+///
+/// Soundings are grouped by station, launch time, and phase, so the ascent and descent of a
+/// launch stay separate soundings. Soundings missing a station identifier or launch time can't
+/// be matched and are passed through unchanged. Groups are returned in the order they first
+/// appear.
+///
+/// Within a group the soundings are ranked by update number, highest first, and then by number
+/// of levels. Levels at the same pressure are combined, keeping the value from the higher ranked
+/// sounding when both have one and combining the significance flags.
+pub fn merge_soundings(soundings: impl IntoIterator<Item = Sounding>) -> Vec<Sounding> {
+    let mut groups: Vec<Vec<Sounding>> = vec![];
+    for sounding in soundings {
+        let key = merge_key(&sounding);
+        match groups
+            .iter_mut()
+            .find(|group| key.is_some() && merge_key(&group[0]) == key)
+        {
+            Some(group) => group.push(sounding),
+            None => groups.push(vec![sounding]),
+        }
+    }
+
+    groups.into_iter().map(merge_group).collect()
+}
+
+fn merge_key(sounding: &Sounding) -> Option<(String, Timestamp, Phase)> {
+    Some((
+        sounding.station().identifier()?,
+        sounding.launch_time()?,
+        sounding.phase(),
+    ))
+}
+
+fn merge_group(mut group: Vec<Sounding>) -> Sounding {
+    if group.len() == 1 {
+        return group.pop().unwrap();
+    }
+
+    // Stable sort, so ties keep their input order.
+    group.sort_by(|a, b| {
+        b.update_number()
+            .cmp(&a.update_number())
+            .then(b.levels().len().cmp(&a.levels().len()))
+    });
+
+    let mut station = Station::default();
+    let mut radiosonde_type = None;
+    let mut levels: Vec<Level> = vec![];
+    for sounding in &group {
+        let other = sounding.station();
+        station.wmo_block = station.wmo_block.or(other.wmo_block);
+        station.wmo_station = station.wmo_station.or(other.wmo_station);
+        if station.call_sign.is_none() {
+            station.call_sign = other.call_sign.clone();
+        }
+        station.latitude = station.latitude.or(other.latitude);
+        station.longitude = station.longitude.or(other.longitude);
+        station.elevation = station.elevation.or(other.elevation);
+        radiosonde_type = radiosonde_type.or(sounding.radiosonde_type());
+
+        for lvl in sounding.levels() {
+            match levels
+                .iter_mut()
+                .find(|l| lvl.pressure.is_some() && l.pressure == lvl.pressure)
+            {
+                Some(existing) => fill_level(existing, lvl),
+                None => levels.push(*lvl),
+            }
+        }
+    }
+
+    let phase = group[0].phase();
+
+    // Ascents run from high to low pressure and descents the other way. Levels without a
+    // pressure go last.
+    levels.sort_by(|a, b| match (a.pressure, b.pressure) {
+        (Some(a), Some(b)) => match phase {
+            Phase::Ascent => b.total_cmp(&a),
+            Phase::Descent => a.total_cmp(&b),
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+
+    Sounding {
+        station,
+        radiosonde_type,
+        ..group[0].with_levels(levels)
+    }
+}
+
+/// Fill in the values missing from `level` with those from `other`.
+fn fill_level(level: &mut Level, other: &Level) {
+    level.time_offset = level.time_offset.or(other.time_offset);
+    level.significance = match (level.significance, other.significance) {
+        (Some(a), Some(b)) => Some(a | b),
+        (a, b) => a.or(b),
+    };
+    if level.height.is_none() {
+        level.height = other.height;
+        level.derived |= other.derived & Level::DERIVED_HEIGHT;
+    }
+    level.gnss_height = level.gnss_height.or(other.gnss_height);
+    level.temperature = level.temperature.or(other.temperature);
+    if level.dewpoint.is_none() {
+        level.dewpoint = other.dewpoint;
+        level.derived |= other.derived & Level::DERIVED_DEWPOINT;
+    }
+    level.relative_humidity = level.relative_humidity.or(other.relative_humidity);
+    level.wind_direction = level.wind_direction.or(other.wind_direction);
+    level.wind_speed = level.wind_speed.or(other.wind_speed);
+    level.lat_displacement = level.lat_displacement.or(other.lat_displacement);
+    level.lon_displacement = level.lon_displacement.or(other.lon_displacement);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge_standard_and_significant() {
+        let station = Station {
+            wmo_block: Some(72),
+            wmo_station: Some(776),
+            ..Station::default()
+        };
+        let time = Some(Timestamp {
+            year: 2017,
+            month: 8,
+            day: 31,
+            hour: 11,
+            minute: 0,
+            second: 0,
+        });
+        let level = |hpa: f64, t: Option<f64>, sig: u32| Level {
+            significance: Some(sig),
+            pressure: Some(hpa * 100.0),
+            temperature: t,
+            ..Level::default()
+        };
+
+        let standard = Sounding::new(
+            Phase::Ascent,
+            station.clone(),
+            time,
+            None,
+            vec![
+                level(850.0, Some(280.0), Level::STANDARD),
+                level(500.0, None, Level::STANDARD),
+            ],
+        );
+        let significant = Sounding::new(
+            Phase::Ascent,
+            station,
+            time,
+            Some(17),
+            vec![
+                level(700.0, Some(270.0), Level::SIG_TEMPERATURE),
+                level(500.0, Some(255.0), Level::SIG_TEMPERATURE),
+            ],
+        );
+        let other = Sounding::new(Phase::Descent, Station::default(), None, None, vec![]);
+
+        let merged = merge_soundings(vec![standard, other, significant]);
+        assert_eq!(merged.len(), 2);
+
+        let sounding = &merged[0];
+        assert_eq!(sounding.radiosonde_type(), Some(17));
+
+        let levels = sounding.levels();
+        let pressures: Vec<_> = levels.iter().filter_map(|l| l.pressure).collect();
+        assert_eq!(pressures, vec![85000.0, 70000.0, 50000.0]);
+        assert_eq!(levels[2].temperature, Some(255.0));
+        assert!(levels[2].has_significance(Level::STANDARD));
+        assert!(levels[2].has_significance(Level::SIG_TEMPERATURE));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{
+    builder::with_offset, metrics::MetricsSummary, scan_to_bufr_start, sounding::Timestamp,
+    BufrMessage, Cancelled, MessageDecoder,
+};
+use std::{
+    error::Error,
+    io::{Read, Seek, SeekFrom},
+};
+
+/// How far a `BufrMessages` iterator has got, passed to its progress callback.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progress {
+    /// Bytes of the input read so far.
+    pub bytes_processed: u64,
+    /// The size of the input, if it could be found when the iterator was made.
+    pub total_bytes: Option<u64>,
+    /// Messages decoded so far, including any that failed to decode.
+    pub messages_decoded: usize,
+    /// The Section 1 time of the last message decoded.
+    pub time: Option<Timestamp>,
+    /// The station of the first sounding in the last message decoded.
+    pub station: Option<String>,
+}
+
+/// Iterates over the BUFR messages in a file or stream, see `MessageDecoder::messages`.
+///
+/// A message that fails to decode is returned as an error and the iterator moves on to the next
+/// one. Iteration stops when there are no more messages, or after returning a `Cancelled` error
+/// if the decoder's cancel flag is set.
+///
+/// To continue an interrupted job, save `position` after each message and pass it to
+/// `resume_from` on a new iterator.
+pub struct BufrMessages<'a, R> {
+    decoder: MessageDecoder,
+    reader: R,
+    total_bytes: Option<u64>,
+    offset: Option<u64>,
+    position: u64,
+    messages_decoded: usize,
+    progress: Option<ProgressCallback<'a>>,
+    cancelled: bool,
+    metrics: MetricsSummary,
+}
+
+struct ProgressCallback<'a> {
+    every: usize,
+    callback: Box<dyn FnMut(&Progress) + 'a>,
+}
+
+impl<'a, R: Read + Seek> BufrMessages<'a, R> {
+    pub(crate) fn new(decoder: MessageDecoder, mut reader: R) -> Self {
+        let total_bytes = stream_len(&mut reader).ok();
+        let position = reader.stream_position().unwrap_or_default();
+
+        BufrMessages {
+            decoder,
+            reader,
+            total_bytes,
+            offset: None,
+            position,
+            messages_decoded: 0,
+            progress: None,
+            cancelled: false,
+            metrics: MetricsSummary::default(),
+        }
+    }
+
+    /// Call `callback` after every `every` messages, and after the last one.
+    pub fn on_progress(mut self, every: usize, callback: impl FnMut(&Progress) + 'a) -> Self {
+        self.progress = Some(ProgressCallback {
+            every: every.max(1),
+            callback: Box::new(callback),
+        });
+        self
+    }
+
+    /// Start reading at `offset` bytes into the input, e.g. a `position` saved by an earlier
+    /// run.
+    pub fn resume_from(mut self, offset: u64) -> Result<Self, Box<dyn Error>> {
+        self.position = self.reader.seek(SeekFrom::Start(offset))?;
+        Ok(self)
+    }
+
+    /// The byte offset in the input of the start of the last message returned, `None` before
+    /// the first.
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// The byte offset in the input where the last message returned ended, or where iteration
+    /// started. Resuming from here continues with the next message.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// The totals of the messages returned so far, if the decoder was built with
+    /// `DecoderBuilder::metrics`. Each message has its own, see `BufrMessage::metrics`.
+    pub fn metrics(&self) -> &MetricsSummary {
+        &self.metrics
+    }
+
+    fn report(&mut self, message: Option<&BufrMessage>) {
+        let Some(progress) = &mut self.progress else {
+            return;
+        };
+
+        (progress.callback)(&Progress {
+            bytes_processed: self.reader.stream_position().unwrap_or_default(),
+            total_bytes: self.total_bytes,
+            messages_decoded: self.messages_decoded,
+            time: message.map(BufrMessage::nominal_time),
+            station: message.and_then(|message| {
+                message
+                    .soundings()
+                    .first()
+                    .and_then(|sounding| sounding.station().identifier())
+            }),
+        });
+    }
+}
+
+impl<R: Read + Seek> Iterator for BufrMessages<'_, R> {
+    type Item = Result<BufrMessage, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cancelled {
+            return None;
+        }
+        if self.decoder.is_cancelled() {
+            self.cancelled = true;
+            return Some(Err(Cancelled.into()));
+        }
+
+        let message = loop {
+            if scan_to_bufr_start(&mut self.reader).is_err() {
+                // Nothing more to read, let the callback see the final count once.
+                if self.messages_decoded > 0 {
+                    self.report(None);
+                    self.progress = None;
+                }
+                return None;
+            }
+
+            self.offset = self.reader.stream_position().ok();
+            let message = self
+                .decoder
+                .read_bufr_message(&mut self.reader)
+                .map_err(|err| with_offset(err, self.offset));
+            self.position = self.reader.stream_position().unwrap_or(self.position);
+
+            // Messages of categories that weren't asked for are skipped without decoding.
+            match &message {
+                Ok(bufr) if !self.decoder.wants(bufr.category()) => continue,
+                _ => break message,
+            }
+        };
+        if message.as_ref().is_err_and(|err| err.is::<Cancelled>()) {
+            self.cancelled = true;
+            return Some(message);
+        }
+        self.messages_decoded += 1;
+        if self.decoder.records_metrics() {
+            match message.as_ref().map(BufrMessage::metrics) {
+                Ok(Some(metrics)) => self.metrics.add(metrics),
+                Ok(None) => {}
+                Err(_) => self.metrics.failed += 1,
+            }
+        }
+
+        if self
+            .progress
+            .as_ref()
+            .is_some_and(|progress| self.messages_decoded.is_multiple_of(progress.every))
+        {
+            self.report(message.as_ref().ok());
+        }
+
+        Some(message)
+    }
+}
+
+fn stream_len(reader: &mut impl Seek) -> Result<u64, Box<dyn Error>> {
+    let position = reader.stream_position()?;
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(position))?;
+    Ok(len)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Cancelled, DataCategory, DecoderBuilder};
+    use std::{
+        fs::File,
+        io::{BufReader, Cursor},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    #[test]
+    fn test_messages_progress() {
+        let f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+
+        let mut reports = vec![];
+        let decoder = DecoderBuilder::new().build();
+        let messages: Vec<_> = decoder
+            .messages(f)
+            .on_progress(1, |progress| reports.push(progress.clone()))
+            .collect();
+
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].is_ok());
+
+        // One report for the message and one at the end.
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].messages_decoded, 1);
+        assert_eq!(reports[0].station.as_deref(), Some("MSO1"));
+        assert_eq!(reports[0].time.unwrap().hour, 18);
+        assert_eq!(reports[1].time, None);
+        assert_eq!(reports[1].bytes_processed, reports[1].total_bytes.unwrap());
+    }
+
+    #[test]
+    fn test_messages_resume() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap() as u64;
+        let mut stream = file.clone();
+        stream.extend(b"between");
+        stream.extend(&file);
+
+        let decoder = DecoderBuilder::new().build();
+        let mut messages = decoder.messages(Cursor::new(&stream));
+        assert_eq!((messages.offset(), messages.position()), (None, 0));
+        assert!(messages.next().unwrap().is_ok());
+        assert_eq!(messages.offset(), Some(start));
+        let stopped = messages.position();
+        assert!(stream[..stopped as usize].ends_with(b"7777"));
+
+        // Pick up where the first run stopped.
+        let mut resumed = decoder
+            .messages(Cursor::new(&stream))
+            .resume_from(stopped)
+            .unwrap();
+        assert!(resumed.next().unwrap().is_ok());
+        let second = file.len() as u64 + b"between".len() as u64 + start;
+        assert_eq!(resumed.offset(), Some(second));
+        assert!(resumed.next().is_none());
+        assert_eq!(resumed.offset(), Some(second));
+    }
+
+    #[test]
+    fn test_messages_cancelled() {
+        let f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let decoder = DecoderBuilder::new().cancel_flag(cancel.clone()).build();
+        let mut messages = decoder.messages(f);
+
+        cancel.store(true, Ordering::Relaxed);
+        let err = messages.next().unwrap().err().unwrap();
+        assert!(err.is::<Cancelled>());
+        assert!(messages.next().is_none());
+
+        // Also stopped inside a message.
+        let mut f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+        crate::scan_to_bufr_start(&mut f).unwrap();
+        let err = decoder.read_bufr_message(&mut f).err().unwrap();
+        assert!(err.is::<Cancelled>());
+    }
+
+    #[test]
+    fn test_messages_categories() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let message = &file[start..];
+
+        // The same message again as surface land data, octet 11 of an edition 4 Section 1.
+        let mut surface = message.to_vec();
+        assert_eq!(surface[8 + 10], 2);
+        surface[8 + 10] = 0;
+        let mut stream = surface.clone();
+        stream.extend(message);
+        stream.extend(&surface);
+
+        let decoder = DecoderBuilder::new()
+            .categories([DataCategory::VerticalSoundings])
+            .build();
+        let messages: Vec<_> = decoder
+            .messages(Cursor::new(&stream))
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].category(), DataCategory::VerticalSoundings);
+        assert_eq!(messages[0].soundings().len(), 1);
+
+        let skipped = decoder.read_bufr_message(surface.as_slice()).unwrap();
+        assert_eq!(skipped.category(), DataCategory::SurfaceLand);
+        assert!(skipped.soundings().is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::section4::{DataNode, Value};
+use std::{io::Read, time::Duration};
+
+/// How decoding one message went, recorded with `DecoderBuilder::metrics`, see
+/// `BufrMessage::metrics`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DecodeMetrics {
+    /// Wall time from reading `BUFR` through `7777`.
+    pub decode_time: Duration,
+    /// Octets read for the message.
+    pub bytes_read: u64,
+    /// The message length from Section 0, which should equal `bytes_read`.
+    pub declared_length: u64,
+    /// Values decoded in all the subsets, including missing ones.
+    pub elements: usize,
+    pub missing: usize,
+    /// Things decoding got past that may mean a bad message or out of date tables, e.g.
+    /// descriptors handled by the `UnknownDescriptorHandler`.
+    pub warnings: Vec<String>,
+}
+
+impl DecodeMetrics {
+    pub(crate) fn new(
+        decode_time: Duration,
+        bytes_read: u64,
+        declared_length: u64,
+        subsets: &[Vec<DataNode>],
+        mut warnings: Vec<String>,
+    ) -> Self {
+        if bytes_read != declared_length {
+            warnings.push(format!(
+                "Read {} octets of a message {} octets long",
+                bytes_read, declared_length
+            ));
+        }
+
+        let mut metrics = DecodeMetrics {
+            decode_time,
+            bytes_read,
+            declared_length,
+            warnings,
+            ..DecodeMetrics::default()
+        };
+        for subset in subsets {
+            metrics.count(subset);
+        }
+
+        metrics
+    }
+
+    fn count(&mut self, nodes: &[DataNode]) {
+        for node in nodes {
+            match node {
+                DataNode::Element { value, .. } => {
+                    self.elements += 1;
+                    if matches!(value, Value::Missing) {
+                        self.missing += 1;
+                    }
+                }
+                DataNode::Sequence { children, .. } => self.count(children),
+                DataNode::Replication { repetitions, .. } => {
+                    repetitions.iter().for_each(|rep| self.count(rep))
+                }
+            }
+        }
+    }
+}
+
+/// Totals of the `DecodeMetrics` of many messages, see `BufrMessages::metrics` and
+/// `CorpusSoundings::metrics`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsSummary {
+    /// Messages decoded, not counting failures.
+    pub messages: usize,
+    /// Messages that failed to decode.
+    pub failed: usize,
+    pub decode_time: Duration,
+    pub bytes_read: u64,
+    pub elements: usize,
+    pub missing: usize,
+    pub warnings: usize,
+    /// Messages where fewer or more octets were read than Section 0 declared.
+    pub length_mismatches: usize,
+}
+
+impl MetricsSummary {
+    pub fn add(&mut self, metrics: &DecodeMetrics) {
+        self.messages += 1;
+        self.decode_time += metrics.decode_time;
+        self.bytes_read += metrics.bytes_read;
+        self.elements += metrics.elements;
+        self.missing += metrics.missing;
+        self.warnings += metrics.warnings.len();
+        if metrics.bytes_read != metrics.declared_length {
+            self.length_mismatches += 1;
+        }
+    }
+
+    /// The mean decode time of the messages decoded, zero before the first.
+    pub fn mean_decode_time(&self) -> Duration {
+        match u32::try_from(self.messages) {
+            Ok(0) => Duration::ZERO,
+            Ok(messages) => self.decode_time / messages,
+            Err(_) => self.decode_time.div_f64(self.messages as f64),
+        }
+    }
+}
+
+/// Counts the octets read through it.
+pub(crate) struct Counted<R> {
+    inner: R,
+    pub(crate) bytes: u64,
+}
+
+impl<R> Counted<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Counted { inner, bytes: 0 }
+    }
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Corpus, DecoderBuilder};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_decode_metrics() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+
+        let plain = DecoderBuilder::new().build();
+        assert!(plain
+            .read_bufr_message(&file[start..])
+            .unwrap()
+            .metrics()
+            .is_none());
+
+        let decoder = DecoderBuilder::new().metrics(true).build();
+        let bufr = decoder.read_bufr_message(&file[start..]).unwrap();
+        let metrics = bufr.metrics().unwrap();
+        assert_eq!(metrics.bytes_read, (file.len() - start) as u64);
+        assert_eq!(metrics.declared_length, metrics.bytes_read);
+        assert!(metrics.warnings.is_empty());
+        assert!(metrics.elements > 4879 * 10);
+        assert!(metrics.missing > 0 && metrics.missing < metrics.elements);
+
+        // The message, then a broken copy of it.
+        let mut stream = file.clone();
+        stream.extend(&file[..file.len() - 100]);
+        let mut messages = decoder.messages(Cursor::new(&stream));
+        let results: Vec<_> = messages.by_ref().collect();
+        assert!(results[0].is_ok() && results[1].is_err());
+        let totals = messages.metrics();
+        assert_eq!((totals.messages, totals.failed), (1, 1));
+        assert_eq!(totals.elements, metrics.elements);
+        assert_eq!(totals.length_mismatches, 0);
+
+        let dir = std::env::temp_dir().join(format!("sonde-bufr-metrics-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.bufr"), &file).unwrap();
+        std::fs::write(dir.join("b.bufr"), &file).unwrap();
+        let corpus = Corpus::new(&dir).unwrap();
+        let mut soundings = corpus.soundings(&plain);
+        assert_eq!(soundings.by_ref().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let totals = soundings.metrics();
+        assert_eq!(totals.messages, 2);
+        assert_eq!(totals.bytes_read, 2 * metrics.bytes_read);
+        assert!(totals.mean_decode_time() <= totals.decode_time);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{
+    sounding::Sounding,
+    thermo::{mixing_ratio, G},
+};
+
+impl Sounding {
+    /// The largest pressure gap (Pa) between levels with a dewpoint that `precipitable_water`
+    /// will integrate across.
+    pub const DEFAULT_PRECIPITABLE_WATER_GAP: f64 = 10_000.0;
+
+    /// Precipitable water in mm (kg/m²), see `precipitable_water_with_gap`.
+    pub fn precipitable_water(&self) -> Option<f64> {
+        self.precipitable_water_with_gap(Self::DEFAULT_PRECIPITABLE_WATER_GAP)
+    }
+
+    /// Precipitable water in mm (kg/m²), the mixing ratio integrated over pressure from the
+    /// lowest to the highest level with a dewpoint.
+    ///
+    /// Returns `None` if there are fewer than two levels with a dewpoint or if any two
+    /// neighbouring levels with a dewpoint are more than `max_gap` Pa apart, rather than
+    /// underestimating across the missing layer.
+    pub fn precipitable_water_with_gap(&self, max_gap: f64) -> Option<f64> {
+        let mut moist: Vec<(f64, f64)> = self
+            .levels()
+            .iter()
+            .filter_map(|lvl| {
+                let p = lvl.pressure?;
+                Some((p, mixing_ratio(lvl.dewpoint?, p)))
+            })
+            .collect();
+        moist.sort_by(|a, b| b.0.total_cmp(&a.0));
+        moist.dedup_by(|a, b| a.0 == b.0);
+
+        if moist.len() < 2 {
+            return None;
+        }
+
+        let mut pw = 0.0;
+        for pair in moist.windows(2) {
+            let ((p0, w0), (p1, w1)) = (pair[0], pair[1]);
+            if p0 - p1 > max_gap {
+                return None;
+            }
+            pw += (w0 + w1) / 2.0 * (p0 - p1);
+        }
+
+        Some(pw / G)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        sounding::{Level, Phase, Sounding, Station},
+        thermo::mixing_ratio,
+    };
+
+    #[test]
+    fn test_precipitable_water() {
+        let level = |hpa: f64, td: Option<f64>| Level {
+            pressure: Some(hpa * 100.0),
+            dewpoint: td,
+            ..Level::default()
+        };
+        let sounding =
+            |levels| Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        // A uniform 280 K dewpoint through a 100 hPa layer, near uniform mixing ratio.
+        let moist = sounding(vec![
+            level(1000.0, Some(280.0)),
+            level(950.0, None),
+            level(900.0, Some(280.0)),
+        ]);
+        let w = (mixing_ratio(280.0, 100_000.0) + mixing_ratio(280.0, 90_000.0)) / 2.0;
+        let pw = moist.precipitable_water().unwrap();
+        assert!((pw - w * 10_000.0 / 9.80665).abs() < 1.0e-9);
+
+        // A 200 hPa gap is too wide with the default tolerance.
+        let gappy = sounding(vec![level(1000.0, Some(280.0)), level(800.0, Some(270.0))]);
+        assert!(gappy.precipitable_water().is_none());
+        assert!(gappy.precipitable_water_with_gap(20_000.0).is_some());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    export::{json_number, json_string},
+    index::walk_archive,
+    sounding::{Level, Sounding},
+    MessageDecoder,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    io::Write,
+    path::Path,
+};
+
+/// The monitoring report for one station and month, see `summarize_archive`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonthlySummary {
+    /// See `Station::identifier`.
+    pub station: String,
+    pub year: u16,
+    pub month: u8,
+    pub launches: usize,
+    /// The mean of the lowest pressure each sounding reached, Pa.
+    pub mean_top_pressure: Option<f64>,
+    /// The percentage of levels without a temperature.
+    pub missing_temperature: f64,
+    /// The percentage of levels without a dewpoint.
+    pub missing_dewpoint: f64,
+    /// The percentage of levels without a wind direction and speed.
+    pub missing_wind: f64,
+    /// Days of the month without a launch.
+    pub gap_days: u32,
+}
+
+/// Summarize every sounding under `dir` per station and month, sorted by station and then
+/// month. Soundings go in the month of their launch time, or of the nominal time if they don't
+/// have one, and soundings without a station identifier are left out.
+pub fn summarize_archive(dir: impl AsRef<Path>) -> Result<Vec<MonthlySummary>, Box<dyn Error>> {
+    let mut soundings = vec![];
+    walk_archive(dir.as_ref(), &MessageDecoder::default(), |_, _, bufr| {
+        for mut sounding in bufr.soundings() {
+            if sounding.launch_time().is_none() {
+                sounding.launch_time = Some(bufr.nominal_time());
+            }
+            soundings.push(sounding);
+        }
+    })?;
+
+    Ok(summarize_soundings(&soundings))
+}
+
+/// Summarize soundings per station and month of the launch time, see `summarize_archive`.
+pub fn summarize_soundings(soundings: &[Sounding]) -> Vec<MonthlySummary> {
+    let mut groups: BTreeMap<(String, u16, u8), Vec<&Sounding>> = BTreeMap::new();
+    for sounding in soundings {
+        let (Some(station), Some(time)) = (sounding.station().identifier(), sounding.launch_time())
+        else {
+            continue;
+        };
+        groups
+            .entry((station, time.year, time.month))
+            .or_default()
+            .push(sounding);
+    }
+
+    groups
+        .into_iter()
+        .map(|((station, year, month), soundings)| {
+            let tops: Vec<f64> = soundings
+                .iter()
+                .filter_map(|s| {
+                    s.levels()
+                        .iter()
+                        .filter_map(|lvl| lvl.pressure)
+                        .min_by(f64::total_cmp)
+                })
+                .collect();
+            let mean_top_pressure =
+                (!tops.is_empty()).then(|| tops.iter().sum::<f64>() / tops.len() as f64);
+
+            let levels: Vec<_> = soundings.iter().flat_map(|s| s.levels()).collect();
+            let missing = |is_missing: &dyn Fn(&Level) -> bool| {
+                if levels.is_empty() {
+                    return 100.0;
+                }
+                let count = levels.iter().filter(|lvl| is_missing(lvl)).count();
+                100.0 * count as f64 / levels.len() as f64
+            };
+
+            let days: BTreeSet<u8> = soundings
+                .iter()
+                .filter_map(|s| s.launch_time())
+                .map(|t| t.day)
+                .collect();
+
+            MonthlySummary {
+                missing_temperature: missing(&|lvl| lvl.temperature.is_none()),
+                missing_dewpoint: missing(&|lvl| lvl.dewpoint.is_none()),
+                missing_wind: missing(&|lvl| {
+                    lvl.wind_direction.is_none() || lvl.wind_speed.is_none()
+                }),
+                gap_days: days_in_month(year, month).saturating_sub(days.len() as u32),
+                station,
+                year,
+                month,
+                launches: soundings.len(),
+                mean_top_pressure,
+            }
+        })
+        .collect()
+}
+
+/// Write the summaries as CSV with a header row. Pressures are in hPa.
+pub fn write_monthly_csv(
+    mut w: impl Write,
+    summaries: &[MonthlySummary],
+) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        w,
+        "station,month,launches,mean_top_pressure_hpa,missing_temperature_pct,\
+         missing_dewpoint_pct,missing_wind_pct,gap_days"
+    )?;
+    for s in summaries {
+        writeln!(
+            w,
+            "{},{:04}-{:02},{},{},{:.1},{:.1},{:.1},{}",
+            s.station,
+            s.year,
+            s.month,
+            s.launches,
+            s.mean_top_pressure
+                .map_or(String::new(), |p| format!("{:.1}", p / 100.0)),
+            s.missing_temperature,
+            s.missing_dewpoint,
+            s.missing_wind,
+            s.gap_days
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the summaries as a JSON array with the same fields as `write_monthly_csv`.
+pub fn write_monthly_json(
+    mut w: impl Write,
+    summaries: &[MonthlySummary],
+) -> Result<(), Box<dyn Error>> {
+    write!(w, "[")?;
+    for (i, s) in summaries.iter().enumerate() {
+        let sep = if i > 0 { "," } else { "" };
+        write!(
+            w,
+            "{}{{\"station\":{},\"month\":\"{:04}-{:02}\",\"launches\":{},\
+             \"mean_top_pressure_hpa\":{},\"missing_temperature_pct\":{},\
+             \"missing_dewpoint_pct\":{},\"missing_wind_pct\":{},\"gap_days\":{}}}",
+            sep,
+            json_string(Some(&s.station)),
+            s.year,
+            s.month,
+            s.launches,
+            json_number(s.mean_top_pressure.map(|p| p / 100.0)),
+            json_number(Some(s.missing_temperature)),
+            json_number(Some(s.missing_dewpoint)),
+            json_number(Some(s.missing_wind)),
+            s.gap_days
+        )?;
+    }
+    writeln!(w, "]")?;
+    Ok(())
+}
+
+pub(crate) fn days_in_month(year: u16, month: u8) -> u32 {
+    let leap = (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station, Timestamp};
+
+    #[test]
+    fn test_summarize_soundings() {
+        let station = Station {
+            wmo_block: Some(72),
+            wmo_station: Some(773),
+            ..Station::default()
+        };
+        let sounding = |month: u8, day: u8, top: f64| {
+            let time = Timestamp {
+                year: 2024,
+                month,
+                day,
+                hour: 0,
+                minute: 0,
+                second: 0,
+            };
+            let levels = vec![
+                Level {
+                    pressure: Some(90_000.0),
+                    temperature: Some(290.0),
+                    wind_direction: Some(180.0),
+                    wind_speed: Some(4.0),
+                    ..Level::default()
+                },
+                Level {
+                    pressure: Some(top),
+                    temperature: Some(220.0),
+                    dewpoint: Some(200.0),
+                    ..Level::default()
+                },
+            ];
+            Sounding::new(Phase::Ascent, station.clone(), Some(time), None, levels)
+        };
+
+        let summaries = summarize_soundings(&[
+            sounding(2, 1, 1_000.0),
+            sounding(2, 1, 3_000.0),
+            sounding(2, 3, 2_000.0),
+            sounding(3, 1, 1_000.0),
+        ]);
+        assert_eq!(summaries.len(), 2);
+        let feb = &summaries[0];
+        assert_eq!(
+            (feb.station.as_str(), feb.year, feb.month),
+            ("72773", 2024, 2)
+        );
+        assert_eq!(feb.launches, 3);
+        assert_eq!(feb.mean_top_pressure, Some(2_000.0));
+        assert_eq!(feb.missing_temperature, 0.0);
+        assert_eq!(feb.missing_dewpoint, 50.0);
+        assert_eq!(feb.missing_wind, 50.0);
+        // 2024 is a leap year.
+        assert_eq!(feb.gap_days, 27);
+        assert_eq!(summaries[1].gap_days, 30);
+
+        let mut csv = vec![];
+        write_monthly_csv(&mut csv, &summaries).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "72773,2024-02,3,20.0,0.0,50.0,50.0,27"
+        );
+
+        let mut json = vec![];
+        write_monthly_json(&mut json, &summaries[1..]).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap().trim_end(),
+            "[{\"station\":\"72773\",\"month\":\"2024-03\",\"launches\":1,\
+             \"mean_top_pressure_hpa\":10,\"missing_temperature_pct\":0,\
+             \"missing_dewpoint_pct\":50,\"missing_wind_pct\":50,\"gap_days\":30}]"
+        );
+    }
+}
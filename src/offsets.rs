@@ -0,0 +1,223 @@
+use crate::{
+    builder::with_offset,
+    scan_to_bufr_start, section0,
+    section1::{self, Section1},
+    section2,
+    section3::{self, Descriptor},
+    sounding::Timestamp,
+    BufrMessage, MessageDecoder,
+};
+use std::{
+    error::Error,
+    io::{Read, Seek, SeekFrom},
+};
+
+/// Where a message is in a file, and what's in its Section 1, see `scan_offsets`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageOffset {
+    /// Byte offset of the start of the message.
+    pub offset: u64,
+    /// Octets in the message, from `BUFR` through `7777`.
+    pub length: usize,
+    pub edition: u8,
+    /// The Table A data category.
+    pub data_category: u8,
+    /// The typical time from Section 1.
+    pub nominal_time: Timestamp,
+}
+
+/// A message's Section 0, 1, and 3 headers, see `scan_headers`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageSummary {
+    /// Where the message is, with its Section 1 time and category.
+    pub location: MessageOffset,
+    pub master_table: u8,
+    pub data_subcategory: u8,
+    pub update_number: u8,
+    /// The number of subsets in Section 4.
+    pub subsets: u16,
+    pub compressed: bool,
+    /// The unexpanded descriptors from Section 3.
+    pub descriptors: Vec<Descriptor>,
+}
+
+/// Find every message in `reader` from its current position, reading only Sections 0 and 1 and
+/// seeking past the rest. Pass the offsets to `decode_at` to decode messages in any order, or
+/// split them between threads.
+///
+/// A `BUFR` that isn't followed by valid Sections 0 and 1, or whose length doesn't lead to a
+/// `7777`, is skipped.
+pub fn scan_offsets(reader: impl Read + Seek) -> Result<Vec<MessageOffset>, Box<dyn Error>> {
+    scan(reader, |_, location, _| Ok(location))
+}
+
+/// Like `scan_offsets`, but also reading Sections 2 and 3, so there's enough to list the
+/// messages, e.g. in an inventory, before decoding any of them. Pass the ones wanted to
+/// `decode_selected`.
+pub fn scan_headers(reader: impl Read + Seek) -> Result<Vec<MessageSummary>, Box<dyn Error>> {
+    scan(reader, |reader, location, section_1| {
+        section2::read_section_2(&mut *reader, section_1.section_2_exists())?;
+        let section_3 = section3::read_section_3(reader)?;
+
+        Ok(MessageSummary {
+            location,
+            master_table: section_1.master_table(),
+            data_subcategory: section_1.data_subcategory(),
+            update_number: section_1.update_number(),
+            subsets: section_3.num_datasets(),
+            compressed: section_3.compressed_data(),
+            descriptors: section_3.descriptors().to_vec(),
+        })
+    })
+}
+
+/// Read the headers of every message with `summarize`, which is given the reader just after
+/// Section 1.
+fn scan<R: Read + Seek, T>(
+    mut reader: R,
+    summarize: impl Fn(&mut R, MessageOffset, &Section1) -> Result<T, Box<dyn Error>>,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let mut summaries = vec![];
+    while scan_to_bufr_start(&mut reader).is_ok() {
+        let offset = reader.stream_position()?;
+
+        let summary = section0::read_section_0(&mut reader).and_then(|section_0| {
+            let section_1 = section1::read_section_1(&mut reader)?;
+            let location = MessageOffset {
+                offset,
+                length: section_0.message_size(),
+                edition: section_0.bufr_version(),
+                data_category: section_1.data_category(),
+                nominal_time: section_1.time(),
+            };
+            Ok((
+                location.length,
+                summarize(&mut reader, location, &section_1)?,
+            ))
+        });
+        match summary {
+            Ok((length, summary)) if ends_at(&mut reader, offset, length) => {
+                summaries.push(summary);
+            }
+            _ => {
+                reader.seek(SeekFrom::Start(offset + 1))?;
+            }
+        }
+    }
+
+    Ok(summaries)
+}
+
+/// Whether the message at `offset` ends with `7777` after `length` octets, leaving the reader
+/// after it if so.
+fn ends_at(mut reader: impl Read + Seek, offset: u64, length: usize) -> bool {
+    let mut end = [0; 4];
+    length >= 8
+        && reader
+            .seek(SeekFrom::Start(offset + length as u64 - 4))
+            .and_then(|_| reader.read_exact(&mut end))
+            .is_ok()
+        && &end == b"7777"
+}
+
+/// Decode the message starting `offset` bytes into `reader`, e.g. a `MessageOffset::offset`.
+pub fn decode_at(reader: impl Read + Seek, offset: u64) -> Result<BufrMessage, Box<dyn Error>> {
+    MessageDecoder::default().decode_at(reader, offset)
+}
+
+/// Decode the `selected` messages from an earlier `scan_headers` of `reader`, in order.
+pub fn decode_selected<'s>(
+    reader: impl Read + Seek,
+    selected: impl IntoIterator<Item = &'s MessageSummary>,
+) -> Vec<Result<BufrMessage, Box<dyn Error>>> {
+    MessageDecoder::default().decode_selected(reader, selected)
+}
+
+impl MessageDecoder {
+    /// Decode the message starting `offset` bytes into `reader`, see `scan_offsets`.
+    pub fn decode_at(
+        &self,
+        mut reader: impl Read + Seek,
+        offset: u64,
+    ) -> Result<BufrMessage, Box<dyn Error>> {
+        reader.seek(SeekFrom::Start(offset))?;
+        self.read_bufr_message(reader)
+            .map_err(|err| with_offset(err, Some(offset)))
+    }
+
+    /// Decode the `selected` messages from an earlier `scan_headers` of `reader`, in order.
+    pub fn decode_selected<'s>(
+        &self,
+        mut reader: impl Read + Seek,
+        selected: impl IntoIterator<Item = &'s MessageSummary>,
+    ) -> Vec<Result<BufrMessage, Box<dyn Error>>> {
+        selected
+            .into_iter()
+            .map(|summary| self.decode_at(&mut reader, summary.location.offset))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_scan_offsets() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let message = crate::read_bufr_bytes(&file[start..]).unwrap();
+
+        // A false start, then the message twice.
+        let mut stream = b"BUFR not a message".to_vec();
+        stream.extend(&message);
+        stream.extend(b"gap");
+        stream.extend(&message);
+
+        let offsets = scan_offsets(Cursor::new(&stream)).unwrap();
+        assert_eq!(offsets.len(), 2);
+        assert_eq!(offsets[0].offset, 18);
+        assert_eq!(offsets[1].offset, 18 + message.len() as u64 + 3);
+        assert_eq!(offsets[0].length, message.len());
+        assert_eq!(offsets[0].edition, 4);
+        assert_eq!(offsets[0].data_category, 2);
+        assert_eq!(offsets[0].nominal_time.hour, 18);
+
+        // Decode the last one first.
+        let mut reader = Cursor::new(&stream);
+        for entry in offsets.iter().rev() {
+            let bufr = decode_at(&mut reader, entry.offset).unwrap();
+            assert_eq!(bufr.soundings()[0].levels().len(), 4879);
+        }
+    }
+
+    #[test]
+    fn test_scan_headers() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let message = crate::read_bufr_bytes(&file[start..]).unwrap();
+        let mut stream = message.clone();
+        stream.extend(b"BUFR not a message");
+        stream.extend(&message);
+
+        let headers = scan_headers(Cursor::new(&stream)).unwrap();
+        assert_eq!(headers.len(), 2);
+        let bufr = crate::BufrMessage::from_bytes(&message).unwrap();
+        assert_eq!(
+            headers[0].location,
+            scan_offsets(Cursor::new(&message)).unwrap()[0]
+        );
+        assert_eq!(headers[0].master_table, 0);
+        assert_eq!(headers[0].subsets, 1);
+        assert_eq!(headers[0].descriptors, bufr.section_3.descriptors());
+        assert_eq!(headers[1].location.offset, message.len() as u64 + 18);
+
+        let decoded = decode_selected(Cursor::new(&stream), &headers[1..]);
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(
+            decoded[0].as_ref().unwrap().soundings()[0].levels().len(),
+            4879
+        );
+    }
+}
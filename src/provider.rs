@@ -0,0 +1,199 @@
+use crate::{
+    section1::Section1,
+    section3::Descriptor,
+    tables::{lookup_element, lookup_sequence, ElementOverride, TableOverrides},
+};
+use std::{collections::HashSet, error::Error, fmt};
+
+/// The tables a BUFR message says it was encoded with, from Section 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TableVersion {
+    /// 0 for meteorology, 10 for oceanography.
+    pub master_table: u8,
+    pub master_table_version: u8,
+    /// 0 if no local tables are used.
+    pub local_tables_version: u8,
+    /// Originating centre, common code table C-11.
+    pub centre: u16,
+    pub sub_centre: u16,
+}
+
+impl TableVersion {
+    pub(crate) fn of(section_1: &Section1) -> Self {
+        TableVersion {
+            master_table: section_1.master_table(),
+            master_table_version: section_1.master_table_version(),
+            local_tables_version: section_1.local_tables_version(),
+            centre: section_1.originating_centre(),
+            sub_centre: section_1.originating_subcenter(),
+        }
+    }
+}
+
+/// Where the decoder gets Table B and Table D definitions from in place of the built-in tables,
+/// e.g. a database, a small embedded subset, or tables fetched over the network. See
+/// `DecoderBuilder::table_provider`.
+///
+/// Each message's descriptors are looked up once, before Section 4 is decoded, so a provider
+/// doesn't need to be fast. Anything the provider doesn't know is unknown to the decoder.
+pub trait TableProvider: Send + Sync {
+    fn element(&self, descriptor: Descriptor, version: &TableVersion) -> Option<ElementOverride>;
+
+    fn sequence(&self, descriptor: Descriptor, version: &TableVersion) -> Option<Vec<Descriptor>>;
+}
+
+impl<T: TableProvider + ?Sized> TableProvider for std::sync::Arc<T> {
+    fn element(&self, descriptor: Descriptor, version: &TableVersion) -> Option<ElementOverride> {
+        (**self).element(descriptor, version)
+    }
+
+    fn sequence(&self, descriptor: Descriptor, version: &TableVersion) -> Option<Vec<Descriptor>> {
+        (**self).sequence(descriptor, version)
+    }
+}
+
+impl fmt::Debug for dyn TableProvider + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TableProvider")
+    }
+}
+
+/// The built-in WMO tables, whatever the version, which is what the decoder uses without a
+/// provider.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BuiltinTables;
+
+impl TableProvider for BuiltinTables {
+    fn element(&self, descriptor: Descriptor, _: &TableVersion) -> Option<ElementOverride> {
+        lookup_element(descriptor).map(ElementOverride::from)
+    }
+
+    fn sequence(&self, descriptor: Descriptor, _: &TableVersion) -> Option<Vec<Descriptor>> {
+        lookup_sequence(descriptor).map(|sequence| sequence.descriptors)
+    }
+}
+
+/// Just these definitions, whatever the version.
+impl TableProvider for TableOverrides {
+    fn element(&self, descriptor: Descriptor, _: &TableVersion) -> Option<ElementOverride> {
+        self.element_override(descriptor).cloned()
+    }
+
+    fn sequence(&self, descriptor: Descriptor, _: &TableVersion) -> Option<Vec<Descriptor>> {
+        TableOverrides::sequence(self, descriptor).map(<[Descriptor]>::to_vec)
+    }
+}
+
+/// The definitions `descriptors` need from `provider`, following sequences, with `overrides`
+/// on top. Only these are used to decode the message.
+pub(crate) fn resolve(
+    provider: &dyn TableProvider,
+    version: &TableVersion,
+    descriptors: &[Descriptor],
+    overrides: Option<&TableOverrides>,
+) -> Result<TableOverrides, Box<dyn Error>> {
+    let mut resolved = TableOverrides::new().exclusive();
+    let mut seen = HashSet::new();
+    let mut pending: Vec<Descriptor> = descriptors.to_vec();
+
+    while let Some(descriptor) = pending.pop() {
+        if !seen.insert(descriptor) {
+            continue;
+        }
+        match descriptor.f_value() {
+            0 if overrides.is_none_or(|o| o.element(descriptor).is_none()) => {
+                if let Some(element) = provider.element(descriptor, version) {
+                    resolved.add_element(descriptor, element)?;
+                }
+            }
+            3 => {
+                if let Some(sequence) = overrides.and_then(|o| o.sequence(descriptor)) {
+                    pending.extend_from_slice(sequence);
+                } else if let Some(sequence) = provider.sequence(descriptor, version) {
+                    pending.extend_from_slice(&sequence);
+                    resolved.add_sequence(descriptor, sequence)?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(overrides) = overrides {
+        resolved.merge(overrides.clone());
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{table_b_entries, DecoderBuilder};
+    use std::sync::{Arc, Mutex};
+
+    /// The built-in tables, recording what's asked for.
+    struct Recording(Mutex<Vec<(Descriptor, TableVersion)>>);
+
+    impl TableProvider for Recording {
+        fn element(
+            &self,
+            descriptor: Descriptor,
+            version: &TableVersion,
+        ) -> Option<ElementOverride> {
+            self.0.lock().unwrap().push((descriptor, *version));
+            BuiltinTables.element(descriptor, version)
+        }
+
+        fn sequence(
+            &self,
+            descriptor: Descriptor,
+            version: &TableVersion,
+        ) -> Option<Vec<Descriptor>> {
+            self.0.lock().unwrap().push((descriptor, *version));
+            BuiltinTables.sequence(descriptor, version)
+        }
+    }
+
+    #[test]
+    fn test_table_provider() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let expected = crate::read_bufr_message(&file[start..])
+            .unwrap()
+            .soundings();
+
+        let recording = Arc::new(Recording(Mutex::new(vec![])));
+        let decoder = DecoderBuilder::new()
+            .table_provider(recording.clone())
+            .build();
+        let soundings = decoder
+            .read_bufr_message(&file[start..])
+            .unwrap()
+            .soundings();
+        assert_eq!(soundings[0].levels(), expected[0].levels());
+
+        let asked = recording.0.lock().unwrap();
+        assert!(asked.iter().any(|(d, _)| *d == Descriptor::new(3, 9, 52)));
+        assert_eq!(asked[0].1.master_table, 0);
+        // Each descriptor is asked for once.
+        let unique: HashSet<_> = asked.iter().map(|(d, _)| d).collect();
+        assert_eq!(unique.len(), asked.len());
+
+        // An embedded subset without the temperature can't decode the message.
+        let mut subset = TableOverrides::new();
+        for element in table_b_entries() {
+            if element.descriptor != Descriptor::new(0, 12, 101) {
+                subset
+                    .add_element(element.descriptor, element.into())
+                    .unwrap();
+            }
+        }
+        for sequence in crate::table_d_entries() {
+            subset
+                .add_sequence(sequence.descriptor, sequence.descriptors)
+                .unwrap();
+        }
+        let decoder = DecoderBuilder::new().table_provider(subset).build();
+        assert!(decoder.read_bufr_message(&file[start..]).is_err());
+    }
+}
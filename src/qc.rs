@@ -0,0 +1,781 @@
+use std::{collections::HashMap, error::Error, io::Write};
+
+use crate::{
+    export::{json_number, json_string},
+    section3::Descriptor,
+    sounding::{self, Level, Phase, Sounding, Timestamp},
+    thermo::{mixing_ratio, virtual_temperature, CP, G, RD},
+};
+
+/// A level that failed a quality control check.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QcIssue {
+    /// Index of the level in `Sounding::levels`.
+    pub level: usize,
+    /// The `Level::qc` flag the check sets.
+    pub flag: u32,
+    pub message: String,
+}
+
+/// A quality control check. Checks flag levels rather than removing them, so the decoded data is
+/// kept as it was reported.
+pub trait QcCheck {
+    /// A short name for reports.
+    fn name(&self) -> &'static str;
+
+    /// Set the check's flag on the levels that fail it and describe each failure.
+    fn apply(&self, sounding: &mut Sounding) -> Vec<QcIssue>;
+}
+
+/// Flags layers whose lapse rate exceeds the dry adiabatic lapse rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SuperadiabaticCheck {
+    /// How far beyond the dry adiabatic lapse rate a layer may be, K/km.
+    pub threshold: f64,
+    /// Thinner layers are combined with the levels above them until they are at least this deep
+    /// (m), so sensor noise in high resolution data isn't flagged.
+    pub min_depth: f64,
+    /// Don't check the layer starting at the lowest level, where strong surface heating can make
+    /// a real superadiabatic layer.
+    pub skip_surface_layer: bool,
+}
+
+impl Default for SuperadiabaticCheck {
+    fn default() -> Self {
+        SuperadiabaticCheck {
+            threshold: 1.0,
+            min_depth: 50.0,
+            skip_surface_layer: true,
+        }
+    }
+}
+
+impl QcCheck for SuperadiabaticCheck {
+    fn name(&self) -> &'static str {
+        "superadiabatic"
+    }
+
+    fn apply(&self, sounding: &mut Sounding) -> Vec<QcIssue> {
+        let dry_adiabatic = G / CP * 1000.0; // K/km
+
+        let mut profile: Vec<(usize, f64, f64)> = sounding
+            .levels()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, lvl)| Some((i, lvl.height?, lvl.temperature?)))
+            .collect();
+        profile.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let mut issues = vec![];
+        let mut base = 0;
+        while base + 1 < profile.len() {
+            let (i0, z0, t0) = profile[base];
+            let Some(top) =
+                (base + 1..profile.len()).find(|&j| profile[j].1 - z0 >= self.min_depth)
+            else {
+                break;
+            };
+            let (i1, z1, t1) = profile[top];
+
+            let lapse_rate = (t0 - t1) / (z1 - z0) * 1000.0;
+            if lapse_rate > dry_adiabatic + self.threshold
+                && !(self.skip_surface_layer && base == 0)
+            {
+                for i in [i0, i1] {
+                    if issues.iter().any(|issue: &QcIssue| issue.level == i) {
+                        continue;
+                    }
+                    issues.push(QcIssue {
+                        level: i,
+                        flag: Level::QC_SUPERADIABATIC,
+                        message: format!(
+                            "lapse rate of {:.1} K/km from {:.0} to {:.0} m",
+                            lapse_rate, z0, z1
+                        ),
+                    });
+                }
+            }
+
+            base = top;
+        }
+
+        let levels = sounding.levels_mut();
+        for issue in &issues {
+            levels[issue.level].qc |= issue.flag;
+        }
+
+        issues
+    }
+}
+
+/// Flags reported heights that disagree with the hydrostatic thickness of the layer below them.
+///
+/// Heights are integrated up from the lowest reported height using the reported pressures and
+/// temperatures. Each reported height that agrees restarts the integration, so one bad height
+/// doesn't cause the levels above it to be flagged too. Derived heights aren't checked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HydrostaticCheck {
+    /// The largest allowed difference between the reported and integrated heights, gpm.
+    pub max_discrepancy: f64,
+}
+
+impl Default for HydrostaticCheck {
+    fn default() -> Self {
+        HydrostaticCheck {
+            max_discrepancy: 50.0,
+        }
+    }
+}
+
+impl QcCheck for HydrostaticCheck {
+    fn name(&self) -> &'static str {
+        "hydrostatic"
+    }
+
+    fn apply(&self, sounding: &mut Sounding) -> Vec<QcIssue> {
+        // (index, pressure, virtual temperature, reported height)
+        let mut profile: Vec<(usize, f64, f64, Option<f64>)> = sounding
+            .levels()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, lvl)| {
+                let p = lvl.pressure?;
+                let w = lvl.dewpoint.map(|td| mixing_ratio(td, p)).unwrap_or(0.0);
+                let tv = virtual_temperature(lvl.temperature?, w);
+                let z = lvl
+                    .height
+                    .filter(|_| !lvl.is_derived(Level::DERIVED_HEIGHT));
+                Some((i, p, tv, z))
+            })
+            .collect();
+        profile.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let mut issues = vec![];
+        // The height integrated up from the last good reported height.
+        let mut anchor: Option<f64> = None;
+        for k in 0..profile.len() {
+            let (i, p, tv, z) = profile[k];
+            if k > 0 {
+                let (_, p0, tv0, _) = profile[k - 1];
+                anchor = anchor.map(|z0| z0 + RD / G * (tv0 + tv) / 2.0 * (p0 / p).ln());
+            }
+
+            let Some(z) = z else {
+                continue;
+            };
+
+            match anchor {
+                Some(expected) if (z - expected).abs() > self.max_discrepancy => {
+                    issues.push(QcIssue {
+                        level: i,
+                        flag: Level::QC_HYDROSTATIC,
+                        message: format!(
+                            "height {:.0} gpm at {:.1} hPa, hydrostatic height {:.0} gpm",
+                            z,
+                            p / 100.0,
+                            expected
+                        ),
+                    });
+                }
+                _ => anchor = Some(z),
+            }
+        }
+
+        let levels = sounding.levels_mut();
+        for issue in &issues {
+            levels[issue.level].qc |= issue.flag;
+        }
+
+        issues
+    }
+}
+
+/// Flags levels that are out of order for the flight direction, and levels that repeat an
+/// earlier pressure with different values.
+///
+/// Levels are compared with the last level that was in order, so in an ascent a level whose
+/// pressure is higher, or whose height is lower, than the one before it is flagged (the reverse
+/// in a descent). Repeated pressures are only flagged if both levels report a height,
+/// temperature, dewpoint, or wind and they differ by more than the tolerance, since high
+/// resolution data often repeats a pressure at the resolution it's encoded to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MonotonicCheck {
+    /// gpm
+    pub height_tolerance: f64,
+    /// K
+    pub temperature_tolerance: f64,
+    /// K, larger than the temperature tolerance since very dry air gives noisy dewpoints.
+    pub dewpoint_tolerance: f64,
+    /// Degrees, the smaller angle between the two directions.
+    pub wind_direction_tolerance: f64,
+    /// m/s
+    pub wind_speed_tolerance: f64,
+}
+
+impl Default for MonotonicCheck {
+    fn default() -> Self {
+        MonotonicCheck {
+            height_tolerance: 30.0,
+            temperature_tolerance: 1.0,
+            dewpoint_tolerance: 5.0,
+            wind_direction_tolerance: 30.0,
+            wind_speed_tolerance: 5.0,
+        }
+    }
+}
+
+impl QcCheck for MonotonicCheck {
+    fn name(&self) -> &'static str {
+        "monotonic"
+    }
+
+    fn apply(&self, sounding: &mut Sounding) -> Vec<QcIssue> {
+        let rising = sounding.phase() == Phase::Ascent;
+        let levels = sounding.levels();
+
+        let mut issues = vec![];
+        let mut last_p: Option<f64> = None;
+        let mut last_z: Option<f64> = None;
+        let mut seen: HashMap<u64, usize> = HashMap::new();
+        for (i, lvl) in levels.iter().enumerate() {
+            if let Some(p) = lvl.pressure {
+                match last_p {
+                    Some(p0) if (p > p0) == rising && p != p0 => issues.push(QcIssue {
+                        level: i,
+                        flag: Level::QC_PRESSURE_ORDER,
+                        message: format!(
+                            "pressure {:.1} hPa after {:.1} hPa",
+                            p / 100.0,
+                            p0 / 100.0
+                        ),
+                    }),
+                    _ => last_p = Some(p),
+                }
+
+                if let Some(&j) = seen.get(&p.to_bits()) {
+                    if self.conflicts(&levels[j], lvl) {
+                        issues.push(QcIssue {
+                            level: i,
+                            flag: Level::QC_DUPLICATE,
+                            message: format!(
+                                "{:.1} hPa repeats level {} with different values",
+                                p / 100.0,
+                                j
+                            ),
+                        });
+                    }
+                } else {
+                    seen.insert(p.to_bits(), i);
+                }
+            }
+
+            if let Some(z) = lvl.height {
+                match last_z {
+                    Some(z0) if (z < z0) == rising && z != z0 => issues.push(QcIssue {
+                        level: i,
+                        flag: Level::QC_HEIGHT_ORDER,
+                        message: format!("height {:.0} gpm after {:.0} gpm", z, z0),
+                    }),
+                    _ => last_z = Some(z),
+                }
+            }
+        }
+
+        let levels = sounding.levels_mut();
+        for issue in &issues {
+            levels[issue.level].qc |= issue.flag;
+        }
+
+        issues
+    }
+}
+
+impl MonotonicCheck {
+    /// Whether two levels both report a value and the values differ by more than the tolerance.
+    fn conflicts(&self, a: &Level, b: &Level) -> bool {
+        let differ = |x: Option<f64>, y: Option<f64>, tolerance: f64| matches!((x, y), (Some(x), Some(y)) if (x - y).abs() > tolerance);
+        // Directions wrap around, so 355 and 5 are 10 degrees apart.
+        let turn = a.wind_direction.zip(b.wind_direction).map(|(x, y)| {
+            let turn = (x - y).rem_euclid(360.0);
+            turn.min(360.0 - turn)
+        });
+
+        differ(a.height, b.height, self.height_tolerance)
+            || differ(a.temperature, b.temperature, self.temperature_tolerance)
+            || differ(a.dewpoint, b.dewpoint, self.dewpoint_tolerance)
+            || turn.is_some_and(|turn| turn > self.wind_direction_tolerance)
+            || differ(a.wind_speed, b.wind_speed, self.wind_speed_tolerance)
+    }
+}
+
+/// Flags values outside plausible limits.
+///
+/// Limits are kept per descriptor, so they can be changed for any element of a level: pressure
+/// (0-07-004), height (0-10-009), wind direction and speed (0-11-001, 0-11-002), temperature and
+/// dewpoint (0-12-101, 0-12-103) and relative humidity (0-13-003).
+#[derive(Clone, Debug, PartialEq)]
+pub struct RangeCheck {
+    limits: HashMap<Descriptor, (f64, f64)>,
+}
+
+type Getter = fn(&Level) -> Option<f64>;
+
+/// The elements `RangeCheck` looks at, in the order it reports them.
+const RANGE_ELEMENTS: [(Descriptor, &str, Getter); 7] = [
+    (sounding::PRESSURE, "pressure", |lvl| lvl.pressure),
+    (sounding::GEOPOTENTIAL_HEIGHT, "height", |lvl| lvl.height),
+    (sounding::TEMPERATURE, "temperature", |lvl| lvl.temperature),
+    (sounding::DEWPOINT, "dewpoint", |lvl| lvl.dewpoint),
+    (sounding::RELATIVE_HUMIDITY, "relative humidity", |lvl| {
+        lvl.relative_humidity
+    }),
+    (sounding::WIND_DIRECTION, "wind direction", |lvl| {
+        lvl.wind_direction
+    }),
+    (sounding::WIND_SPEED, "wind speed", |lvl| lvl.wind_speed),
+];
+
+impl Default for RangeCheck {
+    fn default() -> Self {
+        RangeCheck {
+            limits: [
+                (sounding::PRESSURE, (100.0, 110_000.0)),
+                (sounding::GEOPOTENTIAL_HEIGHT, (-500.0, 50_000.0)),
+                (sounding::TEMPERATURE, (160.0, 340.0)),
+                (sounding::DEWPOINT, (140.0, 340.0)),
+                (sounding::RELATIVE_HUMIDITY, (0.0, 100.0)),
+                (sounding::WIND_DIRECTION, (0.0, 360.0)),
+                (sounding::WIND_SPEED, (0.0, 150.0)),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+}
+
+impl RangeCheck {
+    /// Replace the limits (inclusive, in BUFR units) for an element.
+    pub fn with_limits(mut self, descriptor: Descriptor, min: f64, max: f64) -> Self {
+        self.limits.insert(descriptor, (min, max));
+        self
+    }
+
+    /// Stop checking an element.
+    pub fn without(mut self, descriptor: Descriptor) -> Self {
+        self.limits.remove(&descriptor);
+        self
+    }
+
+    /// The limits for an element, if it's checked.
+    pub fn limits(&self, descriptor: Descriptor) -> Option<(f64, f64)> {
+        self.limits.get(&descriptor).copied()
+    }
+}
+
+impl QcCheck for RangeCheck {
+    fn name(&self) -> &'static str {
+        "range"
+    }
+
+    fn apply(&self, sounding: &mut Sounding) -> Vec<QcIssue> {
+        let mut issues = vec![];
+        for (i, lvl) in sounding.levels().iter().enumerate() {
+            for (descriptor, name, get) in RANGE_ELEMENTS {
+                let (Some((min, max)), Some(v)) = (self.limits(descriptor), get(lvl)) else {
+                    continue;
+                };
+                if v < min || v > max {
+                    issues.push(QcIssue {
+                        level: i,
+                        flag: Level::QC_RANGE,
+                        message: format!("{} {} outside [{}, {}]", name, v, min, max),
+                    });
+                }
+            }
+        }
+
+        let levels = sounding.levels_mut();
+        for issue in &issues {
+            levels[issue.level].qc |= issue.flag;
+        }
+
+        issues
+    }
+}
+
+/// Reports the levels the quality information sent with the data marks as suspect, which are
+/// flagged with `Level::QC_REPORTED` when they're decoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReportedQualityCheck;
+
+impl QcCheck for ReportedQualityCheck {
+    fn name(&self) -> &'static str {
+        "reported_quality"
+    }
+
+    fn apply(&self, sounding: &mut Sounding) -> Vec<QcIssue> {
+        sounding
+            .levels()
+            .iter()
+            .enumerate()
+            .filter(|(_, lvl)| lvl.has_qc_flag(Level::QC_REPORTED))
+            .map(|(i, _)| QcIssue {
+                level: i,
+                flag: Level::QC_REPORTED,
+                message: "marked suspect by the originating centre".to_owned(),
+            })
+            .collect()
+    }
+}
+
+/// The checks `Sounding::quality_control` runs by default, with their default settings.
+pub fn default_checks() -> Vec<Box<dyn QcCheck>> {
+    vec![
+        Box::new(RangeCheck::default()),
+        Box::new(MonotonicCheck::default()),
+        Box::new(HydrostaticCheck::default()),
+        Box::new(SuperadiabaticCheck::default()),
+    ]
+}
+
+/// The issues one check found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QcCheckResult {
+    pub name: &'static str,
+    pub issues: Vec<QcIssue>,
+}
+
+impl QcCheckResult {
+    pub fn passed(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// The result of running quality control checks on a sounding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QcReport {
+    pub station: Option<String>,
+    pub launch_time: Option<Timestamp>,
+    pub phase: Phase,
+    pub num_levels: usize,
+    /// In the order the checks ran.
+    pub checks: Vec<QcCheckResult>,
+}
+
+impl QcReport {
+    /// Whether every check passed.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(QcCheckResult::passed)
+    }
+
+    /// The indexes of the levels any check flagged, in order.
+    pub fn flagged_levels(&self) -> Vec<usize> {
+        let mut levels: Vec<usize> = self
+            .checks
+            .iter()
+            .flat_map(|check| check.issues.iter().map(|issue| issue.level))
+            .collect();
+        levels.sort_unstable();
+        levels.dedup();
+        levels
+    }
+
+    /// Write the report as a JSON object with the sounding, the overall result, and each check
+    /// with its issues.
+    pub fn write_json(&self, mut w: impl Write) -> Result<(), Box<dyn Error>> {
+        writeln!(w, "{{")?;
+        writeln!(
+            w,
+            "  \"station\": {},",
+            json_string(self.station.as_deref())
+        )?;
+        writeln!(
+            w,
+            "  \"launch_time\": {},",
+            json_string(self.launch_time.map(|t| t.to_string()).as_deref())
+        )?;
+        writeln!(w, "  \"phase\": \"{}\",", self.phase)?;
+        writeln!(w, "  \"levels\": {},", self.num_levels)?;
+        writeln!(w, "  \"passed\": {},", self.passed())?;
+        writeln!(w, "  \"checks\": [")?;
+        for (i, check) in self.checks.iter().enumerate() {
+            writeln!(
+                w,
+                "    {{\"name\": \"{}\", \"passed\": {}, \"issues\": [",
+                check.name,
+                check.passed()
+            )?;
+            for (j, issue) in check.issues.iter().enumerate() {
+                writeln!(
+                    w,
+                    "      {{\"level\": {}, \"flag\": {}, \"message\": {}}}{}",
+                    issue.level,
+                    json_number(Some(f64::from(issue.flag))),
+                    json_string(Some(&issue.message)),
+                    if j + 1 == check.issues.len() { "" } else { "," }
+                )?;
+            }
+            writeln!(
+                w,
+                "    ]}}{}",
+                if i + 1 == self.checks.len() { "" } else { "," }
+            )?;
+        }
+        writeln!(w, "  ]")?;
+        writeln!(w, "}}")?;
+
+        Ok(())
+    }
+
+    /// Write the report as CSV with one row per issue. A check that passed gets one row with
+    /// empty level, flag, and message fields, so every check that ran is listed.
+    pub fn write_csv(&self, mut w: impl Write) -> Result<(), Box<dyn Error>> {
+        writeln!(w, "check,level,flag,message")?;
+        for check in &self.checks {
+            if check.passed() {
+                writeln!(w, "{},,,", check.name)?;
+            }
+            for issue in &check.issues {
+                writeln!(
+                    w,
+                    "{},{},{},\"{}\"",
+                    check.name,
+                    issue.level,
+                    issue.flag,
+                    issue.message.replace('"', "\"\"")
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Sounding {
+    /// Run quality control checks in order, flagging the levels that fail them.
+    pub fn quality_control(&mut self, checks: &[Box<dyn QcCheck>]) -> QcReport {
+        let checks = checks
+            .iter()
+            .map(|check| QcCheckResult {
+                name: check.name(),
+                issues: check.apply(self),
+            })
+            .collect();
+
+        QcReport {
+            station: self.station().identifier(),
+            launch_time: self.launch_time(),
+            phase: self.phase(),
+            num_levels: self.levels().len(),
+            checks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::Station;
+
+    #[test]
+    fn test_superadiabatic() {
+        let levels = [
+            (1000.0, 300.0),
+            (1100.0, 298.0), // superadiabatic surface layer
+            (1200.0, 297.0),
+            (1210.0, 296.5), // thin and noisy, combined with the layer above
+            (1300.0, 296.1),
+            (1400.0, 293.0), // 31 K/km
+            (1500.0, 292.0),
+        ]
+        .iter()
+        .map(|&(z, t)| Level {
+            height: Some(z),
+            temperature: Some(t),
+            ..Level::default()
+        })
+        .collect();
+        let mut sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        let issues = SuperadiabaticCheck::default().apply(&mut sounding);
+        let flagged: Vec<usize> = issues.iter().map(|issue| issue.level).collect();
+        assert_eq!(flagged, vec![4, 5]);
+        assert!(sounding.levels()[5].has_qc_flag(Level::QC_SUPERADIABATIC));
+        assert!(!sounding.levels()[1].has_qc_flag(Level::QC_SUPERADIABATIC));
+
+        let check = SuperadiabaticCheck {
+            skip_surface_layer: false,
+            ..SuperadiabaticCheck::default()
+        };
+        let issues = check.apply(&mut sounding);
+        assert_eq!(issues[0].level, 0);
+    }
+
+    #[test]
+    fn test_hydrostatic() {
+        let thickness = |p0: f64, p1: f64| 287.04 / 9.80665 * 270.0 * (p0 / p1).ln();
+        let z850 = 100.0 + thickness(1000.0, 850.0);
+        let z700 = z850 + thickness(850.0, 700.0);
+        let z500 = z700 + thickness(700.0, 500.0);
+
+        let levels = [
+            (1000.0, Some(100.0)),
+            (850.0, Some(z850 + 10.0)),
+            (700.0, Some(z700 + 1000.0)), // a typo in the height
+            (600.0, None),
+            (500.0, Some(z500)),
+        ]
+        .iter()
+        .map(|&(hpa, z)| Level {
+            pressure: Some(hpa * 100.0),
+            height: z,
+            temperature: Some(270.0),
+            ..Level::default()
+        })
+        .collect();
+        let mut sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        let issues = HydrostaticCheck::default().apply(&mut sounding);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].level, 2);
+        assert!(sounding.levels()[2].has_qc_flag(Level::QC_HYDROSTATIC));
+    }
+
+    #[test]
+    fn test_monotonic() {
+        let level = |hpa: f64, z: f64, t: Option<f64>| Level {
+            pressure: Some(hpa * 100.0),
+            height: Some(z),
+            temperature: t,
+            ..Level::default()
+        };
+        let levels = vec![
+            level(1000.0, 100.0, Some(290.0)),
+            level(900.0, 1000.0, Some(285.0)),
+            level(900.0, 1000.0, None),        // a repeat with less data
+            level(900.0, 1005.0, Some(284.9)), // a repeat within tolerance
+            level(950.0, 1100.0, Some(284.0)), // pressure out of order
+            level(850.0, 900.0, Some(283.0)),  // height out of order
+            level(850.0, 1500.0, Some(281.0)), // conflicting duplicate
+            level(800.0, 2000.0, Some(280.0)),
+        ];
+        let mut sounding = Sounding::new(
+            Phase::Ascent,
+            Station::default(),
+            None,
+            None,
+            levels.clone(),
+        );
+
+        let issues = MonotonicCheck::default().apply(&mut sounding);
+        let flagged: Vec<(usize, u32)> = issues.iter().map(|i| (i.level, i.flag)).collect();
+        assert_eq!(
+            flagged,
+            vec![
+                (4, Level::QC_PRESSURE_ORDER),
+                (5, Level::QC_HEIGHT_ORDER),
+                (6, Level::QC_DUPLICATE),
+            ]
+        );
+
+        // The same levels reversed are a clean descent.
+        let mut sounding = Sounding::new(
+            Phase::Descent,
+            Station::default(),
+            None,
+            None,
+            vec![levels[7], levels[1], levels[0]],
+        );
+        assert!(MonotonicCheck::default().apply(&mut sounding).is_empty());
+
+        // Wind directions either side of north are close, opposite ones conflict.
+        let wind = |dir: f64| Level {
+            pressure: Some(50_000.0),
+            wind_direction: Some(dir),
+            ..Level::default()
+        };
+        let mut sounding = Sounding::new(
+            Phase::Ascent,
+            Station::default(),
+            None,
+            None,
+            vec![wind(355.0), wind(5.0), wind(175.0)],
+        );
+        let issues = MonotonicCheck::default().apply(&mut sounding);
+        let flagged: Vec<usize> = issues.iter().map(|i| i.level).collect();
+        assert_eq!(flagged, vec![2]);
+    }
+
+    #[test]
+    fn test_range() {
+        let levels = vec![
+            Level {
+                temperature: Some(290.0),
+                wind_speed: Some(10.0),
+                ..Level::default()
+            },
+            Level {
+                temperature: Some(400.0),
+                wind_speed: Some(10.0),
+                ..Level::default()
+            },
+            Level {
+                temperature: Some(290.0),
+                wind_speed: Some(70.0),
+                ..Level::default()
+            },
+        ];
+        let mut sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        let issues = RangeCheck::default().apply(&mut sounding);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].level, 1);
+        assert!(sounding.levels()[1].has_qc_flag(Level::QC_RANGE));
+
+        let wind_speed = Descriptor::new(0, 11, 2);
+        let strict = RangeCheck::default()
+            .with_limits(wind_speed, 0.0, 50.0)
+            .without(Descriptor::new(0, 12, 101));
+        assert_eq!(strict.limits(wind_speed), Some((0.0, 50.0)));
+        let issues = strict.apply(&mut sounding);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].level, 2);
+    }
+
+    #[test]
+    fn test_report() {
+        let levels = vec![
+            Level {
+                temperature: Some(290.0),
+                ..Level::default()
+            },
+            Level {
+                temperature: Some(400.0),
+                ..Level::default()
+            },
+        ];
+        let mut sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        let report = sounding.quality_control(&default_checks());
+        assert!(!report.passed());
+        assert_eq!(report.checks.len(), 4);
+        assert_eq!(report.flagged_levels(), vec![1]);
+
+        let mut csv = vec![];
+        report.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("check,level,flag,message"));
+        assert_eq!(
+            lines.next(),
+            Some("range,1,32,\"temperature 400 outside [160, 340]\"")
+        );
+        assert_eq!(lines.next(), Some("monotonic,,,"));
+
+        let mut json = vec![];
+        report.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains("\"passed\": false,"));
+        assert!(json.contains("{\"name\": \"hydrostatic\", \"passed\": true, \"issues\": ["));
+    }
+}
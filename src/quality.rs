@@ -0,0 +1,172 @@
+use crate::{
+    section3::Descriptor,
+    section4::{DataNode, Value},
+    sounding::Level,
+};
+
+const DATA_PRESENT: Descriptor = Descriptor::new(0, 31, 31);
+const QUALITY_INFORMATION: Descriptor = Descriptor::new(0, 33, 2);
+const QUALITY_INFORMATION_3BIT: Descriptor = Descriptor::new(0, 33, 3);
+const PERCENT_CONFIDENCE: Descriptor = Descriptor::new(0, 33, 7);
+const QC_INDICATION: Descriptor = Descriptor::new(0, 33, 20);
+
+/// Values with less confidence than this, %, are bad.
+const MIN_CONFIDENCE: f64 = 50.0;
+
+/// An element of a subset, and the index of the level it's part of.
+struct Entry<'a> {
+    /// `None` for a delayed replication factor, which bit maps count as an element.
+    descriptor: Option<Descriptor>,
+    value: Option<&'a Value>,
+    level: Option<usize>,
+}
+
+/// Set `Level::QC_REPORTED` on the levels with a value the quality information in the subset
+/// (operator 2-22-000 and a data present bit map) marks as suspect or bad. Only the first bit map
+/// is used, and the bits refer back to the elements just before it.
+pub(crate) fn flag_reported_quality(
+    nodes: &[DataNode],
+    level_nodes: &[Vec<DataNode>],
+    levels: &mut [Level],
+) {
+    let mut entries = vec![];
+    flatten(nodes, level_nodes, None, &mut entries);
+
+    let Some(start) = entries
+        .iter()
+        .position(|e| e.descriptor == Some(DATA_PRESENT))
+    else {
+        return;
+    };
+    let len = entries[start..]
+        .iter()
+        .take_while(|e| e.descriptor == Some(DATA_PRESENT))
+        .count();
+    // A delayed replication of the bit map has its factor after the operator too.
+    let data_end = match start.checked_sub(1) {
+        Some(i) if entries[i].descriptor.is_none() => i,
+        _ => start,
+    };
+    let Some(first) = data_end.checked_sub(len) else {
+        return;
+    };
+
+    let present = (0..len)
+        .filter(|&i| entries[start + i].value.and_then(Value::as_f64) == Some(0.0))
+        .map(|i| first + i);
+    let quality = entries[start + len..].iter().filter(|e| {
+        e.descriptor
+            .is_some_and(|d| d.f_value() == 0 && d.x_value() == 33)
+    });
+    for (i, q) in present.zip(quality) {
+        if let (Some(level), Some(descriptor)) = (entries[i].level, q.descriptor) {
+            if is_bad(descriptor, q.value) {
+                levels[level].qc |= Level::QC_REPORTED;
+            }
+        }
+    }
+}
+
+fn is_bad(descriptor: Descriptor, value: Option<&Value>) -> bool {
+    let Some(value) = value.and_then(Value::as_f64) else {
+        return false;
+    };
+
+    match descriptor {
+        // Code tables 0-33-002 and 0-33-003: 1 is data suspect.
+        QUALITY_INFORMATION | QUALITY_INFORMATION_3BIT => value == 1.0,
+        PERCENT_CONFIDENCE => value < MIN_CONFIDENCE,
+        // Code table 0-33-020: inconsistent, doubtful, or wrong.
+        QC_INDICATION => (1.0..=3.0).contains(&value),
+        _ => false,
+    }
+}
+
+fn flatten<'a>(
+    nodes: &'a [DataNode],
+    level_nodes: &[Vec<DataNode>],
+    level: Option<usize>,
+    out: &mut Vec<Entry<'a>>,
+) {
+    for node in nodes {
+        match node {
+            DataNode::Element {
+                descriptor, value, ..
+            } => out.push(Entry {
+                descriptor: Some(*descriptor),
+                value: Some(value),
+                level,
+            }),
+            DataNode::Sequence { children, .. } => flatten(children, level_nodes, level, out),
+            DataNode::Replication {
+                descriptor,
+                repetitions,
+            } => {
+                if descriptor.y_value() == 0 {
+                    out.push(Entry {
+                        descriptor: None,
+                        value: None,
+                        level,
+                    });
+                }
+                let is_levels = std::ptr::eq(repetitions.as_slice(), level_nodes);
+                for (i, rep) in repetitions.iter().enumerate() {
+                    flatten(
+                        rep,
+                        level_nodes,
+                        if is_levels { Some(i) } else { level },
+                        out,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Sounding, PRESSURE, TEMPERATURE};
+
+    #[test]
+    fn test_flag_reported_quality() {
+        let element = |descriptor, value| DataNode::Element {
+            descriptor,
+            value: Value::Float(value),
+            raw: None,
+        };
+        let delayed = |descriptor, repetitions| DataNode::Replication {
+            descriptor,
+            repetitions,
+        };
+
+        // Two levels, then quality information for the 5 elements before it: the levels'
+        // delayed replication factor and their pressures and temperatures.
+        let profile = delayed(
+            Descriptor::new(1, 2, 0),
+            vec![
+                vec![element(PRESSURE, 85000.0), element(TEMPERATURE, 280.0)],
+                vec![element(PRESSURE, 70000.0), element(TEMPERATURE, 270.0)],
+            ],
+        );
+        let bit_map = delayed(
+            Descriptor::new(1, 1, 0),
+            [0.0, 1.0, 0.0, 0.0, 0.0]
+                .map(|bit| vec![element(DATA_PRESENT, bit)])
+                .to_vec(),
+        );
+        let confidence = delayed(
+            Descriptor::new(1, 1, 0),
+            [90.0, 95.0, 20.0, 80.0]
+                .map(|c| vec![element(PERCENT_CONFIDENCE, c)])
+                .to_vec(),
+        );
+        let nodes = [profile, bit_map, confidence];
+
+        let sounding = Sounding::from_subset(&nodes, Phase::Ascent).unwrap();
+        let levels = sounding.levels();
+        assert!(!levels[0].has_qc_flag(Level::QC_REPORTED));
+        // 20% confidence in the second pressure.
+        assert!(levels[1].has_qc_flag(Level::QC_REPORTED));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::sounding::{Sounding, Timestamp};
+
+/// What `Sounding::redacted` removes or coarsens before a sounding is shared. The default
+/// strips call signs, rounds launch times down to the hour, and rounds the station position to
+/// 0.1 degrees, but keeps the level displacements and radiosonde type.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Redaction {
+    /// Remove the call sign, which identifies ships and mobile stations. WMO station numbers
+    /// are public and kept.
+    pub strip_call_sign: bool,
+    /// Round the launch time down to the hour.
+    pub hourly_time: bool,
+    /// Round the station latitude and longitude to this many decimal places.
+    pub position_decimals: Option<i32>,
+    /// Remove the latitude and longitude displacement of each level, the balloon's track.
+    pub strip_displacements: bool,
+    /// Remove the radiosonde type.
+    pub strip_radiosonde_type: bool,
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Redaction {
+            strip_call_sign: true,
+            hourly_time: true,
+            position_decimals: Some(1),
+            strip_displacements: false,
+            strip_radiosonde_type: false,
+        }
+    }
+}
+
+impl Sounding {
+    /// A copy of the sounding with identifying metadata removed or coarsened, to pass to any of
+    /// the exporters. Serial numbers aren't decoded, so there are none to remove.
+    pub fn redacted(&self, redaction: &Redaction) -> Sounding {
+        let mut station = self.station().clone();
+        if redaction.strip_call_sign {
+            station.call_sign = None;
+        }
+        if let Some(decimals) = redaction.position_decimals {
+            let factor = 10f64.powi(decimals);
+            let round = |val: f64| (val * factor).round() / factor;
+            station.latitude = station.latitude.map(round);
+            station.longitude = station.longitude.map(round);
+        }
+
+        let launch_time = self.launch_time().map(|t| {
+            if redaction.hourly_time {
+                Timestamp {
+                    minute: 0,
+                    second: 0,
+                    ..t
+                }
+            } else {
+                t
+            }
+        });
+        let radiosonde_type = self
+            .radiosonde_type()
+            .filter(|_| !redaction.strip_radiosonde_type);
+
+        let mut levels = self.levels().to_vec();
+        if redaction.strip_displacements {
+            for lvl in &mut levels {
+                lvl.lat_displacement = None;
+                lvl.lon_displacement = None;
+            }
+        }
+
+        Sounding {
+            station,
+            launch_time,
+            radiosonde_type,
+            ..self.with_levels(levels)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        sounding::{Level, Phase, Station},
+        ReportType,
+    };
+
+    #[test]
+    fn test_redacted() {
+        let station = Station {
+            call_sign: Some("WTEC".to_owned()),
+            latitude: Some(21.3456),
+            longitude: Some(-157.8765),
+            ..Station::default()
+        };
+        let time = Timestamp {
+            year: 2020,
+            month: 6,
+            day: 1,
+            hour: 11,
+            minute: 17,
+            second: 42,
+        };
+        let level = Level {
+            lat_displacement: Some(0.01),
+            ..Level::default()
+        };
+        let mut sounding =
+            Sounding::new(Phase::Ascent, station, Some(time), Some(152), vec![level]);
+        sounding.set_report_type(Some(ReportType::TempMobil));
+
+        let redacted = sounding.redacted(&Redaction::default());
+        assert_eq!(redacted.station().call_sign, None);
+        assert_eq!(redacted.station().latitude, Some(21.3));
+        assert_eq!(redacted.station().longitude, Some(-157.9));
+        let t = redacted.launch_time().unwrap();
+        assert_eq!((t.hour, t.minute, t.second), (11, 0, 0));
+        assert_eq!(redacted.radiosonde_type(), Some(152));
+        assert_eq!(redacted.levels()[0].lat_displacement, Some(0.01));
+        assert_eq!(redacted.report_type(), Some(ReportType::TempMobil));
+
+        let redacted = sounding.redacted(&Redaction {
+            strip_displacements: true,
+            strip_radiosonde_type: true,
+            ..Redaction::default()
+        });
+        assert_eq!(redacted.radiosonde_type(), None);
+        assert_eq!(redacted.levels()[0].lat_displacement, None);
+    }
+}
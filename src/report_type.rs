@@ -0,0 +1,150 @@
+use crate::{section3::Descriptor, sounding::Station};
+use std::{error::Error, fmt::Display, str::FromStr};
+
+/// The kind of upper air report, as named in the WMO TAC codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReportType {
+    Temp,
+    TempShip,
+    TempMobil,
+    TempDrop,
+    Pilot,
+    PilotShip,
+    PilotMobil,
+}
+
+impl ReportType {
+    const PILOT_TEMPLATES: [Descriptor; 2] = [Descriptor::new(3, 9, 50), Descriptor::new(3, 9, 51)];
+    const TEMP_TEMPLATES: [Descriptor; 3] = [
+        Descriptor::new(3, 9, 52),
+        Descriptor::new(3, 9, 56),
+        Descriptor::new(3, 9, 57),
+    ];
+    const DROP_TEMPLATE: Descriptor = Descriptor::new(3, 9, 53);
+
+    /// Classify a report by its international data sub-category (common code table C-13) for
+    /// data category 2, or failing that by its template. A template alone can't tell a mobile
+    /// station from a fixed one, but a station with a call sign and no WMO number is a ship.
+    pub(crate) fn classify(
+        data_category: u8,
+        data_sub_category: u8,
+        descriptors: &[Descriptor],
+        station: Option<&Station>,
+    ) -> Option<Self> {
+        if data_category == 2 {
+            let from_sub_category = match data_sub_category {
+                1 => Some(ReportType::Pilot),
+                2 => Some(ReportType::PilotShip),
+                3 => Some(ReportType::PilotMobil),
+                4 => Some(ReportType::Temp),
+                5 => Some(ReportType::TempShip),
+                6 => Some(ReportType::TempMobil),
+                7 => Some(ReportType::TempDrop),
+                _ => None,
+            };
+            if from_sub_category.is_some() {
+                return from_sub_category;
+            }
+        }
+
+        let is_ship = station.is_some_and(|station| {
+            station.call_sign.is_some()
+                && (station.wmo_block.is_none() || station.wmo_station.is_none())
+        });
+        descriptors.iter().find_map(|d| {
+            if *d == Self::DROP_TEMPLATE {
+                Some(ReportType::TempDrop)
+            } else if Self::TEMP_TEMPLATES.contains(d) {
+                Some(if is_ship {
+                    ReportType::TempShip
+                } else {
+                    ReportType::Temp
+                })
+            } else if Self::PILOT_TEMPLATES.contains(d) {
+                Some(if is_ship {
+                    ReportType::PilotShip
+                } else {
+                    ReportType::Pilot
+                })
+            } else {
+                None
+            }
+        })
+    }
+
+    /// A lower case name for file names, e.g. `temp_ship`.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            ReportType::Temp => "temp",
+            ReportType::TempShip => "temp_ship",
+            ReportType::TempMobil => "temp_mobil",
+            ReportType::TempDrop => "temp_drop",
+            ReportType::Pilot => "pilot",
+            ReportType::PilotShip => "pilot_ship",
+            ReportType::PilotMobil => "pilot_mobil",
+        }
+    }
+}
+
+/// Parses a `tag`, e.g. `temp_drop`.
+impl FromStr for ReportType {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const ALL: [ReportType; 7] = [
+            ReportType::Temp,
+            ReportType::TempShip,
+            ReportType::TempMobil,
+            ReportType::TempDrop,
+            ReportType::Pilot,
+            ReportType::PilotShip,
+            ReportType::PilotMobil,
+        ];
+        ALL.into_iter()
+            .find(|r| r.tag() == s)
+            .ok_or_else(|| format!("Unknown report type: {}", s).into())
+    }
+}
+
+impl Display for ReportType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.tag().replace('_', " ").to_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let temp = [Descriptor::new(3, 9, 52)];
+        assert_eq!(
+            ReportType::classify(2, 5, &temp, None),
+            Some(ReportType::TempShip)
+        );
+        assert_eq!(
+            ReportType::classify(2, 255, &temp, None),
+            Some(ReportType::Temp)
+        );
+
+        let ship = Station {
+            call_sign: Some("WTEC".to_owned()),
+            ..Station::default()
+        };
+        assert_eq!(
+            ReportType::classify(2, 255, &temp, Some(&ship)),
+            Some(ReportType::TempShip)
+        );
+        assert_eq!(
+            ReportType::classify(2, 0, &[Descriptor::new(3, 9, 53)], Some(&ship)),
+            Some(ReportType::TempDrop)
+        );
+        assert_eq!(ReportType::classify(0, 4, &[], None), None);
+        assert_eq!(ReportType::TempMobil.to_string(), "TEMP MOBIL");
+        assert_eq!(
+            "pilot_ship".parse::<ReportType>().ok(),
+            Some(ReportType::PilotShip)
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Products from a NOAAPort SBN feed or an LDM product stream.
+//!
+//! Each product is framed as `SOH \r\r\n NNN \r\r\n` with a sequence number, then optionally a
+//! communications control block (CCB), the WMO abbreviated heading `TTAAii CCCC YYGGgg [BBB]`
+//! ending in `\r\r\n`, the product data, and `\r\r\n ETX`.
+
+use crate::{read_3_octet_usize, BufrMessage, MessageDecoder};
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+};
+
+const SOH: u8 = 0x01;
+const START: &[u8] = b"\x01\r\r\n";
+const END: &[u8] = b"\r\r\n\x03";
+const LINE_END: &[u8] = b"\r\r\n";
+
+/// A WMO abbreviated heading, e.g. `IUSZ53 KWBC 311800 RRA`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WmoHeading {
+    /// TTAAii, the data type and area.
+    pub data_type: String,
+    /// CCCC, the originating centre.
+    pub centre: String,
+    /// YYGGgg, the day of the month, hour, and minute.
+    pub time: String,
+    /// BBB, set for delayed, corrected, or amended products.
+    pub bbb: Option<String>,
+}
+
+impl WmoHeading {
+    /// Parse a heading line without its line ending. `None` if it isn't one.
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (data_type, centre, time, bbb) = match fields[..] {
+            [data_type, centre, time] => (data_type, centre, time, None),
+            [data_type, centre, time, bbb] => (data_type, centre, time, Some(bbb)),
+            _ => return None,
+        };
+        if data_type.len() != 6
+            || centre.len() != 4
+            || time.len() != 6
+            || !time.bytes().all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+
+        Some(WmoHeading {
+            data_type: data_type.to_owned(),
+            centre: centre.to_owned(),
+            time: time.to_owned(),
+            bbb: bbb.map(str::to_owned),
+        })
+    }
+}
+
+/// One framed product, see `Products`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Product {
+    /// The sequence number after the SOH.
+    pub sequence: Option<u32>,
+    /// The communications control block, if the feed kept it.
+    pub ccb: Option<Vec<u8>>,
+    pub heading: Option<WmoHeading>,
+    /// Everything after the heading, up to the end of the frame.
+    pub data: Vec<u8>,
+}
+
+impl Product {
+    /// Decode the BUFR messages in the product data.
+    pub fn bufr_messages(&self) -> Vec<Result<BufrMessage, Box<dyn Error>>> {
+        MessageDecoder::default()
+            .messages(Cursor::new(&self.data))
+            .collect()
+    }
+
+    /// Split one frame, from the SOH up to the ETX.
+    fn parse(frame: &[u8]) -> Self {
+        let mut rest = &frame[START.len()..];
+
+        let mut sequence = None;
+        if let Some((line, after)) = split_line(rest) {
+            if let Ok(num) = std::str::from_utf8(line).unwrap_or("").trim().parse() {
+                sequence = Some(num);
+                rest = after;
+            }
+        }
+
+        let mut ccb = None;
+        let mut heading = parse_heading(rest);
+        // Text headings can look like a CCB flag too, so only look for one without a heading.
+        // The flag is 01 in the top two bits, and the length is in units of 2 bytes.
+        if heading.is_none() {
+            if let [flag, len, ..] = *rest {
+                let len = 2 * ((((flag & 0x3f) as usize) << 8) | len as usize);
+                if flag & 0xc0 == 0x40 && len >= 2 && len <= rest.len() {
+                    ccb = Some(rest[..len].to_vec());
+                    rest = &rest[len..];
+                    heading = parse_heading(rest);
+                }
+            }
+        }
+        if let Some((_, after)) = heading {
+            rest = after;
+        }
+
+        Product {
+            sequence,
+            ccb,
+            heading: heading.map(|(heading, _)| heading),
+            data: rest.to_vec(),
+        }
+    }
+}
+
+/// A heading line off the front of `data`, and the data after it.
+fn parse_heading(data: &[u8]) -> Option<(WmoHeading, &[u8])> {
+    let (line, after) = split_line(data)?;
+    let heading = WmoHeading::parse(std::str::from_utf8(line).ok()?)?;
+    Some((heading, after))
+}
+
+/// Split a line ending in `\r\r\n` off the front of `data`.
+fn split_line(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    // Headings are short, so don't search into binary product data.
+    let end = data
+        .get(..data.len().min(128))?
+        .windows(LINE_END.len())
+        .position(|w| w == LINE_END)?;
+    Some((&data[..end], &data[end + LINE_END.len()..]))
+}
+
+/// Read products from a stream as they arrive, skipping any bytes between frames.
+pub struct Products<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Products<R> {
+    pub fn new(reader: R) -> Self {
+        Products {
+            reader,
+            buffer: vec![],
+            eof: false,
+        }
+    }
+
+    /// Move the first SOH to the start of the buffer, dropping anything before it, and find the
+    /// end of its frame.
+    fn find_frame(&mut self) -> Option<usize> {
+        let start = match self.buffer.windows(START.len()).position(|w| w == START) {
+            Some(start) => start,
+            None => {
+                // Keep a trailing partial start marker.
+                let keep = self
+                    .buffer
+                    .iter()
+                    .rposition(|&b| b == SOH)
+                    .filter(|&i| self.buffer.len() - i < START.len())
+                    .unwrap_or(self.buffer.len());
+                self.buffer.drain(..keep);
+                return None;
+            }
+        };
+        self.buffer.drain(..start);
+
+        find_end(&self.buffer, START.len())
+    }
+}
+
+/// The position of the end marker, stepping over BUFR messages so binary data that happens to
+/// look like the marker isn't taken for it. `None` if the frame isn't complete yet.
+fn find_end(data: &[u8], from: usize) -> Option<usize> {
+    let mut i = from;
+    while i + END.len() <= data.len() {
+        if data[i..].starts_with(b"BUFR") {
+            if let Some(len) = data
+                .get(i + 4..i + 7)
+                .and_then(|b| read_3_octet_usize(b).ok())
+            {
+                if len >= 8 {
+                    i += len;
+                    continue;
+                }
+            }
+        }
+        if data[i..].starts_with(END) {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+impl<R: Read> Iterator for Products<R> {
+    type Item = Result<Product, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(end) = self.find_frame() {
+                let product = Product::parse(&self.buffer[..end]);
+                self.buffer.drain(..end + END.len());
+                return Some(Ok(product));
+            }
+
+            if self.eof {
+                if self.buffer.is_empty() {
+                    return None;
+                }
+                self.buffer.clear();
+                return Some(Err("Stream ended in the middle of a product".into()));
+            }
+
+            let mut chunk = [0; 64 * 1024];
+            match self.reader.read(&mut chunk) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.eof = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_products() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let message = crate::read_bufr_bytes(&file[start..]).unwrap();
+
+        let mut stream = b"line noise".to_vec();
+        stream.extend(b"\x01\r\r\n123 \r\r\nIUSZ53 KWBC 311800\r\r\n");
+        stream.extend(&message);
+        stream.extend(b"\r\r\n\x03");
+        // A text product with a 24 byte CCB and a corrected heading.
+        stream.extend(b"\x01\r\r\n124 \r\r\n\x40\x0c");
+        stream.extend([0; 22]);
+        stream.extend(b"SXUS70 KWNO 311805 CCA\r\r\nSome text\r\r\n\x03");
+        // Cut off part way through.
+        stream.extend(b"\x01\r\r\n125 \r\r\nIUSZ53 KWBC 311800\r\r\nBUFR");
+
+        let products: Vec<_> = Products::new(Cursor::new(stream)).collect();
+        assert_eq!(products.len(), 3);
+        assert!(products[2].is_err());
+
+        let bufr = products[0].as_ref().unwrap();
+        assert_eq!(bufr.sequence, Some(123));
+        assert_eq!(bufr.ccb, None);
+        assert_eq!(
+            bufr.heading,
+            Some(WmoHeading {
+                data_type: "IUSZ53".to_owned(),
+                centre: "KWBC".to_owned(),
+                time: "311800".to_owned(),
+                bbb: None,
+            })
+        );
+        assert_eq!(bufr.data, message);
+        let messages = bufr.bufr_messages();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].as_ref().unwrap().soundings().len(), 1);
+
+        let text = products[1].as_ref().unwrap();
+        assert_eq!(text.sequence, Some(124));
+        assert_eq!(text.ccb.as_ref().map(Vec::len), Some(24));
+        let heading = text.heading.as_ref().unwrap();
+        assert_eq!(
+            (heading.data_type.as_str(), heading.bbb.as_deref()),
+            ("SXUS70", Some("CCA"))
+        );
+        assert_eq!(text.data, b"Some text");
+        assert!(text.bufr_messages().is_empty());
+
+        assert_eq!(WmoHeading::parse("not a heading"), None);
+    }
+}
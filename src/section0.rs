@@ -6,6 +6,17 @@ pub struct Section0 {
     bufr_version: u8,
 }
 
+impl Section0 {
+    /// Octets in the whole message, from `BUFR` through `7777`.
+    pub fn message_size(&self) -> usize {
+        self.message_size
+    }
+
+    pub fn bufr_version(&self) -> u8 {
+        self.bufr_version
+    }
+}
+
 impl Display for Section0 {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         writeln!(f, "  BUFR version: {}", self.bufr_version)?;
@@ -18,8 +29,9 @@ impl Display for Section0 {
 pub(super) fn read_section_0(mut f: impl Read) -> Result<Section0, Box<dyn Error>> {
     let mut bufr_name: [u8; 4] = [0; 4];
     f.read_exact(&mut bufr_name)?;
-    let bufr_name = std::str::from_utf8(&bufr_name)?;
-    assert_eq!(bufr_name, "BUFR");
+    if &bufr_name != b"BUFR" {
+        return Err("Not a BUFR message".into());
+    }
 
     let message_size = read_3_octet_usize(&mut f)?;
     let bufr_version = read_1_octet_u8(&mut f)?;
@@ -1,4 +1,5 @@
 use super::{read_1_octet_u8, read_2_octet_u16, read_3_octet_usize};
+use crate::sounding::Timestamp;
 use std::{error::Error, fmt::Display, io::Read};
 
 pub struct Section1 {
@@ -26,6 +27,59 @@ impl Section1 {
     pub fn section_2_exists(&self) -> bool {
         self.section_2_present
     }
+
+    /// The BUFR master table, 0 for meteorology and 10 for oceanography.
+    pub fn master_table(&self) -> u8 {
+        self.master_table
+    }
+
+    /// Common code table C-11.
+    pub fn originating_centre(&self) -> u16 {
+        self.originating_center
+    }
+
+    pub fn originating_subcenter(&self) -> u16 {
+        self.originating_subcenter
+    }
+
+    pub fn master_table_version(&self) -> u8 {
+        self.bufr_master_table_version
+    }
+
+    pub fn local_tables_version(&self) -> u8 {
+        self.local_tables_version
+    }
+
+    pub fn update_number(&self) -> u8 {
+        self.update_num
+    }
+
+    pub fn data_category(&self) -> u8 {
+        self.data_category
+    }
+
+    /// The international data sub-category, common code table C-13.
+    pub fn data_subcategory(&self) -> u8 {
+        self.data_subcategory
+    }
+
+    /// The typical time of the data, for soundings this is the nominal (synoptic) time.
+    pub fn time(&self) -> Timestamp {
+        Timestamp {
+            year: self.year,
+            month: self.month,
+            day: self.day,
+            hour: self.hour,
+            minute: self.minute,
+            second: self.second,
+        }
+    }
+
+    /// Table A category 11 messages carry table definitions rather than observations, e.g. the
+    /// dictionary (DX) messages at the start of NCEP tanks.
+    pub fn is_table_message(&self) -> bool {
+        self.data_category == 11
+    }
 }
 
 impl Display for Section1 {
@@ -80,6 +134,9 @@ pub(super) fn read_section_1(mut f: impl Read) -> Result<Section1, Box<dyn Error
     let minute = read_1_octet_u8(&mut f)?;                                          // octet 21
     let second = read_1_octet_u8(&mut f)?;                                          // octet 22
 
+    if section_size < 22 {
+        return Err(format!("Section 1 is too short: {} octets", section_size).into());
+    }
     let mut extra_data = vec![];
     f.take(section_size as u64 - 22).read_to_end(&mut extra_data)?;
 
@@ -27,10 +27,12 @@ pub(super) fn read_section_2(
 ) -> Result<Section2, Box<dyn Error>> {
     if section_2_present {
         let section_size = read_3_octet_usize(&mut f)?;
+        if section_size < 4 {
+            return Err(format!("Section 2 is too short: {} octets", section_size).into());
+        }
 
-        // TODO: Check if this is zero and return an error if it isn't
-        let reserved = read_1_octet_u8(&mut f)?;
-        debug_assert_eq!(reserved, 0);
+        // Reserved, should be zero but ignored so a slightly off message still decodes.
+        let _reserved = read_1_octet_u8(&mut f)?;
 
         let mut section_data = vec![];
         f.take(section_size as u64 - 4)
@@ -1,5 +1,5 @@
 use super::{read_1_octet_u8, read_2_octet_u16, read_3_octet_usize};
-use std::{error::Error, fmt::Display, io::Read};
+use std::{error::Error, fmt::Display, io::Read, str::FromStr};
 
 pub struct Section3 {
     section_size: usize,
@@ -34,9 +34,14 @@ impl Section3 {
     pub fn compressed_data(&self) -> bool {
         self.compressed_data
     }
+
+    pub fn num_datasets(&self) -> u16 {
+        self.num_datasets
+    }
 }
 
-#[derive(Clone, Copy, Debug, Hash)]
+/// An F-X-Y descriptor. Descriptors order by F, then X, then Y.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Descriptor {
     f: u8,
     x: u8,
@@ -60,6 +65,10 @@ impl Display for Descriptor {
 }
 
 impl Descriptor {
+    pub const fn new(f: u8, x: u8, y: u8) -> Self {
+        Descriptor { f, x, y }
+    }
+
     pub fn string_form(&self) -> String {
         format!("{:01}{:02}{:03}", self.f, self.x, self.y)
     }
@@ -69,9 +78,9 @@ impl Descriptor {
         let xs = &s[1..3];
         let ys = &s[3..];
 
-        let f = u8::from_str_radix(fs, 10).unwrap();
-        let x = u8::from_str_radix(xs, 10).unwrap();
-        let y = u8::from_str_radix(ys, 10).unwrap();
+        let f = fs.parse::<u8>().unwrap();
+        let x = xs.parse::<u8>().unwrap();
+        let y = ys.parse::<u8>().unwrap();
 
         Descriptor { f, x, y }
     }
@@ -85,6 +94,13 @@ impl Descriptor {
         Descriptor { f, x, y }
     }
 
+    /// The 16 bit form used in Section 3, the inverse of `decode_binary_descriptor`.
+    pub fn encode_binary_descriptor(&self) -> u16 {
+        (u16::from(self.f & 0b11) << 14)
+            | (u16::from(self.x & 0b0011_1111) << 8)
+            | u16::from(self.y)
+    }
+
     pub fn f_value(&self) -> u8 {
         self.f
     }
@@ -98,6 +114,29 @@ impl Descriptor {
     }
 }
 
+/// Parses the six digit form, `"012101"`, or with dashes, `"0-12-101"`.
+impl FromStr for Descriptor {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('-').collect();
+        let (fs, xs, ys) = match parts[..] {
+            [f, x, y] => (f, x, y),
+            [fxy] if fxy.len() == 6 && fxy.is_ascii() => (&fxy[0..1], &fxy[1..3], &fxy[3..]),
+            _ => return Err(format!("Invalid descriptor: {}", s).into()),
+        };
+
+        let f = fs.parse::<u8>()?;
+        let x = xs.parse::<u8>()?;
+        let y = ys.parse::<u8>()?;
+        if f > 3 || x > 63 {
+            return Err(format!("Invalid descriptor: {}", s).into());
+        }
+
+        Ok(Descriptor { f, x, y })
+    }
+}
+
 #[rustfmt::skip]
 pub(super) fn read_section_3(mut f: impl Read) -> Result<Section3, Box<dyn Error>> {
     let mut octets_read: usize = 0;
@@ -105,10 +144,13 @@ pub(super) fn read_section_3(mut f: impl Read) -> Result<Section3, Box<dyn Error
     let section_size = read_3_octet_usize(&mut f)?;
     octets_read += 3;
 
-    // TODO: Check if this is zero and return an error if it isn't
-    let reserved = read_1_octet_u8(&mut f)?;
+    if section_size < 7 {
+        return Err(format!("Section 3 is too short: {} octets", section_size).into());
+    }
+
+    // Reserved, should be zero but ignored so a slightly off message still decodes.
+    let _reserved = read_1_octet_u8(&mut f)?;
     octets_read += 1;
-    debug_assert_eq!(reserved, 0, "Section 3: Octet 4 must be zero.");
 
     let num_datasets = read_2_octet_u16(&mut f)?;
     octets_read += 2;
@@ -118,9 +160,7 @@ pub(super) fn read_section_3(mut f: impl Read) -> Result<Section3, Box<dyn Error
 
     let observed_data = (d_flags & 0b1000_0000u8) > 0;
     let compressed_data = (d_flags & 0b0100_0000u8) > 0;
-    let bits_3_to_8 = d_flags & 0b0011_1111u8;
-    // TODO: Check if this is zero and return an error if it isn't
-    debug_assert_eq!(bits_3_to_8, 0, "Section 3: Bits 3-8 nonzero in octet 7.");
+    // Bits 3-8 are reserved and ignored.
 
     let num_descriptors = (section_size - 7) / 2;
     //dbg!(num_descriptors);
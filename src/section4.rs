@@ -1,255 +1,924 @@
 use super::{read_1_octet_u8, read_3_octet_usize};
 use crate::{
-    bit_buffer::BitBuffer,
+    bit_buffer::{apply_scale, BitBuffer},
+    builder::{check_budget, check_cancelled},
+    compressed::read_compressed_subsets,
+    expansion::MAX_DEPTH,
     section3::{Descriptor, Section3},
-    table_b, table_d,
+    tables::{self, TableOverrides},
+    trace::TraceEntry,
+    unknown::{UnknownDescriptorAction, UnknownDescriptorHandler},
+    DecodeOptions,
 };
-use std::{collections::HashMap, error::Error, fmt::Display, io::Read};
+use std::{error::Error, fmt::Display, io::Read, ops::Range, sync::atomic::AtomicBool};
 
 pub struct Section4 {
     section_size: usize,
-    float_vals: HashMap<&'static str, f64>,
-    float_arrays: HashMap<&'static str, Vec<f64>>,
+    subsets: Vec<Vec<DataNode>>,
+    trace: Vec<TraceEntry>,
+    // The bits of each subset, counted from the end of the 4 octet section header.
+    subset_bits: Vec<Range<usize>>,
+    warnings: Vec<String>,
+}
+
+impl Section4 {
+    pub fn subsets(&self) -> &[Vec<DataNode>] {
+        &self.subsets
+    }
+
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
+    pub(crate) fn subset_bits(&self) -> &[Range<usize>] {
+        &self.subset_bits
+    }
+
+    pub(crate) fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
 }
 
 impl Display for Section4 {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         writeln!(f, "Section Size: {}", self.section_size)?;
+        writeln!(f, "     Subsets: {}", self.subsets.len())?;
         writeln!(f)?;
 
-        writeln!(f, "Float Values:")?;
-        for (k, v) in self.float_vals.iter() {
-            writeln!(f, "    {} : {}", k, v)?;
+        for (i, subset) in self.subsets.iter().enumerate() {
+            writeln!(f, "Subset {}:", i + 1)?;
+            for node in subset {
+                node.fmt_indented(f, 4)?;
+            }
+            writeln!(f)?;
         }
-        writeln!(f)?;
 
-        writeln!(f, "Float Arrays:")?;
-        for (k, v) in self.float_arrays.iter() {
-            writeln!(f, "    {}: {} values", k, v.len())?;
-        }
-        writeln!(f)?;
         Ok(())
     }
 }
 
-pub struct TableBEntry {
+#[derive(Clone, Copy, Debug)]
+pub struct TableBEntry<'a> {
     pub(crate) width_bits: usize,
-    pub(crate) element_name: &'static str,
-    pub(crate) units: &'static str,
+    pub(crate) element_name: &'a str,
+    pub(crate) units: &'a str,
     pub(crate) reference_val: i64,
     pub(crate) scale_val: i32,
+    pub(crate) crex_units: &'a str,
+    pub(crate) crex_scale: i32,
+    pub(crate) crex_width: usize,
 }
 
-fn read_element_descriptor(
-    f: &mut BitBuffer,
-    desc: &Descriptor,
-    float_vals: &mut HashMap<&'static str, f64>,
-    float_arrays: &mut HashMap<&'static str, Vec<f64>>,
-    is_array_val: bool,
-) -> Result<(), Box<dyn Error>> {
-    //dbg!("read_element_descriptor");
-
-    let desc = table_b::TABLE_B.get(&desc.string_form() as &str).unwrap();
-    let bits = desc.width_bits;
-    let name = desc.element_name;
-    //dbg!(name, desc.units, bits, desc.reference_val, desc.scale_val);
-
-    match desc.units {
-        "CCITT IA5" => {
-            let _val = f.read_text(bits)?;
-            //dbg!(val, "-+-+-+-+-+-+-+-+-+-+-+-+-+-");
-        }
-        "Numeric" => {
-            let _val = f.read_i64(bits, desc.reference_val)?;
-            //dbg!(val, "-*-*-*-*-*-*-*-*-*-*-*-*-*-");
-        }
-        "Code table" => {
-            let _val = f.read_i64(bits, desc.reference_val)?;
-            //dbg!(val, "-^-^-^-^-^-^-^-^-^-^-^-^-^-");
-        }
-        _ => {
-            let val = f.read_f64(bits, desc.reference_val, desc.scale_val)?;
-            //dbg!(val, "-x-x-x-x-x-x-x-x-x-x-x-x-x-");
-            if is_array_val {
-                let vec = float_arrays.entry(name).or_insert(vec![]);
-                vec.push(val.unwrap_or(f64::NAN));
-            } else {
-                let val = val.unwrap_or(f64::NAN);
-                let v = float_vals.insert(name, val);
-                debug_assert!(v.is_none(), "overwriting float val {}", name);
-            }
-        }
-    }
-
-    Ok(())
-}
-
-fn read_replication_descriptor<'a>(
-    f: &mut BitBuffer,
-    desc: &Descriptor,
-    iter: &mut dyn Iterator<Item = &'a Descriptor>,
-    float_vals: &mut HashMap<&'static str, f64>,
-    float_arrays: &mut HashMap<&'static str, Vec<f64>>,
-) -> Result<(), Box<dyn Error>> {
-    //dbg!("read_replication_descriptor");
-
-    debug_assert_eq!(
-        desc.f_value(),
-        1,
-        "Not a replication descriptor, f={}",
-        desc.f_value()
-    );
-
-    let num_descriptors = desc.x_value();
-    //dbg!(num_descriptors);
-
-    let mut num_repititions: usize = desc.y_value() as usize;
-    //dbg!(num_repititions);
-
-    if num_repititions == 0 {
-        let reps = iter.next().unwrap();
-        debug_assert_eq!(reps.f_value(), 0);
-        debug_assert_eq!(reps.x_value(), 31);
-        let bits = match reps.y_value() {
-            1 => 8,
-            2 => 16,
-            _ => panic!(
-                "unimplemented replication descriptor: {}",
-                reps.string_form()
-            ),
-        };
+/// A single decoded element value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Missing,
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    /// A value decoded with `DecoderBuilder::exact_decimals`.
+    Decimal(Decimal),
+    /// The bits of a number too wide for an `i64`, right aligned in whole octets, e.g. a local
+    /// descriptor or one widened by 2-01. The reference value and scale aren't applied.
+    Bytes(Vec<u8>),
+}
 
-        num_repititions = f.read_usize(bits)?.unwrap();
+/// A number in exact decimal form, `mantissa` × 10<sup>-`scale`</sup>, as BUFR transmits it.
+/// Unlike an `f64`, 0.1 hPa steps stay exact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    pub mantissa: i64,
+    /// The Table B scale, the number of digits after the decimal point when positive.
+    pub scale: i32,
+}
+
+impl Decimal {
+    /// The nearest `f64`.
+    pub fn to_f64(self) -> f64 {
+        apply_scale(self.mantissa, self.scale)
     }
+}
 
-    let mut descriptors = Vec::with_capacity(num_descriptors as usize);
+impl Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        if self.scale <= 0 {
+            write!(f, "{}", self.mantissa)?;
+            return (0..-self.scale).try_for_each(|_| write!(f, "0"));
+        }
+
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
 
-    for _ in 0..num_descriptors {
-        match iter.next() {
-            Some(desc) => descriptors.push(desc),
-            None => panic!("Ran out of descriptors in replication!"),
+/// The value of a CCITT IA5 text element, where all bits set means missing. Bytes that aren't
+/// valid UTF-8 are replaced rather than failing the message.
+pub(crate) fn text_value(bytes: Vec<u8>) -> Value {
+    if !bytes.is_empty() && bytes.iter().all(|&b| b == 0xff) {
+        return Value::Missing;
+    }
+
+    match String::from_utf8(bytes) {
+        Ok(text) => Value::Text(text),
+        Err(err) => Value::Text(String::from_utf8_lossy(err.as_bytes()).into_owned()),
+    }
+}
+
+impl Value {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(v) => Some(*v as f64),
+            Value::Float(v) => Some(*v),
+            Value::Decimal(v) => Some(v.to_f64()),
+            _ => None,
         }
     }
-    let descriptors = descriptors;
 
-    for _ in 0..num_repititions {
-        for desc in &descriptors {
-            match desc.f_value() {
-                0 => read_element_descriptor(f, &desc, float_vals, float_arrays, true)?,
-                1 => read_replication_descriptor(f, &desc, iter, float_vals, float_arrays)?,
-                2 => panic!("Operator descriptors not supported at this time."),
-                3 => read_sequence_descriptor(f, &desc, float_vals, float_arrays, true)?,
-                _ => panic!("Unknown descriptor type."),
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Value::Missing => write!(f, "MISSING"),
+            Value::Integer(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::Decimal(v) => write!(f, "{}", v),
+            Value::Text(v) => write!(f, "{}", v.trim_end()),
+            Value::Bytes(v) => {
+                write!(f, "0x")?;
+                v.iter().try_for_each(|b| write!(f, "{:02x}", b))
             }
         }
     }
+}
 
-    Ok(())
+/// The decoded data for a subset is a tree that mirrors the expansion of the Section 3
+/// descriptors.
+#[derive(Clone, Debug)]
+pub enum DataNode {
+    Element {
+        descriptor: Descriptor,
+        value: Value,
+        /// The integer as transmitted in BUFR, before the reference value and scale were
+        /// applied, so it can be kept or re-encoded exactly. `None` for text, missing values,
+        /// values wider than 64 bits, and CREX.
+        raw: Option<u64>,
+    },
+    Sequence {
+        descriptor: Descriptor,
+        children: Vec<DataNode>,
+    },
+    Replication {
+        descriptor: Descriptor,
+        repetitions: Vec<Vec<DataNode>>,
+    },
 }
 
-fn read_sequence_descriptor<'a>(
-    f: &mut BitBuffer,
-    desc: &Descriptor,
-    float_vals: &mut HashMap<&'static str, f64>,
-    float_arrays: &mut HashMap<&'static str, Vec<f64>>,
-    in_array: bool,
-) -> Result<(), Box<dyn Error>> {
-    let sequence = table_d::TABLE_D.get(&desc.string_form() as &str).unwrap();
-    let sequence: Vec<_> = sequence
-        .iter()
-        .map(|d| Descriptor::from_string_form(d))
-        .collect();
+impl DataNode {
+    /// Forget the raw integers, to compare decoded data with data made by hand.
+    #[cfg(test)]
+    pub(crate) fn clear_raw(nodes: &mut [DataNode]) {
+        for node in nodes {
+            match node {
+                DataNode::Element { raw, .. } => *raw = None,
+                DataNode::Sequence { children, .. } => DataNode::clear_raw(children),
+                DataNode::Replication { repetitions, .. } => repetitions
+                    .iter_mut()
+                    .for_each(|rep| DataNode::clear_raw(rep)),
+            }
+        }
+    }
+
+    fn fmt_indented(
+        &self,
+        f: &mut std::fmt::Formatter,
+        indent: usize,
+    ) -> Result<(), std::fmt::Error> {
+        match self {
+            DataNode::Element {
+                descriptor, value, ..
+            } => {
+                let name = tables::lookup_element(*descriptor)
+                    .map(|entry| entry.name)
+                    .unwrap_or("Unknown element");
+                writeln!(f, "{:indent$}{} : {}", "", name, value, indent = indent)
+            }
+            DataNode::Sequence {
+                descriptor,
+                children,
+            } => {
+                writeln!(
+                    f,
+                    "{:indent$}Sequence {}:",
+                    "",
+                    descriptor.string_form(),
+                    indent = indent
+                )?;
+                for child in children {
+                    child.fmt_indented(f, indent + 4)?;
+                }
+                Ok(())
+            }
+            DataNode::Replication {
+                descriptor,
+                repetitions,
+            } => {
+                writeln!(
+                    f,
+                    "{:indent$}Replication {}: {} repetitions",
+                    "",
+                    descriptor.string_form(),
+                    repetitions.len(),
+                    indent = indent
+                )
+            }
+        }
+    }
+}
+
+/// The state set by the Table C operators that modify how elements are read, and how numbers
+/// are returned.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Operators {
+    // Operator 2-01-YYY
+    pub(crate) width_change: i32,
+    // Operator 2-02-YYY
+    pub(crate) scale_change: i32,
+    // Operator 2-07-YYY
+    pub(crate) srw_increase: i32,
+    // Operator 2-08-YYY
+    pub(crate) text_width: Option<usize>,
+    // Return scaled numbers as `Value::Decimal`, not set by an operator.
+    pub(crate) decimals: bool,
+}
+
+impl Operators {
+    /// Update the state for a Table C operator.
+    pub(crate) fn apply(&mut self, desc: Descriptor) -> Result<(), Box<dyn Error>> {
+        let y = desc.y_value() as i32;
+
+        match desc.x_value() {
+            1 => self.width_change = if y == 0 { 0 } else { y - 128 },
+            2 => self.scale_change = if y == 0 { 0 } else { y - 128 },
+            7 => self.srw_increase = y,
+            8 => self.text_width = if y == 0 { None } else { Some(8 * y as usize) },
+            // Quality information and bit maps, which are read as ordinary elements.
+            22 | 35 | 36 if y == 0 => {}
+            37 if y == 0 || y == 255 => {}
+            _ => {
+                return Err(format!(
+                    "Operator descriptor not supported at this time: {}",
+                    desc.string_form()
+                )
+                .into())
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Where the decoder gets element values from, e.g. the bits of a BUFR Section 4 or the
+/// characters of a CREX data section.
+pub(crate) trait ValueSource {
+    fn read_value(&mut self, entry: &TableBEntry, ops: &Operators)
+        -> Result<Value, Box<dyn Error>>;
+
+    fn read_replication_factor(&mut self, entry: &TableBEntry) -> Result<usize, Box<dyn Error>>;
+
+    /// How far into the data the next value starts, in the source's units (bits or characters).
+    fn position(&self) -> usize;
+
+    /// The raw integer behind the last value read, if the source has one.
+    fn last_raw(&self) -> Option<u64> {
+        None
+    }
+
+    /// Forget the last value read, for sources that keep the values they read.
+    fn discard_last(&mut self) {}
+}
+
+/// How an element's bits are read once the operators are applied.
+pub(crate) enum ElementBits {
+    Text(usize),
+    /// A number wider than 64 bits, kept as its bits.
+    Bytes(usize),
+    Integer {
+        width: usize,
+        reference: i64,
+    },
+    Float {
+        width: usize,
+        reference: i64,
+        scale: i32,
+    },
+}
+
+impl ElementBits {
+    pub(crate) fn new(entry: &TableBEntry, ops: &Operators) -> Result<Self, Box<dyn Error>> {
+        let bits = match entry.units {
+            "CCITT IA5" => ElementBits::Text(ops.text_width.unwrap_or(entry.width_bits)),
+            "Code table" | "Flag table" => ElementBits::Integer {
+                width: entry.width_bits,
+                reference: entry.reference_val,
+            },
+            units => {
+                let width =
+                    entry.width_bits as i32 + ops.width_change + (10 * ops.srw_increase + 2) / 3;
+                let width = usize::try_from(width)?;
+                let scale = entry.scale_val + ops.scale_change + ops.srw_increase;
+                let reference = 10i64
+                    .checked_pow(ops.srw_increase as u32)
+                    .and_then(|factor| entry.reference_val.checked_mul(factor))
+                    .ok_or("Operator 2-07 increased the reference value too far")?;
+
+                if scale == 0 && units == "Numeric" {
+                    ElementBits::Integer { width, reference }
+                } else {
+                    ElementBits::Float {
+                        width,
+                        reference,
+                        scale,
+                    }
+                }
+            }
+        };
+
+        // Operators in a corrupt message can make widths that can't be read.
+        match bits {
+            ElementBits::Text(width) if width == 0 || !width.is_multiple_of(8) => {
+                Err(format!("Invalid text width of {} bits", width).into())
+            }
+            ElementBits::Integer { width: 0, .. } | ElementBits::Float { width: 0, .. } => {
+                Err("Invalid element width of 0 bits".into())
+            }
+            ElementBits::Integer { width, .. } | ElementBits::Float { width, .. } if width > 64 => {
+                Ok(ElementBits::Bytes(width))
+            }
+            bits => Ok(bits),
+        }
+    }
+}
+
+impl ValueSource for BitBuffer<'_> {
+    fn read_value(
+        &mut self,
+        entry: &TableBEntry,
+        ops: &Operators,
+    ) -> Result<Value, Box<dyn Error>> {
+        let value = match ElementBits::new(entry, ops)? {
+            ElementBits::Text(bits) => text_value(self.read_bytes(bits)?),
+            ElementBits::Bytes(bits) => self
+                .read_wide(bits)?
+                .map(Value::Bytes)
+                .unwrap_or(Value::Missing),
+            ElementBits::Integer { width, reference } => self
+                .read_i64(width, reference)?
+                .map(Value::Integer)
+                .unwrap_or(Value::Missing),
+            ElementBits::Float {
+                width,
+                reference,
+                scale,
+            } => self
+                .read_i64(width, reference)?
+                .map(|v| scaled_value(v, scale, ops.decimals))
+                .unwrap_or(Value::Missing),
+        };
+
+        Ok(value)
+    }
+
+    fn read_replication_factor(&mut self, entry: &TableBEntry) -> Result<usize, Box<dyn Error>> {
+        Ok(self.read_usize(entry.width_bits)?.unwrap_or(0))
+    }
+
+    fn position(&self) -> usize {
+        self.bit_offset()
+    }
+
+    fn last_raw(&self) -> Option<u64> {
+        BitBuffer::last_raw(self)
+    }
+}
 
-    let mut desc_iter = sequence.iter();
+/// A number with its reference value added, scaled as an `f64` or kept as a `Decimal`.
+pub(crate) fn scaled_value(val: i64, scale: i32, decimals: bool) -> Value {
+    if decimals {
+        Value::Decimal(Decimal {
+            mantissa: val,
+            scale,
+        })
+    } else {
+        Value::Float(apply_scale(val, scale))
+    }
+}
+
+/// Walks the descriptors and reads their values from the source, keeping track of the state set
+/// by Table C operators.
+pub(crate) struct Decoder<'a, S: ValueSource + ?Sized> {
+    source: &'a mut S,
+    ops: Operators,
+    overrides: Option<&'a TableOverrides>,
+    // The subset number and the elements read so far, if tracing.
+    trace: Option<(usize, Vec<TraceEntry>)>,
+    cancel: Option<&'a AtomicBool>,
+    // The memory budget for the whole message, and the bytes of it used so far.
+    budget: Option<usize>,
+    used: usize,
+    // How deeply nested the sequences and replications being decoded are.
+    depth: usize,
+    unknown: Option<&'a dyn UnknownDescriptorHandler>,
+    warnings: Vec<String>,
+}
+
+impl<'a, S: ValueSource + ?Sized> Decoder<'a, S> {
+    pub(crate) fn new(source: &'a mut S) -> Self {
+        Decoder {
+            source,
+            ops: Operators::default(),
+            overrides: None,
+            trace: None,
+            cancel: None,
+            budget: None,
+            used: 0,
+            depth: 0,
+            unknown: None,
+            warnings: vec![],
+        }
+    }
+
+    /// Ask `handler` what to do with elements that aren't in the tables, rather than failing.
+    pub(crate) fn with_unknown(
+        mut self,
+        handler: Option<&'a dyn UnknownDescriptorHandler>,
+    ) -> Self {
+        self.unknown = handler;
+        self
+    }
+
+    /// Record a `TraceEntry` for each element read, see `take_trace`.
+    pub(crate) fn with_trace(mut self, subset: usize) -> Self {
+        self.trace = Some((subset, vec![]));
+        self
+    }
+
+    pub(crate) fn take_trace(&mut self) -> Vec<TraceEntry> {
+        self.trace
+            .as_mut()
+            .map(|(_, trace)| std::mem::take(trace))
+            .unwrap_or_default()
+    }
+
+    /// Descriptors the `UnknownDescriptorHandler` defined or skipped.
+    pub(crate) fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    fn record(&mut self, descriptor: Descriptor, start: usize, value: &Value) {
+        if let Some((subset, trace)) = &mut self.trace {
+            trace.push(TraceEntry {
+                subset: *subset,
+                descriptor,
+                bit_offset: start,
+                width_bits: self.source.position() - start,
+                raw: self.source.last_raw(),
+                value: value.clone(),
+            });
+        }
+    }
+
+    /// Look descriptors up in `overrides` before the built-in tables.
+    pub(crate) fn with_overrides(mut self, overrides: Option<&'a TableOverrides>) -> Self {
+        self.overrides = overrides;
+        self
+    }
 
-    loop {
-        let desc = match desc_iter.next() {
-            Some(desc) => desc,
-            None => break,
+    /// Return scaled numbers as `Value::Decimal`.
+    pub(crate) fn with_decimals(mut self, decimals: bool) -> Self {
+        self.ops.decimals = decimals;
+        self
+    }
+
+    /// Return a `Cancelled` error between repetitions once `cancel` is set.
+    pub(crate) fn with_cancel(mut self, cancel: Option<&'a AtomicBool>) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Return a `BudgetExceeded` error once the nodes decoded, along with the `used` bytes
+    /// already decoded from the message, would take more than `budget` bytes.
+    pub(crate) fn with_budget(mut self, budget: Option<usize>, used: usize) -> Self {
+        self.budget = budget;
+        self.used = used;
+        self
+    }
+
+    /// The bytes used by the message so far, see `with_budget`.
+    pub(crate) fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Count `bytes` against the budget, before allocating them.
+    fn charge(&mut self, bytes: usize) -> Result<(), Box<dyn Error>> {
+        self.used = self.used.saturating_add(bytes);
+        check_budget(self.budget, self.used)
+    }
+
+    pub(crate) fn decode_descriptors(
+        &mut self,
+        descriptors: &[Descriptor],
+    ) -> Result<Vec<DataNode>, Box<dyn Error>> {
+        self.charge(descriptors.len() * std::mem::size_of::<DataNode>())?;
+        if self.depth > MAX_DEPTH {
+            return Err("Sequences and replications are nested too deeply".into());
+        }
+        self.depth += 1;
+        let nodes = self.decode_nested(descriptors);
+        self.depth -= 1;
+        nodes
+    }
+
+    fn decode_nested(
+        &mut self,
+        descriptors: &[Descriptor],
+    ) -> Result<Vec<DataNode>, Box<dyn Error>> {
+        let mut nodes = Vec::with_capacity(descriptors.len());
+
+        let mut i = 0;
+        while i < descriptors.len() {
+            let desc = descriptors[i];
+            i += 1;
+
+            match desc.f_value() {
+                0 => nodes.extend(self.decode_element(desc)?),
+                1 => {
+                    let (node, consumed) = self.decode_replication(desc, &descriptors[i..])?;
+                    i += consumed;
+                    nodes.push(node);
+                }
+                2 => self.ops.apply(desc)?,
+                3 => nodes.push(self.decode_sequence(desc)?),
+                _ => return Err(format!("Unknown descriptor type: {}", desc.string_form()).into()),
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    /// Decode an element, or nothing if it's unknown and the handler skips it.
+    fn decode_element(&mut self, desc: Descriptor) -> Result<Option<DataNode>, Box<dyn Error>> {
+        let err = match tables::table_b_entry(desc, self.overrides) {
+            Ok(entry) => return self.read_element(desc, &entry, self.ops).map(Some),
+            Err(err) => err,
         };
 
-        //dbg!("read_sequence_descriptor loop", desc.string_form(), "-------------------");
+        let action = match self.unknown {
+            Some(handler) => handler.handle(desc),
+            None => UnknownDescriptorAction::Abort,
+        };
+        match action {
+            UnknownDescriptorAction::Define(element) => {
+                tables::check_width(desc, element.width_bits)?;
+                self.warnings.push(format!(
+                    "{} isn't in the tables, decoded as {}",
+                    desc.string_form(),
+                    element.name
+                ));
+                self.read_element(desc, &element.entry(), self.ops)
+                    .map(Some)
+            }
+            UnknownDescriptorAction::Skip { bits } => {
+                tables::check_width(desc, bits)?;
+                self.warnings.push(format!(
+                    "{} isn't in the tables, skipped {} bits",
+                    desc.string_form(),
+                    bits
+                ));
+                let entry = TableBEntry {
+                    width_bits: bits,
+                    element_name: "Skipped",
+                    units: "Code table",
+                    reference_val: 0,
+                    scale_val: 0,
+                    crex_units: "Code table",
+                    crex_scale: 0,
+                    crex_width: 0,
+                };
+                self.read_element(desc, &entry, Operators::default())?;
+                self.source.discard_last();
+                Ok(None)
+            }
+            UnknownDescriptorAction::Abort => Err(err),
+        }
+    }
 
-        match desc.f_value() {
-            0 => read_element_descriptor(f, &desc, float_vals, float_arrays, in_array)?,
-            1 => read_replication_descriptor(f, &desc, &mut desc_iter, float_vals, float_arrays)?,
-            2 => panic!("Operator descriptors not supported at this time."),
-            3 => read_sequence_descriptor(f, &desc, float_vals, float_arrays, in_array)?,
-            _ => panic!("Unknown descriptor type."),
+    fn read_element(
+        &mut self,
+        desc: Descriptor,
+        entry: &TableBEntry,
+        ops: Operators,
+    ) -> Result<DataNode, Box<dyn Error>> {
+        let start = self.source.position();
+        let value = self.source.read_value(entry, &ops)?;
+        self.record(desc, start, &value);
+        match &value {
+            Value::Text(text) => self.charge(text.len())?,
+            Value::Bytes(bytes) => self.charge(bytes.len())?,
+            _ => {}
         }
+
+        Ok(DataNode::Element {
+            descriptor: desc,
+            value,
+            raw: self.source.last_raw(),
+        })
+    }
+
+    /// Decode a replication, returning the node and the number of descriptors that followed the
+    /// replication descriptor that were consumed by it.
+    fn decode_replication(
+        &mut self,
+        desc: Descriptor,
+        following: &[Descriptor],
+    ) -> Result<(DataNode, usize), Box<dyn Error>> {
+        let num_descriptors = desc.x_value() as usize;
+        let mut num_repetitions = desc.y_value() as usize;
+        let mut consumed = 0;
+        if num_descriptors == 0 {
+            return Err(format!("{} replicates no descriptors", desc.string_form()).into());
+        }
+
+        if num_repetitions == 0 {
+            let reps = following
+                .first()
+                .ok_or("Ran out of descriptors in delayed replication!")?;
+            if reps.f_value() != 0 || reps.x_value() != 31 {
+                return Err(format!(
+                    "Expected delayed replication factor, found {}",
+                    reps.string_form()
+                )
+                .into());
+            }
+            let entry = tables::table_b_entry(*reps, self.overrides)?;
+
+            let start = self.source.position();
+            num_repetitions = self.source.read_replication_factor(&entry)?;
+            self.record(*reps, start, &Value::Integer(num_repetitions as i64));
+            consumed += 1;
+        }
+
+        let group = following
+            .get(consumed..(consumed + num_descriptors))
+            .ok_or("Ran out of descriptors in replication!")?;
+        consumed += num_descriptors;
+
+        self.charge(num_repetitions.saturating_mul(std::mem::size_of::<Vec<DataNode>>()))?;
+        let mut repetitions = Vec::with_capacity(num_repetitions);
+        for _ in 0..num_repetitions {
+            check_cancelled(self.cancel)?;
+            repetitions.push(self.decode_descriptors(group)?);
+        }
+
+        Ok((
+            DataNode::Replication {
+                descriptor: desc,
+                repetitions,
+            },
+            consumed,
+        ))
     }
-    Ok(())
+
+    fn decode_sequence(&mut self, desc: Descriptor) -> Result<DataNode, Box<dyn Error>> {
+        let sequence = tables::table_d_sequence(desc, self.overrides)?;
+
+        let children = self.decode_descriptors(sequence)?;
+
+        Ok(DataNode::Sequence {
+            descriptor: desc,
+            children,
+        })
+    }
+}
+
+/// Read past Section 4 without decoding it, for messages whose data we don't interpret.
+pub(super) fn skip_section_4(mut f: impl Read) -> Result<Section4, Box<dyn Error>> {
+    let section_size = read_3_octet_usize(&mut f)?;
+
+    let skip = (section_size as u64).saturating_sub(3);
+    let skipped = std::io::copy(&mut f.take(skip), &mut std::io::sink())?;
+    if skipped < skip {
+        return Err("Unexpected end of data in Section 4.".into());
+    }
+
+    Ok(Section4 {
+        section_size,
+        subsets: vec![],
+        trace: vec![],
+        subset_bits: vec![],
+        warnings: vec![],
+    })
 }
 
 pub(super) fn read_section_4(
     mut f: impl Read,
     sec3: &Section3,
+    options: &DecodeOptions,
 ) -> Result<Section4, Box<dyn Error>> {
     let mut octets_read: usize = 0;
-    let mut float_vals = HashMap::new();
-    let mut float_arrays = HashMap::new();
 
     let section_size = read_3_octet_usize(&mut f)?;
     octets_read += 3;
 
-    // TODO: Check if this is zero and return an error if it isn't
-    let reserved = read_1_octet_u8(&mut f)?;
+    // Reserved, should be zero but ignored so a slightly off message still decodes.
+    let _reserved = read_1_octet_u8(&mut f)?;
     octets_read += 1;
-    debug_assert_eq!(reserved, 0, "Section 4: Octet 4 must be zero.");
 
     let descriptors = sec3.descriptors();
-    assert!(!descriptors.is_empty());
-
-    let mut desc_iter = descriptors.iter();
+    if descriptors.is_empty() {
+        return Err("Section 3 has no descriptors".into());
+    }
 
-    let bytes_left_in_section = section_size - octets_read;
+    let bytes_left_in_section = section_size
+        .checked_sub(octets_read)
+        .ok_or("Section 4 is too short")?;
     let mut bit_buffer = BitBuffer::new(&mut f, bytes_left_in_section);
 
-    loop {
-        let descriptor = match desc_iter.next() {
-            Some(desc) => desc,
-            None => break,
-        };
-        //dbg!(descriptor.string_form());
-
-        match descriptor.f_value() {
-            0 => read_element_descriptor(
-                &mut bit_buffer,
-                descriptor,
-                &mut float_vals,
-                &mut float_arrays,
-                false,
-            )?,
-            1 => read_replication_descriptor(
-                &mut bit_buffer,
-                descriptor,
-                &mut desc_iter,
-                &mut float_vals,
-                &mut float_arrays,
-            )?,
-            2 => panic!("Operator descriptors not supported at this time."),
-            3 => read_sequence_descriptor(
-                &mut bit_buffer,
-                descriptor,
-                &mut float_vals,
-                &mut float_arrays,
-                false,
-            )?,
-            _ => panic!("Unknown descriptor type."),
+    let num_subsets = sec3.num_datasets() as usize;
+    let (subsets, trace, subset_bits, warnings) = if sec3.compressed_data() {
+        let (subsets, trace, warnings) =
+            read_compressed_subsets(&mut bit_buffer, descriptors, num_subsets, options)?;
+        // The values of the subsets are interleaved, so no subset has bits of its own.
+        (subsets, trace, vec![], warnings)
+    } else {
+        let mut subsets = Vec::with_capacity(num_subsets);
+        let mut trace = vec![];
+        let mut warnings = vec![];
+        let mut subset_bits = Vec::with_capacity(num_subsets);
+        let mut used = 0;
+        for i in 0..num_subsets {
+            check_cancelled(options.cancel)?;
+            let start = bit_buffer.bit_offset();
+            let mut decoder = Decoder::new(&mut bit_buffer)
+                .with_overrides(options.overrides)
+                .with_cancel(options.cancel)
+                .with_budget(options.budget, used)
+                .with_decimals(options.decimals)
+                .with_unknown(options.unknown);
+            if options.trace {
+                decoder = decoder.with_trace(i);
+            }
+            subsets.push(decoder.decode_descriptors(descriptors)?);
+            used = decoder.used();
+            subset_bits.push(start..decoder.source.position());
+            trace.extend(decoder.take_trace());
+            warnings.extend(decoder.take_warnings());
         }
-    }
+        (subsets, trace, subset_bits, warnings)
+    };
+
+    // The bit buffer starts after the section header.
+    let trace = trace
+        .into_iter()
+        .map(|entry| TraceEntry {
+            bit_offset: entry.bit_offset + 8 * octets_read,
+            ..entry
+        })
+        .collect();
 
     octets_read += bit_buffer.bytes_read();
 
     // Push the stream ahead to the end of the section
-    //dbg!(section_size - octets_read);
     for _ in 0..(section_size - octets_read) {
         let _v = read_1_octet_u8(&mut f)?;
     }
 
     Ok(Section4 {
         section_size,
-        float_vals,
-        float_arrays,
+        subsets,
+        trace,
+        subset_bits,
+        warnings,
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bit_writer::BitWriter;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_increase_scale_reference_width() {
+        // 2-07-001 takes pressure (0-07-004) from 14 bits at scale -1 to 18 bits at scale 0.
+        let descriptors = [
+            Descriptor::new(2, 7, 1),
+            Descriptor::new(0, 7, 4),
+            Descriptor::new(2, 7, 0),
+            Descriptor::new(0, 7, 4),
+        ];
+
+        let raw: u64 = (85_001 << 46) | (8_500 << 32);
+        let mut data = Cursor::new(raw.to_be_bytes()[..4].to_vec());
+        let mut bits = BitBuffer::new(&mut data, 4);
+        let nodes = Decoder::new(&mut bits)
+            .decode_descriptors(&descriptors)
+            .unwrap();
+
+        let values: Vec<_> = nodes
+            .iter()
+            .map(|node| match node {
+                DataNode::Element { value, .. } => value.clone(),
+                _ => panic!("expected an element"),
+            })
+            .collect();
+        assert_eq!(values, vec![Value::Float(85_001.0), Value::Float(85_000.0)]);
+    }
+
+    #[test]
+    fn test_invalid_descriptors_are_errors() {
+        let decode = |descriptors: &[Descriptor]| {
+            let mut data = Cursor::new(vec![0; 64]);
+            let mut bits = BitBuffer::new(&mut data, 64);
+            Decoder::new(&mut bits).decode_descriptors(descriptors)
+        };
+        let pressure = Descriptor::new(0, 7, 4);
+
+        // Narrowed to nothing, and a reference too big to scale.
+        assert!(decode(&[Descriptor::new(2, 1, 114), pressure]).is_err());
+        assert!(decode(&[Descriptor::new(2, 7, 200), pressure]).is_err());
+        // Replicating nothing.
+        assert!(decode(&[Descriptor::new(1, 0, 5)]).is_err());
+        // Running out of data.
+        assert!(decode(&[Descriptor::new(1, 1, 255), pressure]).is_err());
+    }
+
+    #[test]
+    fn test_wide_elements() {
+        // 2-01-255 widens pressure from 14 to 141 bits.
+        let descriptors = [Descriptor::new(2, 1, 255), Descriptor::new(0, 7, 4)];
+        let mut wide = vec![0; 18];
+        wide[0] = 0x1f;
+        wide[17] = 0x01;
+
+        let mut writer = BitWriter::new();
+        writer.write_bytes(&wide, 141).unwrap();
+        writer.write_missing(141);
+        assert!(writer.write_bytes(&[0xff; 18], 141).is_err());
+        let mut data = Cursor::new(writer.into_bytes());
+        let mut bits = BitBuffer::new(&mut data, 36);
+
+        let mut decoder = Decoder::new(&mut bits);
+        let values: Vec<_> = [&descriptors, &descriptors]
+            .iter()
+            .flat_map(|descriptors| decoder.decode_descriptors(&descriptors[..]).unwrap())
+            .map(|node| match node {
+                DataNode::Element { value, .. } => value,
+                _ => panic!("expected an element"),
+            })
+            .collect();
+        assert_eq!(values, vec![Value::Bytes(wide), Value::Missing]);
+        assert_eq!(values[0].to_string(), format!("0x1f{}01", "00".repeat(16)));
+    }
+
+    #[test]
+    fn test_decimal_display() {
+        let decimal = |mantissa, scale| Decimal { mantissa, scale }.to_string();
+        assert_eq!(decimal(85_001, -1), "850010");
+        assert_eq!(decimal(28_215, 2), "282.15");
+        assert_eq!(decimal(-5, 2), "-0.05");
+        assert_eq!(decimal(7, 0), "7");
+        assert_eq!(
+            Decimal {
+                mantissa: 1,
+                scale: 1
+            }
+            .to_f64(),
+            0.1
+        );
+    }
+}
@@ -11,8 +11,9 @@ impl Display for Section5 {
 pub(super) fn read_section_5(mut f: impl Read) -> Result<Section5, Box<dyn Error>> {
     let mut section_end: [u8; 4] = [0; 4];
     f.read_exact(&mut section_end)?;
-    let section_end = std::str::from_utf8(&section_end)?;
-    assert_eq!(section_end, "7777");
+    if &section_end != b"7777" {
+        return Err("Section 5 isn't 7777".into());
+    }
 
     Ok(Section5 {})
 }
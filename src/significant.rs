@@ -0,0 +1,256 @@
+use crate::{
+    derive::VaporPressureFormula,
+    sounding::{Level, Sounding},
+};
+
+/// How far a profile may depart from a straight line in log pressure between significant
+/// levels, see `Sounding::significant_levels`. The defaults are the limits of the WMO Manual on
+/// Codes for parts B and D of TEMP reports and for PILOT wind levels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SignificantLevelTolerances {
+    /// K, up to the first tropopause or 300 hPa, whichever is lower.
+    pub temperature: f64,
+    /// K, above the first tropopause or 300 hPa.
+    pub upper_temperature: f64,
+    /// %
+    pub relative_humidity: f64,
+    /// Degrees, only where the wind is at least `wind_speed`.
+    pub wind_direction: f64,
+    /// m/s
+    pub wind_speed: f64,
+    /// Keep the standard pressure levels too, the way a part A report would have them.
+    pub standard_levels: bool,
+}
+
+impl Default for SignificantLevelTolerances {
+    fn default() -> Self {
+        SignificantLevelTolerances {
+            temperature: 1.0,
+            upper_temperature: 2.0,
+            relative_humidity: 15.0,
+            wind_direction: 10.0,
+            wind_speed: 5.0,
+            standard_levels: false,
+        }
+    }
+}
+
+impl Sounding {
+    /// Reduce a high resolution profile to its significant levels, the fewest levels that
+    /// reproduce the temperature, humidity, and wind within `tolerances` when interpolated
+    /// between, like the levels a TTBB or PPBB report would have.
+    ///
+    /// The first and last levels and any surface, tropopause, or maximum wind levels are always
+    /// kept. Starting from those, the level furthest out of tolerance between each pair of
+    /// kept levels is added until none is left, separately for the temperature, the humidity, and
+    /// the wind. Added levels are flagged with `Level::SIG_TEMPERATURE`, `Level::SIG_HUMIDITY`,
+    /// or `Level::SIG_WIND`. Levels without a pressure are dropped.
+    pub fn significant_levels(&self, tolerances: &SignificantLevelTolerances) -> Sounding {
+        let mut protected = Level::SURFACE | Level::TROPOPAUSE | Level::MAX_WIND;
+        if tolerances.standard_levels {
+            protected |= Level::STANDARD;
+        }
+
+        let levels: Vec<Level> = self
+            .levels()
+            .iter()
+            .filter(|lvl| lvl.pressure.is_some())
+            .copied()
+            .collect();
+        let mut flags: Vec<Option<u32>> = levels
+            .iter()
+            .enumerate()
+            .map(|(i, lvl)| {
+                let keep = i == 0 || i + 1 == levels.len() || lvl.has_significance(protected);
+                keep.then_some(0)
+            })
+            .collect();
+
+        // The temperature tolerance is tighter below the first tropopause or 300 hPa.
+        let upper_pressure = levels
+            .iter()
+            .find(|lvl| lvl.has_significance(Level::TROPOPAUSE))
+            .and_then(|lvl| lvl.pressure)
+            .map_or(30_000.0, |p| p.max(30_000.0));
+        let temperature_error = |below: &Level, lvl: &Level, above: &Level| {
+            let tolerance = if lvl.pressure? >= upper_pressure {
+                tolerances.temperature
+            } else {
+                tolerances.upper_temperature
+            };
+            let t = lvl.temperature?;
+            Some((t - interpolate(below, lvl, above, |l| l.temperature)?).abs() / tolerance)
+        };
+
+        let formula = VaporPressureFormula::default();
+        let rh = |lvl: &Level| {
+            lvl.relative_humidity.or_else(|| {
+                let (t, td) = (lvl.temperature? - 273.15, lvl.dewpoint? - 273.15);
+                Some(
+                    100.0 * formula.saturation_vapor_pressure(td)
+                        / formula.saturation_vapor_pressure(t),
+                )
+            })
+        };
+        let humidity_error = |below: &Level, lvl: &Level, above: &Level| {
+            let error = (rh(lvl)? - interpolate(below, lvl, above, rh)?).abs();
+            Some(error / tolerances.relative_humidity)
+        };
+
+        let wind_error = |below: &Level, lvl: &Level, above: &Level| {
+            let (u, v) = lvl.wind_components()?;
+            let u_i = interpolate(below, lvl, above, |l| l.wind_components().map(|(u, _)| u))?;
+            let v_i = interpolate(below, lvl, above, |l| l.wind_components().map(|(_, v)| v))?;
+
+            let (speed, speed_i) = (u.hypot(v), u_i.hypot(v_i));
+            let mut error = (speed - speed_i).abs() / tolerances.wind_speed;
+            if speed.min(speed_i) >= tolerances.wind_speed {
+                let turn = (v.atan2(u) - v_i.atan2(u_i)).to_degrees().rem_euclid(360.0);
+                error = error.max(turn.min(360.0 - turn) / tolerances.wind_direction);
+            }
+            Some(error)
+        };
+
+        select(
+            &levels,
+            &mut flags,
+            Level::SIG_TEMPERATURE,
+            temperature_error,
+        );
+        select(&levels, &mut flags, Level::SIG_HUMIDITY, humidity_error);
+        select(&levels, &mut flags, Level::SIG_WIND, wind_error);
+
+        let levels = levels
+            .into_iter()
+            .zip(flags)
+            .filter_map(|(mut lvl, flags)| {
+                let flags = flags?;
+                if flags != 0 {
+                    lvl.significance = Some(lvl.significance.unwrap_or(0) | flags);
+                }
+                Some(lvl)
+            })
+            .collect();
+
+        self.with_levels(levels)
+    }
+}
+
+/// Keep the level with the largest `error`, relative to its tolerance, between each pair of kept
+/// levels until they're all within tolerance, flagging the ones added with `flag`. Only levels
+/// `error` has a value for are used as the ends of a segment.
+fn select(
+    levels: &[Level],
+    flags: &mut [Option<u32>],
+    flag: u32,
+    error: impl Fn(&Level, &Level, &Level) -> Option<f64>,
+) {
+    let has_value = |i: usize| {
+        let lvl = &levels[i];
+        error(lvl, lvl, lvl).is_some()
+    };
+    let candidates: Vec<usize> = (0..levels.len()).filter(|&i| has_value(i)).collect();
+    if candidates.len() < 3 {
+        return;
+    }
+
+    // Segments between neighbouring kept candidates. The first and last candidates are kept
+    // too, so there is something to interpolate from at the ends.
+    let ends: Vec<usize> = (0..candidates.len())
+        .filter(|&k| k == 0 || k + 1 == candidates.len() || flags[candidates[k]].is_some())
+        .collect();
+    for &k in &ends {
+        flags[candidates[k]].get_or_insert(flag);
+    }
+    let mut segments: Vec<(usize, usize)> = ends.windows(2).map(|w| (w[0], w[1])).collect();
+
+    while let Some((lo, hi)) = segments.pop() {
+        let (below, above) = (&levels[candidates[lo]], &levels[candidates[hi]]);
+        let worst = (lo + 1..hi)
+            .filter_map(|k| Some((k, error(below, &levels[candidates[k]], above)?)))
+            .filter(|(_, err)| *err > 1.0)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((k, _)) = worst {
+            let kept = &mut flags[candidates[k]];
+            *kept = Some(kept.unwrap_or(0) | flag);
+            segments.push((lo, k));
+            segments.push((k, hi));
+        }
+    }
+}
+
+/// The value from `get` interpolated to `lvl` in log pressure between `below` and `above`.
+fn interpolate(
+    below: &Level,
+    lvl: &Level,
+    above: &Level,
+    get: impl Fn(&Level) -> Option<f64>,
+) -> Option<f64> {
+    let (x0, x, x1) = (
+        below.pressure?.ln(),
+        lvl.pressure?.ln(),
+        above.pressure?.ln(),
+    );
+    let (y0, y1) = (get(below)?, get(above)?);
+    if x1 == x0 {
+        return Some(y0);
+    }
+
+    Some(y0 + (y1 - y0) * (x - x0) / (x1 - x0))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_significant_levels() {
+        // Linear in log pressure, apart from a 3 K inversion at 800 hPa and a wind shift at
+        // 600 hPa.
+        let levels: Vec<Level> = (0..=900)
+            .map(|i| {
+                let hpa = 1000.0 - f64::from(i);
+                let t = 290.0 + 40.0 * (hpa / 1000.0f64).ln();
+                Level {
+                    pressure: Some(hpa * 100.0),
+                    temperature: Some(t + 3.0 * (1.0 - (hpa - 800.0).abs() / 20.0).max(0.0)),
+                    relative_humidity: Some(50.0),
+                    wind_direction: Some(if hpa > 600.0 { 180.0 } else { 270.0 }),
+                    wind_speed: Some(20.0),
+                    ..Level::default()
+                }
+            })
+            .collect();
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        let reduced = sounding.significant_levels(&SignificantLevelTolerances::default());
+        let sig = |flag| {
+            reduced
+                .levels()
+                .iter()
+                .filter(move |lvl| lvl.has_significance(flag))
+                .filter_map(|lvl| lvl.pressure)
+                .collect::<Vec<f64>>()
+        };
+
+        assert!(reduced.levels().len() < 20);
+        assert_eq!(reduced.levels()[0].pressure, Some(100_000.0));
+        assert_eq!(reduced.levels().last().unwrap().pressure, Some(10_000.0));
+        assert!(sig(Level::SIG_TEMPERATURE).contains(&80_000.0));
+        assert!(sig(Level::SIG_HUMIDITY).is_empty());
+        let wind = sig(Level::SIG_WIND);
+        assert!(wind.contains(&60_000.0) && wind.contains(&60_100.0));
+
+        // Every level left out is within tolerance of the levels kept either side.
+        let kept = reduced.levels();
+        for lvl in sounding.levels() {
+            let p = lvl.pressure.unwrap();
+            let above = kept.iter().position(|k| k.pressure.unwrap() <= p).unwrap();
+            let below = &kept[above.saturating_sub(1)];
+            let t = interpolate(below, lvl, &kept[above], |l| l.temperature).unwrap();
+            assert!((t - lvl.temperature.unwrap()).abs() <= 1.0);
+        }
+    }
+}
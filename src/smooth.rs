@@ -0,0 +1,144 @@
+use crate::sounding::{Level, Sounding};
+
+/// Settings for `Sounding::smooth`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Smoothing {
+    /// The width of the running mean in seconds of flight time.
+    pub window: f64,
+    pub temperature: bool,
+    pub dewpoint: bool,
+    /// Smooths the wind components and recomputes the direction and speed.
+    pub wind: bool,
+}
+
+impl Default for Smoothing {
+    /// A 20 second window, about 100 m of ascent, for every value.
+    fn default() -> Self {
+        Smoothing {
+            window: 20.0,
+            temperature: true,
+            dewpoint: true,
+            wind: true,
+        }
+    }
+}
+
+impl Sounding {
+    /// A copy of the sounding with a running mean, centred on each level, of the values chosen
+    /// in `smoothing`, for high resolution profiles too noisy for lapse rates. The mean is over
+    /// the levels within half the window of flight time either side, so levels without a time
+    /// offset are left as they are. Smoothed levels are flagged with `Level::SMOOTHED`, and
+    /// `self` keeps the raw values.
+    pub fn smooth(&self, smoothing: &Smoothing) -> Sounding {
+        let mut smoothed = self.clone();
+
+        // Levels with a time, in time order.
+        let levels = self.levels();
+        let mut order: Vec<(usize, f64)> = levels
+            .iter()
+            .enumerate()
+            .filter_map(|(i, lvl)| Some((i, lvl.time_offset?)))
+            .collect();
+        order.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        // Running sums and counts of each value, over `order`.
+        let running = |value: &dyn Fn(&Level) -> Option<f64>| {
+            let mut sums = vec![(0.0, 0usize)];
+            for &(i, _) in &order {
+                let (sum, count) = *sums.last().unwrap_or(&(0.0, 0));
+                sums.push(match value(&levels[i]) {
+                    Some(v) => (sum + v, count + 1),
+                    None => (sum, count),
+                });
+            }
+            sums
+        };
+        let temperature = running(&|lvl| lvl.temperature);
+        let dewpoint = running(&|lvl| lvl.dewpoint);
+        let u = running(&|lvl| lvl.wind_components().map(|(u, _)| u));
+        let v = running(&|lvl| lvl.wind_components().map(|(_, v)| v));
+
+        let half = smoothing.window / 2.0;
+        let (mut lo, mut hi) = (0, 0);
+        for (k, &(i, t)) in order.iter().enumerate() {
+            while order[lo].1 < t - half {
+                lo += 1;
+            }
+            while hi < order.len() && order[hi].1 <= t + half {
+                hi += 1;
+            }
+            let mean = |sums: &[(f64, usize)]| {
+                let (sum, count) = (sums[hi].0 - sums[lo].0, sums[hi].1 - sums[lo].1);
+                (count > 0).then(|| sum / count as f64)
+            };
+            debug_assert!(lo <= k && k < hi);
+
+            let lvl = &mut smoothed.levels_mut()[i];
+            let mut changed = false;
+            if smoothing.temperature && lvl.temperature.is_some() {
+                lvl.temperature = mean(&temperature);
+                changed = true;
+            }
+            if smoothing.dewpoint && lvl.dewpoint.is_some() {
+                lvl.dewpoint = mean(&dewpoint);
+                changed = true;
+            }
+            if smoothing.wind && lvl.wind_components().is_some() {
+                if let (Some(u), Some(v)) = (mean(&u), mean(&v)) {
+                    lvl.set_wind_components(u, v);
+                    changed = true;
+                }
+            }
+            if changed {
+                lvl.derived |= Level::SMOOTHED;
+            }
+        }
+
+        smoothed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_smooth() {
+        // Temperature alternating a degree either side of a steady lapse rate.
+        let levels: Vec<Level> = (0..60)
+            .map(|i| Level {
+                time_offset: Some(f64::from(i)),
+                temperature: Some(
+                    290.0 - 0.03 * f64::from(i) + if i % 2 == 0 { 1.0 } else { -1.0 },
+                ),
+                dewpoint: (i != 30).then_some(280.0),
+                wind_direction: Some(270.0),
+                wind_speed: Some(10.0),
+                ..Level::default()
+            })
+            .chain([Level {
+                temperature: Some(300.0),
+                ..Level::default()
+            }])
+            .collect();
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+
+        let smoothed = sounding.smooth(&Smoothing {
+            window: 10.0,
+            ..Smoothing::default()
+        });
+        let lvl = smoothed.levels()[30];
+        // 11 levels from 25 to 35 s, 5 warm and 6 cold.
+        assert!((lvl.temperature.unwrap() - (290.0 - 0.9 - 1.0 / 11.0)).abs() < 1.0e-9);
+        assert_eq!(lvl.dewpoint, None);
+        assert!((lvl.wind_speed.unwrap() - 10.0).abs() < 1.0e-9);
+        assert!(lvl.is_derived(Level::SMOOTHED));
+
+        // The raw values are still there, and the level without a time is left alone.
+        assert_eq!(sounding.levels()[30].temperature, Some(290.0 - 0.9 + 1.0));
+        assert_eq!(smoothed.levels()[60].temperature, Some(300.0));
+        assert!(!smoothed.levels()[60].is_derived(Level::SMOOTHED));
+        assert_eq!(smoothed.levels()[29].dewpoint, Some(280.0));
+    }
+}
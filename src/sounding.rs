@@ -0,0 +1,530 @@
+use crate::{
+    quality,
+    report_type::ReportType,
+    section3::Descriptor,
+    section4::{DataNode, Value},
+};
+use std::fmt::Display;
+
+/// The part of the balloon flight a sounding describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Ascent,
+    Descent,
+}
+
+impl Phase {
+    /// Sequence 3-09-056 is the radiosonde descent template. 3-09-057 is a TEMP ascent with
+    /// higher precision pressure and height.
+    const DESCENT_TEMPLATES: [Descriptor; 1] = [Descriptor::new(3, 9, 56)];
+
+    pub(crate) fn from_descriptors(descriptors: &[Descriptor]) -> Self {
+        if descriptors
+            .iter()
+            .any(|d| Phase::DESCENT_TEMPLATES.contains(d))
+        {
+            Phase::Descent
+        } else {
+            Phase::Ascent
+        }
+    }
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Phase::Ascent => write!(f, "Ascent"),
+            Phase::Descent => write!(f, "Descent"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Station {
+    pub wmo_block: Option<u8>,
+    pub wmo_station: Option<u16>,
+    pub call_sign: Option<String>,
+    /// Degrees north.
+    pub latitude: Option<f64>,
+    /// Degrees east.
+    pub longitude: Option<f64>,
+    /// Meters above mean sea level.
+    pub elevation: Option<f64>,
+}
+
+impl Station {
+    /// The WMO block and station number as a five digit string, or the call sign for ships and
+    /// mobile stations.
+    pub fn identifier(&self) -> Option<String> {
+        match (self.wmo_block, self.wmo_station) {
+            (Some(block), Some(station)) => Some(format!("{:02}{:03}", block, station)),
+            _ => self.call_sign.clone(),
+        }
+    }
+}
+
+/// A single level of a profile, in the units used by BUFR.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Level {
+    /// Seconds since launch.
+    pub time_offset: Option<f64>,
+    /// Extended vertical sounding significance, flag table 0-08-042.
+    pub significance: Option<u32>,
+    /// Pa
+    pub pressure: Option<f64>,
+    /// gpm
+    pub height: Option<f64>,
+    /// Geometric height above mean sea level from GNSS, 0-07-007 or 0-07-002, in m. See
+    /// `Sounding::height_discrepancies`.
+    pub gnss_height: Option<f64>,
+    /// K
+    pub temperature: Option<f64>,
+    /// K
+    pub dewpoint: Option<f64>,
+    /// %
+    pub relative_humidity: Option<f64>,
+    /// Degrees, direction the wind is blowing from.
+    pub wind_direction: Option<f64>,
+    /// m/s
+    pub wind_speed: Option<f64>,
+    /// Degrees of latitude from the launch site.
+    pub lat_displacement: Option<f64>,
+    /// Degrees of longitude from the launch site.
+    pub lon_displacement: Option<f64>,
+    /// Which values were computed rather than reported, see `Level::DERIVED_DEWPOINT`.
+    pub derived: u32,
+    /// Quality control checks the level failed, see `Level::QC_SUPERADIABATIC`.
+    pub qc: u32,
+}
+
+impl Level {
+    /// Bits of the extended vertical sounding significance flag table, 0-08-042.
+    pub const SURFACE: u32 = 1 << 17;
+    pub const STANDARD: u32 = 1 << 16;
+    pub const TROPOPAUSE: u32 = 1 << 15;
+    pub const MAX_WIND: u32 = 1 << 14;
+    pub const SIG_TEMPERATURE: u32 = 1 << 13;
+    pub const SIG_HUMIDITY: u32 = 1 << 12;
+    pub const SIG_WIND: u32 = 1 << 11;
+
+    /// Flags for `derived`.
+    pub const DERIVED_DEWPOINT: u32 = 1 << 0;
+    pub const DERIVED_HEIGHT: u32 = 1 << 1;
+    pub const CORRECTED_HUMIDITY: u32 = 1 << 2;
+    pub const GNSS_HEIGHT: u32 = 1 << 3;
+    pub const SMOOTHED: u32 = 1 << 4;
+
+    /// Flags for `qc`.
+    pub const QC_SUPERADIABATIC: u32 = 1 << 0;
+    pub const QC_HYDROSTATIC: u32 = 1 << 1;
+    pub const QC_PRESSURE_ORDER: u32 = 1 << 2;
+    pub const QC_HEIGHT_ORDER: u32 = 1 << 3;
+    pub const QC_DUPLICATE: u32 = 1 << 4;
+    pub const QC_RANGE: u32 = 1 << 5;
+    /// Marked suspect by the quality information sent with the data.
+    pub const QC_REPORTED: u32 = 1 << 6;
+
+    pub fn has_significance(&self, flag: u32) -> bool {
+        self.significance.is_some_and(|sig| sig & flag != 0)
+    }
+
+    pub fn is_derived(&self, flag: u32) -> bool {
+        self.derived & flag != 0
+    }
+
+    pub fn has_qc_flag(&self, flag: u32) -> bool {
+        self.qc & flag != 0
+    }
+
+    /// The (u, v) components of the wind in m/s, positive towards the east and north.
+    pub fn wind_components(&self) -> Option<(f64, f64)> {
+        let dir = self.wind_direction?.to_radians();
+        let spd = self.wind_speed?;
+
+        Some((-spd * dir.sin(), -spd * dir.cos()))
+    }
+
+    /// Set the wind direction and speed from (u, v) components in m/s. A calm wind has a
+    /// direction of 0.
+    pub fn set_wind_components(&mut self, u: f64, v: f64) {
+        let spd = u.hypot(v);
+        let dir = if spd == 0.0 {
+            0.0
+        } else {
+            (-u).atan2(-v).to_degrees().rem_euclid(360.0)
+        };
+
+        self.wind_direction = Some(dir);
+        self.wind_speed = Some(spd);
+    }
+}
+
+/// A vertical profile extracted from a single BUFR subset.
+#[derive(Clone, Debug)]
+pub struct Sounding {
+    pub(crate) phase: Phase,
+    pub(crate) station: Station,
+    pub(crate) launch_time: Option<Timestamp>,
+    pub(crate) radiosonde_type: Option<u16>,
+    pub(crate) update_number: u8,
+    pub(crate) report_type: Option<ReportType>,
+    pub(crate) levels: Vec<Level>,
+}
+
+impl Sounding {
+    pub fn new(
+        phase: Phase,
+        station: Station,
+        launch_time: Option<Timestamp>,
+        radiosonde_type: Option<u16>,
+        levels: Vec<Level>,
+    ) -> Self {
+        Sounding {
+            phase,
+            station,
+            launch_time,
+            radiosonde_type,
+            update_number: 0,
+            report_type: None,
+            levels,
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn station(&self) -> &Station {
+        &self.station
+    }
+
+    pub fn launch_time(&self) -> Option<Timestamp> {
+        self.launch_time
+    }
+
+    /// Code table 0-02-011.
+    pub fn radiosonde_type(&self) -> Option<u16> {
+        self.radiosonde_type
+    }
+
+    /// The update sequence number from Section 1, zero for an original report.
+    pub fn update_number(&self) -> u8 {
+        self.update_number
+    }
+
+    pub(crate) fn set_update_number(&mut self, update_number: u8) {
+        self.update_number = update_number;
+    }
+
+    /// TEMP, TEMP SHIP, PILOT, etc., from the message the sounding was decoded from.
+    pub fn report_type(&self) -> Option<ReportType> {
+        self.report_type
+    }
+
+    pub(crate) fn set_report_type(&mut self, report_type: Option<ReportType>) {
+        self.report_type = report_type;
+    }
+
+    pub fn levels(&self) -> &[Level] {
+        &self.levels
+    }
+
+    pub(crate) fn levels_mut(&mut self) -> &mut [Level] {
+        &mut self.levels
+    }
+
+    /// The same sounding with other levels. Other changes go in a struct update, e.g.
+    /// `Sounding { phase, ..self.with_levels(levels) }`, so nothing else is lost.
+    pub(crate) fn with_levels(&self, levels: Vec<Level>) -> Self {
+        Sounding {
+            phase: self.phase,
+            station: self.station.clone(),
+            launch_time: self.launch_time,
+            radiosonde_type: self.radiosonde_type,
+            update_number: self.update_number,
+            report_type: self.report_type,
+            levels,
+        }
+    }
+
+    /// Build a sounding from a decoded subset, returns `None` if the subset doesn't contain a
+    /// replicated set of levels.
+    pub(crate) fn from_subset(nodes: &[DataNode], phase: Phase) -> Option<Self> {
+        let level_nodes = find_levels(nodes)?;
+        let mut levels: Vec<Level> = level_nodes.iter().map(|rep| build_level(rep)).collect();
+        quality::flag_reported_quality(nodes, level_nodes, &mut levels);
+
+        let mut header = vec![];
+        collect_elements(nodes, &mut header, false);
+        let find = |desc: Descriptor| {
+            header
+                .iter()
+                .filter(|(d, _)| *d == desc)
+                .find_map(|(_, v)| v.as_f64())
+        };
+
+        let call_sign = header
+            .iter()
+            .filter(|(d, _)| *d == CALL_SIGN)
+            .find_map(|(_, v)| v.as_str())
+            .map(|s| {
+                s.trim_matches(|c: char| c.is_whitespace() || c == '\0')
+                    .to_owned()
+            })
+            .filter(|s| !s.is_empty());
+
+        let station = Station {
+            wmo_block: find(WMO_BLOCK).map(|v| v as u8),
+            wmo_station: find(WMO_STATION).map(|v| v as u16),
+            call_sign,
+            latitude: find(LATITUDE).or_else(|| find(LATITUDE_COARSE)),
+            longitude: find(LONGITUDE).or_else(|| find(LONGITUDE_COARSE)),
+            elevation: find(STATION_HEIGHT).or_else(|| find(RELEASE_HEIGHT)),
+        };
+
+        let launch_time = match (find(YEAR), find(MONTH), find(DAY), find(HOUR)) {
+            (Some(year), Some(month), Some(day), Some(hour)) => Some(Timestamp {
+                year: year as u16,
+                month: month as u8,
+                day: day as u8,
+                hour: hour as u8,
+                minute: find(MINUTE).unwrap_or(0.0) as u8,
+                second: find(SECOND).unwrap_or(0.0) as u8,
+            }),
+            _ => None,
+        };
+
+        let radiosonde_type = find(RADIOSONDE_TYPE).map(|v| v as u16);
+
+        Some(Sounding::new(
+            phase,
+            station,
+            launch_time,
+            radiosonde_type,
+            levels,
+        ))
+    }
+}
+
+pub(crate) const WMO_BLOCK: Descriptor = Descriptor::new(0, 1, 1);
+pub(crate) const WMO_STATION: Descriptor = Descriptor::new(0, 1, 2);
+pub(crate) const CALL_SIGN: Descriptor = Descriptor::new(0, 1, 11);
+pub(crate) const RADIOSONDE_TYPE: Descriptor = Descriptor::new(0, 2, 11);
+pub(crate) const YEAR: Descriptor = Descriptor::new(0, 4, 1);
+pub(crate) const MONTH: Descriptor = Descriptor::new(0, 4, 2);
+pub(crate) const DAY: Descriptor = Descriptor::new(0, 4, 3);
+pub(crate) const HOUR: Descriptor = Descriptor::new(0, 4, 4);
+pub(crate) const MINUTE: Descriptor = Descriptor::new(0, 4, 5);
+pub(crate) const SECOND: Descriptor = Descriptor::new(0, 4, 6);
+pub(crate) const TIME_OFFSET: Descriptor = Descriptor::new(0, 4, 86);
+pub(crate) const LATITUDE: Descriptor = Descriptor::new(0, 5, 1);
+const LATITUDE_COARSE: Descriptor = Descriptor::new(0, 5, 2);
+pub(crate) const LAT_DISPLACEMENT: Descriptor = Descriptor::new(0, 5, 15);
+pub(crate) const LONGITUDE: Descriptor = Descriptor::new(0, 6, 1);
+const LONGITUDE_COARSE: Descriptor = Descriptor::new(0, 6, 2);
+pub(crate) const LON_DISPLACEMENT: Descriptor = Descriptor::new(0, 6, 15);
+pub(crate) const PRESSURE: Descriptor = Descriptor::new(0, 7, 4);
+pub(crate) const RELEASE_HEIGHT: Descriptor = Descriptor::new(0, 7, 7);
+pub(crate) const GEOPOTENTIAL_HEIGHT_PILOT: Descriptor = Descriptor::new(0, 7, 9);
+const HEIGHT_OR_ALTITUDE: Descriptor = Descriptor::new(0, 7, 2);
+pub(crate) const STATION_HEIGHT: Descriptor = Descriptor::new(0, 7, 30);
+pub(crate) const SIGNIFICANCE: Descriptor = Descriptor::new(0, 8, 42);
+pub(crate) const GEOPOTENTIAL_HEIGHT: Descriptor = Descriptor::new(0, 10, 9);
+pub(crate) const WIND_DIRECTION: Descriptor = Descriptor::new(0, 11, 1);
+pub(crate) const WIND_SPEED: Descriptor = Descriptor::new(0, 11, 2);
+pub(crate) const TEMPERATURE: Descriptor = Descriptor::new(0, 12, 101);
+const TEMPERATURE_COARSE: Descriptor = Descriptor::new(0, 12, 1);
+pub(crate) const DEWPOINT: Descriptor = Descriptor::new(0, 12, 103);
+const DEWPOINT_COARSE: Descriptor = Descriptor::new(0, 12, 3);
+pub(crate) const RELATIVE_HUMIDITY: Descriptor = Descriptor::new(0, 13, 3);
+
+/// Flatten the elements in a tree into `out`, optionally descending into replications.
+pub(crate) fn collect_elements<'a>(
+    nodes: &'a [DataNode],
+    out: &mut Vec<(Descriptor, &'a Value)>,
+    into_replications: bool,
+) {
+    for_each_element(nodes, into_replications, &mut |desc, value| {
+        out.push((desc, value))
+    });
+}
+
+/// Visit the elements in a tree in order, without collecting them.
+fn for_each_element<'a>(
+    nodes: &'a [DataNode],
+    into_replications: bool,
+    visit: &mut impl FnMut(Descriptor, &'a Value),
+) {
+    for node in nodes {
+        match node {
+            DataNode::Element {
+                descriptor, value, ..
+            } => visit(*descriptor, value),
+            DataNode::Sequence { children, .. } => {
+                for_each_element(children, into_replications, visit)
+            }
+            DataNode::Replication { repetitions, .. } => {
+                if into_replications {
+                    for rep in repetitions {
+                        for_each_element(rep, into_replications, visit);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Find the replication holding the profile. Other replications (e.g. the wind shear data in
+/// 3-03-051) may also carry a pressure, so require a temperature or wind direction too.
+fn find_levels(nodes: &[DataNode]) -> Option<&[Vec<DataNode>]> {
+    for node in nodes {
+        match node {
+            DataNode::Element { .. } => {}
+            DataNode::Sequence { children, .. } => {
+                if let Some(levels) = find_levels(children) {
+                    return Some(levels);
+                }
+            }
+            DataNode::Replication { repetitions, .. } => {
+                if let Some(first) = repetitions.first() {
+                    let (mut pressure, mut profile) = (false, false);
+                    for_each_element(first, false, &mut |desc, _| match desc {
+                        PRESSURE => pressure = true,
+                        TEMPERATURE | TEMPERATURE_COARSE | WIND_DIRECTION => profile = true,
+                        _ => {}
+                    });
+
+                    if pressure && profile {
+                        return Some(repetitions);
+                    }
+                }
+
+                for rep in repetitions {
+                    if let Some(levels) = find_levels(rep) {
+                        return Some(levels);
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn build_level(nodes: &[DataNode]) -> Level {
+    // Called for every level, so visit the elements rather than collecting them.
+    let mut level = Level::default();
+    for_each_element(nodes, false, &mut |desc, value| {
+        let slot = match desc {
+            TIME_OFFSET => &mut level.time_offset,
+            PRESSURE => &mut level.pressure,
+            GEOPOTENTIAL_HEIGHT | GEOPOTENTIAL_HEIGHT_PILOT => &mut level.height,
+            // Within a level 0-07-007 is the height of the sonde, not of the release.
+            HEIGHT_OR_ALTITUDE | RELEASE_HEIGHT => &mut level.gnss_height,
+            TEMPERATURE | TEMPERATURE_COARSE => &mut level.temperature,
+            DEWPOINT | DEWPOINT_COARSE => &mut level.dewpoint,
+            RELATIVE_HUMIDITY => &mut level.relative_humidity,
+            WIND_DIRECTION => &mut level.wind_direction,
+            WIND_SPEED => &mut level.wind_speed,
+            LAT_DISPLACEMENT => &mut level.lat_displacement,
+            LON_DISPLACEMENT => &mut level.lon_displacement,
+            SIGNIFICANCE => {
+                if level.significance.is_none() {
+                    level.significance = value.as_i64().map(|v| v as u32);
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        if slot.is_none() {
+            *slot = value.as_f64();
+        }
+    });
+
+    level
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_phase_from_descriptors() {
+        let ascent = [Descriptor::new(3, 9, 52), Descriptor::new(0, 1, 81)];
+        assert_eq!(Phase::from_descriptors(&ascent), Phase::Ascent);
+
+        let descent = [Descriptor::new(3, 9, 56), Descriptor::new(0, 1, 81)];
+        assert_eq!(Phase::from_descriptors(&descent), Phase::Descent);
+
+        let precise_ascent = [Descriptor::new(3, 9, 57)];
+        assert_eq!(Phase::from_descriptors(&precise_ascent), Phase::Ascent);
+    }
+
+    #[test]
+    fn test_find_levels_skips_wind_shear() {
+        let element = |desc, value| DataNode::Element {
+            descriptor: desc,
+            value: Value::Float(value),
+            raw: None,
+        };
+
+        let shear = DataNode::Replication {
+            descriptor: Descriptor::new(1, 1, 0),
+            repetitions: vec![vec![element(PRESSURE, 50000.0)]],
+        };
+        let profile = DataNode::Replication {
+            descriptor: Descriptor::new(1, 1, 0),
+            repetitions: vec![
+                vec![element(PRESSURE, 85000.0), element(TEMPERATURE, 280.0)],
+                vec![element(PRESSURE, 70000.0), element(TEMPERATURE, 270.0)],
+            ],
+        };
+
+        let nodes = [element(WMO_BLOCK, 72.0), shear, profile];
+        let sounding = Sounding::from_subset(&nodes, Phase::Ascent).unwrap();
+
+        assert_eq!(sounding.station().wmo_block, Some(72));
+        assert_eq!(sounding.levels().len(), 2);
+        assert_eq!(sounding.levels()[1].pressure, Some(70000.0));
+        assert_eq!(sounding.levels()[1].temperature, Some(270.0));
+    }
+
+    #[test]
+    fn test_wind_components() {
+        // A west wind blows towards the east.
+        let lvl = Level {
+            wind_direction: Some(270.0),
+            wind_speed: Some(10.0),
+            ..Level::default()
+        };
+        let (u, v) = lvl.wind_components().unwrap();
+        assert!((u - 10.0).abs() < 1.0e-9);
+        assert!(v.abs() < 1.0e-9);
+
+        let mut lvl = Level::default();
+        lvl.set_wind_components(-5.0, -5.0);
+        assert!((lvl.wind_direction.unwrap() - 45.0).abs() < 1.0e-9);
+        assert!((lvl.wind_speed.unwrap() - 50.0f64.sqrt()).abs() < 1.0e-9);
+    }
+}
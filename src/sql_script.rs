@@ -0,0 +1,313 @@
+use crate::sounding::Sounding;
+use std::{error::Error, io::Write};
+
+/// How a launch already in the archive is handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Keep the existing launch and its levels.
+    Append,
+    /// Replace the existing launch and its levels.
+    Upsert,
+}
+
+/// Writes soundings as a SQL script for SQLite. It doesn't write a database itself, the crate has
+/// no SQLite dependency, so the output has to be run by `sqlite3`, e.g.
+/// `sonde-app | sqlite3 archive.db`.
+///
+/// The schema normalizes the data into stations, launches, and levels tables. A launch is keyed
+/// by station, launch time, phase, and the Section 1 update number.
+pub struct SqlScriptWriter<W: Write> {
+    w: W,
+    mode: WriteMode,
+    wrote_schema: bool,
+}
+
+const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS stations (
+    station_id INTEGER PRIMARY KEY,
+    station_key TEXT NOT NULL UNIQUE,
+    wmo_block INTEGER,
+    wmo_station INTEGER,
+    call_sign TEXT
+);
+CREATE TABLE IF NOT EXISTS launches (
+    launch_id INTEGER PRIMARY KEY,
+    station_id INTEGER NOT NULL REFERENCES stations(station_id),
+    launch_time TEXT NOT NULL,
+    phase TEXT NOT NULL,
+    update_number INTEGER NOT NULL,
+    latitude REAL,
+    longitude REAL,
+    elevation REAL,
+    radiosonde_type INTEGER,
+    UNIQUE (station_id, launch_time, phase, update_number)
+);
+CREATE TABLE IF NOT EXISTS levels (
+    launch_id INTEGER NOT NULL REFERENCES launches(launch_id) ON DELETE CASCADE,
+    level_index INTEGER NOT NULL,
+    time_offset REAL,
+    significance INTEGER,
+    pressure REAL,
+    height REAL,
+    gnss_height REAL,
+    temperature REAL,
+    dewpoint REAL,
+    relative_humidity REAL,
+    wind_direction REAL,
+    wind_speed REAL,
+    lat_displacement REAL,
+    lon_displacement REAL,
+    PRIMARY KEY (launch_id, level_index)
+);
+CREATE INDEX IF NOT EXISTS launches_by_time ON launches(launch_time);
+CREATE INDEX IF NOT EXISTS launches_by_station_time ON launches(station_id, launch_time);
+CREATE INDEX IF NOT EXISTS levels_by_pressure ON levels(launch_id, pressure);
+";
+
+impl<W: Write> SqlScriptWriter<W> {
+    pub fn new(w: W, mode: WriteMode) -> Self {
+        SqlScriptWriter {
+            w,
+            mode,
+            wrote_schema: false,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+
+    /// Write the statements that add one sounding, each sounding is its own transaction.
+    pub fn write(&mut self, sounding: &Sounding) -> Result<(), Box<dyn Error>> {
+        if !self.wrote_schema {
+            self.w.write_all(SCHEMA.as_bytes())?;
+            self.wrote_schema = true;
+        }
+
+        let station = sounding.station();
+        let key = station
+            .identifier()
+            .ok_or("Sounding has no station identifier")?;
+        let launch_time = sounding
+            .launch_time()
+            .ok_or("Sounding has no launch time")?;
+
+        let key = text(Some(&key));
+        let station_id = format!(
+            "(SELECT station_id FROM stations WHERE station_key = {})",
+            key
+        );
+        let launch_id = format!(
+            "(SELECT launch_id FROM launches WHERE station_id = {} AND launch_time = '{}' \
+             AND phase = '{}' AND update_number = {})",
+            station_id,
+            launch_time,
+            sounding.phase(),
+            sounding.update_number()
+        );
+
+        writeln!(self.w, "BEGIN;")?;
+        writeln!(
+            self.w,
+            "INSERT INTO stations (station_key, wmo_block, wmo_station, call_sign) \
+             VALUES ({}, {}, {}, {}) ON CONFLICT (station_key) DO NOTHING;",
+            key,
+            real(station.wmo_block.map(f64::from)),
+            real(station.wmo_station.map(f64::from)),
+            text(station.call_sign.as_deref())
+        )?;
+
+        let conflict = match self.mode {
+            WriteMode::Append => "DO NOTHING",
+            WriteMode::Upsert => {
+                "DO UPDATE SET latitude = excluded.latitude, longitude = excluded.longitude, \
+                 elevation = excluded.elevation, radiosonde_type = excluded.radiosonde_type"
+            }
+        };
+        writeln!(
+            self.w,
+            "INSERT INTO launches (station_id, launch_time, phase, update_number, latitude, \
+             longitude, elevation, radiosonde_type) VALUES ({}, '{}', '{}', {}, {}, {}, {}, {}) \
+             ON CONFLICT (station_id, launch_time, phase, update_number) {};",
+            station_id,
+            launch_time,
+            sounding.phase(),
+            sounding.update_number(),
+            real(station.latitude),
+            real(station.longitude),
+            real(station.elevation),
+            real(sounding.radiosonde_type().map(f64::from)),
+            conflict
+        )?;
+
+        if self.mode == WriteMode::Upsert {
+            writeln!(
+                self.w,
+                "DELETE FROM levels WHERE launch_id = {};",
+                launch_id
+            )?;
+        }
+
+        if !sounding.levels().is_empty() {
+            writeln!(
+                self.w,
+                "INSERT OR IGNORE INTO levels (launch_id, level_index, time_offset, \
+                 significance, pressure, height, gnss_height, temperature, dewpoint, \
+                 relative_humidity, wind_direction, wind_speed, lat_displacement, \
+                 lon_displacement) \
+                 SELECT {}, column1, column2, column3, column4, column5, column6, column7, \
+                 column8, column9, column10, column11, column12, column13 FROM (VALUES",
+                launch_id
+            )?;
+
+            let num_levels = sounding.levels().len();
+            for (i, lvl) in sounding.levels().iter().enumerate() {
+                writeln!(
+                    self.w,
+                    "({}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}){}",
+                    i,
+                    real(lvl.time_offset),
+                    real(lvl.significance.map(f64::from)),
+                    real(lvl.pressure),
+                    real(lvl.height),
+                    real(lvl.gnss_height),
+                    real(lvl.temperature),
+                    real(lvl.dewpoint),
+                    real(lvl.relative_humidity),
+                    real(lvl.wind_direction),
+                    real(lvl.wind_speed),
+                    real(lvl.lat_displacement),
+                    real(lvl.lon_displacement),
+                    if i + 1 == num_levels { ");" } else { "," }
+                )?;
+            }
+        }
+
+        writeln!(self.w, "COMMIT;")?;
+
+        Ok(())
+    }
+}
+
+fn real(val: Option<f64>) -> String {
+    match val {
+        Some(val) if val.is_finite() => format!("{}", val),
+        _ => "NULL".to_owned(),
+    }
+}
+
+fn text(val: Option<&str>) -> String {
+    match val {
+        Some(val) => format!("'{}'", val.replace('\'', "''")),
+        None => "NULL".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Level, Phase, Station, Timestamp};
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_write_modes() {
+        let station = Station {
+            call_sign: Some("O'HARE".to_owned()),
+            ..Station::default()
+        };
+        let time = Timestamp {
+            year: 2017,
+            month: 8,
+            day: 31,
+            hour: 12,
+            minute: 0,
+            second: 0,
+        };
+        let sounding = Sounding::new(
+            Phase::Ascent,
+            station,
+            Some(time),
+            None,
+            vec![Level {
+                gnss_height: Some(1012.5),
+                relative_humidity: Some(47.0),
+                ..Level::default()
+            }],
+        );
+
+        let mut w = SqlScriptWriter::new(vec![], WriteMode::Append);
+        w.write(&sounding).unwrap();
+        w.write(&sounding).unwrap();
+        let sql = String::from_utf8(w.into_inner()).unwrap();
+        assert_eq!(sql.matches("CREATE TABLE IF NOT EXISTS levels").count(), 1);
+        assert!(sql.contains("'O''HARE'"));
+        assert!(!sql.contains("DELETE FROM levels"));
+        assert!(sql.contains("(0, NULL, NULL, NULL, NULL, 1012.5, NULL, NULL, 47, NULL,"));
+
+        let mut w = SqlScriptWriter::new(vec![], WriteMode::Upsert);
+        w.write(&sounding).unwrap();
+        let sql = String::from_utf8(w.into_inner()).unwrap();
+        assert!(sql.contains("DELETE FROM levels"));
+    }
+
+    #[test]
+    fn test_load_with_sqlite3() {
+        let file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let sounding = &crate::read_bufr_message(&file[start..])
+            .unwrap()
+            .soundings()[0];
+
+        let mut w = SqlScriptWriter::new(vec![], WriteMode::Append);
+        w.write(sounding).unwrap();
+        w.write(sounding).unwrap();
+        let append = w.into_inner();
+        let mut w = SqlScriptWriter::new(vec![], WriteMode::Upsert);
+        w.write(sounding).unwrap();
+        let upsert = w.into_inner();
+
+        let dir = std::env::temp_dir().join(format!("sonde-bufr-sqlite-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("archive.db");
+        let sqlite3 = |input: &[u8]| {
+            let mut child = Command::new("sqlite3")
+                .arg(&db)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            child.stdin.take().unwrap().write_all(input)?;
+            child.wait_with_output()
+        };
+
+        let output = match sqlite3(&append) {
+            Ok(output) => output,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                eprintln!("sqlite3 isn't installed, not loading the script");
+                std::fs::remove_dir_all(&dir).unwrap();
+                return;
+            }
+            Err(err) => panic!("{}", err),
+        };
+        assert!(output.status.success() && output.stderr.is_empty());
+        assert!(sqlite3(&upsert).unwrap().stderr.is_empty());
+
+        let query = b"SELECT COUNT(*) FROM launches;\n\
+            SELECT COUNT(*), COUNT(temperature) FROM levels;\n\
+            SELECT station_key FROM stations;\n";
+        let output = sqlite3(query).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let levels = sounding.levels();
+        let with_temperature = levels.iter().filter(|l| l.temperature.is_some()).count();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            format!(
+                "1\n{}|{}\n{}\n",
+                levels.len(),
+                with_temperature,
+                sounding.station().identifier().unwrap()
+            )
+        );
+    }
+}
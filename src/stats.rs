@@ -0,0 +1,238 @@
+use crate::{
+    export::{json_number, json_string},
+    read_3_octet_usize, read_bufr_bytes, read_bufr_message, scan_to_bufr_start,
+    section3::Descriptor,
+    section4::Value,
+    sounding::{collect_elements, Timestamp},
+    tables::lookup_element,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::Display,
+    fs::File,
+    io::{BufReader, Read, Seek, Write},
+    path::Path,
+};
+
+/// Statistics about the messages in a file, for data quality monitoring.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileStats {
+    pub messages: usize,
+    /// Messages that couldn't be decoded, e.g. ones using unsupported operators. They count
+    /// towards `messages` and `compressed` but nothing else.
+    pub undecoded: usize,
+    /// Messages with compressed data in Section 3.
+    pub compressed: usize,
+    pub subsets: usize,
+    /// Station identifiers, see `Station::identifier`.
+    pub stations: BTreeSet<String>,
+    /// The earliest and latest nominal times in Section 1.
+    pub first_time: Option<Timestamp>,
+    pub last_time: Option<Timestamp>,
+    /// The number of levels of each sounding, in the order read.
+    pub levels_per_sounding: Vec<usize>,
+    /// How many values of each element were read and how many of them were missing.
+    pub element_counts: BTreeMap<Descriptor, (usize, usize)>,
+}
+
+impl FileStats {
+    /// Read the statistics of every message in a file.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read(mut f: impl Read + Seek) -> Result<Self, Box<dyn Error>> {
+        let mut stats = FileStats::default();
+        while scan_to_bufr_start(&mut f).is_ok() {
+            stats.add_message(&read_bufr_bytes(&mut f)?);
+        }
+        Ok(stats)
+    }
+
+    /// Add one message, from `BUFR` through `7777` (see `read_bufr_bytes`).
+    pub fn add_message(&mut self, message: &[u8]) {
+        self.messages += 1;
+        if is_compressed(message) == Some(true) {
+            self.compressed += 1;
+        }
+
+        let Ok(bufr) = read_bufr_message(message) else {
+            self.undecoded += 1;
+            return;
+        };
+
+        let time = bufr.nominal_time();
+        self.first_time = Some(self.first_time.map_or(time, |t| t.min(time)));
+        self.last_time = Some(self.last_time.map_or(time, |t| t.max(time)));
+
+        let subsets = bufr.section_4.subsets();
+        self.subsets += subsets.len();
+        for subset in subsets {
+            let mut elements = vec![];
+            collect_elements(subset, &mut elements, true);
+            for (descriptor, value) in elements {
+                let (total, missing) = self.element_counts.entry(descriptor).or_default();
+                *total += 1;
+                if *value == Value::Missing {
+                    *missing += 1;
+                }
+            }
+        }
+
+        for sounding in bufr.soundings() {
+            self.stations.extend(sounding.station().identifier());
+            self.levels_per_sounding.push(sounding.levels().len());
+        }
+    }
+
+    /// The percentage of the values of an element that were missing, `None` if there were none.
+    pub fn missing_percent(&self, descriptor: Descriptor) -> Option<f64> {
+        let &(total, missing) = self.element_counts.get(&descriptor)?;
+        (total > 0).then(|| 100.0 * missing as f64 / total as f64)
+    }
+
+    /// The minimum, quartiles, and maximum of `levels_per_sounding`.
+    pub fn levels_quartiles(&self) -> Option<[usize; 5]> {
+        let mut sorted = self.levels_per_sounding.clone();
+        sorted.sort_unstable();
+        let last = sorted.len().checked_sub(1)?;
+        Some([0, 1, 2, 3, 4].map(|q| sorted[last * q / 4]))
+    }
+
+    /// Write the statistics as a JSON object, with the elements keyed by descriptor.
+    pub fn write_json(&self, mut w: impl Write) -> Result<(), Box<dyn Error>> {
+        let time = |t: Option<Timestamp>| json_string(t.map(|t| t.to_string()).as_deref());
+
+        write!(
+            w,
+            "{{\"messages\":{},\"undecoded\":{},\"compressed\":{},\"subsets\":{},\"stations\":[",
+            self.messages, self.undecoded, self.compressed, self.subsets
+        )?;
+        for (i, station) in self.stations.iter().enumerate() {
+            let sep = if i > 0 { "," } else { "" };
+            write!(w, "{}{}", sep, json_string(Some(station)))?;
+        }
+        write!(
+            w,
+            "],\"first_time\":{},\"last_time\":{},\"levels_per_sounding\":",
+            time(self.first_time),
+            time(self.last_time)
+        )?;
+        match self.levels_quartiles() {
+            Some([min, q1, median, q3, max]) => write!(
+                w,
+                "{{\"min\":{},\"q1\":{},\"median\":{},\"q3\":{},\"max\":{}}}",
+                min, q1, median, q3, max
+            )?,
+            None => write!(w, "null")?,
+        }
+
+        write!(w, ",\"elements\":{{")?;
+        for (i, (descriptor, &(total, _))) in self.element_counts.iter().enumerate() {
+            let sep = if i > 0 { "," } else { "" };
+            write!(
+                w,
+                "{}\"{}\":{{\"count\":{},\"missing_percent\":{}}}",
+                sep,
+                descriptor.string_form(),
+                total,
+                json_number(self.missing_percent(*descriptor))
+            )?;
+        }
+        writeln!(w, "}}}}")?;
+
+        Ok(())
+    }
+}
+
+impl Display for FileStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        writeln!(f, "   Messages: {}", self.messages)?;
+        writeln!(f, "  Undecoded: {}", self.undecoded)?;
+        writeln!(f, " Compressed: {}", self.compressed)?;
+        writeln!(f, "    Subsets: {}", self.subsets)?;
+        writeln!(f, "   Stations: {}", self.stations.len())?;
+        if let (Some(first), Some(last)) = (self.first_time, self.last_time) {
+            writeln!(f, "   Coverage: {} to {}", first, last)?;
+        }
+        if let Some([min, q1, median, q3, max]) = self.levels_quartiles() {
+            writeln!(
+                f,
+                "     Levels: min {} q1 {} median {} q3 {} max {}",
+                min, q1, median, q3, max
+            )?;
+        }
+
+        writeln!(f, "Missing values by element:")?;
+        for (descriptor, &(total, _)) in &self.element_counts {
+            let name = lookup_element(*descriptor).map_or("", |def| def.name);
+            writeln!(
+                f,
+                "  {} {:6.2}% of {:7} {}",
+                descriptor.string_form(),
+                self.missing_percent(*descriptor).unwrap_or(0.0),
+                total,
+                name
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The compressed data flag of Section 3 read straight from the message, so it's known for
+/// messages that can't be decoded. `None` if the section lengths don't make sense.
+fn is_compressed(message: &[u8]) -> Option<bool> {
+    let len = |start: usize| read_3_octet_usize(message.get(start..)?).ok();
+
+    let mut section_3 = 8 + len(8)?;
+    // Octet 10 of Section 1 flags an optional Section 2.
+    if message.get(8 + 9)? & 0x80 != 0 {
+        section_3 += len(section_3)?;
+    }
+    // Bit 2 of octet 7 of Section 3.
+    Some(message.get(section_3 + 6)? & 0x40 != 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_stats() {
+        let stats = FileStats::from_path("test-data/2017083115.bufr").unwrap();
+        assert_eq!(stats.messages, 1);
+        assert_eq!(stats.undecoded, 0);
+        assert_eq!(stats.compressed, 0);
+        assert_eq!(stats.subsets, 1);
+        assert_eq!(stats.stations.len(), 1);
+        assert_eq!(stats.first_time, stats.last_time);
+        assert_eq!(stats.levels_per_sounding, [4879]);
+        assert_eq!(stats.levels_quartiles(), Some([4879; 5]));
+
+        let pressure = Descriptor::new(0, 7, 4);
+        // Every level has a pressure, and so does the wind shear data.
+        assert!(stats.element_counts[&pressure].0 > 4879);
+        assert!(stats.missing_percent(pressure).unwrap() < 1.0);
+
+        let mut json = vec![];
+        stats.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with("{\"messages\":1,\"undecoded\":0,\"compressed\":0,"));
+        assert!(!stats.to_string().is_empty());
+
+        // A message flagged as compressed that isn't doesn't decode but is counted.
+        let mut message = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = message.windows(4).position(|w| w == b"BUFR").unwrap();
+        message.drain(..start);
+        let section_3 = 8 + 22;
+        message[section_3 + 6] |= 0x40;
+        let mut stats = FileStats::default();
+        stats.add_message(&message);
+        assert_eq!(
+            (stats.messages, stats.compressed, stats.undecoded),
+            (1, 1, 1)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{conformance::STANDARD_LEVELS, sounding::Sounding};
+use std::fmt::Display;
+
+impl Sounding {
+    /// One line with the station, launch time, report type, level count, and top of the
+    /// profile, the first line of the `Display` output.
+    pub fn summary(&self) -> String {
+        let station = self
+            .station()
+            .identifier()
+            .unwrap_or_else(|| "Unknown station".to_owned());
+        let launch = self
+            .launch_time()
+            .map_or("unknown launch time".to_owned(), |t| t.to_string());
+        let report_type = self
+            .report_type()
+            .map_or("Unclassified".to_owned(), |r| r.to_string());
+
+        let mut summary = format!(
+            "{} {} {} {}, {} levels",
+            station,
+            launch,
+            report_type,
+            self.phase(),
+            self.levels().len()
+        );
+
+        let top = self
+            .levels()
+            .iter()
+            .filter(|lvl| lvl.pressure.is_some())
+            .min_by(|a, b| a.pressure.unwrap().total_cmp(&b.pressure.unwrap()));
+        if let Some(top) = top {
+            summary += &format!(" to {:.1} hPa", top.pressure.unwrap() / 100.0);
+            if let Some(height) = top.height {
+                summary += &format!(" ({:.0} m)", height);
+            }
+        }
+
+        summary
+    }
+}
+
+/// The summary line and a table of the values interpolated to the standard pressure levels in
+/// the profile.
+impl Display for Sounding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        writeln!(f, "{}", self.summary())?;
+        write!(
+            f,
+            "{:>7} {:>7} {:>6} {:>6} {:>4} {:>4} {:>5}",
+            "hPa", "m", "T C", "Td C", "RH %", "Dir", "m/s"
+        )?;
+
+        let val = |v: Option<f64>, width: usize, decimals: usize| match v {
+            Some(v) => format!("{:>width$.decimals$}", v),
+            None => format!("{:>width$}", "-"),
+        };
+        let celsius = |v: Option<f64>| v.map(|k| k - 273.15);
+
+        for (p, lvl) in STANDARD_LEVELS
+            .iter()
+            .zip(self.interpolate_to(&STANDARD_LEVELS))
+        {
+            let values = [
+                lvl.height,
+                lvl.temperature,
+                lvl.dewpoint,
+                lvl.relative_humidity,
+                lvl.wind_direction,
+                lvl.wind_speed,
+            ];
+            if values.iter().all(Option::is_none) {
+                continue;
+            }
+
+            write!(
+                f,
+                "\n{:>7.0} {} {} {} {} {} {}",
+                p / 100.0,
+                val(lvl.height, 7, 0),
+                val(celsius(lvl.temperature), 6, 1),
+                val(celsius(lvl.dewpoint), 6, 1),
+                val(lvl.relative_humidity, 4, 0),
+                val(lvl.wind_direction, 4, 0),
+                val(lvl.wind_speed, 5, 1)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        sounding::{Level, Phase, Station, Timestamp},
+        Sounding,
+    };
+
+    #[test]
+    fn test_display() {
+        let level = |hpa: f64, height, t| Level {
+            pressure: Some(hpa * 100.0),
+            height: Some(height),
+            temperature: Some(t),
+            wind_direction: Some(270.0),
+            wind_speed: Some(10.0),
+            ..Level::default()
+        };
+        let station = Station {
+            wmo_block: Some(72),
+            wmo_station: Some(776),
+            ..Station::default()
+        };
+        let launch = Timestamp {
+            year: 2017,
+            month: 8,
+            day: 31,
+            hour: 12,
+            minute: 0,
+            second: 0,
+        };
+        let levels = vec![
+            level(900.0, 1000.0, 293.15),
+            level(850.0, 1500.0, 288.15),
+            level(700.0, 3100.0, 278.15),
+        ];
+        let sounding = Sounding::new(Phase::Ascent, station, Some(launch), None, levels);
+
+        assert_eq!(
+            sounding.summary(),
+            "72776 2017-08-31 12:00:00 Unclassified Ascent, 3 levels to 700.0 hPa (3100 m)"
+        );
+
+        let text = sounding.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        // The summary, the header, and 850 and 700 hPa.
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], "    hPa       m    T C   Td C RH %  Dir   m/s");
+        assert_eq!(lines[2], "    850    1500   15.0      -    -  270  10.0");
+        assert!(lines[1..].iter().all(|line| line.len() == lines[1].len()));
+    }
+}
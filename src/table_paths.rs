@@ -0,0 +1,150 @@
+use crate::tables::TableOverrides;
+use std::{
+    env,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+/// The environment variable with extra table paths, separated like `PATH`.
+pub const TABLES_ENV_VAR: &str = "SONDE_BUFR_TABLES";
+
+/// Where `DecoderBuilder::search_table_paths` looks for table override files, lowest priority
+/// first: the per-user data directory, the per-user config directory, and then each path in
+/// `SONDE_BUFR_TABLES`. The directories are `sonde-bufr/tables` under `$XDG_DATA_HOME` (or
+/// `~/.local/share`) and `$XDG_CONFIG_HOME` (or `~/.config`), and under `%APPDATA%` on Windows.
+///
+/// The returned list includes directories that don't exist.
+pub fn table_search_paths() -> Vec<PathBuf> {
+    let var = |name: &str| {
+        env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    let home = var("HOME");
+    let under_home = |dir: &str| home.as_ref().map(|home| home.join(dir));
+
+    let data = var("XDG_DATA_HOME").or_else(|| under_home(".local/share"));
+    let config = var("XDG_CONFIG_HOME")
+        .or_else(|| under_home(".config"))
+        .or_else(|| var("APPDATA"));
+
+    let mut paths: Vec<PathBuf> = [data, config]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join("sonde-bufr").join("tables"))
+        .collect();
+    if let Some(list) = env::var_os(TABLES_ENV_VAR) {
+        paths.extend(env::split_paths(&list).filter(|p| !p.as_os_str().is_empty()));
+    }
+
+    paths
+}
+
+/// Read a TOML overrides file, or every `.toml` file in a directory in name order with later
+/// files winning.
+pub(crate) fn read_table_path(path: &Path) -> Result<TableOverrides, Box<dyn Error>> {
+    let context = |err: Box<dyn Error>| format!("{}: {}", path.display(), err);
+    if !path.is_dir() {
+        return TableOverrides::read(path).map_err(|err| context(err).into());
+    }
+
+    let mut files = vec![];
+    for entry in std::fs::read_dir(path).map_err(|err| context(err.into()))? {
+        let file = entry?.path();
+        if file.is_file() && file.extension().is_some_and(|ext| ext == "toml") {
+            files.push(file);
+        }
+    }
+    files.sort();
+
+    let mut overrides = TableOverrides::new();
+    for file in files {
+        let tables =
+            TableOverrides::read(&file).map_err(|err| format!("{}: {}", file.display(), err))?;
+        overrides.merge(tables);
+    }
+
+    Ok(overrides)
+}
+
+/// The overrides from the search paths that exist, see `table_search_paths`. A path from the
+/// environment variable that doesn't exist is an error, as that's likely a mistake.
+pub(crate) fn search_tables() -> Result<TableOverrides, Box<dyn Error>> {
+    let from_env: Vec<PathBuf> = env::var_os(TABLES_ENV_VAR)
+        .map(|list| env::split_paths(&list).collect())
+        .unwrap_or_default();
+
+    let mut overrides = TableOverrides::new();
+    for path in table_search_paths() {
+        if !path.exists() {
+            if from_env.contains(&path) {
+                return Err(
+                    format!("{} in {} doesn't exist", path.display(), TABLES_ENV_VAR).into(),
+                );
+            }
+            continue;
+        }
+        overrides.merge(read_table_path(&path)?);
+    }
+
+    Ok(overrides)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{section3::Descriptor, DecoderBuilder};
+    use std::fs;
+
+    #[test]
+    fn test_table_search_paths() {
+        let dir = env::temp_dir().join(format!("sonde-bufr-tables-{}", std::process::id()));
+        let data_tables = dir.join("data").join("sonde-bufr").join("tables");
+        let env_tables = dir.join("env");
+        fs::create_dir_all(&data_tables).unwrap();
+        fs::create_dir_all(&env_tables).unwrap();
+        // Station height in whole metres instead of tenths.
+        let whole_metres = "[[element]]\ndescriptor = \"0-07-030\"\nscale = 0\n";
+        fs::write(data_tables.join("height.toml"), whole_metres).unwrap();
+        let local = "[[element]]\ndescriptor = \"0-63-001\"\nunits = \"K\"\nwidth_bits = 12\n";
+        fs::write(env_tables.join("local.toml"), local).unwrap();
+        fs::write(env_tables.join("notes.txt"), "not a table").unwrap();
+        let tenths = dir.join("tenths.toml");
+        fs::write(
+            &tenths,
+            "[[element]]\ndescriptor = \"0-07-030\"\nscale = 1\n",
+        )
+        .unwrap();
+
+        // The only test that sets these.
+        env::set_var("XDG_DATA_HOME", dir.join("data"));
+        env::set_var("XDG_CONFIG_HOME", dir.join("config"));
+        env::set_var(TABLES_ENV_VAR, dir.join("missing"));
+        assert!(search_tables().is_err());
+
+        env::set_var(TABLES_ENV_VAR, &env_tables);
+        let paths = table_search_paths();
+        assert_eq!(paths.first(), Some(&data_tables));
+        assert_eq!(paths.last(), Some(&env_tables));
+        let found = search_tables().unwrap();
+        assert!(found.element(Descriptor::new(0, 63, 1)).is_some());
+
+        let file = fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        let elevation = |builder: DecoderBuilder| {
+            let bufr = builder.build().read_bufr_message(&file[start..]).unwrap();
+            bufr.soundings()[0].station().elevation
+        };
+        let searched = DecoderBuilder::new().search_table_paths().unwrap();
+        assert_eq!(elevation(searched), Some(12_250.0));
+        // An explicit path wins, even given first.
+        let explicit = DecoderBuilder::new()
+            .table_path(&tenths)
+            .unwrap()
+            .search_table_paths()
+            .unwrap();
+        assert_eq!(elevation(explicit), Some(1225.0));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
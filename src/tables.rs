@@ -0,0 +1,633 @@
+use crate::{section3::Descriptor, section4::TableBEntry, table_b, table_d};
+use lazy_static::lazy_static;
+use std::{collections::HashMap, error::Error, hash::Hasher, path::Path};
+
+lazy_static! {
+    // The built-in tables keyed by descriptor, so lookups while decoding don't format or parse
+    // descriptor strings. Built once, on first use.
+    static ref ELEMENTS: HashMap<Descriptor, TableBEntry<'static>> = table_b::TABLE_B
+        .iter()
+        .map(|(fxy, entry)| (Descriptor::from_string_form(fxy), *entry))
+        .collect();
+    static ref SEQUENCES: HashMap<Descriptor, Vec<Descriptor>> = table_d::TABLE_D
+        .iter()
+        .map(|(fxy, seq)| {
+            let seq = seq.iter().map(|d| Descriptor::from_string_form(d)).collect();
+            (Descriptor::from_string_form(fxy), seq)
+        })
+        .collect();
+}
+
+/// A Table B element as the decoder knows it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ElementDefinition {
+    pub descriptor: Descriptor,
+    pub name: &'static str,
+    pub units: &'static str,
+    pub scale: i32,
+    pub reference: i64,
+    pub width_bits: usize,
+    pub crex_units: &'static str,
+    pub crex_scale: i32,
+    /// Characters
+    pub crex_width: usize,
+}
+
+/// A Table D sequence as the decoder knows it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SequenceDefinition {
+    pub descriptor: Descriptor,
+    /// The descriptors the sequence expands to, which may themselves be sequences.
+    pub descriptors: Vec<Descriptor>,
+}
+
+impl ElementDefinition {
+    fn new(descriptor: Descriptor, entry: &TableBEntry<'static>) -> Self {
+        ElementDefinition {
+            descriptor,
+            name: entry.element_name,
+            units: entry.units,
+            scale: entry.scale_val,
+            reference: entry.reference_val,
+            width_bits: entry.width_bits,
+            crex_units: entry.crex_units,
+            crex_scale: entry.crex_scale,
+            crex_width: entry.crex_width,
+        }
+    }
+}
+
+/// Every built-in Table B element, ordered by descriptor.
+pub fn table_b_entries() -> Vec<ElementDefinition> {
+    let mut entries: Vec<_> = ELEMENTS
+        .iter()
+        .map(|(&descriptor, entry)| ElementDefinition::new(descriptor, entry))
+        .collect();
+    entries.sort_by_key(|entry| entry.descriptor);
+    entries
+}
+
+/// Every built-in Table D sequence, ordered by descriptor.
+pub fn table_d_entries() -> Vec<SequenceDefinition> {
+    let mut entries: Vec<_> = SEQUENCES
+        .iter()
+        .map(|(&descriptor, descriptors)| SequenceDefinition {
+            descriptor,
+            descriptors: descriptors.clone(),
+        })
+        .collect();
+    entries.sort_by_key(|entry| entry.descriptor);
+    entries
+}
+
+/// The built-in Table B definition of an element descriptor.
+pub fn lookup_element(descriptor: Descriptor) -> Option<ElementDefinition> {
+    ELEMENTS
+        .get(&descriptor)
+        .map(|entry| ElementDefinition::new(descriptor, entry))
+}
+
+/// The built-in Table D definition of a sequence descriptor.
+pub fn lookup_sequence(descriptor: Descriptor) -> Option<SequenceDefinition> {
+    SEQUENCES.get(&descriptor).map(|seq| SequenceDefinition {
+        descriptor,
+        descriptors: seq.clone(),
+    })
+}
+
+/// The definition of an element, from `overrides` if it's there and otherwise the built-in table,
+/// unless the overrides are `exclusive`.
+pub(crate) fn table_b_entry(
+    desc: Descriptor,
+    overrides: Option<&TableOverrides>,
+) -> Result<TableBEntry<'_>, Box<dyn Error>> {
+    let built_in = overrides.is_none_or(|overrides| !overrides.exclusive);
+    overrides
+        .and_then(|overrides| overrides.element(desc))
+        .or_else(|| ELEMENTS.get(&desc).copied().filter(|_| built_in))
+        .ok_or_else(|| format!("Unknown Table B descriptor: {}", desc.string_form()).into())
+}
+
+/// The descriptors of a sequence, from `overrides` if it's there and otherwise the built-in
+/// table, unless the overrides are `exclusive`.
+pub(crate) fn table_d_sequence(
+    desc: Descriptor,
+    overrides: Option<&TableOverrides>,
+) -> Result<&[Descriptor], Box<dyn Error>> {
+    let built_in = overrides.is_none_or(|overrides| !overrides.exclusive);
+    overrides
+        .and_then(|overrides| overrides.sequence(desc))
+        .or_else(|| SEQUENCES.get(&desc).map(Vec::as_slice).filter(|_| built_in))
+        .ok_or_else(|| format!("Unknown Table D descriptor: {}", desc.string_form()).into())
+}
+
+/// A Table B element to add to, or replace in, the built-in table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElementOverride {
+    pub name: String,
+    pub units: String,
+    pub scale: i32,
+    pub reference: i64,
+    pub width_bits: usize,
+    pub crex_units: String,
+    pub crex_scale: i32,
+    /// Characters
+    pub crex_width: usize,
+}
+
+impl From<ElementDefinition> for ElementOverride {
+    fn from(element: ElementDefinition) -> Self {
+        ElementOverride {
+            name: element.name.to_owned(),
+            units: element.units.to_owned(),
+            scale: element.scale,
+            reference: element.reference,
+            width_bits: element.width_bits,
+            crex_units: element.crex_units.to_owned(),
+            crex_scale: element.crex_scale,
+            crex_width: element.crex_width,
+        }
+    }
+}
+
+impl ElementOverride {
+    pub(crate) fn entry(&self) -> TableBEntry<'_> {
+        TableBEntry {
+            width_bits: self.width_bits,
+            element_name: &self.name,
+            units: &self.units,
+            reference_val: self.reference,
+            scale_val: self.scale,
+            crex_units: &self.crex_units,
+            crex_scale: self.crex_scale,
+            crex_width: self.crex_width,
+        }
+    }
+}
+
+/// Fails if an element is wider than the decoder can read.
+pub(crate) fn check_width(descriptor: Descriptor, width_bits: usize) -> Result<(), Box<dyn Error>> {
+    if width_bits == 0 || width_bits > table_b::MAX_BIT_WIDTH {
+        return Err(format!(
+            "{} has a width of {} bits, it must be 1 to {}",
+            descriptor.string_form(),
+            width_bits,
+            table_b::MAX_BIT_WIDTH
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Table B elements and Table D sequences that take the place of the built-in definitions,
+/// e.g. for local descriptors. See `DecoderBuilder::table_overrides`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableOverrides {
+    elements: HashMap<Descriptor, ElementOverride>,
+    sequences: HashMap<Descriptor, Vec<Descriptor>>,
+    // Whether these are all the definitions, for another master table.
+    exclusive: bool,
+}
+
+impl TableOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace an element. Fails if the width is wider than the decoder can read.
+    pub fn add_element(
+        &mut self,
+        descriptor: Descriptor,
+        element: ElementOverride,
+    ) -> Result<(), Box<dyn Error>> {
+        if descriptor.f_value() != 0 {
+            return Err(format!("{} isn't a Table B descriptor", descriptor.string_form()).into());
+        }
+        check_width(descriptor, element.width_bits)?;
+
+        self.elements.insert(descriptor, element);
+        Ok(())
+    }
+
+    /// Add or replace a sequence.
+    pub fn add_sequence(
+        &mut self,
+        descriptor: Descriptor,
+        descriptors: Vec<Descriptor>,
+    ) -> Result<(), Box<dyn Error>> {
+        if descriptor.f_value() != 3 {
+            return Err(format!("{} isn't a Table D descriptor", descriptor.string_form()).into());
+        }
+        if descriptors.is_empty() {
+            return Err(format!("{} is an empty sequence", descriptor.string_form()).into());
+        }
+
+        self.sequences.insert(descriptor, descriptors);
+        Ok(())
+    }
+
+    /// Add everything from `other`, replacing any definitions of the same descriptors.
+    pub fn merge(&mut self, other: TableOverrides) {
+        self.elements.extend(other.elements);
+        self.sequences.extend(other.sequences);
+    }
+
+    /// Feed the definitions to `hasher` in descriptor order, so equal overrides hash the same
+    /// however they were built.
+    pub(crate) fn hash_into(&self, hasher: &mut impl Hasher) {
+        let mut elements: Vec<_> = self.elements.iter().collect();
+        elements.sort_by_key(|(descriptor, _)| **descriptor);
+        for (descriptor, element) in elements {
+            hasher.write(format!("{:?} {:?}\n", descriptor, element).as_bytes());
+        }
+
+        let mut sequences: Vec<_> = self.sequences.iter().collect();
+        sequences.sort_by_key(|(descriptor, _)| **descriptor);
+        for (descriptor, descriptors) in sequences {
+            hasher.write(format!("{:?} {:?}\n", descriptor, descriptors).as_bytes());
+        }
+
+        hasher.write_u8(u8::from(self.exclusive));
+    }
+
+    /// Use only these definitions, not the built-in ones, see `DecoderBuilder::master_table`.
+    pub(crate) fn exclusive(mut self) -> Self {
+        self.exclusive = true;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty() && self.sequences.is_empty()
+    }
+
+    /// Read overrides from a TOML file, see `from_toml`.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parse overrides from TOML with an `[[element]]` table for each element and a
+    /// `[[sequence]]` table for each sequence:
+    ///
+    /// ```toml
+    /// [[element]]
+    /// descriptor = "0-63-001"
+    /// name = "Local temperature"
+    /// units = "K"
+    /// scale = 1
+    /// reference = 0
+    /// width_bits = 12
+    ///
+    /// [[sequence]]
+    /// descriptor = "3-63-001"
+    /// descriptors = ["0-07-004", "0-63-001"]
+    /// ```
+    ///
+    /// An element that replaces a built-in one only needs the keys that change. A new element
+    /// needs `units` and `width_bits`. The CREX keys, `crex_units`, `crex_scale` and
+    /// `crex_width`, default to the BUFR ones and a width of 0 (not used in CREX).
+    ///
+    /// Only a subset of TOML is read, and JSON isn't supported. The file may have the two kinds of
+    /// table, `#` comments, and `key = value` lines where the value is an integer, a string in
+    /// double quotes without escapes, or an array of those strings that may span several lines.
+    pub fn from_toml(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut overrides = TableOverrides::new();
+        for table in parse_toml_tables(text)? {
+            let context =
+                |err: Box<dyn Error>| format!("Table overrides line {}: {}", table.line, err);
+            match table.kind.as_str() {
+                "element" => {
+                    let (descriptor, element) = element_from_table(&table).map_err(context)?;
+                    overrides
+                        .add_element(descriptor, element)
+                        .map_err(context)?;
+                }
+                "sequence" => {
+                    let descriptor = table.descriptor("descriptor").map_err(context)?;
+                    let descriptors = match table.get("descriptors") {
+                        Some(TomlValue::Array(items)) => items
+                            .iter()
+                            .map(|item| item.parse())
+                            .collect::<Result<_, _>>()
+                            .map_err(context)?,
+                        _ => return Err(context("expected a descriptors array".into()).into()),
+                    };
+                    overrides
+                        .add_sequence(descriptor, descriptors)
+                        .map_err(context)?;
+                }
+                kind => return Err(context(format!("unknown table [[{}]]", kind).into()).into()),
+            }
+        }
+
+        Ok(overrides)
+    }
+
+    pub(crate) fn element(&self, descriptor: Descriptor) -> Option<TableBEntry<'_>> {
+        self.element_override(descriptor)
+            .map(ElementOverride::entry)
+    }
+
+    pub(crate) fn element_override(&self, descriptor: Descriptor) -> Option<&ElementOverride> {
+        self.elements.get(&descriptor)
+    }
+
+    pub(crate) fn sequence(&self, descriptor: Descriptor) -> Option<&[Descriptor]> {
+        self.sequences.get(&descriptor).map(Vec::as_slice)
+    }
+}
+
+fn element_from_table(table: &TomlTable) -> Result<(Descriptor, ElementOverride), Box<dyn Error>> {
+    let descriptor = table.descriptor("descriptor")?;
+    let builtin = lookup_element(descriptor);
+
+    let string = |key: &str, default: Option<&str>| match table.get(key) {
+        Some(TomlValue::String(val)) => Ok(val.clone()),
+        Some(_) => Err(format!("{} must be a string", key)),
+        None => default
+            .map(str::to_owned)
+            .ok_or_else(|| format!("missing {}", key)),
+    };
+    let integer = |key: &str, default: Option<i64>| match table.get(key) {
+        Some(TomlValue::Integer(val)) => Ok(*val),
+        Some(_) => Err(format!("{} must be an integer", key)),
+        None => default.ok_or_else(|| format!("missing {}", key)),
+    };
+
+    let name = string("name", Some(builtin.map(|b| b.name).unwrap_or_default()))?;
+    let units = string("units", builtin.map(|b| b.units))?;
+    let scale = integer("scale", Some(builtin.map(|b| b.scale).unwrap_or(0).into()))?;
+    let reference = integer("reference", Some(builtin.map(|b| b.reference).unwrap_or(0)))?;
+    let width_bits = integer("width_bits", builtin.map(|b| b.width_bits as i64))?;
+
+    let crex_units = string(
+        "crex_units",
+        Some(builtin.map(|b| b.crex_units).unwrap_or(&units)),
+    )?;
+    let crex_scale = integer(
+        "crex_scale",
+        Some(builtin.map(|b| b.crex_scale.into()).unwrap_or(scale)),
+    )?;
+    let crex_width = integer(
+        "crex_width",
+        Some(builtin.map(|b| b.crex_width as i64).unwrap_or(0)),
+    )?;
+
+    Ok((
+        descriptor,
+        ElementOverride {
+            name,
+            units,
+            scale: i32::try_from(scale)?,
+            reference,
+            width_bits: usize::try_from(width_bits)?,
+            crex_units,
+            crex_scale: i32::try_from(crex_scale)?,
+            crex_width: usize::try_from(crex_width)?,
+        },
+    ))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TomlValue {
+    String(String),
+    Integer(i64),
+    Array(Vec<String>),
+}
+
+/// A `[[kind]]` table and the line it starts on.
+#[derive(Debug)]
+struct TomlTable {
+    line: usize,
+    kind: String,
+    values: HashMap<String, TomlValue>,
+}
+
+impl TomlTable {
+    fn get(&self, key: &str) -> Option<&TomlValue> {
+        self.values.get(key)
+    }
+
+    fn descriptor(&self, key: &str) -> Result<Descriptor, Box<dyn Error>> {
+        match self.get(key) {
+            Some(TomlValue::String(val)) => val.parse(),
+            _ => Err(format!("expected a {} string", key).into()),
+        }
+    }
+}
+
+fn parse_toml_tables(text: &str) -> Result<Vec<TomlTable>, Box<dyn Error>> {
+    let mut tables: Vec<TomlTable> = vec![];
+
+    let mut lines = text.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let err = |msg: &str| format!("Table overrides line {}: {}", i + 1, msg);
+
+        let mut line = strip_toml_comment(line).trim().to_owned();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(kind) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+            tables.push(TomlTable {
+                line: i + 1,
+                kind: kind.trim().to_owned(),
+                values: HashMap::new(),
+            });
+            continue;
+        }
+
+        let table = tables
+            .last_mut()
+            .ok_or_else(|| err("expected [[element]] or [[sequence]]"))?;
+        // Arrays may continue over several lines, but not into the next table.
+        while open_brackets(&line) > 0 {
+            let next = match lines.next() {
+                Some((_, next)) if !next.trim_start().starts_with("[[") => next,
+                _ => return Err(err("unterminated array").into()),
+            };
+            line.push(' ');
+            line.push_str(strip_toml_comment(next).trim());
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| err("expected key = value"))?;
+        let value = parse_toml_value(value.trim()).ok_or_else(|| err("invalid value"))?;
+        table.values.insert(key.trim().to_owned(), value);
+    }
+
+    Ok(tables)
+}
+
+/// How many more `[` than `]` there are outside of strings.
+fn open_brackets(line: &str) -> i32 {
+    let mut in_string = false;
+    let mut depth = 0;
+    for c in line.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '[' if !in_string => depth += 1,
+            ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+fn strip_toml_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_toml_value(value: &str) -> Option<TomlValue> {
+    let string = |s: &str| {
+        s.strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .filter(|s| !s.contains(['"', '\\']))
+            .map(str::to_owned)
+    };
+
+    if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        // Split on the commas between strings, not those inside them.
+        let mut in_string = false;
+        items
+            .split(|c| {
+                if c == '"' {
+                    in_string = !in_string;
+                }
+                c == ',' && !in_string
+            })
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(string)
+            .collect::<Option<_>>()
+            .map(TomlValue::Array)
+    } else if value.starts_with('"') {
+        string(value).map(TomlValue::String)
+    } else {
+        value.replace('_', "").parse().ok().map(TomlValue::Integer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let temperature = lookup_element(Descriptor::new(0, 12, 101)).unwrap();
+        assert_eq!(temperature.units, "K");
+        assert_eq!(temperature.scale, 2);
+        assert_eq!(temperature.width_bits, 16);
+        assert!(lookup_element(Descriptor::new(0, 63, 255)).is_none());
+
+        let entries = table_b_entries();
+        assert!(entries
+            .windows(2)
+            .all(|w| w[0].descriptor < w[1].descriptor));
+        assert!(entries.contains(&temperature));
+
+        let precise_temp = lookup_sequence(Descriptor::new(3, 9, 57)).unwrap();
+        assert!(!precise_temp.descriptors.is_empty());
+        assert_eq!(table_d_entries().len(), table_d::TABLE_D.len());
+
+        // The built-in sequences are expanded once and shared.
+        let first = table_d_sequence(precise_temp.descriptor, None).unwrap();
+        let second = table_d_sequence(precise_temp.descriptor, None).unwrap();
+        assert_eq!(first, precise_temp.descriptors.as_slice());
+        assert!(std::ptr::eq(first, second));
+    }
+
+    #[test]
+    fn test_overrides_from_toml() {
+        let toml = r#"
+            # A local element and a change to a built-in one.
+            [[element]]
+            descriptor = "0-63-001"
+            name = "Local temperature"  # not in Table B
+            units = "K"
+            scale = 1
+            width_bits = 12
+
+            [[element]]
+            descriptor = "012101"
+            width_bits = 17
+
+            [[sequence]]
+            descriptor = "3-63-001"
+            descriptors = [
+                "0-07-004",
+                "0-63-001",
+            ]
+        "#;
+        let overrides = TableOverrides::from_toml(toml).unwrap();
+
+        let local = overrides.element(Descriptor::new(0, 63, 1)).unwrap();
+        assert_eq!(local.element_name, "Local temperature");
+        assert_eq!(local.crex_units, "K");
+        assert_eq!(local.crex_scale, 1);
+
+        let temperature = overrides.element(Descriptor::new(0, 12, 101)).unwrap();
+        assert_eq!(temperature.width_bits, 17);
+        assert_eq!(temperature.units, "K");
+        assert_eq!(temperature.scale_val, 2);
+
+        assert_eq!(
+            overrides.sequence(Descriptor::new(3, 63, 1)),
+            Some(&[Descriptor::new(0, 7, 4), Descriptor::new(0, 63, 1)][..])
+        );
+
+        let missing_width = "[[element]]\ndescriptor = \"0-63-002\"\nunits = \"m\"\n";
+        let err = TableOverrides::from_toml(missing_width).unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+        assert!(TableOverrides::from_toml("[[element]]\ndescriptor = \"0-99-001\"").is_err());
+    }
+
+    #[test]
+    fn test_toml_strings_and_arrays() {
+        // `#`, brackets and commas inside strings are part of the string.
+        let toml = r#"
+            [[element]]
+            descriptor = "0-63-003"
+            name = "Sensor #2 [raw, uncorrected" # the comment
+            units = "K"
+            width_bits = 12
+
+            [[sequence]]
+            descriptor = "3-63-002"
+            descriptors = ["0-07-004", # pressure
+                "0-63-003"]
+        "#;
+        let overrides = TableOverrides::from_toml(toml).unwrap();
+        let element = overrides.element(Descriptor::new(0, 63, 3)).unwrap();
+        assert_eq!(element.element_name, "Sensor #2 [raw, uncorrected");
+        assert_eq!(
+            overrides.sequence(Descriptor::new(3, 63, 2)),
+            Some(&[Descriptor::new(0, 7, 4), Descriptor::new(0, 63, 3)][..])
+        );
+
+        let unterminated = r#"
+            [[sequence]]
+            descriptor = "3-63-002"
+            descriptors = ["0-07-004",
+
+            [[sequence]]
+            descriptor = "3-63-003"
+            descriptors = ["0-07-004"]
+        "#;
+        let err = TableOverrides::from_toml(unterminated).unwrap_err();
+        assert!(err.to_string().contains("line 4: unterminated array"));
+        assert!(TableOverrides::from_toml("[[sequence]]\ndescriptors = [\"0-07-004\"").is_err());
+
+        // Escapes aren't supported, rather than read wrong.
+        let escaped = "[[element]]\ndescriptor = \"0-63-003\"\nname = \"a \\\"b\\\"\"\n";
+        assert!(TableOverrides::from_toml(escaped).is_err());
+    }
+}
@@ -0,0 +1,597 @@
+use crate::{
+    monthly::days_in_month,
+    report_type::ReportType,
+    sounding::{Level, Phase, Sounding, Station, Timestamp},
+};
+use std::error::Error;
+
+/// Parse a traditional alphanumeric (TAC) TEMP report into a sounding. Any of the parts TTAA,
+/// TTBB, TTCC, and TTDD present in `text` are merged into one profile.
+///
+/// TAC only carries the day and hour of the observation, so the year and month must be
+/// supplied. The launch time is the observation time, or the hour and minute of the 8GGgg group
+/// after 31313 when there is one. The station position isn't part of a land TEMP report and is
+/// left unset. A part reported as NIL is an empty part.
+pub fn parse_temp(text: &str, year: u16, month: u8) -> Result<Sounding, Box<dyn Error>> {
+    let parts = split_parts(text);
+    if parts.is_empty() {
+        return Err("No TEMP parts (TTAA, TTBB, TTCC, TTDD) found".into());
+    }
+
+    let mut parser = TempParser::default();
+    for (part, groups) in &parts {
+        parser.parse_part(part, groups)?;
+    }
+
+    let (day, hour) = parser.time.ok_or("Missing TEMP date/time group")?;
+    let launch_time = Some(match parser.launch {
+        Some((launch_hour, minute)) => {
+            launch_timestamp(year, month, day, hour, launch_hour, minute)
+        }
+        None => Timestamp {
+            year,
+            month,
+            day,
+            hour,
+            minute: 0,
+            second: 0,
+        },
+    });
+
+    parser.levels.sort_by(|a, b| {
+        let a = a.pressure.unwrap_or(0.0);
+        let b = b.pressure.unwrap_or(0.0);
+        b.total_cmp(&a)
+    });
+
+    let mut sounding = Sounding::new(
+        Phase::Ascent,
+        parser.station,
+        launch_time,
+        parser.radiosonde_type,
+        parser.levels,
+    );
+    sounding.set_report_type(Some(ReportType::Temp));
+
+    Ok(sounding)
+}
+
+/// The launch time from the 8GGgg group. Launches are up to about an hour before the nominal
+/// time, so a launch hour well after it was on the day before, e.g. 2315 for a 00 UTC report.
+fn launch_timestamp(
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    launch_hour: u8,
+    minute: u8,
+) -> Timestamp {
+    let (mut year, mut month, mut day) = (year, month, day);
+    if i32::from(launch_hour) - i32::from(hour) > 12 {
+        if day > 1 {
+            day -= 1;
+        } else {
+            (year, month) = if month > 1 {
+                (year, month - 1)
+            } else {
+                (year - 1, 12)
+            };
+            day = days_in_month(year, month) as u8;
+        }
+    }
+
+    Timestamp {
+        year,
+        month,
+        day,
+        hour: launch_hour,
+        minute,
+        second: 0,
+    }
+}
+
+/// Split a report into its parts, each a part name and the groups that follow it.
+fn split_parts(text: &str) -> Vec<(&str, Vec<&str>)> {
+    let mut parts: Vec<(&str, Vec<&str>)> = vec![];
+    let mut in_part = false;
+
+    for token in text.split_whitespace() {
+        let ends_part = token.ends_with('=');
+        let token = token.trim_end_matches('=');
+
+        if matches!(token, "TTAA" | "TTBB" | "TTCC" | "TTDD") {
+            parts.push((token, vec![]));
+            in_part = true;
+        } else if in_part && !token.is_empty() {
+            if let Some((_, groups)) = parts.last_mut() {
+                groups.push(token);
+            }
+        }
+
+        if ends_part {
+            in_part = false;
+        }
+    }
+
+    parts
+}
+
+#[derive(Default)]
+struct TempParser {
+    station: Station,
+    time: Option<(u8, u8)>,
+    // Hour and minute from 8GGgg.
+    launch: Option<(u8, u8)>,
+    radiosonde_type: Option<u16>,
+    levels: Vec<Level>,
+}
+
+/// Standard isobaric surfaces of Part A as (code, hPa), in reporting order.
+const PART_A_LEVELS: [(&str, f64); 11] = [
+    ("00", 1000.0),
+    ("92", 925.0),
+    ("85", 850.0),
+    ("70", 700.0),
+    ("50", 500.0),
+    ("40", 400.0),
+    ("30", 300.0),
+    ("25", 250.0),
+    ("20", 200.0),
+    ("15", 150.0),
+    ("10", 100.0),
+];
+
+/// Standard isobaric surfaces of Part C as (code, hPa), in reporting order.
+const PART_C_LEVELS: [(&str, f64); 5] = [
+    ("70", 70.0),
+    ("50", 50.0),
+    ("30", 30.0),
+    ("20", 20.0),
+    ("10", 10.0),
+];
+
+impl TempParser {
+    fn parse_part(&mut self, part: &str, groups: &[&str]) -> Result<(), Box<dyn Error>> {
+        // Parts with nothing to report are sent as e.g. `TTCC NIL=` or `TTCC 56001 72776 NIL=`.
+        let nil = groups.iter().position(|g| *g == "NIL");
+        let groups = &groups[..nil.unwrap_or(groups.len())];
+        if groups.len() < 2 {
+            return match nil {
+                Some(_) => Ok(()),
+                None => Err(format!("{} is too short", part).into()),
+            };
+        }
+
+        // YYGGI(d) IIiii
+        let date_group = groups[0];
+        let day: u8 = date_group.get(0..2).ok_or("Bad date group")?.parse()?;
+        let hour: u8 = date_group.get(2..4).ok_or("Bad date group")?.parse()?;
+        let knots = day > 50;
+        let day = if knots { day - 50 } else { day };
+        self.time = Some((day, hour));
+
+        let station_group = groups[1];
+        self.station.wmo_block = station_group.get(0..2).and_then(|s| s.parse().ok());
+        self.station.wmo_station = station_group.get(2..5).and_then(|s| s.parse().ok());
+
+        let last_wind = date_group.get(4..5).and_then(|s| s.parse::<u8>().ok());
+        let groups = &groups[2..];
+
+        match part {
+            "TTAA" => self.parse_standard_part(groups, &PART_A_LEVELS, last_wind, knots, false),
+            "TTCC" => self.parse_standard_part(groups, &PART_C_LEVELS, last_wind, knots, true),
+            "TTBB" => self.parse_significant_part(groups, knots, false),
+            "TTDD" => self.parse_significant_part(groups, knots, true),
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    /// Parts A and C, the standard isobaric surfaces followed by tropopause and max wind data.
+    fn parse_standard_part(
+        &mut self,
+        groups: &[&str],
+        standard_levels: &[(&str, f64)],
+        last_wind: Option<u8>,
+        knots: bool,
+        upper: bool,
+    ) {
+        let mut i = 0;
+
+        // Winds are reported up to the standard surface given by Id.
+        let last_wind_pressure = last_wind.and_then(|id| {
+            standard_levels
+                .iter()
+                .map(|(_, hpa)| *hpa)
+                .rfind(|hpa| last_wind_indicator(*hpa) == id)
+        });
+
+        // Surface, 99PPP TTTDD dddff
+        if !upper && groups.first().is_some_and(|g| g.starts_with("99")) {
+            // Only the last three digits of the pressure are reported.
+            let pressure = groups[0].get(2..5).and_then(parse_hpa);
+            let pressure = pressure.map(|p| if p < 100.0 { p + 1000.0 } else { p });
+            let (temperature, dewpoint) = parse_temperature_group(groups.get(1));
+            let (wind_direction, wind_speed) = parse_wind_group(groups.get(2), knots);
+            self.add_level(Level {
+                significance: Some(Level::SURFACE),
+                pressure: pressure.map(|p| p * 100.0),
+                temperature,
+                dewpoint,
+                wind_direction,
+                wind_speed,
+                ..Level::default()
+            });
+            i += 3;
+        }
+
+        // Standard levels, PPhhh TTTDD dddff
+        let mut remaining = standard_levels;
+        while let Some(group) = groups.get(i) {
+            let code = group.get(0..2).unwrap_or("");
+            let Some(pos) = remaining.iter().position(|(c, _)| *c == code) else {
+                break;
+            };
+            let hpa = remaining[pos].1;
+            remaining = &remaining[(pos + 1)..];
+
+            let height = group.get(2..5).and_then(|hhh| standard_height(hpa, hhh));
+            let (temperature, dewpoint) = parse_temperature_group(groups.get(i + 1));
+            i += 2;
+
+            let mut level = Level {
+                significance: Some(Level::STANDARD),
+                pressure: Some(hpa * 100.0),
+                height,
+                temperature,
+                dewpoint,
+                ..Level::default()
+            };
+
+            if last_wind_pressure.is_some_and(|last| hpa >= last) {
+                let (wind_direction, wind_speed) = parse_wind_group(groups.get(i), knots);
+                level.wind_direction = wind_direction;
+                level.wind_speed = wind_speed;
+                i += 1;
+            }
+
+            self.add_level(level);
+        }
+
+        // Tropopause, maximum wind, and the remaining sections
+        let to_pa = |ppp: &str| parse_hpa(ppp).map(|p| if upper { p * 10.0 } else { p * 100.0 });
+        while let Some(group) = groups.get(i) {
+            match group.get(0..2).unwrap_or("") {
+                _ if group.ends_with("999")
+                    && (group.starts_with("88") || group.starts_with("77")) =>
+                {
+                    i += 1;
+                }
+                "88" => {
+                    let (temperature, dewpoint) = parse_temperature_group(groups.get(i + 1));
+                    let (wind_direction, wind_speed) = parse_wind_group(groups.get(i + 2), knots);
+                    self.add_level(Level {
+                        significance: Some(Level::TROPOPAUSE),
+                        pressure: group.get(2..5).and_then(to_pa),
+                        temperature,
+                        dewpoint,
+                        wind_direction,
+                        wind_speed,
+                        ..Level::default()
+                    });
+                    i += 3;
+                }
+                "77" | "66" => {
+                    let (wind_direction, wind_speed) = parse_wind_group(groups.get(i + 1), knots);
+                    self.add_level(Level {
+                        significance: Some(Level::MAX_WIND),
+                        pressure: group.get(2..5).and_then(to_pa),
+                        wind_direction,
+                        wind_speed,
+                        ..Level::default()
+                    });
+                    i += 2;
+
+                    // Optional vertical wind shear group, 4vbvb
+                    if groups.get(i).is_some_and(|g| g.starts_with('4')) {
+                        i += 1;
+                    }
+                }
+                "31" if *group == "31313" => {
+                    i += 1 + self.parse_instrument_section(&groups[i + 1..]);
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Parts B and D, significant temperature levels then significant wind levels after 21212.
+    fn parse_significant_part(&mut self, groups: &[&str], knots: bool, upper: bool) {
+        let to_pa = |ppp: &str| {
+            parse_hpa(ppp).map(|p| {
+                if upper {
+                    p * 10.0
+                } else if p < 100.0 {
+                    (p + 1000.0) * 100.0
+                } else {
+                    p * 100.0
+                }
+            })
+        };
+
+        let mut in_winds = false;
+        let mut i = 0;
+        while let Some(group) = groups.get(i) {
+            if group.len() != 5 {
+                i += 1;
+                continue;
+            }
+
+            // Significant levels are numbered 00, 11, 22, ... anything else starts a section.
+            let bytes = group.as_bytes();
+            if bytes[0] != bytes[1] {
+                match *group {
+                    "21212" => {
+                        in_winds = true;
+                        i += 1;
+                    }
+                    "31313" => {
+                        i += 1 + self.parse_instrument_section(&groups[i + 1..]);
+                    }
+                    _ => break,
+                }
+                continue;
+            }
+
+            let pressure = group.get(2..5).and_then(to_pa);
+            let level = if in_winds {
+                let (wind_direction, wind_speed) = parse_wind_group(groups.get(i + 1), knots);
+                Level {
+                    significance: Some(Level::SIG_WIND),
+                    pressure,
+                    wind_direction,
+                    wind_speed,
+                    ..Level::default()
+                }
+            } else {
+                let (temperature, dewpoint) = parse_temperature_group(groups.get(i + 1));
+                let surface = !upper && i == 0 && group.starts_with("00");
+                let significance = if surface {
+                    Level::SURFACE
+                } else {
+                    Level::SIG_TEMPERATURE | Level::SIG_HUMIDITY
+                };
+                Level {
+                    significance: Some(significance),
+                    pressure,
+                    temperature,
+                    dewpoint,
+                    ..Level::default()
+                }
+            };
+
+            self.add_level(level);
+            i += 2;
+        }
+    }
+
+    /// Section 31313, sr rara sasa and an optional 8GGgg launch time; rara uses the same code
+    /// table as 0-02-011. Returns the number of groups read.
+    fn parse_instrument_section(&mut self, groups: &[&str]) -> usize {
+        let Some(group) = groups.first() else {
+            return 0;
+        };
+        if let Some(rara) = group.get(1..3).and_then(|s| s.parse().ok()) {
+            self.radiosonde_type = Some(rara);
+        }
+
+        let launch = groups
+            .get(1)
+            .filter(|g| g.len() == 5 && g.starts_with('8'))
+            .and_then(|g| Some((g[1..3].parse::<u8>().ok()?, g[3..5].parse::<u8>().ok()?)))
+            .filter(|&(hour, minute)| hour < 24 && minute < 60);
+        match launch {
+            Some(launch) => {
+                self.launch = Some(launch);
+                2
+            }
+            None => 1,
+        }
+    }
+
+    /// Add a level, merging it into any level already reported at the same pressure.
+    fn add_level(&mut self, level: Level) {
+        let Some(pressure) = level.pressure else {
+            return;
+        };
+
+        let existing = self
+            .levels
+            .iter_mut()
+            .find(|lvl| lvl.pressure.is_some_and(|p| (p - pressure).abs() < 1.0));
+
+        match existing {
+            Some(lvl) => {
+                lvl.significance =
+                    Some(lvl.significance.unwrap_or(0) | level.significance.unwrap_or(0));
+                lvl.height = lvl.height.or(level.height);
+                lvl.temperature = lvl.temperature.or(level.temperature);
+                lvl.dewpoint = lvl.dewpoint.or(level.dewpoint);
+                lvl.wind_direction = lvl.wind_direction.or(level.wind_direction);
+                lvl.wind_speed = lvl.wind_speed.or(level.wind_speed);
+            }
+            None => self.levels.push(level),
+        }
+    }
+}
+
+/// The Id indicator for the standard surface at `hpa`.
+fn last_wind_indicator(hpa: f64) -> u8 {
+    match hpa as u32 {
+        1000 => 0,
+        925 => 9,
+        850 => 8,
+        hpa if hpa >= 100 => (hpa / 100) as u8,
+        hpa => (hpa / 10) as u8,
+    }
+}
+
+fn parse_hpa(ppp: &str) -> Option<f64> {
+    ppp.parse::<u16>().ok().map(f64::from)
+}
+
+/// Heights of the standard surfaces are reported modulo 1000 m or 1000 dam, so pick the value
+/// closest to the standard atmosphere.
+fn standard_height(hpa: f64, hhh: &str) -> Option<f64> {
+    let hhh: f64 = hhh.parse::<u16>().ok()?.into();
+
+    let (nominal, unit) = match hpa as u32 {
+        1000 => {
+            // Heights below sea level are reported as 500 + |h|.
+            return Some(if hhh >= 500.0 { 500.0 - hhh } else { hhh });
+        }
+        925 => (760.0, 1.0),
+        850 => (1460.0, 1.0),
+        700 => (3010.0, 1.0),
+        500 => (5570.0, 10.0),
+        400 => (7180.0, 10.0),
+        300 => (9160.0, 10.0),
+        250 => (10360.0, 10.0),
+        200 => (11790.0, 10.0),
+        150 => (13610.0, 10.0),
+        100 => (16180.0, 10.0),
+        70 => (18440.0, 10.0),
+        50 => (20580.0, 10.0),
+        30 => (23850.0, 10.0),
+        20 => (26480.0, 10.0),
+        10 => (31060.0, 10.0),
+        _ => return None,
+    };
+
+    (0..4)
+        .map(|k| (hhh + 1000.0 * k as f64) * unit)
+        .min_by(|a: &f64, b: &f64| (a - nominal).abs().total_cmp(&(b - nominal).abs()))
+}
+
+/// TTTDD, temperature in tenths of a degree C with the sign in the parity of the tenths digit,
+/// followed by the dewpoint depression. Returns (temperature, dewpoint) in K.
+fn parse_temperature_group(group: Option<&&str>) -> (Option<f64>, Option<f64>) {
+    let Some(group) = group else {
+        return (None, None);
+    };
+
+    let temperature = group.get(0..3).and_then(|ttt| {
+        let tenths: u16 = ttt.parse().ok()?;
+        let t = f64::from(tenths) / 10.0;
+        Some(if tenths.is_multiple_of(2) { t } else { -t })
+    });
+
+    let depression = group.get(3..5).and_then(|dd| {
+        let dd: u8 = dd.parse().ok()?;
+        match dd {
+            0..=50 => Some(f64::from(dd) / 10.0),
+            56..=99 => Some(f64::from(dd) - 50.0),
+            _ => None,
+        }
+    });
+
+    let dewpoint = temperature.zip(depression).map(|(t, dd)| t - dd + 273.15);
+    (temperature.map(|t| t + 273.15), dewpoint)
+}
+
+/// dddff, direction to the nearest 5 degrees with the hundreds of the speed added to the units
+/// digit of the direction. Returns (direction, speed in m/s).
+fn parse_wind_group(group: Option<&&str>, knots: bool) -> (Option<f64>, Option<f64>) {
+    let Some(group) = group else {
+        return (None, None);
+    };
+
+    let ddd: Option<u16> = group.get(0..3).and_then(|s| s.parse().ok());
+    let ff: Option<u16> = group.get(3..5).and_then(|s| s.parse().ok());
+
+    match (ddd, ff) {
+        (Some(ddd), Some(ff)) => {
+            let direction = (ddd / 5) * 5;
+            let speed = f64::from(ff + (ddd % 5) * 100);
+            let speed = if knots { speed * 0.514444 } else { speed };
+            (Some(f64::from(direction)), Some(speed))
+        }
+        _ => (None, None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_temp() {
+        let text = "TTAA 56001 72776 99870 22656 27010 00114 ///// ///// 92765 ///// ///// \
+            85526 25456 22515 70164 10856 23525 50586 07765 24028 40754 19165 24532 \
+            30953 33757 24546 25069 415// 24552 20206 497// 24552 15381 577// 25034 \
+            10642 655// 26521 88195 535// 24551 77230 24558 41617 51515 10164 00060=\n\
+            TTBB 5600/ 72776 00870 22656 11850 25456 22700 10856 33500 07765 \
+            21212 00870 27010 11800 23018 31313 58708 82302=\n\
+            TTCC NIL=";
+
+        let sounding = parse_temp(text, 2017, 8).unwrap();
+        assert_eq!(sounding.station().wmo_block, Some(72));
+        assert_eq!(sounding.station().wmo_station, Some(776));
+        assert_eq!(sounding.radiosonde_type(), Some(87));
+        // Launched at 2302 UTC for the 00 UTC report on the 6th.
+        let launch = sounding.launch_time().unwrap();
+        assert_eq!((launch.day, launch.hour, launch.minute), (5, 23, 2));
+
+        let levels = sounding.levels();
+        let at = |hpa: f64| {
+            levels
+                .iter()
+                .find(|lvl| lvl.pressure == Some(hpa * 100.0))
+                .unwrap()
+        };
+
+        let sfc = at(870.0);
+        assert!(sfc.has_significance(Level::SURFACE));
+        assert!((sfc.temperature.unwrap() - 295.75).abs() < 1.0e-9);
+        assert!((sfc.dewpoint.unwrap() - 289.75).abs() < 1.0e-9);
+        assert_eq!(sfc.wind_direction, Some(270.0));
+
+        let mb500 = at(500.0);
+        assert_eq!(mb500.height, Some(5860.0));
+        assert!((mb500.temperature.unwrap() - 265.45).abs() < 1.0e-9);
+        assert!((mb500.wind_speed.unwrap() - 28.0 * 0.514444).abs() < 1.0e-9);
+
+        assert_eq!(at(250.0).height, Some(10690.0));
+        assert_eq!(at(100.0).height, Some(16420.0));
+        assert!(at(195.0).has_significance(Level::TROPOPAUSE));
+        assert!(at(230.0).has_significance(Level::MAX_WIND));
+        assert!(at(800.0).has_significance(Level::SIG_WIND));
+
+        // Part B 850 hPa merged into the standard level.
+        assert!(at(850.0).has_significance(Level::STANDARD));
+        assert!(at(850.0).has_significance(Level::SIG_TEMPERATURE));
+
+        // Levels are ordered from the surface up.
+        let pressures: Vec<f64> = levels.iter().filter_map(|l| l.pressure).collect();
+        assert!(pressures.windows(2).all(|w| w[0] > w[1]));
+    }
+
+    #[test]
+    fn test_nil_parts_and_launch_time() {
+        let text = "TTAA 51001 72776 NIL=\nTTBB 5100/ 72776 00870 22656 31313 58708 80005=";
+        let sounding = parse_temp(text, 2017, 1).unwrap();
+        assert_eq!(sounding.levels().len(), 1);
+        let launch = sounding.launch_time().unwrap();
+        assert_eq!((launch.day, launch.hour, launch.minute), (1, 0, 5));
+
+        // Launched the day before the 00 UTC report on the 1st of the month.
+        let text = "TTBB 5100/ 72776 00870 22656 31313 58708 82315=";
+        let launch = parse_temp(text, 2017, 1).unwrap().launch_time().unwrap();
+        assert_eq!((launch.year, launch.month, launch.day), (2016, 12, 31));
+        assert_eq!((launch.hour, launch.minute), (23, 15));
+
+        assert!(parse_temp("TTAA NIL=", 2017, 1).is_err());
+        assert!(parse_temp("TTAA 51001=", 2017, 1).is_err());
+    }
+}
@@ -0,0 +1,454 @@
+use crate::{
+    conformance::{check_sounding, Violation, STANDARD_LEVELS},
+    encode::{DataDescription, MessageHeader, Section3Builder},
+    expansion::ExpansionNode,
+    section3::Descriptor,
+    section4::{DataNode, Value},
+    sounding::{
+        Level, Phase, Sounding, Station, Timestamp, CALL_SIGN, DAY, DEWPOINT, GEOPOTENTIAL_HEIGHT,
+        GEOPOTENTIAL_HEIGHT_PILOT, HOUR, LATITUDE, LAT_DISPLACEMENT, LONGITUDE, LON_DISPLACEMENT,
+        MINUTE, MONTH, PRESSURE, RADIOSONDE_TYPE, RELATIVE_HUMIDITY, RELEASE_HEIGHT, SECOND,
+        SIGNIFICANCE, STATION_HEIGHT, TEMPERATURE, TIME_OFFSET, WIND_DIRECTION, WIND_SPEED,
+        WMO_BLOCK, WMO_STATION, YEAR,
+    },
+};
+use std::{cmp::Ordering, error::Error};
+
+const FLIGHT_NUMBER: Descriptor = Descriptor::new(0, 1, 6);
+const TIME_SIGNIFICANCE: Descriptor = Descriptor::new(0, 8, 21);
+
+/// Code 18 of the time significance table, 0-08-021.
+const LAUNCH_TIME: i64 = 18;
+
+/// The WMO sounding templates `SoundingEncoder` fills in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Template {
+    /// 3-09-052, a high resolution radiosonde ascent (TEMP).
+    Temp,
+    /// 3-09-056, a radiosonde descent.
+    Descent,
+    /// 3-09-050, a high resolution PILOT, winds without temperature.
+    Pilot,
+    /// 3-09-053, a dropsonde (TEMP DROP).
+    Dropsonde,
+}
+
+impl Template {
+    /// The Table D sequence of the template.
+    pub fn descriptor(self) -> Descriptor {
+        match self {
+            Template::Temp => Descriptor::new(3, 9, 52),
+            Template::Descent => Descriptor::new(3, 9, 56),
+            Template::Pilot => Descriptor::new(3, 9, 50),
+            Template::Dropsonde => Descriptor::new(3, 9, 53),
+        }
+    }
+
+    /// International data sub-category, common code table C-13.
+    fn data_sub_category(self) -> u8 {
+        match self {
+            Template::Temp | Template::Descent => 4,
+            Template::Pilot => 1,
+            Template::Dropsonde => 7,
+        }
+    }
+
+    /// Whether pressure increases through the flight.
+    fn falling(self) -> bool {
+        matches!(self, Template::Descent | Template::Dropsonde)
+    }
+}
+
+/// Encodes a sounding as a BUFR message using one of the WMO templates.
+///
+/// Fill in the station, launch time, and levels and the encoder puts the values where the
+/// template wants them. Levels are put in flight order, by time since launch if every level has
+/// one and otherwise by pressure. Levels without a significance get one: the first level of an
+/// ascent is the surface and levels at standard pressures are standard levels. Levels at the
+/// launch time without a displacement are put at the launch site. Everything the template has
+/// that a `Sounding` doesn't, such as cloud data and wind shear, is encoded as missing.
+#[derive(Clone, Debug)]
+pub struct SoundingEncoder {
+    template: Template,
+    station: Station,
+    launch_time: Option<Timestamp>,
+    radiosonde_type: Option<u16>,
+    levels: Vec<Level>,
+    header: Option<MessageHeader>,
+}
+
+impl SoundingEncoder {
+    pub fn new(template: Template) -> Self {
+        SoundingEncoder {
+            template,
+            station: Station::default(),
+            launch_time: None,
+            radiosonde_type: None,
+            levels: vec![],
+            header: None,
+        }
+    }
+
+    /// Take the station, launch time, radiosonde type, and levels from `sounding`.
+    pub fn sounding(self, sounding: &Sounding) -> Self {
+        let mut encoder = self
+            .station(sounding.station().clone())
+            .levels(sounding.levels().to_vec());
+        encoder.launch_time = sounding.launch_time();
+        encoder.radiosonde_type = sounding.radiosonde_type();
+        encoder
+    }
+
+    pub fn station(mut self, station: Station) -> Self {
+        self.station = station;
+        self
+    }
+
+    pub fn launch_time(mut self, launch_time: Timestamp) -> Self {
+        self.launch_time = Some(launch_time);
+        self
+    }
+
+    /// Radiosonde type, code table 0-02-011.
+    pub fn radiosonde_type(mut self, radiosonde_type: u16) -> Self {
+        self.radiosonde_type = Some(radiosonde_type);
+        self
+    }
+
+    pub fn levels(mut self, levels: Vec<Level>) -> Self {
+        self.levels = levels;
+        self
+    }
+
+    /// Set Section 1. By default it's `MessageHeader::new` at the launch time with the data
+    /// sub-category of the template.
+    pub fn header(mut self, header: MessageHeader) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    /// Encode the sounding as one message with a single subset.
+    pub fn encode(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let description = Section3Builder::new()
+            .sequence(self.template.descriptor())
+            .build()?;
+        let subset = self.subset(&description)?;
+
+        description.encode(&self.message_header()?, &[subset])
+    }
+
+    /// Section 1 for the message, see `header`.
+    pub(crate) fn message_header(&self) -> Result<MessageHeader, Box<dyn Error>> {
+        match (self.header, self.launch_time) {
+            (Some(header), _) => Ok(header),
+            (None, Some(time)) => Ok(MessageHeader {
+                data_sub_category: self.template.data_sub_category(),
+                ..MessageHeader::new(time)
+            }),
+            (None, None) => Err("Encoding needs a launch time or a header".into()),
+        }
+    }
+
+    /// The sounding as a subset of `description`, which needs a delayed replication of levels
+    /// like the template's.
+    pub(crate) fn subset(
+        &self,
+        description: &DataDescription,
+    ) -> Result<Vec<DataNode>, Box<dyn Error>> {
+        let levels = self.prepared_levels();
+        let mut levels_used = false;
+        let subset = fill(
+            description.expansion(),
+            &|desc| self.header_value(desc),
+            &levels,
+            &mut levels_used,
+        );
+        if !levels_used {
+            return Err(format!(
+                "{} has no replication for the levels",
+                description
+                    .descriptors()
+                    .iter()
+                    .map(Descriptor::string_form)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )
+            .into());
+        }
+
+        Ok(subset)
+    }
+
+    /// Check the sounding as it will be encoded, with the levels ordered and flagged, see
+    /// `check_sounding`.
+    pub fn check_conformance(&self) -> Vec<Violation> {
+        let phase = match self.template {
+            Template::Descent => Phase::Descent,
+            _ => Phase::Ascent,
+        };
+        let sounding = Sounding::new(
+            phase,
+            self.station.clone(),
+            self.launch_time,
+            self.radiosonde_type,
+            self.prepared_levels(),
+        );
+        check_sounding(&sounding)
+    }
+
+    fn header_value(&self, desc: Descriptor) -> Value {
+        let station = &self.station;
+        let time = self.launch_time;
+        let float = |val: Option<f64>| val.map_or(Value::Missing, Value::Float);
+        let integer = |val: Option<i64>| val.map_or(Value::Missing, Value::Integer);
+
+        match desc {
+            WMO_BLOCK => integer(station.wmo_block.map(i64::from)),
+            WMO_STATION => integer(station.wmo_station.map(i64::from)),
+            CALL_SIGN | FLIGHT_NUMBER => station
+                .call_sign
+                .clone()
+                .map_or(Value::Missing, Value::Text),
+            RADIOSONDE_TYPE => integer(self.radiosonde_type.map(i64::from)),
+            TIME_SIGNIFICANCE => integer(time.map(|_| LAUNCH_TIME)),
+            YEAR => integer(time.map(|t| t.year.into())),
+            MONTH => integer(time.map(|t| t.month.into())),
+            DAY => integer(time.map(|t| t.day.into())),
+            HOUR => integer(time.map(|t| t.hour.into())),
+            MINUTE => integer(time.map(|t| t.minute.into())),
+            SECOND => integer(time.map(|t| t.second.into())),
+            LATITUDE => float(station.latitude),
+            LONGITUDE => float(station.longitude),
+            STATION_HEIGHT | RELEASE_HEIGHT => float(station.elevation),
+            _ => Value::Missing,
+        }
+    }
+
+    /// The levels in flight order with significance and launch displacements filled in.
+    fn prepared_levels(&self) -> Vec<Level> {
+        let mut levels = self.levels.clone();
+
+        if levels.iter().all(|lvl| lvl.time_offset.is_some()) {
+            levels.sort_by(|a, b| a.time_offset.unwrap().total_cmp(&b.time_offset.unwrap()));
+        } else {
+            let falling = self.template.falling();
+            levels.sort_by(|a, b| match (a.pressure, b.pressure) {
+                (Some(a), Some(b)) if falling => a.total_cmp(&b),
+                (Some(a), Some(b)) => b.total_cmp(&a),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            });
+        }
+
+        let ascent = !self.template.falling();
+        for (i, lvl) in levels.iter_mut().enumerate() {
+            if lvl.significance.is_none() {
+                let mut significance = 0;
+                if i == 0 && ascent {
+                    significance |= Level::SURFACE;
+                }
+                if lvl.pressure.is_some_and(|p| STANDARD_LEVELS.contains(&p)) {
+                    significance |= Level::STANDARD;
+                }
+                lvl.significance = Some(significance);
+            }
+
+            if lvl.time_offset == Some(0.0) {
+                lvl.lat_displacement.get_or_insert(0.0);
+                lvl.lon_displacement.get_or_insert(0.0);
+            }
+        }
+
+        levels
+    }
+}
+
+fn level_value(lvl: &Level, desc: Descriptor) -> Value {
+    let val = match desc {
+        SIGNIFICANCE => {
+            return lvl
+                .significance
+                .map_or(Value::Missing, |v| Value::Integer(v.into()))
+        }
+        TIME_OFFSET => lvl.time_offset,
+        PRESSURE => lvl.pressure,
+        GEOPOTENTIAL_HEIGHT | GEOPOTENTIAL_HEIGHT_PILOT => lvl.height,
+        TEMPERATURE => lvl.temperature,
+        DEWPOINT => lvl.dewpoint,
+        RELATIVE_HUMIDITY => lvl.relative_humidity,
+        WIND_DIRECTION => lvl.wind_direction,
+        WIND_SPEED => lvl.wind_speed,
+        LAT_DISPLACEMENT => lvl.lat_displacement,
+        LON_DISPLACEMENT => lvl.lon_displacement,
+        _ => None,
+    };
+    val.map_or(Value::Missing, Value::Float)
+}
+
+/// Build the data for an expansion, taking element values from `value`. The first delayed
+/// replication of levels gets one repetition per level and other delayed replications get none.
+fn fill(
+    expansion: &[ExpansionNode],
+    value: &dyn Fn(Descriptor) -> Value,
+    levels: &[Level],
+    levels_used: &mut bool,
+) -> Vec<DataNode> {
+    let mut nodes = vec![];
+    for node in expansion {
+        match node {
+            ExpansionNode::Element { descriptor, .. } => nodes.push(DataNode::Element {
+                descriptor: *descriptor,
+                value: value(*descriptor),
+                raw: None,
+            }),
+            ExpansionNode::Operator { .. } => {}
+            ExpansionNode::Sequence {
+                descriptor,
+                children,
+            } => nodes.push(DataNode::Sequence {
+                descriptor: *descriptor,
+                children: fill(children, value, levels, levels_used),
+            }),
+            ExpansionNode::Replication {
+                descriptor,
+                factor,
+                children,
+            } => {
+                let repetitions = if factor.is_none() {
+                    (0..descriptor.y_value())
+                        .map(|_| fill(children, value, levels, levels_used))
+                        .collect()
+                } else if !*levels_used && is_profile(children) {
+                    *levels_used = true;
+                    levels
+                        .iter()
+                        .map(|lvl| fill(children, &|desc| level_value(lvl, desc), &[], &mut true))
+                        .collect()
+                } else {
+                    vec![]
+                };
+                nodes.push(DataNode::Replication {
+                    descriptor: *descriptor,
+                    repetitions,
+                });
+            }
+        }
+    }
+    nodes
+}
+
+/// Whether a replication is of levels, the same test as the decoder uses to find them.
+fn is_profile(children: &[ExpansionNode]) -> bool {
+    fn elements(nodes: &[ExpansionNode], out: &mut Vec<Descriptor>) {
+        for node in nodes {
+            match node {
+                ExpansionNode::Element { descriptor, .. } => out.push(*descriptor),
+                ExpansionNode::Sequence { children, .. } => elements(children, out),
+                _ => {}
+            }
+        }
+    }
+
+    let mut found = vec![];
+    elements(children, &mut found);
+    found.contains(&PRESSURE) && (found.contains(&TEMPERATURE) || found.contains(&WIND_DIRECTION))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{read_bufr_message, scan_to_bufr_start, sounding::Phase, DataCategory};
+    use std::{fs::File, io::BufReader};
+
+    #[test]
+    fn test_sounding_encoder() {
+        let mut f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+        scan_to_bufr_start(&mut f).unwrap();
+        let original = read_bufr_message(&mut f).unwrap().soundings().remove(0);
+
+        // Re-encoding a decoded sounding gives it back.
+        let message = SoundingEncoder::new(Template::Temp)
+            .sounding(&original)
+            .encode()
+            .unwrap();
+        let bufr = read_bufr_message(message.as_slice()).unwrap();
+        assert_eq!(bufr.category(), DataCategory::VerticalSoundings);
+        let decoded = bufr.soundings().remove(0);
+        assert_eq!(decoded.station(), original.station());
+        assert_eq!(decoded.launch_time(), original.launch_time());
+        assert_eq!(decoded.radiosonde_type(), original.radiosonde_type());
+        assert_eq!(decoded.levels(), original.levels());
+
+        // The encoder orders the levels and flags them.
+        let level = |hpa: f64, t: f64| Level {
+            pressure: Some(hpa * 100.0),
+            temperature: Some(t),
+            wind_direction: Some(180.0),
+            wind_speed: Some(5.0),
+            ..Level::default()
+        };
+        let station = Station {
+            wmo_block: Some(72),
+            wmo_station: Some(773),
+            latitude: Some(46.92),
+            longitude: Some(-114.09),
+            elevation: Some(972.0),
+            ..Station::default()
+        };
+        let time = original.launch_time().unwrap();
+        for template in [Template::Temp, Template::Pilot, Template::Dropsonde] {
+            let message = SoundingEncoder::new(template)
+                .station(station.clone())
+                .launch_time(time)
+                .levels(vec![level(850.0, 280.0), level(900.0, 285.0)])
+                .encode()
+                .unwrap();
+            let decoded = read_bufr_message(message.as_slice())
+                .unwrap()
+                .soundings()
+                .remove(0);
+            assert_eq!(decoded.phase(), Phase::Ascent);
+            if template != Template::Dropsonde {
+                assert_eq!(decoded.station().identifier().as_deref(), Some("72773"));
+            }
+
+            let levels = decoded.levels();
+            let pressures: Vec<_> = levels.iter().map(|lvl| lvl.pressure).collect();
+            if template == Template::Dropsonde {
+                assert_eq!(pressures, [Some(85_000.0), Some(90_000.0)]);
+            } else {
+                assert_eq!(pressures, [Some(90_000.0), Some(85_000.0)]);
+                assert!(levels[0].has_significance(Level::SURFACE));
+                assert!(levels[1].has_significance(Level::STANDARD));
+            }
+            // PILOT doesn't carry temperature.
+            let has_temperature = levels[0].temperature.is_some();
+            assert_eq!(has_temperature, template != Template::Pilot);
+            assert_eq!(levels[0].wind_speed, Some(5.0));
+        }
+
+        let descent = SoundingEncoder::new(Template::Descent)
+            .station(station.clone())
+            .launch_time(time)
+            .levels(vec![level(850.0, 280.0)])
+            .encode()
+            .unwrap();
+        let decoded = read_bufr_message(descent.as_slice()).unwrap().soundings();
+        assert_eq!(decoded[0].phase(), Phase::Descent);
+
+        assert!(SoundingEncoder::new(Template::Temp).encode().is_err());
+
+        // Only the radiosonde type is missing once the encoder has flagged the levels.
+        let encoder = SoundingEncoder::new(Template::Temp)
+            .station(station)
+            .launch_time(time)
+            .levels(vec![level(850.0, 280.0), level(900.0, 285.0)]);
+        let rules: Vec<_> = encoder
+            .check_conformance()
+            .into_iter()
+            .map(|v| v.rule)
+            .collect();
+        assert_eq!(rules, ["B/C25.3"]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Synthetic TEMP messages for decoder tests, so they don't need binary fixtures.
+
+use crate::{
+    encode::Section3Builder,
+    section3::Descriptor,
+    sounding::{
+        Level, Station, Timestamp, DEWPOINT, GEOPOTENTIAL_HEIGHT, PRESSURE, SIGNIFICANCE,
+        TEMPERATURE, WIND_DIRECTION, WIND_SPEED,
+    },
+    template::{SoundingEncoder, Template},
+};
+use std::error::Error;
+
+/// Builds a TEMP message with a subset for each station added.
+///
+/// By default the subsets use the WMO high resolution template, 3-09-052, uncompressed. With
+/// `operators` they use a short list of descriptors instead that changes the width and scale
+/// of the pressure and temperature with operators 2-01, 2-02, and 2-07. Compressed subsets need
+/// the same number of levels.
+#[derive(Clone, Debug)]
+pub struct SyntheticTemp {
+    launch_time: Timestamp,
+    subsets: Vec<(Station, Vec<Level>)>,
+    compressed: bool,
+    operators: bool,
+}
+
+impl SyntheticTemp {
+    pub fn new(launch_time: Timestamp) -> Self {
+        SyntheticTemp {
+            launch_time,
+            subsets: vec![],
+            compressed: false,
+            operators: false,
+        }
+    }
+
+    /// Add a subset for `station` with `levels`, see `synthetic_levels`.
+    pub fn subset(mut self, station: Station, levels: Vec<Level>) -> Self {
+        self.subsets.push((station, levels));
+        self
+    }
+
+    /// Compress the data across subsets.
+    pub fn compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Use descriptors with Table C operators instead of the template.
+    pub fn operators(mut self, operators: bool) -> Self {
+        self.operators = operators;
+        self
+    }
+
+    /// The descriptors for Section 3.
+    pub fn descriptors(&self) -> Vec<Descriptor> {
+        if !self.operators {
+            return vec![Template::Temp.descriptor()];
+        }
+
+        let operator = |x, y| Descriptor::new(2, x, y);
+        let level = [
+            SIGNIFICANCE,
+            // Pressure to the nearest Pa instead of 10 Pa.
+            operator(7, 1),
+            PRESSURE,
+            operator(7, 0),
+            GEOPOTENTIAL_HEIGHT,
+            // Temperature to the nearest mK, with 4 more bits.
+            operator(2, 129),
+            operator(1, 132),
+            TEMPERATURE,
+            operator(1, 0),
+            operator(2, 0),
+            DEWPOINT,
+            WIND_DIRECTION,
+            WIND_SPEED,
+        ];
+
+        let mut descriptors = vec![
+            Descriptor::new(3, 1, 1),
+            Descriptor::new(3, 1, 11),
+            Descriptor::new(3, 1, 12),
+            Descriptor::new(3, 1, 21),
+            Descriptor::new(0, 7, 30),
+            Descriptor::new(1, level.len() as u8, 0),
+            Descriptor::new(0, 31, 2),
+        ];
+        descriptors.extend(level);
+        descriptors
+    }
+
+    /// Encode the message, from `BUFR` through `7777`.
+    pub fn encode(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let description = Section3Builder::new()
+            .descriptors(&self.descriptors())
+            .build()?;
+
+        let mut header = None;
+        let mut subsets = vec![];
+        for (station, levels) in &self.subsets {
+            let encoder = SoundingEncoder::new(Template::Temp)
+                .station(station.clone())
+                .launch_time(self.launch_time)
+                .levels(levels.clone());
+            header.get_or_insert(encoder.message_header()?);
+            subsets.push(encoder.subset(&description)?);
+        }
+        let header = header.ok_or("A message needs at least one subset")?;
+
+        if self.compressed {
+            description.encode_compressed(&header, &subsets)
+        } else {
+            description.encode(&header, &subsets)
+        }
+    }
+}
+
+/// `count` levels from 1000 hPa up in 10 hPa steps, with a 6.5 K/km lapse rate, moist near the
+/// ground, and the wind veering and strengthening with height. `seed` shifts the temperatures
+/// and wind so each station's profile is different.
+pub fn synthetic_levels(count: usize, seed: u32) -> Vec<Level> {
+    let offset = f64::from(seed % 10);
+    (0..count)
+        .map(|i| {
+            let i = i as f64;
+            let pressure = 100_000.0 - 1_000.0 * i;
+            // Roughly the standard atmosphere near the ground.
+            let height = 111.0 + 8.4 * i * 10.0;
+            let temperature = 288.15 + offset - 0.0065 * height;
+            Level {
+                time_offset: Some(5.0 * i),
+                pressure: Some(pressure),
+                height: Some(height.round()),
+                temperature: Some(temperature),
+                dewpoint: Some(temperature - 2.0 - i),
+                wind_direction: Some((180.0 + 10.0 * (offset + i)) % 360.0),
+                wind_speed: Some(2.0 + i + offset / 10.0),
+                ..Level::default()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::read_bufr_message;
+
+    #[test]
+    fn test_synthetic_temp() {
+        let time = Timestamp {
+            year: 2024,
+            month: 5,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        };
+        let station = |number| Station {
+            wmo_block: Some(72),
+            wmo_station: Some(number),
+            latitude: Some(47.46),
+            longitude: Some(-111.38),
+            elevation: Some(1130.0),
+            ..Station::default()
+        };
+        let profiles = [synthetic_levels(20, 0), synthetic_levels(20, 3)];
+
+        for (compressed, operators) in [(false, false), (true, false), (false, true), (true, true)]
+        {
+            let message = SyntheticTemp::new(time)
+                .subset(station(775), profiles[0].clone())
+                .subset(station(776), profiles[1].clone())
+                .compressed(compressed)
+                .operators(operators)
+                .encode()
+                .unwrap();
+            let bufr = read_bufr_message(message.as_slice()).unwrap();
+            assert_eq!(bufr.descriptors()[0].f_value(), 3);
+
+            let soundings = bufr.soundings();
+            assert_eq!(soundings.len(), 2);
+            for (sounding, profile) in soundings.iter().zip(&profiles) {
+                assert_eq!(sounding.launch_time(), Some(time));
+                assert_eq!(sounding.levels().len(), 20);
+                for (lvl, expected) in sounding.levels().iter().zip(profile) {
+                    assert_eq!(lvl.pressure, expected.pressure);
+                    assert_eq!(lvl.height, expected.height);
+                    let t = (lvl.temperature.unwrap() - expected.temperature.unwrap()).abs();
+                    // The operators keep another decimal of the temperature.
+                    assert!(t < if operators { 0.001 } else { 0.01 });
+                    assert_eq!(lvl.wind_direction, expected.wind_direction);
+                }
+            }
+            assert_eq!(soundings[1].station().wmo_station, Some(776));
+        }
+
+        // Compressed subsets need the same shape.
+        let uneven = SyntheticTemp::new(time)
+            .subset(station(775), synthetic_levels(3, 0))
+            .subset(station(776), synthetic_levels(4, 0))
+            .compressed(true)
+            .encode();
+        assert!(uneven.is_err());
+    }
+}
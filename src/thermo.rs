@@ -0,0 +1,144 @@
+//! Thermodynamic helpers shared by the analysis code. Temperatures are in K and pressures in Pa.
+
+use crate::derive::VaporPressureFormula;
+
+/// Gas constant for dry air, J / (kg K).
+pub(crate) const RD: f64 = 287.04;
+/// Specific heat of dry air at constant pressure, J / (kg K).
+pub(crate) const CP: f64 = 1005.7;
+/// Latent heat of vaporization, J / kg.
+pub(crate) const LV: f64 = 2.501e6;
+/// Ratio of the gas constants of dry air and water vapor.
+pub(crate) const EPSILON: f64 = 0.622;
+/// Standard gravity, m / s².
+pub(crate) const G: f64 = 9.80665;
+const KAPPA: f64 = RD / CP;
+
+/// Saturation vapor pressure in Pa.
+pub(crate) fn vapor_pressure(t: f64) -> f64 {
+    VaporPressureFormula::Bolton.saturation_vapor_pressure(t - 273.15) * 100.0
+}
+
+/// Mixing ratio in kg/kg of air with the dewpoint `td` at pressure `p`.
+pub(crate) fn mixing_ratio(td: f64, p: f64) -> f64 {
+    let e = vapor_pressure(td);
+    EPSILON * e / (p - e)
+}
+
+/// The dewpoint of air with mixing ratio `w` at pressure `p`.
+pub(crate) fn dewpoint_from_mixing_ratio(w: f64, p: f64) -> Option<f64> {
+    let e = w * p / (EPSILON + w) / 100.0;
+    if e <= 0.0 {
+        return None;
+    }
+
+    // Invert Bolton's formula.
+    let x = (e / 6.112).ln();
+    Some(243.5 * x / (17.67 - x) + 273.15)
+}
+
+pub(crate) fn potential_temperature(t: f64, p: f64) -> f64 {
+    t * (100_000.0 / p).powf(KAPPA)
+}
+
+/// The temperature at `p` of air with potential temperature `theta`.
+pub(crate) fn temperature_from_theta(theta: f64, p: f64) -> f64 {
+    theta * (p / 100_000.0).powf(KAPPA)
+}
+
+pub(crate) fn virtual_temperature(t: f64, w: f64) -> f64 {
+    t * (1.0 + w / EPSILON) / (1.0 + w)
+}
+
+/// The pressure and temperature of the lifting condensation level, Bolton (1980) eq. 15.
+pub(crate) fn lcl(t: f64, td: f64, p: f64) -> (f64, f64) {
+    let t_lcl = 1.0 / (1.0 / (td - 56.0) + (t / td).ln() / 800.0) + 56.0;
+    let p_lcl = p * (t_lcl / t).powf(1.0 / KAPPA);
+
+    (p_lcl, t_lcl)
+}
+
+/// Follow a saturated (pseudo-adiabatic) parcel at `t` from `p0` to `p1`.
+pub(crate) fn moist_adiabat(t: f64, p0: f64, p1: f64) -> f64 {
+    let dt_dp = |t: f64, p: f64| {
+        let ws = EPSILON * vapor_pressure(t) / (p - vapor_pressure(t));
+        (RD * t + LV * ws) / (p * (CP + LV * LV * ws * EPSILON / (RD * t * t)))
+    };
+
+    let num_steps = ((p1 - p0).abs() / 500.0).ceil().max(1.0) as usize;
+    let dp = (p1 - p0) / num_steps as f64;
+
+    let mut t = t;
+    let mut p = p0;
+    for _ in 0..num_steps {
+        // Second order Runge-Kutta.
+        let k1 = dt_dp(t, p);
+        let k2 = dt_dp(t + k1 * dp, p + dp);
+        t += (k1 + k2) / 2.0 * dp;
+        p += dp;
+    }
+
+    t
+}
+
+/// A parcel lifted through the profile one level at a time.
+pub(crate) struct Parcel {
+    p0: f64,
+    t0: f64,
+    w0: f64,
+    p_lcl: f64,
+    t_lcl: f64,
+    /// The last saturated state, to continue the moist adiabat from.
+    moist: Option<(f64, f64)>,
+}
+
+impl Parcel {
+    pub(crate) fn new(p0: f64, t0: f64, td0: f64) -> Self {
+        let (p_lcl, t_lcl) = lcl(t0, td0, p0);
+        Parcel {
+            p0,
+            t0,
+            w0: mixing_ratio(td0, p0),
+            p_lcl,
+            t_lcl,
+            moist: None,
+        }
+    }
+
+    /// The temperature and mixing ratio of the parcel at `p`, which must not be higher than the
+    /// previous pressure it was lifted to.
+    pub(crate) fn lift(&mut self, p: f64) -> (f64, f64) {
+        if p >= self.p_lcl {
+            let theta = potential_temperature(self.t0, self.p0);
+            return (temperature_from_theta(theta, p), self.w0);
+        }
+
+        let (p_start, t_start) = self.moist.unwrap_or((self.p_lcl, self.t_lcl));
+        let t = moist_adiabat(t_start, p_start, p);
+        self.moist = Some((p, t));
+
+        (t, mixing_ratio(t, p))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lifted_parcel() {
+        // The equivalent potential temperature (Bolton 1980, eq. 43) is kept on a moist adiabat.
+        let theta_e = |t: f64, p: f64| {
+            let r = mixing_ratio(t, p) * 1000.0;
+            potential_temperature(t, p) * ((3.376 / t - 0.00254) * r * (1.0 + 0.00081 * r)).exp()
+        };
+        let (t, _) = Parcel::new(100_000.0, 293.15, 293.15).lift(50_000.0);
+        assert!((theta_e(t, 50_000.0) - theta_e(293.15, 100_000.0)).abs() < 1.0);
+
+        let (p_lcl, _) = lcl(303.15, 293.15, 100_000.0);
+        assert!((p_lcl - 86_400.0).abs() < 500.0);
+
+        let td = dewpoint_from_mixing_ratio(mixing_ratio(280.0, 85_000.0), 85_000.0).unwrap();
+        assert!((td - 280.0).abs() < 1.0e-9);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::sounding::{Level, Sounding};
+
+impl Sounding {
+    /// Reduce the profile to at most `max_levels` levels for display tools.
+    ///
+    /// The first and last levels and any surface, tropopause, or maximum wind levels are always
+    /// kept, so the result only has more than `max_levels` levels if there are more of those.
+    /// Other levels are added back one at a time, largest error first, where the error is how far
+    /// the temperature, dewpoint (K), or wind components (m/s) at a level are from a straight line
+    /// in log pressure between the levels kept on either side. Thinning stops when no level is off
+    /// by more than `tolerance`.
+    pub fn thin(&self, max_levels: usize, tolerance: f64) -> Sounding {
+        const PROTECTED: u32 = Level::SURFACE | Level::TROPOPAUSE | Level::MAX_WIND;
+
+        let levels = self.levels();
+        let mut keep = vec![false; levels.len()];
+        for (i, lvl) in levels.iter().enumerate() {
+            keep[i] = i == 0 || i + 1 == levels.len() || lvl.has_significance(PROTECTED);
+        }
+
+        let mut num_kept = keep.iter().filter(|k| **k).count();
+        while num_kept < max_levels && num_kept < levels.len() {
+            // The index of the next kept level at or above each level.
+            let mut next_kept = vec![levels.len() - 1; levels.len()];
+            for i in (0..levels.len()).rev() {
+                if keep[i] || i + 1 == levels.len() {
+                    next_kept[i] = i;
+                } else {
+                    next_kept[i] = next_kept[i + 1];
+                }
+            }
+
+            let mut worst: Option<(usize, f64)> = None;
+            let mut below = 0;
+            for i in 1..levels.len() {
+                if keep[i] {
+                    below = i;
+                    continue;
+                }
+
+                let above = next_kept[i];
+                let err = thinning_error(&levels[below], &levels[i], &levels[above]);
+                if err > tolerance && worst.is_none_or(|(_, w)| err > w) {
+                    worst = Some((i, err));
+                }
+            }
+
+            match worst {
+                Some((i, _)) => {
+                    keep[i] = true;
+                    num_kept += 1;
+                }
+                None => break,
+            }
+        }
+
+        let levels = levels
+            .iter()
+            .zip(keep)
+            .filter(|(_, keep)| *keep)
+            .map(|(lvl, _)| *lvl)
+            .collect();
+
+        self.with_levels(levels)
+    }
+}
+
+/// The largest difference between the values at `lvl` and those interpolated to it from `below`
+/// and `above`.
+fn thinning_error(below: &Level, lvl: &Level, above: &Level) -> f64 {
+    let (Some(p0), Some(p), Some(p1)) = (below.pressure, lvl.pressure, above.pressure) else {
+        return 0.0;
+    };
+    let (x0, x, x1) = (p0.ln(), p.ln(), p1.ln());
+    if x1 == x0 {
+        return 0.0;
+    }
+    let frac = (x - x0) / (x1 - x0);
+
+    let err = |get: &dyn Fn(&Level) -> Option<f64>| match (get(below), get(lvl), get(above)) {
+        (Some(y0), Some(y), Some(y1)) => (y - (y0 + (y1 - y0) * frac)).abs(),
+        _ => 0.0,
+    };
+
+    [
+        err(&|l| l.temperature),
+        err(&|l| l.dewpoint),
+        err(&|l| l.wind_components().map(|(u, _)| u)),
+        err(&|l| l.wind_components().map(|(_, v)| v)),
+    ]
+    .into_iter()
+    .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_thin() {
+        // A linear profile in log pressure with an inversion at 800 hPa and a tropopause.
+        let mut levels: Vec<Level> = (0..=90)
+            .map(|i| {
+                let hpa = 1000.0 - 10.0 * i as f64;
+                let t = 290.0 + 40.0 * (hpa / 1000.0f64).ln();
+                Level {
+                    pressure: Some(hpa * 100.0),
+                    temperature: Some(if hpa == 800.0 { t + 5.0 } else { t }),
+                    ..Level::default()
+                }
+            })
+            .collect();
+        levels[50].significance = Some(Level::TROPOPAUSE);
+
+        let sounding = Sounding::new(Phase::Ascent, Station::default(), None, None, levels);
+        let thinned = sounding.thin(10, 0.5);
+
+        let pressures: Vec<f64> = thinned.levels().iter().filter_map(|l| l.pressure).collect();
+        assert!(pressures.len() <= 10);
+        assert!(pressures.contains(&100000.0));
+        assert!(pressures.contains(&80000.0));
+        assert!(pressures.contains(&50000.0));
+        assert!(pressures.contains(&10000.0));
+    }
+}
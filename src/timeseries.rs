@@ -0,0 +1,201 @@
+use crate::{
+    index::walk_archive,
+    sounding::{Level, Sounding, Timestamp},
+    MessageDecoder,
+};
+use std::{error::Error, io::Write, path::Path, str::FromStr};
+
+/// A value of a level to follow through time, see `extract_time_series`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProfileElement {
+    /// K
+    Temperature,
+    /// K
+    Dewpoint,
+    /// %
+    RelativeHumidity,
+    /// gpm
+    Height,
+    /// Degrees
+    WindDirection,
+    /// m/s
+    WindSpeed,
+}
+
+impl ProfileElement {
+    fn value(self, lvl: &Level) -> Option<f64> {
+        match self {
+            ProfileElement::Temperature => lvl.temperature,
+            ProfileElement::Dewpoint => lvl.dewpoint,
+            ProfileElement::RelativeHumidity => lvl.relative_humidity,
+            ProfileElement::Height => lvl.height,
+            ProfileElement::WindDirection => lvl.wind_direction,
+            ProfileElement::WindSpeed => lvl.wind_speed,
+        }
+    }
+}
+
+impl FromStr for ProfileElement {
+    type Err = Box<dyn Error>;
+
+    /// Parse the ecCodes key (e.g. `airTemperature`) or a short name (e.g. `temperature`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "airTemperature" | "temperature" => ProfileElement::Temperature,
+            "dewpointTemperature" | "dewpoint" => ProfileElement::Dewpoint,
+            "relativeHumidity" | "rh" => ProfileElement::RelativeHumidity,
+            "nonCoordinateGeopotentialHeight" | "geopotentialHeight" | "height" => {
+                ProfileElement::Height
+            }
+            "windDirection" | "direction" => ProfileElement::WindDirection,
+            "windSpeed" | "speed" => ProfileElement::WindSpeed,
+            _ => return Err(format!("Unknown element: {}", s).into()),
+        })
+    }
+}
+
+/// One sounding's value in a time series.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeSeriesPoint {
+    /// The launch time, or the nominal time if there isn't one.
+    pub time: Timestamp,
+    pub value: Option<f64>,
+    /// Whether the value was interpolated rather than reported at the pressure.
+    pub interpolated: bool,
+}
+
+/// The value of `element` at `pressure` (Pa) in every sounding from `station` under `dir`,
+/// sorted by time. See `Station::identifier` for the station and `time_series_point` for how
+/// the value is found.
+pub fn extract_time_series(
+    dir: impl AsRef<Path>,
+    station: &str,
+    element: ProfileElement,
+    pressure: f64,
+) -> Result<Vec<TimeSeriesPoint>, Box<dyn Error>> {
+    let mut points = vec![];
+    walk_archive(dir.as_ref(), &MessageDecoder::default(), |_, _, bufr| {
+        for sounding in bufr.soundings() {
+            if sounding.station().identifier().as_deref() != Some(station) {
+                continue;
+            }
+            let time = sounding.launch_time().unwrap_or(bufr.nominal_time());
+            points.push(time_series_point(&sounding, time, element, pressure));
+        }
+    })?;
+    points.sort_by_key(|point| point.time);
+
+    Ok(points)
+}
+
+/// The value of `element` at `pressure` (Pa) in one sounding: the reported value if a level is
+/// at that pressure and has one, otherwise interpolated with `Sounding::interpolate_to`.
+pub fn time_series_point(
+    sounding: &Sounding,
+    time: Timestamp,
+    element: ProfileElement,
+    pressure: f64,
+) -> TimeSeriesPoint {
+    let reported = sounding
+        .levels()
+        .iter()
+        .filter(|lvl| lvl.pressure == Some(pressure))
+        .find_map(|lvl| element.value(lvl));
+    if reported.is_some() {
+        return TimeSeriesPoint {
+            time,
+            value: reported,
+            interpolated: false,
+        };
+    }
+
+    let value = sounding
+        .interpolate_to(&[pressure])
+        .first()
+        .and_then(|lvl| element.value(lvl));
+    TimeSeriesPoint {
+        time,
+        value,
+        interpolated: value.is_some(),
+    }
+}
+
+/// Write a time series as CSV with a header row. Missing values are empty.
+pub fn write_time_series_csv(
+    mut w: impl Write,
+    points: &[TimeSeriesPoint],
+) -> Result<(), Box<dyn Error>> {
+    writeln!(w, "time,value,interpolated")?;
+    for point in points {
+        let value = point.value.map_or(String::new(), |v| v.to_string());
+        writeln!(w, "{},{},{}", point.time, value, point.interpolated)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Phase, Station};
+
+    #[test]
+    fn test_time_series_point() {
+        let level = |hpa: f64, t: f64| Level {
+            pressure: Some(hpa * 100.0),
+            temperature: Some(t),
+            ..Level::default()
+        };
+        let sounding = Sounding::new(
+            Phase::Ascent,
+            Station::default(),
+            None,
+            None,
+            vec![
+                level(600.0, 260.0),
+                level(500.0, 250.0),
+                level(400.0, 240.0),
+            ],
+        );
+        let time = Timestamp {
+            year: 2024,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        };
+        let element: ProfileElement = "airTemperature".parse().unwrap();
+
+        let reported = time_series_point(&sounding, time, element, 50_000.0);
+        assert_eq!(reported.value, Some(250.0));
+        assert!(!reported.interpolated);
+
+        let interpolated = time_series_point(&sounding, time, element, 45_000.0);
+        let t = interpolated.value.unwrap();
+        assert!(t > 240.0 && t < 250.0);
+        assert!(interpolated.interpolated);
+
+        assert_eq!(
+            time_series_point(&sounding, time, element, 1_000.0).value,
+            None
+        );
+        assert!("pressure".parse::<ProfileElement>().is_err());
+    }
+
+    #[test]
+    fn test_extract_time_series() {
+        let element = ProfileElement::Temperature;
+        let points = extract_time_series("test-data", "MSO1", element, 50_000.0).unwrap();
+        assert_eq!(points.len(), 1);
+        assert!(points[0].value.is_some());
+        assert!(extract_time_series("test-data", "72469", element, 50_000.0)
+            .unwrap()
+            .is_empty());
+
+        let mut csv = vec![];
+        write_time_series_csv(&mut csv, &points).unwrap();
+        assert!(String::from_utf8(csv)
+            .unwrap()
+            .starts_with("time,value,interpolated\n2017-08-31 14:59:00,"));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{section3::Descriptor, section4::Value};
+use std::{error::Error, fmt::Display, io::Write};
+
+/// Where one element was read from in Section 4 and what it decoded to. Recorded when the
+/// decoder is built with `DecoderBuilder::trace`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    /// Index of the subset the element is in.
+    pub subset: usize,
+    pub descriptor: Descriptor,
+    /// Bits from the start of Section 4.
+    pub bit_offset: usize,
+    /// Bits read, after any Table C operators.
+    pub width_bits: usize,
+    /// The integer read before the reference value and scale were applied, `None` for text and
+    /// missing values.
+    pub raw: Option<u64>,
+    pub value: Value,
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "subset {:>3} {} bit {:>7} width {:>3} raw {:>10} value {}",
+            self.subset,
+            self.descriptor.string_form(),
+            self.bit_offset,
+            self.width_bits,
+            self.raw.map(|raw| raw.to_string()).unwrap_or_default(),
+            self.value
+        )
+    }
+}
+
+/// Write a trace as CSV, one row per element. Missing raw values are empty fields.
+pub fn write_trace_csv(mut w: impl Write, trace: &[TraceEntry]) -> Result<(), Box<dyn Error>> {
+    writeln!(w, "subset,descriptor,bit_offset,width_bits,raw,value")?;
+    for entry in trace {
+        writeln!(
+            w,
+            "{},{},{},{},{},\"{}\"",
+            entry.subset,
+            entry.descriptor.string_form(),
+            entry.bit_offset,
+            entry.width_bits,
+            entry.raw.map(|raw| raw.to_string()).unwrap_or_default(),
+            entry.value.to_string().replace('"', "\"\"")
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{scan_to_bufr_start, DecoderBuilder};
+    use std::{fs::File, io::BufReader};
+
+    #[test]
+    fn test_trace() {
+        let mut f = BufReader::new(File::open("test-data/2017083115.bufr").unwrap());
+        scan_to_bufr_start(&mut f).unwrap();
+        let decoder = DecoderBuilder::new().trace(true).build();
+        let bufr = decoder.read_bufr_message(&mut f).unwrap();
+
+        let trace = bufr.trace();
+        assert_eq!(trace[0].bit_offset, 32);
+        assert!(trace
+            .windows(2)
+            .all(|w| w[1].bit_offset == w[0].bit_offset + w[0].width_bits));
+
+        // Pressure is 14 bits in units of 10 Pa.
+        let pressure = trace
+            .iter()
+            .find(|entry| entry.descriptor.string_form() == "007004")
+            .unwrap();
+        assert_eq!(pressure.width_bits, 14);
+        let raw = pressure.raw.unwrap() as f64;
+        assert_eq!(pressure.value.as_f64(), Some(raw * 10.0));
+    }
+}
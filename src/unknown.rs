@@ -0,0 +1,119 @@
+use crate::{section3::Descriptor, tables::ElementOverride};
+use std::fmt;
+
+/// What to do with an element descriptor that isn't in any loaded table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnknownDescriptorAction {
+    /// Decode it with this definition.
+    Define(ElementOverride),
+    /// Step over this many bits without keeping a value. In compressed data this is the width of
+    /// the element, which is followed by the usual increments.
+    Skip { bits: usize },
+    /// Give up on the message, as decoding does without a handler.
+    Abort,
+}
+
+/// Decides what decoding does when it meets an element descriptor that is in neither the
+/// built-in tables nor the overrides, e.g. an undocumented local addition to a feed. See
+/// `DecoderBuilder::unknown_descriptors`. It's asked every time the descriptor is met, and any
+/// closure taking the descriptor is a handler.
+pub trait UnknownDescriptorHandler: Send + Sync {
+    fn handle(&self, descriptor: Descriptor) -> UnknownDescriptorAction;
+}
+
+impl<F> UnknownDescriptorHandler for F
+where
+    F: Fn(Descriptor) -> UnknownDescriptorAction + Send + Sync,
+{
+    fn handle(&self, descriptor: Descriptor) -> UnknownDescriptorAction {
+        self(descriptor)
+    }
+}
+
+impl fmt::Debug for dyn UnknownDescriptorHandler + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UnknownDescriptorHandler")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        sounding::collect_elements, table_b_entries, table_d_entries, DecoderBuilder,
+        TableOverrides, Value,
+    };
+
+    #[test]
+    fn test_unknown_descriptors() {
+        let mut file = std::fs::read("test-data/2017083115.bufr").unwrap();
+        let start = file.windows(4).position(|w| w == b"BUFR").unwrap();
+        // As master table 10 only the registered tables are used, so leaving the station height
+        // (0-07-030) out of them makes it unknown.
+        file[start + 8 + 3] = 10;
+        let station_height = Descriptor::new(0, 7, 30);
+
+        let mut tables = TableOverrides::new();
+        let mut height = None;
+        for element in table_b_entries() {
+            let element_override = ElementOverride {
+                name: element.name.to_owned(),
+                units: element.units.to_owned(),
+                scale: element.scale,
+                reference: element.reference,
+                width_bits: element.width_bits,
+                crex_units: element.crex_units.to_owned(),
+                crex_scale: element.crex_scale,
+                crex_width: element.crex_width,
+            };
+            if element.descriptor == station_height {
+                height = Some(element_override);
+            } else {
+                tables
+                    .add_element(element.descriptor, element_override)
+                    .unwrap();
+            }
+        }
+        for sequence in table_d_entries() {
+            tables
+                .add_sequence(sequence.descriptor, sequence.descriptors)
+                .unwrap();
+        }
+        let height = height.unwrap();
+
+        let decode = |action: UnknownDescriptorAction| {
+            let decoder = DecoderBuilder::new()
+                .master_table(10, tables.clone())
+                .unknown_descriptors(move |descriptor| {
+                    assert_eq!(descriptor, station_height);
+                    action.clone()
+                })
+                .metrics(true)
+                .build();
+            decoder.read_bufr_message(&file[start..]).map(|bufr| {
+                // The station height is met once.
+                assert_eq!(bufr.metrics().unwrap().warnings.len(), 1);
+                let mut elements = vec![];
+                collect_elements(&bufr.subsets()[0], &mut elements, true);
+                elements
+                    .into_iter()
+                    .map(|(d, v)| (d, v.clone()))
+                    .collect::<Vec<(Descriptor, Value)>>()
+            })
+        };
+
+        let defined = decode(UnknownDescriptorAction::Define(height.clone())).unwrap();
+        let elevation = defined.iter().find(|(d, _)| *d == station_height).unwrap();
+        assert_eq!(elevation.1.as_f64(), Some(1225.0));
+
+        let skipped = decode(UnknownDescriptorAction::Skip {
+            bits: height.width_bits,
+        })
+        .unwrap();
+        assert_eq!(skipped.len() + 1, defined.len());
+        assert!(skipped.iter().all(|(d, _)| *d != station_height));
+        assert_eq!(skipped.last(), defined.last());
+
+        assert!(decode(UnknownDescriptorAction::Abort).is_err());
+    }
+}
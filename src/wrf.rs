@@ -0,0 +1,211 @@
+use crate::{
+    sounding::Sounding,
+    thermo::{mixing_ratio, potential_temperature},
+};
+use std::{error::Error, io::Write};
+
+/// How `write_wrf_input_sounding` fills the levels near the ground.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BoundaryLayerFill {
+    /// Use the observed levels as they are.
+    #[default]
+    Observed,
+    /// Replace the potential temperature and mixing ratio of every level within `depth` m of
+    /// the ground with the surface values, a well mixed layer.
+    WellMixed { depth: f64 },
+}
+
+/// Options for `write_wrf_input_sounding`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WrfOptions {
+    pub boundary_layer: BoundaryLayerFill,
+}
+
+/// Write a sounding as a WRF ideal case `input_sounding` file.
+///
+/// The first line is the surface pressure (hPa), potential temperature (K), and mixing ratio
+/// (g/kg). Each following line is a level above the surface: height above the ground (m),
+/// potential temperature (K), mixing ratio (g/kg), and the u and v wind (m/s).
+///
+/// Only levels with a pressure, height, and temperature are used and the lowest is the surface.
+/// A missing dewpoint is taken as dry, and missing winds are interpolated in height from the
+/// levels around them.
+pub fn write_wrf_input_sounding(
+    mut w: impl Write,
+    sounding: &Sounding,
+    options: &WrfOptions,
+) -> Result<(), Box<dyn Error>> {
+    let mut profile: Vec<WrfLevel> = vec![];
+    let mut levels: Vec<_> = sounding
+        .levels()
+        .iter()
+        .filter(|lvl| lvl.pressure.is_some() && lvl.height.is_some())
+        .collect();
+    levels.sort_by(|a, b| b.pressure.unwrap().total_cmp(&a.pressure.unwrap()));
+
+    let mut surface_pressure = None;
+    for lvl in levels {
+        let (Some(p), Some(z), Some(t)) = (lvl.pressure, lvl.height, lvl.temperature) else {
+            continue;
+        };
+        // Heights have to increase for WRF to interpolate.
+        if profile.last().is_some_and(|last| z <= last.height) {
+            continue;
+        }
+
+        surface_pressure.get_or_insert(p);
+        let qv = lvl.dewpoint.map_or(0.0, |td| mixing_ratio(td.min(t), p));
+        profile.push(WrfLevel {
+            height: z,
+            theta: potential_temperature(t, p),
+            qv,
+            wind: lvl.wind_components(),
+        });
+    }
+
+    let Some(p_sfc) = surface_pressure else {
+        return Err("No levels with a pressure, height, and temperature".into());
+    };
+    if profile.len() < 2 {
+        return Err("WRF needs at least one level above the surface".into());
+    }
+
+    let winds = fill_winds(&profile);
+    let WrfLevel {
+        height: z_sfc,
+        theta: theta_sfc,
+        qv: qv_sfc,
+        ..
+    } = profile[0];
+    writeln!(
+        w,
+        "{:10.2} {:10.3} {:10.4}",
+        p_sfc / 100.0,
+        theta_sfc,
+        qv_sfc * 1000.0
+    )?;
+
+    for (lvl, (u, v)) in profile.iter().zip(winds).skip(1) {
+        let (mut theta, mut qv) = (lvl.theta, lvl.qv);
+        let agl = lvl.height - z_sfc;
+        if let BoundaryLayerFill::WellMixed { depth } = options.boundary_layer {
+            if agl <= depth {
+                theta = theta_sfc;
+                qv = qv_sfc;
+            }
+        }
+
+        writeln!(
+            w,
+            "{:10.2} {:10.3} {:10.4} {:10.3} {:10.3}",
+            agl,
+            theta,
+            qv * 1000.0,
+            u,
+            v
+        )?;
+    }
+
+    Ok(())
+}
+
+struct WrfLevel {
+    /// gpm
+    height: f64,
+    /// K
+    theta: f64,
+    /// kg/kg
+    qv: f64,
+    /// (u, v) m/s
+    wind: Option<(f64, f64)>,
+}
+
+/// The wind at every level, interpolating in height across levels without one and holding the
+/// nearest wind beyond the first and last. Calm if there are no winds at all.
+fn fill_winds(profile: &[WrfLevel]) -> Vec<(f64, f64)> {
+    let known: Vec<(f64, (f64, f64))> = profile
+        .iter()
+        .filter_map(|lvl| Some((lvl.height, lvl.wind?)))
+        .collect();
+
+    profile
+        .iter()
+        .map(|lvl| {
+            if let Some(wind) = lvl.wind {
+                return wind;
+            }
+            let z = lvl.height;
+            let above = known.iter().position(|&(zk, _)| zk > z);
+            match above {
+                None => known.last().map_or((0.0, 0.0), |k| k.1),
+                Some(0) => known[0].1,
+                Some(i) => {
+                    let ((z0, (u0, v0)), (z1, (u1, v1))) = (known[i - 1], known[i]);
+                    let frac = (z - z0) / (z1 - z0);
+                    (u0 + (u1 - u0) * frac, v0 + (v1 - v0) * frac)
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sounding::{Level, Phase, Station};
+
+    #[test]
+    fn test_wrf_input_sounding() {
+        let level = |hpa: f64, z: f64, t: f64, wind: Option<f64>| Level {
+            pressure: Some(hpa * 100.0),
+            height: Some(z),
+            temperature: Some(t),
+            dewpoint: Some(t - 5.0),
+            wind_direction: wind.map(|_| 270.0),
+            wind_speed: wind,
+            ..Level::default()
+        };
+        let sounding = Sounding::new(
+            Phase::Ascent,
+            Station::default(),
+            None,
+            None,
+            vec![
+                level(1000.0, 100.0, 300.0, Some(2.0)),
+                level(950.0, 550.0, 297.0, None),
+                level(900.0, 1000.0, 294.0, Some(6.0)),
+            ],
+        );
+
+        let write = |options: &WrfOptions| {
+            let mut out = vec![];
+            write_wrf_input_sounding(&mut out, &sounding, options).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let observed = write(&WrfOptions::default());
+        let lines: Vec<Vec<f64>> = observed
+            .lines()
+            .map(|line| {
+                line.split_whitespace()
+                    .map(|v| v.parse().unwrap())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0][0], 1000.0);
+        assert_eq!(lines[0][1], 300.0);
+        assert_eq!(lines[1][0], 450.0);
+        // Westerly, halfway between 2 and 6 m/s.
+        assert!((lines[1][3] - 4.0).abs() < 1.0e-3);
+        assert!(lines[1][1] > lines[0][1]);
+
+        let mixed = write(&WrfOptions {
+            boundary_layer: BoundaryLayerFill::WellMixed { depth: 500.0 },
+        });
+        let mixed: Vec<&str> = mixed.lines().collect();
+        let first: Vec<&str> = mixed[1].split_whitespace().collect();
+        assert_eq!(first[1], "300.000");
+        assert_eq!(mixed[2], observed.lines().nth(2).unwrap());
+    }
+}